@@ -0,0 +1,48 @@
+#ifndef PDFPARSER_H
+#define PDFPARSER_H
+
+/* Generated by cbindgen from src/ffi.rs; don't edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct PdfDoc PdfDoc;
+
+/*
+ Open a document from the len bytes at data, which are copied.  Returns null if data is null or the bytes
+ aren't a readable PDF.
+
+ # Safety
+ data must point to len readable bytes.
+ */
+PdfDoc *pdfparser_open(const uint8_t *data, size_t len);
+
+/*
+ The number of pages in the document, or 0 if doc is null.
+
+ # Safety
+ doc must be null or a pointer returned by pdfparser_open that hasn't been freed.
+ */
+size_t pdfparser_page_count(const PdfDoc *doc);
+
+/*
+ Extract the text of the page at page_index (counting from 0) as UTF-8, like snprintf: as much as fits is
+ written to buffer, always followed by a NUL if capacity is at least 1, and the full length of the text
+ without the NUL is returned.  Call with a null buffer and a capacity of 0 to find the size needed.  Returns
+ -1 if doc is null or the page can't be read.
+
+ # Safety
+ doc must be null or a live pointer from pdfparser_open, and buffer must be null or point to capacity
+ writable bytes.
+ */
+intptr_t pdfparser_page_text(const PdfDoc *doc, size_t page_index, char *buffer, size_t capacity);
+
+/*
+ Release a document returned by pdfparser_open.  Null is ignored.
+
+ # Safety
+ doc must be null or a pointer from pdfparser_open that hasn't already been freed.
+ */
+void pdfparser_free(PdfDoc *doc);
+
+#endif /* PDFPARSER_H */
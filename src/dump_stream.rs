@@ -0,0 +1,151 @@
+//! The dump-stream subcommand: writes the data of one stream object to a file, either as it is in the file or
+//! with its filters applied, which is most of what looking into a filter bug takes.
+
+use std::io::Write;
+
+use pdfparser::doc_tree::*;
+
+const USAGE: &str = "Usage: pdfparser dump-stream FILE OBJ GEN [--raw|--decoded] [-o OUTPUT]
+Writes the stream's data with its filters applied, or with --raw as it is in the file, to OUTPUT or stdout";
+
+/// What to dump and where, from the command line.
+#[derive(Debug, PartialEq)]
+struct Dump {
+    path: String,
+    id: ObjectId,
+    raw: bool,
+    /// None for stdout
+    output: Option<String>,
+}
+
+impl Dump {
+    /// Read the arguments that follow dump-stream.
+    fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut raw = None;
+        let mut output = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--raw" | "--decoded" => {
+                    if raw.replace(arg == "--raw").is_some() {
+                        return Err("Give one of --raw and --decoded".to_string());
+                    };
+                },
+                "-o" | "--output" => match args.next() {
+                    Some(path) => output = Some(path.clone()),
+                    None => return Err(format!("{} needs a path", arg))
+                },
+                _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("Unknown option {}", arg)),
+                _ => positional.push(arg)
+            };
+        }
+        let (path, number, generation) = match positional.as_slice() {
+            [path, number, generation] => (path, number, generation),
+            _ => return Err("Give a file, an object number and a generation".to_string())
+        };
+        let number = number.parse().map_err(|_| format!("Invalid object number {}", number))?;
+        let generation = generation.parse().map_err(|_| format!("Invalid generation {}", generation))?;
+        Ok(Dump { path: path.to_string(), id: ObjectId::new(number, generation), raw: raw.unwrap_or(false), output })
+    }
+
+    /// The stream's data from the file.
+    fn data(&self, file: &PdfFileHandler) -> Result<Vec<u8>, String> {
+        let object = file.get_object(self.id).map_err(|error| error.to_string())?;
+        if !object.is_stream() {
+            return Err(format!("{} isn't a stream", self.id));
+        };
+        let data = if self.raw { object.raw_stream_data() } else { file.get_stream_data(self.id) };
+        data.map_err(|error| format!("Can't read the data of {}: {}", self.id, error))
+    }
+
+    fn write(&self, data: &[u8]) -> std::io::Result<()> {
+        match &self.output {
+            Some(path) => std::fs::write(path, data),
+            None => {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                stdout.write_all(data)?;
+                stdout.flush()
+            }
+        }
+    }
+}
+
+/// Run the subcommand on the arguments that follow dump-stream, returning the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let dump = match Dump::from_args(args) {
+        Ok(dump) => dump,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            return 2;
+        }
+    };
+    // The files worth dumping streams from are often broken
+    let options = ParserOptions { lenient: true, ..Default::default() };
+    let result = PdfFileHandler::create_pdf_from_file_with_options(&dump.path, options)
+        .map_err(|error| format!("{} can't be opened: {}", dump.path, error))
+        .and_then(|file| dump.data(&file))
+        .and_then(|data| dump.write(&data).map(|()| data.len()).map_err(|error| error.to_string()));
+    match result {
+        Ok(length) => {
+            let kind = if dump.raw { "raw" } else { "decoded" };
+            eprintln!("Wrote {} bytes of {} data from {} to {}", length, kind, dump.id,
+                      dump.output.as_deref().unwrap_or("stdout"));
+            0
+        },
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::file;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_dump_args() {
+        assert_eq!(Dump::from_args(&args("in.pdf 5 0 --raw -o out.bin")),
+                   Ok(Dump { path: "in.pdf".to_string(), id: ObjectId::new(5, 0), raw: true,
+                             output: Some("out.bin".to_string()) }));
+        let dump = Dump::from_args(&args("--decoded in.pdf 12 3")).unwrap();
+        assert_eq!((dump.id, dump.raw, dump.output), (ObjectId::new(12, 3), false, None));
+        assert!(!Dump::from_args(&args("in.pdf 5 0")).unwrap().raw);
+        for line in &["in.pdf 5", "in.pdf 5 0 1", "in.pdf x 0", "in.pdf 5 0 --raw --decoded", "in.pdf 5 0 -o",
+                      "in.pdf 5 0 --hex"] {
+            assert!(Dump::from_args(&args(line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_dump_stream() {
+        let data = file(&["<< /Type /Catalog /Pages 2 0 R >>",
+                          "<< /Type /Pages /Kids [] /Count 0 >>",
+                          "<< /Length 11 /Filter /ASCIIHexDecode >>\nstream\n48656C6C6F>\nendstream"]);
+        let directory = std::env::temp_dir().join(format!("pdfparser-dump-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let input = directory.join("in.pdf");
+        std::fs::write(&input, data).unwrap();
+        let output = directory.join("out.bin");
+        let dump = |options: &str| {
+            run(&args(&format!("{} {} -o {}", input.display(), options, output.display())))
+        };
+
+        assert_eq!(dump("3 0 --decoded"), 0);
+        assert_eq!(std::fs::read(&output).unwrap(), b"Hello");
+        assert_eq!(dump("3 0 --raw"), 0);
+        assert_eq!(std::fs::read(&output).unwrap(), b"48656C6C6F>");
+        // Not a stream, not in the file, and not a command line
+        assert_eq!(dump("1 0"), 1);
+        assert_eq!(dump("9 0"), 1);
+        assert_eq!(dump("3"), 2);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
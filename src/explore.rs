@@ -0,0 +1,315 @@
+//! The explore subcommand: an interactive prompt for walking a file from its trailer through references,
+//! reading dictionaries, dumping stream data and looking at the bytes at an offset.  It opens files leniently
+//! and still works on the raw bytes of ones that can't be opened at all, so it's a way into files the parser
+//! rejects.
+
+use std::io::{self, BufRead, Write};
+
+use pdfparser::doc_tree::*;
+
+/// Bytes of stream data shown by hex when no limit is given.
+const DEFAULT_HEX_LIMIT: usize = 512;
+/// Bytes shown before and after an offset by goto.
+const OFFSET_CONTEXT: usize = 128;
+/// Most array elements or dictionary entries listed for one object.
+const MAX_ITEMS: usize = 100;
+
+const HELP: &str = "\
+Commands:
+  trailer, t          show the trailer
+  obj N [G], o        show object N, generation G or 0
+  N                   follow reference [N] of the object shown
+  back, b             go back to the previous view
+  hex [LIMIT], x      hexdump the decoded data of the stream shown, LIMIT bytes at most
+  raw, r              hexdump the bytes of the object shown as they are in the file
+  goto OFFSET, g      show the bytes around a byte offset, given in decimal or 0x hex
+  help, h             show this list
+  quit, q             leave";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum View {
+    Trailer,
+    Object(ObjectId),
+    Offset(usize),
+}
+
+/// The state of an explore session: the file, what's being looked at, and the way back.
+pub struct Explorer {
+    data: Vec<u8>,
+    /// None if the file couldn't be opened, leaving only its bytes to look at
+    file: Option<PdfFileHandler>,
+    view: Option<View>,
+    history: Vec<View>,
+    /// The references in the view, numbered from 1 for following
+    links: Vec<ObjectId>,
+}
+
+impl Explorer {
+    /// Open a file's bytes for exploring, with why it couldn't be opened if it couldn't.
+    pub fn new(data: Vec<u8>) -> (Self, Option<String>) {
+        let options = ParserOptions { lenient: true, ..Default::default() };
+        let (file, error) = match PdfFileHandler::create_pdf_from_bytes_with_options(data.clone(), options) {
+            Ok(file) => (Some(file), None),
+            Err(error) => (None, Some(error.to_string()))
+        };
+        (Explorer { data, file, view: None, history: Vec::new(), links: Vec::new() }, error)
+    }
+
+    /// Read commands from input until it ends or a quit command, writing what they show to output.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, output: &mut W) -> io::Result<()> {
+        match &self.file {
+            Some(file) => writeln!(output, "{} bytes, {} objects; type help for commands", self.data.len(),
+                                   file.object_map.object_ids().len())?,
+            None => writeln!(output, "{} bytes; only goto works on a file that can't be opened",
+                             self.data.len())?,
+        };
+        if self.file.is_some() {
+            writeln!(output, "{}", self.show(View::Trailer).unwrap_or_else(|error| format!("Error: {}", error)))?;
+        };
+        write!(output, "> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.command(line?.trim()) {
+                Some(Ok(text)) => writeln!(output, "{}", text)?,
+                Some(Err(error)) => writeln!(output, "Error: {}", error)?,
+                None => return Ok(())
+            };
+            write!(output, "> ")?;
+            output.flush()?;
+        }
+        writeln!(output)
+    }
+
+    /// Run one command, giving what it shows, or None for quit.
+    fn command(&mut self, line: &str) -> Option<Result<String, String>> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let argument = |i: usize| words.get(i).map(|word| parse_number(word)).transpose();
+        Some(match words.first().copied().unwrap_or("") {
+            "" => Ok(String::new()),
+            "quit" | "q" | "exit" => return None,
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "trailer" | "t" => self.go(View::Trailer),
+            "obj" | "o" => match (argument(1), argument(2)) {
+                (Ok(Some(number)), Ok(generation)) => {
+                    self.go(View::Object(ObjectId::new(number as u32, generation.unwrap_or(0) as u32)))
+                },
+                _ => Err("obj needs an object number".to_string())
+            },
+            "back" | "b" => match self.history.pop() {
+                Some(view) => self.show(view),
+                None => Err("Nothing to go back to".to_string())
+            },
+            "hex" | "x" => match argument(1) {
+                Ok(limit) => self.hex(limit.unwrap_or(DEFAULT_HEX_LIMIT)),
+                Err(error) => Err(error)
+            },
+            "raw" | "r" => self.raw(),
+            "goto" | "g" => match argument(1) {
+                Ok(Some(offset)) => self.go(View::Offset(offset)),
+                _ => Err("goto needs a byte offset".to_string())
+            },
+            word => match word.parse::<usize>() {
+                Ok(link) if link >= 1 && link <= self.links.len() => self.go(View::Object(self.links[link - 1])),
+                Ok(link) => Err(format!("No reference [{}] here", link)),
+                Err(_) => Err(format!("Unknown command {}; type help for the list", word))
+            }
+        })
+    }
+
+    /// Show a view, keeping the current one to go back to.
+    fn go(&mut self, view: View) -> Result<String, String> {
+        let previous = self.view;
+        let shown = self.show(view)?;
+        if let Some(previous) = previous {
+            self.history.push(previous);
+        };
+        Ok(shown)
+    }
+
+    fn show(&mut self, view: View) -> Result<String, String> {
+        let mut links = Vec::new();
+        let text = match view {
+            View::Trailer => {
+                let trailer = self.file()?.retrieve_trailer().map_err(|error| error.to_string())?;
+                format!("Trailer\n{}", describe(&trailer, &mut links, 0))
+            },
+            View::Object(id) => {
+                let file = self.file()?;
+                let object = file.get_object(id).map_err(|error| error.to_string())?;
+                let location = match file.object_map.locations().find(|(other, _)| *other == id) {
+                    Some((_, ObjectLocation::Offset(offset))) => format!(" at byte {}", offset),
+                    _ => String::new()
+                };
+                format!("{}{}\n{}", id, location, describe(&object, &mut links, 0))
+            },
+            View::Offset(offset) => self.offset_view(offset, &mut links)?,
+        };
+        self.view = Some(view);
+        self.links = links;
+        Ok(text)
+    }
+
+    /// The bytes around an offset, and the object whose definition they're in.
+    fn offset_view(&self, offset: usize, links: &mut Vec<ObjectId>) -> Result<String, String> {
+        if offset >= self.data.len() {
+            return Err(format!("Offset {} is past the end of the file, at {}", offset, self.data.len()));
+        };
+        let start = offset.saturating_sub(OFFSET_CONTEXT) / 16 * 16;
+        let end = (offset + OFFSET_CONTEXT).min(self.data.len());
+        let mut text = format!("Byte {}\n{}", offset, hexdump(&self.data[start..end], start, Some(offset)));
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(text)
+        };
+        let header = file.object_map.locations().filter_map(|(id, location)| match location {
+            ObjectLocation::Offset(start) if start <= offset => Some((start, id)),
+            _ => None
+        }).max();
+        if let Some((start, id)) = header {
+            let within = file.object_map.object_span(id).is_ok_and(|span| span.contains(&offset));
+            text.push_str(&format!("\n{} {}, which starts at byte {} [1]", if within { "In" } else { "After" },
+                                   id, start));
+            links.push(id);
+        };
+        Ok(text)
+    }
+
+    /// The decoded data of the stream shown.
+    fn hex(&self, limit: usize) -> Result<String, String> {
+        let id = self.object_shown()?;
+        let data = self.file()?.get_stream_data(id).map_err(|error| format!("{}; try raw", error))?;
+        let shown = &data[..data.len().min(limit)];
+        let mut text = format!("{} bytes decoded\n{}", data.len(), hexdump(shown, 0, None));
+        if shown.len() < data.len() {
+            text.push_str(&format!("\n... {} more bytes", data.len() - shown.len()));
+        };
+        Ok(text)
+    }
+
+    /// The bytes of the object shown as they are in the file.
+    fn raw(&self) -> Result<String, String> {
+        let id = self.object_shown()?;
+        let span = self.file()?.object_map.object_span(id).map_err(|error| error.to_string())?;
+        Ok(format!("Bytes {} to {}\n{}", span.start, span.end, hexdump(&self.data[span.clone()], span.start, None)))
+    }
+
+    fn object_shown(&self) -> Result<ObjectId, String> {
+        match self.view {
+            Some(View::Object(id)) => Ok(id),
+            _ => Err("Show an object first".to_string())
+        }
+    }
+
+    fn file(&self) -> Result<&PdfFileHandler, String> {
+        self.file.as_ref().ok_or_else(|| "The file couldn't be opened".to_string())
+    }
+}
+
+/// A number given in decimal or as 0x hex.
+fn parse_number(word: &str) -> Result<usize, String> {
+    let parsed = match word.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => word.parse()
+    };
+    parsed.map_err(|_| format!("{} isn't a number", word))
+}
+
+/// An object in PDF syntax, with dictionaries and arrays of objects spread over lines, and each reference
+/// numbered with its place in links.
+fn describe(object: &PdfObject, links: &mut Vec<ObjectId>, indent: usize) -> String {
+    if let Some(id) = object.reference_id() {
+        let number = match links.iter().position(|link| *link == id) {
+            Some(i) => i + 1,
+            None => {
+                links.push(id);
+                links.len()
+            }
+        };
+        return format!("{} {} R [{}]", id.number(), id.generation(), number);
+    };
+    let pad = "  ".repeat(indent + 1);
+    let more = |count: usize| if count > MAX_ITEMS { format!("{}... {} more\n", pad, count - MAX_ITEMS) }
+                              else { String::new() };
+    match object {
+        PdfObject::Actual(PdfData::Dictionary(map)) => {
+            let mut text = String::from("<<\n");
+            for (key, value) in map.iter().take(MAX_ITEMS) {
+                text.push_str(&format!("{}/{} {}\n", pad, key, describe(value, links, indent + 1)));
+            }
+            format!("{}{}{}>>", text, more(map.len()), "  ".repeat(indent))
+        },
+        PdfObject::Actual(PdfData::Array(items)) if items.iter().any(|item| item.is_map() || item.is_array()) => {
+            let mut text = String::from("[\n");
+            for item in items.iter().take(MAX_ITEMS) {
+                text.push_str(&format!("{}{}\n", pad, describe(item, links, indent + 1)));
+            }
+            format!("{}{}{}]", text, more(items.len()), "  ".repeat(indent))
+        },
+        PdfObject::Actual(PdfData::Array(items)) => {
+            let shown: Vec<String> = items.iter().take(MAX_ITEMS).map(|item| describe(item, links, indent)).collect();
+            let rest = if items.len() > MAX_ITEMS { format!(" ... {} more", items.len() - MAX_ITEMS) }
+                       else { String::new() };
+            format!("[{}{}]", shown.join(" "), rest)
+        },
+        PdfObject::Actual(PdfData::BinaryStream(stream)) => {
+            let dict = PdfObject::new_dictionary(std::rc::Rc::new(stream.attributes().clone()));
+            format!("{}\nstream; hex shows its data", describe(&dict, links, indent))
+        },
+        PdfObject::Actual(PdfData::ContentStream(stream)) => {
+            let dict = PdfObject::new_dictionary(std::rc::Rc::new(stream.attributes().clone()));
+            format!("{}\ncontent stream; hex shows its data", describe(&dict, links, indent))
+        },
+        _ => {
+            let mut text = Vec::new();
+            match write_object(&mut text, object, &None, &WriteOptions::default()) {
+                Ok(()) => String::from_utf8_lossy(&text).into_owned(),
+                Err(error) => format!("({})", error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::file;
+
+    fn session(data: Vec<u8>, commands: &str) -> String {
+        let (mut explorer, _) = Explorer::new(data);
+        let mut output = Vec::new();
+        explorer.run(commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_explore() {
+        let data = file(&["<< /Type /Catalog /Pages 2 0 R >>",
+                          "<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+                          "<< /Type /Page /Parent 2 0 R /Contents 4 0 R /MediaBox [0 0 612 792] >>",
+                          "<< /Length 8 >>\nstream\n0 0 m S\nendstream"]);
+        let output = session(data.clone(), "1\n1\n1\n2\nhex\nraw\nb\nb\nobj 9\nnonsense\n7\ngoto 0x12\nq\nobj 1\n");
+        assert!(output.starts_with("421 bytes, 4 objects; type help for commands\nTrailer\n<<\n"), "{}", output);
+        assert!(output.contains("  /Root 1 0 R [1]\n"), "{}", output);
+        // Following links from the trailer down to the content stream
+        assert!(output.contains("Object 1 0 at byte 9\n<<\n  /Type /Catalog\n  /Pages 2 0 R [1]\n>>"), "{}", output);
+        assert!(output.contains("  /Kids [\n    3 0 R [1]\n  ]\n"), "{}", output);
+        assert!(output.contains("Object 4 0 at byte 202\n<<\n  /Length 8\n>>\nstream; hex shows its data"),
+                "{}", output);
+        assert!(output.contains("8 bytes decoded\n 00000000  30 20 30 20 6d 20 53 0a"), "{}", output);
+        assert!(output.contains("Bytes 202 to 257\n 000000ca  34 20 30 20 6f 62 6a"), "{}", output);
+        assert!(output.contains("Error: Object #9 does not exist"), "{}", output);
+        assert!(output.contains("Error: Unknown command nonsense"), "{}", output);
+        assert!(output.contains("Error: No reference [7] here"), "{}", output);
+        assert!(output.contains("Byte 18\n 00000000  25 50 44 46"), "{}", output);
+        assert!(output.contains("\n>00000010  0a 3c 3c 20 2f 54"), "{}", output);
+        assert!(output.contains("In Object 1 0, which starts at byte 9 [1]"), "{}", output);
+        // Nothing after quit runs
+        assert_eq!(output.matches("Object 1 0 at").count(), 1);
+
+        // Without the cross-reference table the file can't be opened, but its bytes can still be looked at
+        let broken = data[..data.len() - 30].to_vec();
+        let output = session(broken, "t\ngoto 3\n");
+        assert!(output.starts_with("391 bytes; only goto works"), "{}", output);
+        assert!(output.contains("Error: The file couldn't be opened\n> Byte 3\n>00000000  25 50 44 46"), "{}", output);
+    }
+}
@@ -0,0 +1,116 @@
+//! A C interface for embedding the parser in other languages.  include/pdfparser.h declares these functions;
+//! regenerate it with `cbindgen --config cbindgen.toml --output include/pdfparser.h` after changing them.
+//!
+//! Documents are opened from bytes and handed out as opaque pointers, which must be released with
+//! pdfparser_free.  Functions never unwind into the caller: errors and panics are reported through the return
+//! value.
+
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::doc_tree::PdfDoc;
+
+/// Open a document from the len bytes at data, which are copied.  Returns null if data is null or the bytes
+/// aren't a readable PDF.
+///
+/// # Safety
+/// data must point to len readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pdfparser_open(data: *const u8, len: usize) -> *mut PdfDoc {
+    if data.is_null() {
+        return ptr::null_mut();
+    };
+    let bytes = slice::from_raw_parts(data, len).to_vec();
+    match catch_unwind(|| PdfDoc::create_pdf_from_bytes(bytes)) {
+        Ok(Ok(doc)) => Box::into_raw(Box::new(doc)),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// The number of pages in the document, or 0 if doc is null.
+///
+/// # Safety
+/// doc must be null or a pointer returned by pdfparser_open that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn pdfparser_page_count(doc: *const PdfDoc) -> usize {
+    match doc.as_ref() {
+        Some(doc) => doc.page_count(),
+        None => 0,
+    }
+}
+
+/// Extract the text of the page at page_index (counting from 0) as UTF-8, like snprintf: as much as fits is
+/// written to buffer, always followed by a NUL if capacity is at least 1, and the full length of the text
+/// without the NUL is returned.  Call with a null buffer and a capacity of 0 to find the size needed.  Returns
+/// -1 if doc is null or the page can't be read.
+///
+/// # Safety
+/// doc must be null or a live pointer from pdfparser_open, and buffer must be null or point to capacity
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pdfparser_page_text(doc: *const PdfDoc, page_index: usize, buffer: *mut c_char,
+                                             capacity: usize) -> isize {
+    let doc = match doc.as_ref() {
+        Some(doc) => doc,
+        None => return -1,
+    };
+    let text = match catch_unwind(AssertUnwindSafe(|| doc.page_text(page_index))) {
+        Ok(Ok(text)) => text,
+        _ => return -1,
+    };
+    if !buffer.is_null() && capacity > 0 {
+        let written = text.len().min(capacity - 1);
+        ptr::copy_nonoverlapping(text.as_ptr(), buffer as *mut u8, written);
+        *buffer.add(written) = 0;
+    };
+    text.len() as isize
+}
+
+/// Release a document returned by pdfparser_open.  Null is ignored.
+///
+/// # Safety
+/// doc must be null or a pointer from pdfparser_open that hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn pdfparser_free(doc: *mut PdfDoc) {
+    if !doc.is_null() {
+        drop(Box::from_raw(doc));
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_page_text() {
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+        builder.add_page_with_entries(b"BT /F1 12 Tf (Hello) Tj ET", TestFilter::None,
+                                      &format!("/Resources << /Font << /F1 {} 0 R >> >>", font));
+        builder.add_page(b"", TestFilter::None);
+        let bytes = builder.build();
+        unsafe {
+            let doc = pdfparser_open(bytes.as_ptr(), bytes.len());
+            assert!(!doc.is_null());
+            assert_eq!(pdfparser_page_count(doc), 2);
+            assert_eq!(pdfparser_page_text(doc, 0, ptr::null_mut(), 0), 6);
+            let mut buffer = [b'x' as c_char; 4];
+            assert_eq!(pdfparser_page_text(doc, 0, buffer.as_mut_ptr(), buffer.len()), 6);
+            assert_eq!(buffer.map(|c| c as u8), *b"Hel\0");
+            let mut buffer = [b'x' as c_char; 16];
+            assert_eq!(pdfparser_page_text(doc, 1, buffer.as_mut_ptr(), buffer.len()), 0);
+            assert_eq!(buffer[0], 0);
+            assert_eq!(pdfparser_page_text(doc, 2, buffer.as_mut_ptr(), buffer.len()), -1);
+            pdfparser_free(doc);
+
+            assert!(pdfparser_open(b"not a pdf".as_ptr(), 9).is_null());
+            assert!(pdfparser_open(b"%PDF".as_ptr(), 4).is_null());
+            assert!(pdfparser_open(ptr::null(), 0).is_null());
+            assert_eq!(pdfparser_page_count(ptr::null()), 0);
+            pdfparser_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#![recursion_limit = "1024"]
+#[path = "pdf_doc/doc_tree.rs"]
+pub mod doc_tree;
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(test)]
+mod test_utils;
+
+#[macro_use]
+extern crate error_chain;
+
+#[macro_use]
+extern crate log;
+
+pub mod errors {
+    error_chain! {
+
+        foreign_links {
+            Fmt(::std::fmt::Error);
+            Io(::std::io::Error);
+            ParseFloat(::std::num::ParseFloatError);
+            ParseInt(::std::num::ParseIntError);
+        }
+        errors {
+            UnavailableType(req: String, thrower: String) {
+                description("Cannot provide requested type")
+                display("Unavailable type {} requested from: {}", req, thrower)
+            }
+            FilterError(description: String, function: &'static str) {
+                description("Error applying/decoding filter")
+                display("{} encountered an error applying/decoding filter {}", function, description)
+            }
+            ParsingError(problem: String) {
+                description("Error parsing PDF file")
+                display("{}", problem)
+            }
+            ContentError(problem: String, offset: usize, after: Option<(usize, String)>, page: Option<usize>) {
+                description("Error parsing content stream")
+                display("{} (content stream byte {}{}{})", problem, offset,
+                        after.as_ref()
+                             .map_or(String::new(), |(index, op)| format!(", after operator {} {}", index, op)),
+                        page.map_or(String::new(), |page| format!(", page {}", page + 1)))
+            }
+            ReferenceError(problem: String) {
+                description("Bad reference")
+                display("{}", problem)
+            }
+            TestingError(text: String) {
+                description("Custom error")
+                display("{}", text)
+            }
+            DocTreeError(text: String) {
+                description("Doc tree error")
+                display("{}", text)
+            }
+            LimitExceeded(limit: &'static str, value: usize, max: usize) {
+                description("Parser limit exceeded")
+                display("{} of {} exceeds the configured maximum of {}", limit, value, max)
+            }
+            ObjectTooComplex(offset: usize, max: usize) {
+                description("Object too complex")
+                display("Object at {} is too complex to parse: it has more than {} tokens", offset, max)
+            }
+            Cancelled {
+                description("Operation cancelled")
+                display("Cancelled through ParserOptions::cancel")
+            }
+        }
+    }
+}
+
+use errors::*;
@@ -1,58 +1,86 @@
-#![recursion_limit = "1024"]
-#[path = "pdf_doc/doc_tree.rs"]
-mod doc_tree;
-
-#[macro_use]
-extern crate error_chain;
-
-extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "logger")]
+extern crate pretty_env_logger;
 
-mod errors {
-    error_chain! {
+#[cfg(feature = "fs")]
+mod dump_stream;
+#[cfg(feature = "explore")]
+mod explore;
 
-        foreign_links {
-            Fmt(::std::fmt::Error);
-            Io(::std::io::Error);
-            ParseFloat(::std::num::ParseFloatError);
-            ParseInt(::std::num::ParseIntError);
+#[cfg(feature = "fs")]
+use pdfparser::doc_tree;
+
+fn main() {
+    #[cfg(feature = "logger")]
+    pretty_env_logger::init_timed();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        #[cfg(feature = "explore")]
+        Some("explore") => std::process::exit(explore(args.get(1))),
+        #[cfg(feature = "fs")]
+        Some("dump-stream") => std::process::exit(dump_stream::run(&args[1..])),
+        _ => {}
+    };
+    error!("Oh no!");
+    #[cfg(feature = "fs")]
+    {
+        let pdf_doc = doc_tree::PdfDoc::create_pdf_from_file("data/document.pdf").unwrap();
+        //let mut pdf_doc = doc_tree::PdfDoc::create_pdf_from_file("data/treatise.pdf").unwrap();
+        println!("{}", pdf_doc);
+    }
+}
+
+/// Run an explore session on stdin and stdout, returning the exit code.
+#[cfg(feature = "explore")]
+fn explore(path: Option<&String>) -> i32 {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: pdfparser explore FILE");
+            return 2;
+        }
+    };
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("Can't read {}: {}", path, error);
+            return 1;
         }
-        errors {
-            UnavailableType(req: String, thrower: String) {
-                description("Cannot provide requested type")
-                display("Unavailable type {} requested from: {}", req, thrower)
-            }
-            FilterError(description: String, function: &'static str) {
-                description("Error applying/decoding filter")
-                display("{} encountered an error applying/decoding filter {}", function, description)
-            }
-            ParsingError(problem: String) {
-                description("Error parsing PDF file")
-                display("{}", problem)
-            }
-            ReferenceError(problem: String) {
-                description("Bad reference")
-                display("{}", problem)
-            }
-            TestingError(text: String) {
-                description("Custom error")
-                display("{}", text)
-            }
-            DocTreeError(text: String) {
-                description("Doc tree error")
-                display("{}", text)
-            }
+    };
+    let (mut explorer, error) = explore::Explorer::new(data);
+    if let Some(error) = error {
+        eprintln!("{} can't be opened: {}", path, error);
+    };
+    let stdin = std::io::stdin();
+    match explorer.run(stdin.lock(), &mut std::io::stdout()) {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("{}", error);
+            1
         }
     }
 }
 
-use errors::*;
+#[cfg(all(test, feature = "fs"))]
+mod test_utils {
+    use std::collections::HashMap;
 
-fn main() {
-    pretty_env_logger::init_timed();
-    error!("Oh no!");
-    let pdf_doc = doc_tree::PdfDoc::create_pdf_from_file("data/document.pdf").unwrap();
-    //let mut pdf_doc = doc_tree::PdfDoc::create_pdf_from_file("data/treatise.pdf").unwrap();
-    println!("{}", pdf_doc);
+    /// A file with the given objects, numbered from 1, and a correct cross-reference table.
+    pub fn file(objects: &[&str]) -> Vec<u8> {
+        let mut data = b"%PDF-1.4\n".to_vec();
+        let mut offsets = HashMap::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.insert(i + 1, data.len());
+            data.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).into_bytes());
+        }
+        let xref = data.len();
+        data.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+        for i in 1..=objects.len() {
+            data.extend(format!("{:010} 00000 n \n", offsets[&i]).into_bytes());
+        }
+        data.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref)
+                        .into_bytes());
+        data
+    }
 }
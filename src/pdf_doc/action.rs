@@ -0,0 +1,264 @@
+use super::*;
+
+/// Most actions followed through /Next, and most levels of a name tree searched, so that cyclic files end.
+const MAX_ACTION_DEPTH: usize = 64;
+
+/// A place in a document to go to (spec 12.3.2).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Destination {
+    /// A page, with how to fit it in the window: /XYZ, /Fit, /FitH and so on, and that fit's parameters, which
+    /// are None where the file gives null to keep the current value.  The page is None if it refers to an
+    /// object that isn't a page of this document.
+    Explicit { page: Option<usize>, fit: String, params: Vec<Option<f32>> },
+    /// A name to look up with PdfDoc::named_destination
+    Named(String),
+}
+
+/// A link or bookmark action (spec 12.6.4).  Actions with types not modelled here keep their /S name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    GoTo(Destination),
+    /// A destination in another file.  Explicit destinations there give page indices directly.
+    GoToR { file: String, destination: Destination, new_window: Option<bool> },
+    Uri(String),
+    /// A viewer command like /NextPage or /Print
+    Named(String),
+    Other(String),
+}
+
+impl PdfDoc {
+    /// The action in an /A or /OpenAction dictionary followed by its /Next actions, in the order they run.
+    pub fn action_chain(&self, action: &PdfObject) -> Result<Vec<Action>> {
+        let mut actions = Vec::new();
+        self.add_actions(action, &mut actions, 0)?;
+        Ok(actions)
+    }
+
+    fn add_actions(&self, action: &PdfObject, actions: &mut Vec<Action>, depth: usize) -> Result<()> {
+        if depth > MAX_ACTION_DEPTH {
+            Err(ErrorKind::DocTreeError("Action chain too long or cyclic".to_string()))?
+        };
+        let dict = action.try_into_map()?;
+        actions.push(self.action(&dict)?);
+        match dict.get("Next") {
+            Some(next) if next.is_array() => {
+                for item in next.try_into_array()?.iter() {
+                    self.add_actions(item, actions, depth + 1)?;
+                }
+            },
+            Some(next) => self.add_actions(next, actions, depth + 1)?,
+            None => {}
+        };
+        Ok(())
+    }
+
+    /// A single action dictionary, ignoring /Next.
+    pub fn action(&self, dict: &PdfMap) -> Result<Action> {
+        let action_type = dict.get("S")
+                              .ok_or_else(|| ErrorKind::DocTreeError("Action dictionary missing /S".to_string()))?
+                              .try_into_string()?;
+        let required = |key: &str| dict.get(key).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("{} action missing /{}", action_type, key)));
+        Ok(match action_type.as_str() {
+            "GoTo" => Action::GoTo(self.destination(required("D")?)?),
+            "GoToR" => Action::GoToR {
+                file: file_specification(required("F")?)?,
+                destination: self.destination(required("D")?)?,
+                new_window: dict.get("NewWindow").map(|flag| flag.try_into_bool()).transpose()?,
+            },
+            "URI" => Action::Uri(String::from_utf8_lossy(&required("URI")?.try_into_bytes()?).to_string()),
+            "Named" => Action::Named(required("N")?.try_into_string()?.to_string()),
+            _ => Action::Other(action_type.to_string()),
+        })
+    }
+
+    /// A destination given as an array, a name or string, or a dictionary with a /D entry.
+    pub fn destination(&self, destination: &PdfObject) -> Result<Destination> {
+        if destination.is_map() {
+            let dict = destination.try_into_map()?;
+            let inner = dict.get("D").ok_or_else(|| ErrorKind::DocTreeError("Destination missing /D".to_string()))?;
+            return self.destination(inner);
+        };
+        if !destination.is_array() {
+            return Ok(Destination::Named(String::from_utf8_lossy(&destination.try_into_bytes()
+                .or_else(|_| destination.try_into_string().map(|name| name.as_bytes().to_vec()))?).to_string()));
+        };
+        let array = destination.try_into_array()?;
+        let (page, fit) = match (array.first(), array.get(1)) {
+            (Some(page), Some(fit)) => (page, fit.try_into_string()?.to_string()),
+            _ => Err(ErrorKind::DocTreeError(format!("Invalid destination array of length {}", array.len())))?
+        };
+        let page = match page.reference_id() {
+            Some(id) => self.page_index_of(id),
+            None => Some(page.try_into_int()?.max(0) as usize)
+        };
+        let params = array[2..].iter()
+                               .map(|param| param.is_number().then(|| param.try_into_number()).transpose())
+                               .collect::<Result<Vec<Option<f32>>>>()?;
+        Ok(Destination::Explicit { page, fit, params })
+    }
+
+    /// Look up a named destination in the catalog's /Dests dictionary or its /Names /Dests name tree.
+    pub fn named_destination(&self, name: &str) -> Result<Option<Destination>> {
+        let catalog = self.root.try_into_map()?;
+        if let Some(dests) = catalog.get("Dests") {
+            if let Some(destination) = dests.try_into_map()?.get(name) {
+                return self.destination(destination).map(Some);
+            };
+        };
+        let tree = match catalog.get("Names") {
+            Some(names) => names.try_into_map()?.get("Dests").cloned(),
+            None => None
+        };
+        match tree {
+            Some(tree) => match name_tree_lookup(&tree, name.as_bytes(), 0)? {
+                Some(destination) => self.destination(&destination).map(Some),
+                None => Ok(None)
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Every URI action in objects reachable from the trailer, in the order they're reached.
+    pub fn uris(&self) -> Result<Vec<String>> {
+        let mut uris = Vec::new();
+        self.walk(|_id, object| collect_uris(object, &mut uris, 0))?;
+        Ok(uris)
+    }
+}
+
+/// Search a name tree (spec 7.9.6), using each node's /Limits to pick the kid to descend into.
+fn name_tree_lookup(node: &PdfObject, name: &[u8], depth: usize) -> Result<Option<SharedObject>> {
+    if depth > MAX_ACTION_DEPTH {
+        Err(ErrorKind::DocTreeError("Name tree too deep or cyclic".to_string()))?
+    };
+    let node = node.try_into_map()?;
+    if let Some(names) = node.get("Names") {
+        let names = names.try_into_array()?;
+        for pair in names.chunks(2) {
+            if pair.len() == 2 && pair[0].try_into_bytes()? == name {
+                return Ok(Some(Rc::clone(&pair[1])));
+            };
+        }
+    };
+    if let Some(kids) = node.get("Kids") {
+        for kid in kids.try_into_array()?.iter() {
+            let limits = kid.try_into_map()?.get("Limits").map(|limits| limits.try_into_array()).transpose()?;
+            if let Some(limits) = limits {
+                if limits.len() == 2 {
+                    let (first, last) = (limits[0].try_into_bytes()?, limits[1].try_into_bytes()?);
+                    if name < &first[..] || name > &last[..] {
+                        continue;
+                    };
+                };
+            };
+            if let Some(found) = name_tree_lookup(kid, name, depth + 1)? {
+                return Ok(Some(found));
+            };
+        }
+    };
+    Ok(None)
+}
+
+/// Add every entry of a name tree to entries, by key.
+pub(super) fn name_tree_entries(node: &PdfObject, entries: &mut BTreeMap<Vec<u8>, SharedObject>, depth: usize)
+                                -> Result<()> {
+    if depth > MAX_ACTION_DEPTH {
+        Err(ErrorKind::DocTreeError("Name tree too deep or cyclic".to_string()))?
+    };
+    let node = node.try_into_map()?;
+    if let Some(names) = node.get("Names") {
+        for pair in names.try_into_array()?.chunks(2) {
+            if pair.len() == 2 {
+                entries.insert(pair[0].try_into_bytes()?, Rc::clone(&pair[1]));
+            };
+        }
+    };
+    if let Some(kids) = node.get("Kids") {
+        for kid in kids.try_into_array()?.iter() {
+            name_tree_entries(kid, entries, depth + 1)?;
+        }
+    };
+    Ok(())
+}
+
+/// The file name in a file specification, which is a string or a dictionary (spec 7.11).
+fn file_specification(spec: &PdfObject) -> Result<String> {
+    if spec.is_map() {
+        let dict = spec.try_into_map()?;
+        let name = ["UF", "F", "Unix", "DOS", "Mac"].iter().find_map(|key| dict.get(key))
+            .ok_or_else(|| ErrorKind::DocTreeError("File specification without a file name".to_string()))?;
+        return file_specification(name);
+    };
+    Ok(String::from_utf8_lossy(&spec.try_into_bytes()?).to_string())
+}
+
+fn collect_uris(object: &PdfObject, uris: &mut Vec<String>, depth: usize) {
+    if depth > MAX_ACTION_DEPTH {
+        return;
+    };
+    match object {
+        PdfObject::Actual(Dictionary(dict)) => {
+            let is_uri = dict.get("S").is_some_and(|s| s.try_into_string().is_ok_and(|s| s.as_str() == "URI"));
+            if let (true, Some(uri)) = (is_uri, dict.get("URI")) {
+                if let Ok(bytes) = uri.try_into_bytes() {
+                    uris.push(String::from_utf8_lossy(&bytes).to_string());
+                };
+            };
+            dict.values().for_each(|value| collect_uris(value, uris, depth + 1));
+        },
+        PdfObject::Actual(Array(array)) => array.iter().for_each(|item| collect_uris(item, uris, depth + 1)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_actions() {
+        let mut builder = MiniPdfBuilder::new().with_catalog_entries(
+            "/Names << /Dests << /Kids [<< /Limits [(a) (m)] /Names [(intro) [4 0 R /Fit]] >> \
+                                       << /Limits [(n) (z)] /Names [(summary) [6 0 R /FitH 700]] >>] >> >>");
+        builder.add_page(b"", TestFilter::None);
+        builder.add_page(b"", TestFilter::None);
+        let next = builder.add_object("<< /S /URI /URI (https://example.com/a) /Next << /S /Named /N /NextPage >> >>");
+        let action = builder.add_object(format!(
+            "<< /S /GoTo /D [6 0 R /XYZ 0 null 1.5] /Next [{} 0 R << /S /GoToR /F << /Type /Filespec /F (other.pdf) >> \
+               /D [0 /Fit] /NewWindow true >> << /S /Launch /F (x) >>] >>", next));
+        builder.add_object(format!("<< /Type /Annot /Subtype /Link /A {} 0 R >>", action));
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let actions = doc.action_chain(&doc.get_object(ObjectId::new(action, 0)).unwrap()).unwrap();
+        assert_eq!(actions, vec![
+            Action::GoTo(Destination::Explicit { page: Some(1), fit: "XYZ".to_string(),
+                                                 params: vec![Some(0.0), None, Some(1.5)] }),
+            Action::Uri("https://example.com/a".to_string()),
+            Action::Named("NextPage".to_string()),
+            Action::GoToR { file: "other.pdf".to_string(), new_window: Some(true), destination:
+                            Destination::Explicit { page: Some(0), fit: "Fit".to_string(), params: vec![] } },
+            Action::Other("Launch".to_string()),
+        ]);
+
+        assert_eq!(doc.named_destination("summary").unwrap(),
+                   Some(Destination::Explicit { page: Some(1), fit: "FitH".to_string(), params: vec![Some(700.0)] }));
+        let intro = doc.named_destination("intro").unwrap();
+        assert!(matches!(intro, Some(Destination::Explicit { page: Some(0), .. })));
+        assert_eq!(doc.named_destination("missing").unwrap(), None);
+        // The link annotation isn't reachable from the trailer, so its URI isn't found
+        assert_eq!(doc.uris().unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_uris() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page_with_entries(b"", TestFilter::None,
+                                      "/Annots [<< /Subtype /Link /A << /S /URI /URI (http://a.example) >> >>]");
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(doc.uris().unwrap(), vec!["http://a.example".to_string()]);
+        let untyped = PdfObject::new_dictionary(Rc::new(PdfMap::new()));
+        assert!(doc.action_chain(&untyped).is_err());
+    }
+}
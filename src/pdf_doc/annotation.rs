@@ -0,0 +1,606 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::*;
+use super::form::number;
+use crate::doc_tree::decode::PdfBinaryStream;
+
+/// Annotation flags (spec 12.5.3) for annotations not shown on screen: Hidden and NoView.
+const UNSEEN_FLAGS: i32 = 2 | 32;
+
+/// An annotation's normal appearance as it's shown on its page, from PdfDoc::annotation_appearances.
+#[derive(Debug, Clone)]
+pub struct AnnotationAppearance {
+    /// The annotation, unless it's a direct dictionary
+    pub id: Option<ObjectId>,
+    pub subtype: String,
+    pub rect: Rect,
+    /// The appearance stream, a form XObject
+    pub appearance: ObjectId,
+    /// The appearance's /Matrix, from its form space to the space of its /BBox
+    pub matrix: Matrix,
+    /// The transformation viewers apply after the matrix (spec 12.5.5): the box bounding the /BBox as the
+    /// matrix transforms it, scaled and moved onto the annotation's /Rect
+    pub fit: Matrix,
+    /// The text and paths of the appearance, in default user space on the page
+    pub text_blocks: Vec<TextBlock>,
+    pub paths: Vec<Path>,
+}
+
+/// An appearance stream with what's needed to place it on its page.
+struct Placement {
+    appearance: ObjectId,
+    stream: Rc<PdfBinaryStream>,
+    rect: Rect,
+    bbox: Rect,
+    matrix: Matrix,
+    fit: Matrix,
+}
+
+/// Where a link made by PdfDoc::add_link goes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    /// A web address or other URI, opened by a URI action
+    Uri(String),
+    /// A page of this document, by index, scrolled to put y at the top of the window
+    Page { page: usize, y: f32 },
+    /// A named destination of this document, looked up when the link is followed
+    Named(String),
+}
+
+impl PdfDoc {
+    /// Add a link annotation (spec 12.5.6.5) covering rect on the page at page_index, drawn without a border,
+    /// and return its id.
+    pub fn add_link(&mut self, page_index: usize, rect: Rect, target: &LinkTarget) -> Result<ObjectId> {
+        let mut annot = annotation_dict("Link", &rect);
+        annot.insert("Border".to_string(), Rc::new(PdfObject::new_array(Rc::new(
+            (0..3).map(|_| Rc::new(PdfObject::new_number_int(0))).collect()))));
+        match target {
+            LinkTarget::Uri(uri) => {
+                let mut action = PdfMap::new();
+                action.insert("S".to_string(), Rc::new(PdfObject::new_name("URI")));
+                action.insert("URI".to_string(), Rc::new(PdfObject::new_byte_string(uri.as_bytes())));
+                annot.insert("A".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(action))));
+            },
+            LinkTarget::Page { page, y } => {
+                annot.insert("Dest".to_string(), Rc::new(self.page_destination(*page, *y)?));
+            },
+            LinkTarget::Named(name) => {
+                // Destination names are matched as bytes, and set_named_destination keys them by their UTF-8
+                annot.insert("Dest".to_string(), Rc::new(PdfObject::new_byte_string(name.as_bytes())));
+            },
+        };
+        self.add_annotation(page_index, annot)
+    }
+
+    /// Add a highlight annotation (spec 12.5.6.10) over the text of a search match, in the given RGB colour,
+    /// and return its id.  Its appearance is made too, multiplying the colour over the text, so that it shows
+    /// the same in viewers that don't draw highlights themselves.
+    pub fn add_highlight(&mut self, found: &SearchMatch, color: [f32; 3]) -> Result<ObjectId> {
+        let bounds = found.rects.iter().copied().reduce(|bounds, rect| bounds.union(&rect))
+                          .ok_or_else(|| ErrorKind::DocTreeError("Search match has no rectangles".to_string()))?;
+        let quad_points = found.quad_points();
+        let number_array = |values: &[f32]| Rc::new(PdfObject::new_array(Rc::new(
+            values.iter().map(|value| Rc::new(PdfObject::new_number_float(*value))).collect())));
+        let mut annot = annotation_dict("Highlight", &bounds);
+        annot.insert("QuadPoints".to_string(), number_array(&quad_points));
+        annot.insert("C".to_string(), number_array(&color));
+        annot.insert("Contents".to_string(), Rc::new(PdfObject::new_text_string(&found.text)));
+
+        let mut drawing = format!("/GS0 gs {} {} {} rg\n", number(color[0]), number(color[1]), number(color[2]));
+        for quad in quad_points.chunks(8) {
+            writeln!(drawing, "{} {} m {} {} l {} {} l {} {} l f", number(quad[0]), number(quad[1]),
+                     number(quad[2]), number(quad[3]), number(quad[6]), number(quad[7]),
+                     number(quad[4]), number(quad[5])).unwrap();
+        }
+        let mut multiply = PdfMap::new();
+        multiply.insert("Type".to_string(), Rc::new(PdfObject::new_name("ExtGState")));
+        multiply.insert("BM".to_string(), Rc::new(PdfObject::new_name("Multiply")));
+        let mut states = PdfMap::new();
+        states.insert("GS0".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(multiply))));
+        let mut resources = PdfMap::new();
+        resources.insert("ExtGState".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(states))));
+        let mut form = PdfMap::new();
+        form.insert("Type".to_string(), Rc::new(PdfObject::new_name("XObject")));
+        form.insert("Subtype".to_string(), Rc::new(PdfObject::new_name("Form")));
+        form.insert("BBox".to_string(), Rc::new(rect_object(&bounds)));
+        form.insert("Resources".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(resources))));
+        let cache = &self.file.object_map;
+        let appearance = cache.add_object(PdfObject::new_binary_stream(PdfBinaryStream::new(form,
+                                                                                            drawing.into_bytes())));
+        let mut appearances = PdfMap::new();
+        appearances.insert("N".to_string(), Rc::new(cache.reference(appearance)));
+        annot.insert("AP".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(appearances))));
+        self.add_annotation(found.page, annot)
+    }
+
+    /// Draw the normal appearances (/AP /N) of the annotations whose /Subtype is one of kinds, such as Highlight
+    /// or Stamp, into the content of their pages, and remove the annotations and their pop-ups, leaving pages
+    /// that look the same without them.  Each appearance is placed as viewers place it; see
+    /// AnnotationAppearance::fit.  Annotations that aren't shown on screen or have no appearance are removed
+    /// without drawing anything.  If widgets are flattened and none are left, the /AcroForm goes too.
+    /// Returns how many annotations were flattened.
+    pub fn flatten_annotations(&mut self, kinds: &[&str]) -> Result<usize> {
+        let mut flattened = 0;
+        for page_index in 0..self.page_count() {
+            let page_id = self.page_id(page_index)?;
+            let annots = match self.get_dict(page_id)?.get("Annots") {
+                Some(annots) => annots.try_into_array()?.as_ref().clone(),
+                None => continue
+            };
+            let annot_count = annots.len();
+            // Names the page's resources already use, as by an earlier flattening
+            let mut taken: HashSet<String> = match self.inherited_page_attribute(page_index, "Resources")? {
+                Some(resources) => match resources.try_into_map()?.get("XObject") {
+                    Some(xobjects) => xobjects.try_into_map()?.keys().cloned().collect(),
+                    None => HashSet::new()
+                },
+                None => HashSet::new()
+            };
+            let mut drawing = String::new();
+            let mut appearances = Vec::new();
+            let mut popups = HashSet::new();
+            let mut kept = Vec::new();
+            for (index, annot) in annots.into_iter().enumerate() {
+                // Annotations that can't be read are left in /Annots as they are
+                let read = |annot: &SharedObject| -> Result<Option<(Rc<PdfMap>, Option<Placement>)>> {
+                    let dict = annot.try_into_map()?;
+                    let subtype = match dict.get("Subtype") {
+                        Some(subtype) => Some(subtype.try_into_string()?),
+                        None => None
+                    };
+                    if !subtype.is_some_and(|subtype| kinds.contains(&subtype.as_str())) {
+                        return Ok(None);
+                    };
+                    let placement = self.appearance_placement(&dict)?;
+                    Ok(Some((dict, placement)))
+                };
+                let (dict, placement) = match read(&annot) {
+                    Ok(Some(flattening)) => flattening,
+                    Ok(None) => {
+                        kept.push(annot);
+                        continue;
+                    },
+                    Err(error) => {
+                        warn!("Not flattening annotation {} on page {}: {}", index, page_index, error);
+                        kept.push(annot);
+                        continue;
+                    }
+                };
+                flattened += 1;
+                if let Some(popup) = dict.get("Popup").and_then(|popup| popup.reference_id()) {
+                    popups.insert(popup);
+                };
+                let placement = match placement {
+                    Some(placement) => placement,
+                    None => continue
+                };
+                let name = (0..).map(|index| format!("FlattenAnnot{}", index))
+                                .find(|name| !taken.contains(name))
+                                .unwrap();
+                taken.insert(name.clone());
+                // Do applies the form's own /Matrix
+                let fit = placement.fit;
+                writeln!(drawing, "q {} {} {} {} {} {} cm /{} Do Q", number(fit.a), number(fit.b), number(fit.c),
+                         number(fit.d), number(fit.e), number(fit.f), name).unwrap();
+                appearances.push((name, placement.appearance));
+            }
+            kept.retain(|annot| !annot.reference_id().is_some_and(|id| popups.contains(&id)));
+            if kept.len() == annot_count {
+                continue;
+            };
+            self.add_flattened_annotations(page_index, page_id, &drawing, appearances, kept)?;
+        }
+        if kinds.contains(&"Widget") && !self.has_widgets()? {
+            let catalog_id = self.catalog_id()?;
+            self.update_dict(catalog_id, |catalog| { catalog.remove("AcroForm"); })?;
+        };
+        self.reload_page_tree()?;
+        Ok(flattened)
+    }
+
+    /// The normal appearances of the annotations on the page at page_index that are shown on screen, in /Annots
+    /// order, with their content interpreted in place on the page.  Annotations that can't be read, such as
+    /// ones with no /BBox or an appearance stream that doesn't parse, are skipped with a warning.
+    pub fn annotation_appearances(&self, page_index: usize) -> Result<Vec<AnnotationAppearance>> {
+        let page_id = self.page_id(page_index)?;
+        let annots = match self.get_dict(page_id)?.get("Annots") {
+            Some(annots) => annots.try_into_array()?,
+            None => return Ok(Vec::new())
+        };
+        let mut appearances = Vec::new();
+        for (index, annot) in annots.iter().enumerate() {
+            match self.annotation_appearance(page_index, annot) {
+                Ok(Some(appearance)) => appearances.push(appearance),
+                Ok(None) => {},
+                Err(error) => warn!("Skipping annotation {} on page {}: {}", index, page_index, error)
+            };
+        }
+        Ok(appearances)
+    }
+
+    /// The normal appearance of one annotation, or None if it isn't shown.
+    fn annotation_appearance(&self, page_index: usize, annot: &SharedObject) -> Result<Option<AnnotationAppearance>> {
+        let dict = annot.try_into_map()?;
+        let placement = match self.appearance_placement(&dict)? {
+            Some(placement) => placement,
+            None => return Ok(None)
+        };
+        // Forms without resources of their own use the page's, as older files expect
+        let resources = match placement.stream.attributes().get("Resources") {
+            Some(resources) => Some(resources.try_into_map()?),
+            None => match self.inherited_page_attribute(page_index, "Resources")? {
+                Some(resources) => Some(resources.try_into_map()?),
+                None => None
+            }
+        };
+        let commands = CommandStream::parse(placement.stream.data()?).chain_err(|| ErrorKind::DocTreeError(
+            format!("Invalid appearance stream {}", placement.appearance)))?;
+        let mut interpreter = Interpreter::with_transform(resources, placement.matrix * placement.fit,
+                                                          Some(placement.bbox));
+        interpreter.run(&commands)?;
+        Ok(Some(AnnotationAppearance {
+            id: annot.reference_id(),
+            subtype: match dict.get("Subtype") {
+                Some(subtype) => subtype.try_into_string()?.to_string(),
+                None => String::new()
+            },
+            rect: placement.rect,
+            appearance: placement.appearance,
+            matrix: placement.matrix,
+            fit: placement.fit,
+            text_blocks: interpreter.text_blocks,
+            paths: interpreter.paths,
+        }))
+    }
+
+    /// The appearance an annotation shows on screen and where it goes, or None if it's hidden or has none.
+    fn appearance_placement(&self, annot: &PdfMap) -> Result<Option<Placement>> {
+        let flags = annot.get("F").map_or(Ok(0), |flags| flags.try_into_int())?;
+        let appearance = match self.normal_appearance(annot)? {
+            Some(appearance) if flags & UNSEEN_FLAGS == 0 => appearance,
+            _ => return Ok(None)
+        };
+        let stream = self.get_object(appearance)?.try_into_binary_stream()?;
+        let matrix = match stream.attributes().get("Matrix") {
+            Some(matrix) => {
+                let values = matrix.try_into_array()?.iter().map(|n| n.try_into_number())
+                                   .collect::<Result<Vec<f32>>>()?;
+                if values.len() != 6 {
+                    Err(ErrorKind::DocTreeError(format!("Invalid /Matrix in appearance stream {}", appearance)))?
+                };
+                Matrix::new(values[0], values[1], values[2], values[3], values[4], values[5])
+            },
+            None => Matrix::identity()
+        };
+        let (rect, bbox) = (dict_rect(annot, "Rect")?, dict_rect(stream.attributes(), "BBox")?);
+        Ok(Some(Placement { appearance, rect, bbox, matrix, fit: appearance_fit(&rect, &bbox, &matrix), stream }))
+    }
+
+    /// The stream an annotation shows normally: its /AP /N, or the entry of /N its /AS picks if it has states.
+    fn normal_appearance(&self, annot: &PdfMap) -> Result<Option<ObjectId>> {
+        let normal = match annot.get("AP") {
+            Some(appearances) => match appearances.try_into_map()?.get("N") {
+                Some(normal) => Rc::clone(normal),
+                None => return Ok(None)
+            },
+            None => return Ok(None)
+        };
+        if normal.is_stream() {
+            return Ok(normal.reference_id());
+        };
+        let state = match annot.get("AS") {
+            Some(state) => state.try_into_string()?,
+            None => return Ok(None)
+        };
+        Ok(normal.try_into_map()?.get(state.as_str()).and_then(|appearance| appearance.reference_id()))
+    }
+
+    /// Append drawing to a page's content, isolated from the existing content's graphics state, with the
+    /// appearances it draws added to the page's XObject resources and its annotations replaced.
+    fn add_flattened_annotations(&self, page_index: usize, page_id: ObjectId, drawing: &str,
+                                 appearances: Vec<(String, ObjectId)>, annots: Vec<SharedObject>) -> Result<()> {
+        let mut resources = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => resources.try_into_map()?.as_ref().clone(),
+            None => PdfMap::new()
+        };
+        let mut xobjects = match resources.get("XObject") {
+            Some(xobjects) => xobjects.try_into_map()?.as_ref().clone(),
+            None => PdfMap::new()
+        };
+        for (name, appearance) in appearances {
+            // Do needs a form XObject, which appearance streams are, whether or not they say so
+            self.update_stream_dict(appearance, |form| {
+                form.insert("Type".to_string(), Rc::new(PdfObject::new_name("XObject")));
+                form.insert("Subtype".to_string(), Rc::new(PdfObject::new_name("Form")));
+            })?;
+            xobjects.insert(name, Rc::new(self.file.object_map.reference(appearance)));
+        }
+        resources.insert("XObject".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(xobjects))));
+        if !drawing.is_empty() {
+            self.wrap_page_content(page_id, b"q\n", format!("Q\n{}", drawing).as_bytes())?;
+        };
+        self.update_dict(page_id, |page| {
+            page.insert("Resources".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(resources))));
+            if annots.is_empty() {
+                page.remove("Annots");
+            } else {
+                page.insert("Annots".to_string(), Rc::new(PdfObject::new_array(Rc::new(annots))));
+            };
+        })
+    }
+
+    /// Replace a stream object with one whose dictionary is an edited copy, keeping its data.
+    fn update_stream_dict<F: FnOnce(&mut PdfMap)>(&self, id: ObjectId, edit: F) -> Result<()> {
+        let stream = self.get_object(id)?.try_into_binary_stream()?;
+        let mut dict = stream.attributes().clone();
+        edit(&mut dict);
+        let data = stream.data()?.to_vec();
+        self.file.object_map.set_object(id, PdfObject::new_binary_stream(PdfBinaryStream::new(dict, data)));
+        Ok(())
+    }
+
+    /// Whether any page has a widget annotation left.
+    fn has_widgets(&self) -> Result<bool> {
+        for page_index in 0..self.page_count() {
+            if let Some(annots) = self.get_dict(self.page_id(page_index)?)?.get("Annots") {
+                // Unreadable annotations, which flattening leaves alone, aren't widgets it can remove
+                for annot in annots.try_into_array()?.iter().filter_map(|annot| annot.try_into_map().ok()) {
+                    let subtype = annot.get("Subtype").cloned();
+                    if subtype.is_some_and(|subtype| subtype.try_into_string().is_ok_and(|s| s.as_str() == "Widget")) {
+                        return Ok(true);
+                    };
+                }
+            };
+        }
+        Ok(false)
+    }
+
+    /// Add an annotation dictionary as an object of its own, with /P pointing at its page, to the end of the
+    /// page's /Annots, and return its id.
+    fn add_annotation(&mut self, page_index: usize, mut annot: PdfMap) -> Result<ObjectId> {
+        let page_id = self.page_id(page_index)?;
+        let cache = &self.file.object_map;
+        annot.insert("P".to_string(), Rc::new(cache.reference(page_id)));
+        let id = cache.add_object(PdfObject::new_dictionary(Rc::new(annot)));
+        let reference = Rc::new(cache.reference(id));
+        match self.get_dict(page_id)?.get("Annots") {
+            // An array that is an object of its own may be shared, but then so is every annotation in it
+            Some(annots) if annots.reference_id().is_some() => {
+                let annots_id = annots.reference_id().unwrap();
+                let mut annots = self.get_array(annots_id)?.as_ref().clone();
+                annots.push(reference);
+                cache.set_object(annots_id, PdfObject::new_array(Rc::new(annots)));
+            },
+            annots => {
+                let mut annots = match annots {
+                    Some(annots) => annots.try_into_array()?.as_ref().clone(),
+                    None => Vec::new()
+                };
+                annots.push(reference);
+                self.update_dict(page_id, |page| {
+                    page.insert("Annots".to_string(), Rc::new(PdfObject::new_array(Rc::new(annots))));
+                })?;
+            }
+        };
+        self.reload_page_tree()?;
+        Ok(id)
+    }
+}
+
+/// The transformation taking an appearance's /BBox, after its /Matrix, onto the annotation's /Rect (spec
+/// 12.5.5).  A box with no width or height is only moved in that direction.
+fn appearance_fit(rect: &Rect, bbox: &Rect, matrix: &Matrix) -> Matrix {
+    let transformed = matrix.transform_rect(bbox);
+    let scale = |to: f32, from: f32| if from > 0.0 { to / from } else { 1.0 };
+    Matrix::translation(-transformed.x0, -transformed.y0)
+        * Matrix::scale(scale(rect.width(), transformed.width()), scale(rect.height(), transformed.height()))
+        * Matrix::translation(rect.x0, rect.y0)
+}
+
+/// A rectangle entry of a dictionary, such as an annotation's /Rect.
+fn dict_rect(dict: &PdfMap, key: &str) -> Result<Rect> {
+    let values = match dict.get(key) {
+        Some(rect) => rect.try_into_array()?.iter().map(|n| n.try_into_number()).collect::<Result<Vec<f32>>>()?,
+        None => Vec::new()
+    };
+    if values.len() != 4 {
+        Err(ErrorKind::DocTreeError(format!("Missing or invalid /{}", key)))?
+    };
+    Ok(Rect::new(values[0], values[1], values[2], values[3]))
+}
+
+/// The entries every new annotation has: its type, its rectangle, and the print flag, so that it's printed
+/// as it's shown.
+fn annotation_dict(subtype: &str, rect: &Rect) -> PdfMap {
+    let mut annot = PdfMap::new();
+    annot.insert("Type".to_string(), Rc::new(PdfObject::new_name("Annot")));
+    annot.insert("Subtype".to_string(), Rc::new(PdfObject::new_name(subtype)));
+    annot.insert("Rect".to_string(), Rc::new(rect_object(rect)));
+    annot.insert("F".to_string(), Rc::new(PdfObject::new_number_int(4)));
+    annot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn numbers(value: &PdfObject) -> Vec<f32> {
+        value.try_into_array().unwrap().iter().map(|n| n.try_into_number().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_add_annotations() {
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let resources = format!("/Resources << /Font << /F1 {} 0 R >> >>", font);
+        let content = b"BT /F1 10 Tf 72 700 Td 12 TL (A world) Tj T* (apart) Tj ET";
+        builder.add_page_with_entries(content, TestFilter::None, &resources);
+        // The second page's annotations are an array object of their own
+        let annots = builder.add_object("[]");
+        builder.add_page_with_entries(b"", TestFilter::None, &format!("/Annots {} 0 R", annots));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let found = pdf.search_regex(Regex::new(r"world\s+apart").unwrap()).next().unwrap().unwrap();
+        assert_eq!(found.quad_points(), vec![84.0, 708.0, 114.0, 708.0, 84.0, 698.0, 114.0, 698.0,
+                                             72.0, 696.0, 102.0, 696.0, 72.0, 686.0, 102.0, 686.0]);
+        let highlight = pdf.add_highlight(&found, [1.0, 1.0, 0.0]).unwrap();
+        let link = pdf.add_link(0, Rect::new(72.0, 600.0, 200.0, 620.0),
+                                &LinkTarget::Uri("https://example.com/a b".to_string())).unwrap();
+        pdf.add_link(1, Rect::new(0.0, 0.0, 10.0, 10.0), &LinkTarget::Page { page: 0, y: 700.0 }).unwrap();
+        pdf.add_link(1, Rect::new(0.0, 0.0, 10.0, 10.0), &LinkTarget::Named("Kapitel \u{FC}".to_string())).unwrap();
+        pdf.set_named_destination("Kapitel \u{FC}", 0, 500.0).unwrap();
+        assert!(pdf.add_link(2, Rect::new(0.0, 0.0, 10.0, 10.0), &LinkTarget::Named("x".to_string())).is_err());
+
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let pdf = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(pdf.uris().unwrap(), vec!["https://example.com/a b".to_string()]);
+        let page = pdf.get_dict(pdf.page_id(0).unwrap()).unwrap();
+        let page_annots = page["Annots"].try_into_array().unwrap();
+        assert_eq!(page_annots.iter().map(|annot| annot.reference_id().unwrap()).collect::<Vec<ObjectId>>(),
+                   vec![highlight, link]);
+        let highlight = pdf.get_dict(highlight).unwrap();
+        assert_eq!(highlight["Subtype"].try_into_string().unwrap().as_str(), "Highlight");
+        assert_eq!(numbers(&highlight["Rect"]), vec![72.0, 686.0, 114.0, 708.0]);
+        assert_eq!(numbers(&highlight["QuadPoints"]).len(), 16);
+        assert_eq!(highlight["P"].reference_id(), Some(pdf.page_id(0).unwrap()));
+        assert_eq!(highlight["Contents"].try_into_text().unwrap(), "world\napart");
+        let appearance = highlight["AP"].try_into_map().unwrap()["N"].reference_id().unwrap();
+        assert_eq!(pdf.get_stream_data(appearance).unwrap(),
+                   b"/GS0 gs 1 1 0 rg\n84 708 m 114 708 l 114 698 l 84 698 l f\n\
+                     72 696 m 102 696 l 102 686 l 72 686 l f\n".to_vec());
+
+        let annots = pdf.get_array(ObjectId::new(annots, 0)).unwrap();
+        let dests = annots.iter().map(|annot| pdf.destination(&annot.try_into_map().unwrap()["Dest"]).unwrap())
+                          .collect::<Vec<Destination>>();
+        assert_eq!(dests, vec![
+            Destination::Explicit { page: Some(0), fit: "XYZ".to_string(), params: vec![None, Some(700.0), None] },
+            Destination::Named("Kapitel \u{FC}".to_string()),
+        ]);
+        assert_eq!(pdf.named_destination("Kapitel \u{FC}").unwrap(), Some(Destination::Explicit {
+            page: Some(0), fit: "XYZ".to_string(), params: vec![None, Some(500.0), None]
+        }));
+    }
+
+    #[test]
+    fn test_flatten_annotations() {
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let highlight = builder.add_stream(b"1 1 0 rg 0 0 20 10 re f", TestFilter::None, "/BBox [0 0 20 10]");
+        let stamp = builder.add_stream(b"BT /F1 5 Tf 5 5 Td (APPROVED) Tj ET", TestFilter::Flate,
+                                       &format!("/BBox [5 5 25 25] /Resources << /Font << /F1 {} 0 R >> >>", font));
+        let annots = [
+            format!("<< /Subtype /Highlight /Rect [100 100 120 110] /AP << /N {} 0 R >> /Popup 7 0 R >>", highlight),
+            "<< /Subtype /Popup /Rect [0 0 50 50] >>".to_string(),
+            "<< /Subtype /Link /Rect [0 0 10 10] /A << /S /URI /URI (http://a.example) >> >>".to_string(),
+            format!("<< /Subtype /Stamp /Rect [200 200 220 220] /AP << /N << /On {} 0 R >> >> /AS /On >>", stamp),
+            format!("<< /Subtype /Stamp /F 2 /Rect [0 0 20 20] /AP << /N {} 0 R >> >>", stamp),
+            format!("<< /Subtype /Widget /FT /Tx /T (name) /Rect [0 0 20 20] /AP << /N {} 0 R >> >>", highlight),
+        ].iter().map(|annot| builder.add_object(annot)).collect::<Vec<u32>>();
+        assert_eq!(annots[1], 7);
+        let refs = annots[..5].iter().map(|id| format!("{} 0 R", id)).collect::<Vec<String>>().join(" ");
+        builder.add_page_with_entries(b"0 0 m 10 10 l S", TestFilter::None, &format!("/Annots [{}]", refs));
+        builder.add_page_with_entries(b"", TestFilter::None, &format!("/Annots [{} 0 R]", annots[5]));
+        let builder = builder.with_catalog_entries(&format!("/AcroForm << /Fields [{} 0 R] >>", annots[5]));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        assert_eq!(pdf.flatten_annotations(&["Highlight", "Stamp"]).unwrap(), 3);
+        assert!(pdf.root.try_into_map().unwrap().contains_key("AcroForm"));
+        assert_eq!(pdf.flatten_annotations(&["Widget"]).unwrap(), 1);
+        assert!(!pdf.root.try_into_map().unwrap().contains_key("AcroForm"));
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let pdf = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+
+        let page = pdf.get_dict(pdf.page_id(0).unwrap()).unwrap();
+        assert_eq!(page["Annots"].try_into_array().unwrap().len(), 1);
+        let xobjects = page["Resources"].try_into_map().unwrap()["XObject"].try_into_map().unwrap();
+        assert_eq!(xobjects.keys().cloned().collect::<Vec<String>>(), vec!["FlattenAnnot0", "FlattenAnnot1"]);
+        let content = String::from_utf8(pdf.page_content(0).unwrap()).unwrap();
+        assert_eq!(content, "q\n\n0 0 m 10 10 l S\nQ\n\
+                             q 1 0 0 1 100 100 cm /FlattenAnnot0 Do Q\n\
+                             q 1 0 0 1 195 195 cm /FlattenAnnot1 Do Q\n");
+        assert!(!pdf.get_dict(pdf.page_id(1).unwrap()).unwrap().contains_key("Annots"));
+
+        // Flattening again adds to the XObjects rather than replacing those already there
+        let mut builder = MiniPdfBuilder::new();
+        let highlight = builder.add_stream(b"0 0 20 10 re f", TestFilter::None, "/BBox [0 0 20 10]");
+        let annots = ["Highlight", "Underline"].iter().map(|subtype| format!(
+            "<< /Subtype /{} /Rect [0 0 20 10] /AP << /N {} 0 R >> >>", subtype, highlight)).collect::<Vec<_>>();
+        builder.add_page_with_entries(b"", TestFilter::None, &format!("/Annots [{}]", annots.join(" ")));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.flatten_annotations(&["Highlight"]).unwrap(), 1);
+        assert_eq!(pdf.flatten_annotations(&["Underline"]).unwrap(), 1);
+        let page = pdf.get_dict(pdf.page_id(0).unwrap()).unwrap();
+        let xobjects = page["Resources"].try_into_map().unwrap()["XObject"].try_into_map().unwrap();
+        assert_eq!(xobjects.keys().cloned().collect::<Vec<String>>(), vec!["FlattenAnnot0", "FlattenAnnot1"]);
+        let content = String::from_utf8(pdf.page_content(0).unwrap()).unwrap();
+        assert!(content.contains("/FlattenAnnot0 Do") && content.contains("/FlattenAnnot1 Do"));
+    }
+
+    #[test]
+    fn test_appearance_placement() {
+        let quarter_turn = Matrix::new(0.0, 1.0, -1.0, 0.0, 0.0, 0.0);
+        let bbox = Rect::new(0.0, 0.0, 20.0, 10.0);
+        assert_eq!(appearance_fit(&Rect::new(100.0, 100.0, 110.0, 120.0), &bbox, &quarter_turn),
+                   Matrix::translation(110.0, 100.0));
+        assert_eq!(appearance_fit(&Rect::new(0.0, 0.0, 40.0, 5.0), &bbox, &Matrix::identity()),
+                   Matrix::scale(2.0, 0.5));
+        assert_eq!(appearance_fit(&Rect::new(5.0, 5.0, 5.0, 5.0), &Rect::new(1.0, 1.0, 1.0, 1.0), &quarter_turn),
+                   Matrix::translation(6.0, 4.0));
+
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let turned = builder.add_stream(b"0 0 m 20 0 l S", TestFilter::None,
+                                        "/BBox [0 0 20 10] /Matrix [0 1 -1 0 0 0]");
+        let stamp = builder.add_stream(b"BT /F1 5 Tf 5 5 Td (APPROVED) Tj ET", TestFilter::Flate,
+                                       &format!("/BBox [5 5 25 25] /Resources << /Font << /F1 {} 0 R >> >>", font));
+        let annots = format!("/Annots [<< /Subtype /Square /Rect [100 100 110 120] /AP << /N {0} 0 R >> >> \
+                              << /Subtype /Stamp /Rect [200 200 240 240] /AP << /N {1} 0 R >> >> \
+                              << /Subtype /Stamp /F 32 /Rect [0 0 20 20] /AP << /N {1} 0 R >> >>]", turned, stamp);
+        builder.add_page_with_entries(b"", TestFilter::None, &annots);
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let appearances = pdf.annotation_appearances(0).unwrap();
+        assert_eq!(appearances.iter().map(|appearance| appearance.subtype.as_str()).collect::<Vec<&str>>(),
+                   vec!["Square", "Stamp"]);
+        assert_eq!((appearances[0].id, appearances[0].matrix), (None, quarter_turn));
+        // Clipped to the /BBox, which is the /Rect on the page
+        let paths = &appearances[0].paths;
+        assert_eq!(paths[0].segments, vec![PathSegment::MoveTo(110.0, 100.0), PathSegment::LineTo(110.0, 120.0)]);
+        assert_eq!(paths[0].clip.as_ref().unwrap().bounds(), Some(Rect::new(100.0, 100.0, 110.0, 120.0)));
+        let stamp = &appearances[1];
+        assert_eq!(stamp.fit, Matrix::new(2.0, 0.0, 0.0, 2.0, 190.0, 190.0));
+        assert_eq!((stamp.text_blocks[0].text.as_str(), stamp.text_blocks[0].x, stamp.text_blocks[0].y),
+                   ("APPROVED", 200.0, 200.0));
+        assert_eq!(stamp.text_blocks[0].font_size, 10.0);
+
+        pdf.flatten_annotations(&["Square", "Stamp"]).unwrap();
+        let content = String::from_utf8(pdf.page_content(0).unwrap()).unwrap();
+        assert!(content.ends_with("q 1 0 0 1 110 100 cm /FlattenAnnot0 Do Q\n\
+                                   q 2 0 0 2 190 190 cm /FlattenAnnot1 Do Q\n"), "{}", content);
+
+        let mut builder = MiniPdfBuilder::new();
+        let stamp = builder.add_stream(b"0 0 m 20 20 l S", TestFilter::None, "/BBox [0 0 20 20]");
+        // Unreadable annotations are skipped: a null, one without a /BBox, and one whose stream doesn't parse
+        let no_bbox = builder.add_stream(b"0 0 m 1 1 l S", TestFilter::None, "");
+        let unparsable = builder.add_stream(b"[1 2 Tj", TestFilter::None, "/BBox [0 0 1 1]");
+        builder.add_page_with_entries(b"", TestFilter::None, &format!(
+            "/Annots [null << /Subtype /Ink /Rect [0 0 1 1] /AP << /N {} 0 R >> >> \
+             << /Subtype /Ink /Rect [0 0 1 1] /AP << /N {} 0 R >> >> \
+             << /Subtype /Stamp /Rect [0 0 20 20] /AP << /N {} 0 R >> >>]", no_bbox, unparsable, stamp));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let appearances = pdf.annotation_appearances(0).unwrap();
+        assert_eq!(appearances.iter().map(|appearance| appearance.subtype.as_str()).collect::<Vec<&str>>(),
+                   vec!["Stamp"]);
+        #[cfg(feature = "render")]
+        assert!(pdf.render_page(0, &RenderOptions::default()).is_ok());
+
+        // Flattening leaves the null and the annotation without a /BBox where they are
+        assert_eq!(pdf.flatten_annotations(&["Ink", "Stamp", "Widget"]).unwrap(), 2);
+        let page = pdf.get_dict(pdf.page_id(0).unwrap()).unwrap();
+        let annots = page["Annots"].try_into_array().unwrap();
+        assert_eq!(annots.len(), 2);
+        assert!(matches!(annots[0].as_ref(), PdfObject::Actual(Null)) && annots[1].try_into_map().is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt::Write;
+
+use super::*;
+use super::form::{literal_string, number};
+use crate::doc_tree::decode::PdfBinaryStream;
+
+/// The standard 14 fonts (spec 9.6.2.2), which every viewer has, so documents using them need no font files.
+const STANDARD_14: [&str; 14] = [
+    "Helvetica", "Helvetica-Bold", "Helvetica-Oblique", "Helvetica-BoldOblique",
+    "Times-Roman", "Times-Bold", "Times-Italic", "Times-BoldItalic",
+    "Courier", "Courier-Bold", "Courier-Oblique", "Courier-BoldOblique",
+    "Symbol", "ZapfDingbats",
+];
+/// Space between the baselines of lines of text, as a multiple of the font size.
+const LINE_SPACING: f32 = 1.2;
+
+/// Creates a document from scratch in memory: pages of simple text in the standard 14 fonts, and entries for
+/// the document information dictionary.  Write the built document out with PdfDoc::write.
+#[derive(Debug, Clone)]
+pub struct DocumentBuilder {
+    version: PDFVersion,
+    info: Vec<(String, String)>,
+    pages: Vec<PageBuilder>,
+}
+
+/// A page being added by DocumentBuilder::add_page.
+#[derive(Debug, Clone)]
+pub struct PageBuilder {
+    width: f32,
+    height: f32,
+    text: Vec<TextItem>,
+}
+
+#[derive(Debug, Clone)]
+struct TextItem {
+    font: String,
+    size: f32,
+    x: f32,
+    y: f32,
+    text: String,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        DocumentBuilder { version: PDFVersion::V1_7, info: Vec::new(), pages: Vec::new() }
+    }
+
+    pub fn with_version(mut self, version: PDFVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Set an entry of the document information dictionary, such as Title, Author or Subject.
+    pub fn with_info(mut self, key: &str, value: &str) -> Self {
+        self.info.retain(|(existing, _)| existing != key);
+        self.info.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add a blank page of the given size in points, after the pages added so far.
+    pub fn add_page(&mut self, width: f32, height: f32) -> &mut PageBuilder {
+        self.pages.push(PageBuilder { width, height, text: Vec::new() });
+        self.pages.last_mut().unwrap()
+    }
+
+    /// Make the document.  Fails if text is in a font that isn't one of the standard 14.
+    pub fn build(&self) -> Result<PdfDoc> {
+        let mut file = PdfFileHandler::new_in_memory(self.version);
+        let cache = Rc::clone(&file.object_map);
+        let mut next_number = 1;
+        let mut new_id = || {
+            next_number += 1;
+            ObjectId::new(next_number - 1, 0)
+        };
+        let catalog_id = new_id();
+        let pages_id = new_id();
+        let mut font_ids: Vec<(&str, ObjectId)> = Vec::new();
+        let mut kids = Vec::new();
+        for page in &self.pages {
+            let mut content = String::new();
+            let mut fonts = PdfMap::new();
+            for item in &page.text {
+                if !STANDARD_14.contains(&item.font.as_str()) {
+                    Err(ErrorKind::DocTreeError(format!("{} is not one of the standard 14 fonts", item.font)))?
+                };
+                let index = match font_ids.iter().position(|(font, _)| *font == item.font) {
+                    Some(index) => index,
+                    None => {
+                        let id = new_id();
+                        cache.set_object(id, font_dictionary(&item.font));
+                        font_ids.push((&item.font, id));
+                        font_ids.len() - 1
+                    }
+                };
+                let resource = format!("F{}", index + 1);
+                fonts.insert(resource.clone(), Rc::new(cache.reference(font_ids[index].1)));
+                write!(content, "BT /{} {} Tf {} TL {} {} Td", resource, number(item.size),
+                       number(item.size * LINE_SPACING), number(item.x), number(item.y)).unwrap();
+                for (i, line) in item.text.lines().enumerate() {
+                    write!(content, "{} {} Tj", if i > 0 { " T*" } else { "" }, literal_string(line)).unwrap();
+                }
+                content.push_str(" ET\n");
+            }
+            let contents_id = new_id();
+            cache.set_object(contents_id, PdfObject::new_binary_stream(PdfBinaryStream::new(PdfMap::new(),
+                                                                                            content.into_bytes())));
+            let mut resources = PdfMap::new();
+            resources.insert("Font".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(fonts))));
+            let media_box = [0.0, 0.0, page.width, page.height].iter()
+                                                                .map(|n| Rc::new(PdfObject::new_number_float(*n)))
+                                                                .collect();
+            let page_id = new_id();
+            cache.set_object(page_id, dictionary(vec![
+                ("Type", PdfObject::new_name("Page")),
+                ("Parent", cache.reference(pages_id)),
+                ("MediaBox", PdfObject::new_array(Rc::new(media_box))),
+                ("Resources", PdfObject::new_dictionary(Rc::new(resources))),
+                ("Contents", cache.reference(contents_id)),
+            ]));
+            kids.push(Rc::new(cache.reference(page_id)));
+        }
+        cache.set_object(pages_id, dictionary(vec![
+            ("Type", PdfObject::new_name("Pages")),
+            ("Count", PdfObject::new_number_int(kids.len() as i64)),
+            ("Kids", PdfObject::new_array(Rc::new(kids))),
+        ]));
+        cache.set_object(catalog_id, dictionary(vec![
+            ("Type", PdfObject::new_name("Catalog")),
+            ("Pages", cache.reference(pages_id)),
+        ]));
+
+        let mut trailer = vec![("Root", cache.reference(catalog_id))];
+        if !self.info.is_empty() {
+            let info_id = new_id();
+            let info = self.info.iter()
+                                .map(|(key, value)| (key.clone(), Rc::new(PdfObject::new_text_string(value))))
+                                .collect();
+            cache.set_object(info_id, PdfObject::new_dictionary(Rc::new(info)));
+            trailer.push(("Info", cache.reference(info_id)));
+        };
+        trailer.push(("Size", PdfObject::new_number_int(next_number)));
+        file.set_trailer(dictionary(trailer).try_into_map()?.as_ref().clone());
+        PdfDoc::from_file_handler(file)
+    }
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageBuilder {
+    /// Show text with its first baseline starting at (x, y), in points from the bottom left corner of the page.
+    /// Each line of the text goes on its own line below the last.  Characters outside WinAnsiEncoding are shown
+    /// as question marks.
+    pub fn add_text(&mut self, font: &str, size: f32, x: f32, y: f32, text: &str) -> &mut Self {
+        self.text.push(TextItem { font: font.to_string(), size, x, y, text: text.to_string() });
+        self
+    }
+}
+
+fn dictionary(entries: Vec<(&str, PdfObject)>) -> PdfObject {
+    let map = entries.into_iter().map(|(key, value)| (key.to_string(), Rc::new(value))).collect();
+    PdfObject::new_dictionary(Rc::new(map))
+}
+
+/// A simple font dictionary for a standard font.  Symbol and ZapfDingbats have their own built-in encodings.
+fn font_dictionary(base_font: &str) -> PdfObject {
+    let mut entries = vec![
+        ("Type", PdfObject::new_name("Font")),
+        ("Subtype", PdfObject::new_name("Type1")),
+        ("BaseFont", PdfObject::new_name(base_font)),
+    ];
+    if base_font != "Symbol" && base_font != "ZapfDingbats" {
+        entries.push(("Encoding", PdfObject::new_name("WinAnsiEncoding")));
+    };
+    dictionary(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_builder() {
+        let mut builder = DocumentBuilder::new().with_info("Title", "Report").with_info("Author", "Ann (Ed.)")
+                                                .with_info("Subject", "Gr\u{F6}\u{DF}e");
+        builder.add_page(612.0, 792.0)
+               .add_text("Helvetica", 12.0, 72.0, 720.0, "Quarterly report")
+               .add_text("Courier", 10.0, 72.0, 700.0, "Total: 42\nChange: +3%");
+        builder.add_page(595.0, 842.0).add_text("Helvetica", 12.0, 72.0, 770.0, "Page two");
+        let doc = builder.build().unwrap();
+        assert_eq!(doc.page_count(), 2);
+        assert_eq!(doc.media_box(1).unwrap(), Rect::new(0.0, 0.0, 595.0, 842.0));
+        assert_eq!(doc.page_text(0).unwrap(), "Quarterly report\nTotal: 42\nChange: +3%\n");
+
+        // The written file reads back the same, with one font object shared between the pages
+        let (bytes, _) = doc.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes.clone()).unwrap();
+        assert_eq!(reread.page_text(1).unwrap(), "Page two\n");
+        assert_eq!(reread.info().unwrap()["Author"], "Ann (Ed.)");
+        assert_eq!(reread.info().unwrap()["Title"], "Report");
+        // Text that isn't ASCII is written as UTF-16 (spec 7.9.2.2), not as UTF-8 bytes
+        assert_eq!(reread.info().unwrap()["Subject"], "Gr\u{F6}\u{DF}e");
+        assert!(!bytes.windows(2).any(|pair| pair == "\u{F6}".as_bytes()));
+        let fonts = reread.get_dict(reread.page_id(1).unwrap()).unwrap()["Resources"].try_into_map().unwrap()
+                          ["Font"].try_into_map().unwrap()["F1"].reference_id();
+        let first_fonts = reread.get_dict(reread.page_id(0).unwrap()).unwrap()["Resources"].try_into_map().unwrap()
+                                ["Font"].try_into_map().unwrap()["F1"].reference_id();
+        assert_eq!(fonts, first_fonts);
+
+        let mut builder = DocumentBuilder::new();
+        builder.add_page(100.0, 100.0).add_text("Comic Sans", 12.0, 0.0, 0.0, "Hi");
+        assert!(builder.build().is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::errors::*;
+
+/// A character map (spec 9.7.5 and 9.10.3): how the bytes of a string split into character codes, and for
+/// ToUnicode maps, the text each code stands for.
+#[derive(Debug, Clone, Default)]
+pub struct CMap {
+    /// Inclusive (low, high) code ranges, with the number of bytes in codes from each range
+    codespace: Vec<(u32, u32, usize)>,
+    unicode: HashMap<u32, String>,
+}
+
+impl CMap {
+    /// A map whose codes are all bytes_per_code bytes long, like Identity-H.
+    pub fn fixed_width(bytes_per_code: usize) -> Self {
+        let high = if bytes_per_code >= 4 { u32::MAX } else { (1 << (8 * bytes_per_code)) - 1 };
+        CMap { codespace: vec![(0, high, bytes_per_code)], unicode: HashMap::new() }
+    }
+
+    /// Parse the body of a CMap stream.  Only the codespace, bfchar and bfrange sections are used.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let stream = CommandStream::parse(data).chain_err(|| ErrorKind::ParsingError("Invalid CMap".to_string()))?;
+        let mut cmap = CMap::default();
+        for command in &stream.commands {
+            match &command.operator[..] {
+                "endcodespacerange" => for pair in command.operands.chunks_exact(2) {
+                    let low = pair[0].try_into_bytes()?;
+                    let high = pair[1].try_into_bytes()?;
+                    cmap.codespace.push((code_from_bytes(&low), code_from_bytes(&high), low.len()));
+                },
+                "endbfchar" => for pair in command.operands.chunks_exact(2) {
+                    let code = code_from_bytes(&pair[0].try_into_bytes()?);
+                    cmap.unicode.insert(code, utf16_to_string(&pair[1].try_into_bytes()?));
+                },
+                "endbfrange" => for triple in command.operands.chunks_exact(3) {
+                    let low = code_from_bytes(&triple[0].try_into_bytes()?);
+                    let high = code_from_bytes(&triple[1].try_into_bytes()?);
+                    if triple[2].is_array() {
+                        for (code, target) in (low..=high).zip(triple[2].try_into_array()?.iter()) {
+                            cmap.unicode.insert(code, utf16_to_string(&target.try_into_bytes()?));
+                        }
+                    } else {
+                        // Successive codes increment the last byte of the destination
+                        let mut target = triple[2].try_into_bytes()?;
+                        for code in low..=high {
+                            cmap.unicode.insert(code, utf16_to_string(&target));
+                            if let Some(last) = target.last_mut() {
+                                *last = last.wrapping_add(1);
+                            };
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(cmap)
+    }
+
+    /// Split string bytes into character codes.  Bytes that match no codespace range consume the shortest code
+    /// length, per spec 9.7.6.3; maps with no codespace use single bytes.
+    pub fn split_codes(&self, bytes: &[u8]) -> Vec<(u32, usize)> {
+        let shortest = self.codespace.iter().map(|(_, _, len)| *len).min().unwrap_or(1);
+        let mut codes = Vec::new();
+        let mut index = 0;
+        while index < bytes.len() {
+            let matched = self.codespace.iter().find(|(low, high, len)| {
+                index + len <= bytes.len() && (*low..=*high).contains(&code_from_bytes(&bytes[index..index + len]))
+            });
+            let len = matched.map_or(shortest, |(_, _, len)| *len).min(bytes.len() - index);
+            codes.push((code_from_bytes(&bytes[index..index + len]), len));
+            index += len;
+        }
+        codes
+    }
+
+    pub fn lookup(&self, code: u32) -> Option<&str> {
+        self.unicode.get(&code).map(|s| &s[..])
+    }
+
+    pub fn has_codespace(&self) -> bool {
+        !self.codespace.is_empty()
+    }
+}
+
+fn code_from_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |code, byte| (code << 8) | *byte as u32)
+}
+
+fn utf16_to_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks(2)
+                               .map(|pair| ((pair[0] as u16) << 8) | *pair.get(1).unwrap_or(&0) as u16)
+                               .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TO_UNICODE: &[u8] = b"/CIDInit /ProcSet findresource begin 12 dict begin begincmap
+/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def
+/CMapName /Adobe-Identity-UCS def /CMapType 2 def
+1 begincodespacerange <0000> <FFFF> endcodespacerange
+2 beginbfchar <0003> <0020> <0011> <D835DC9C> endbfchar
+2 beginbfrange <0024> <0026> <0041> <0030> <0031> [<0066006C> <00E9>] endbfrange
+endcmap CMapName currentdict /CMap defineresource pop end end";
+
+    #[test]
+    fn test_to_unicode() {
+        let cmap = CMap::parse(TO_UNICODE).unwrap();
+        assert_eq!(cmap.lookup(3), Some(" "));
+        assert_eq!(cmap.lookup(0x11), Some("\u{1D49C}"));
+        assert_eq!(cmap.lookup(0x25), Some("B"));
+        assert_eq!(cmap.lookup(0x30), Some("fl"));
+        assert_eq!(cmap.lookup(0x31), Some("é"));
+        assert_eq!(cmap.lookup(0x32), None);
+        assert_eq!(cmap.split_codes(b"\x00\x24\x00\x03"), vec![(0x24, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn test_split_codes() {
+        assert_eq!(CMap::default().split_codes(b"ab"), vec![(0x61, 1), (0x62, 1)]);
+        assert_eq!(CMap::fixed_width(2).split_codes(b"\x01\x02\x03"), vec![(0x0102, 2), (0x03, 1)]);
+        // Mixed one- and two-byte codes, as in Shift-JIS style CMaps
+        let cmap = CMap::parse(b"2 begincodespacerange <00> <80> <8140> <9FFC> endcodespacerange").unwrap();
+        assert_eq!(cmap.split_codes(b"A\x81\x40B"), vec![(0x41, 1), (0x8140, 2), (0x42, 1)]);
+    }
+}
@@ -0,0 +1,226 @@
+use super::*;
+
+/// How deeply colour spaces may nest through base and alternate spaces before parsing gives up, which real files
+/// never come near.
+const MAX_COLOR_SPACE_DEPTH: usize = 8;
+
+/// A colour space (spec 8.6), from an image's /ColorSpace or a page's /Resources, telling how many values make up
+/// a colour and how to interpret them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+    CalGray { white_point: [f32; 3] },
+    CalRGB { white_point: [f32; 3] },
+    Lab { white_point: [f32; 3], range: [f32; 4] },
+    /// Colours in an embedded ICC profile, with the space to use instead if the profile can't be
+    ICCBased { components: usize, alternate: Option<Box<ColorSpace>>, profile: Option<ObjectId> },
+    /// Colour values are indices from 0 to high into lookup, a table of colours in the base space
+    Indexed { base: Box<ColorSpace>, high: u8, lookup: Vec<u8> },
+    /// A single colorant, such as a spot colour, and the space showing it on devices without it.  The tint
+    /// transform mapping tints to the alternate space isn't read.
+    Separation { colorant: String, alternate: Box<ColorSpace> },
+    /// Several colorants, as for Separation
+    DeviceN { colorants: Vec<String>, alternate: Box<ColorSpace> },
+    /// Pattern fills, with the space giving the colour of uncoloured tiling patterns if there is one
+    Pattern { underlying: Option<Box<ColorSpace>> },
+}
+
+impl ColorSpace {
+    /// Read a colour space from its name or array.  The abbreviations of inline images (spec 8.9.7) are
+    /// accepted too.
+    pub fn parse(obj: &PdfObject) -> Result<Self> {
+        ColorSpace::parse_nested(obj, 0)
+    }
+
+    fn parse_nested(obj: &PdfObject, depth: usize) -> Result<Self> {
+        if depth > MAX_COLOR_SPACE_DEPTH {
+            Err(ErrorKind::ParsingError("Colour spaces nest too deeply".to_string()))?
+        };
+        if obj.is_name() {
+            return match obj.try_into_string()?.as_str() {
+                "DeviceGray" | "G" => Ok(ColorSpace::DeviceGray),
+                "DeviceRGB" | "RGB" => Ok(ColorSpace::DeviceRGB),
+                "DeviceCMYK" | "CMYK" => Ok(ColorSpace::DeviceCMYK),
+                "Pattern" => Ok(ColorSpace::Pattern { underlying: None }),
+                name => Err(ErrorKind::ParsingError(format!("Unknown colour space /{}", name)))?
+            };
+        };
+        let array = obj.try_into_array()?;
+        let family = match array.first() {
+            Some(family) => family.try_into_string()?,
+            None => Err(ErrorKind::ParsingError("Empty colour space array".to_string()))?
+        };
+        let operand = |index: usize| array.get(index).ok_or_else(|| ErrorKind::ParsingError(
+            format!("/{} colour space is missing operand {}", family, index)));
+        let nested = |index: usize| -> Result<Box<ColorSpace>> {
+            Ok(Box::new(ColorSpace::parse_nested(operand(index)?, depth + 1)?))
+        };
+        Ok(match family.as_str() {
+            // Some writers put device spaces in arrays
+            "DeviceGray" | "DeviceRGB" | "DeviceCMYK" if array.len() == 1 => {
+                ColorSpace::parse_nested(&array[0], depth)?
+            },
+            "CalGray" | "CalRGB" | "Lab" => {
+                let dict = operand(1)?.try_into_map()?;
+                let white_point = numbers(dict.get("WhitePoint"), [0.0; 3])?;
+                match family.as_str() {
+                    "CalGray" => ColorSpace::CalGray { white_point },
+                    "CalRGB" => ColorSpace::CalRGB { white_point },
+                    _ => ColorSpace::Lab {
+                        white_point,
+                        range: numbers(dict.get("Range"), [-100.0, 100.0, -100.0, 100.0])?,
+                    }
+                }
+            },
+            "ICCBased" => {
+                let profile = operand(1)?;
+                let dict = profile.try_into_binary_stream()?.attributes().clone();
+                let alternate = match dict.get("Alternate") {
+                    Some(alternate) => Some(Box::new(ColorSpace::parse_nested(alternate, depth + 1)?)),
+                    None => None
+                };
+                // Profiles have 1, 3 or 4 components; with any other /N the alternate is all there is to go on
+                match (dict.get("N").map(|n| n.try_into_int()).transpose()?, alternate) {
+                    (Some(n @ (1 | 3 | 4)), alternate) => {
+                        ColorSpace::ICCBased { components: n as usize, alternate, profile: profile.reference_id() }
+                    },
+                    (_, Some(alternate)) => *alternate,
+                    (Some(n), None) => Err(ErrorKind::ParsingError(format!("ICC profile stream has /N {}", n)))?,
+                    (None, None) => Err(ErrorKind::ParsingError("ICC profile stream has no /N".to_string()))?
+                }
+            },
+            "Indexed" | "I" => {
+                let high = operand(2)?.try_into_int()?;
+                if !(0..=255).contains(&high) {
+                    Err(ErrorKind::ParsingError(format!("Indexed colour space has hival {}", high)))?
+                };
+                let table = operand(3)?;
+                let lookup = if table.is_stream() {
+                    table.try_into_binary_stream()?.data()?.to_vec()
+                } else {
+                    table.try_into_bytes()?
+                };
+                ColorSpace::Indexed { base: nested(1)?, high: high as u8, lookup }
+            },
+            "Separation" => ColorSpace::Separation {
+                colorant: operand(1)?.try_into_string()?.as_ref().clone(),
+                alternate: nested(2)?,
+            },
+            "DeviceN" => ColorSpace::DeviceN {
+                colorants: operand(1)?.try_into_array()?
+                                      .iter()
+                                      .map(|name| Ok(name.try_into_string()?.as_ref().clone()))
+                                      .collect::<Result<Vec<String>>>()?,
+                alternate: nested(2)?,
+            },
+            "Pattern" => ColorSpace::Pattern { underlying: if array.len() > 1 { Some(nested(1)?) } else { None } },
+            family => Err(ErrorKind::ParsingError(format!("Unknown colour space /{}", family)))?
+        })
+    }
+
+    /// The colour space family name, such as DeviceRGB or ICCBased.
+    pub fn family(&self) -> &'static str {
+        match self {
+            ColorSpace::DeviceGray => "DeviceGray",
+            ColorSpace::DeviceRGB => "DeviceRGB",
+            ColorSpace::DeviceCMYK => "DeviceCMYK",
+            ColorSpace::CalGray { .. } => "CalGray",
+            ColorSpace::CalRGB { .. } => "CalRGB",
+            ColorSpace::Lab { .. } => "Lab",
+            ColorSpace::ICCBased { .. } => "ICCBased",
+            ColorSpace::Indexed { .. } => "Indexed",
+            ColorSpace::Separation { .. } => "Separation",
+            ColorSpace::DeviceN { .. } => "DeviceN",
+            ColorSpace::Pattern { .. } => "Pattern",
+        }
+    }
+
+    /// How many values make up a colour in the space, as in an image sample.  For Pattern, it's the number of
+    /// the underlying space, if any, leaving out the pattern name.
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray { .. } | ColorSpace::Indexed { .. }
+            | ColorSpace::Separation { .. } => 1,
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB { .. } | ColorSpace::Lab { .. } => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::ICCBased { components, .. } => *components,
+            ColorSpace::DeviceN { colorants, .. } => colorants.len(),
+            ColorSpace::Pattern { underlying } => underlying.as_ref().map_or(0, |space| space.components()),
+        }
+    }
+}
+
+/// An array of numbers of known length, or the default if it's missing.
+fn numbers<const N: usize>(array: Option<&SharedObject>, default: [f32; N]) -> Result<[f32; N]> {
+    let array = match array {
+        Some(array) => array.try_into_array()?,
+        None => return Ok(default)
+    };
+    if array.len() != N {
+        Err(ErrorKind::ParsingError(format!("Expected {} numbers, got {}", N, array.len())))?
+    };
+    let mut values = default;
+    for (value, number) in values.iter_mut().zip(array.iter()) {
+        *value = number.try_into_number()?;
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_color_spaces() {
+        let mut builder = MiniPdfBuilder::new();
+        let icc = builder.add_stream(b"", TestFilter::None, "/N 4 /Alternate /DeviceCMYK");
+        let palette = builder.add_stream(b"\xFF\x00\x00\x00\x00\xFF", TestFilter::None, "");
+        let negative = builder.add_stream(b"", TestFilter::None, "/N -1 /Alternate /DeviceRGB");
+        let two = builder.add_stream(b"", TestFilter::None, "/N 2");
+        builder.add_page_with_entries(b"", TestFilter::None, &format!(
+            "/Resources << /ColorSpace << /CS0 [/ICCBased {} 0 R] /CS1 [/Indexed /DeviceRGB 1 {} 0 R] \
+             /CS2 [/Separation /PANTONE185 /DeviceCMYK << /FunctionType 2 /Domain [0 1] /N 1 >>] \
+             /CS3 [/DeviceN [/Cyan /Spot] [/ICCBased {} 0 R] 5 0 R] /CS4 [/Indexed /DeviceGray 2 <00 80 FF>] \
+             /CS5 [/Pattern /DeviceRGB] /CS6 [/Lab << /WhitePoint [0.95 1 1.09] >>] /CS7 /Bogus \
+             /CS8 [/ICCBased {} 0 R] /CS9 [/ICCBased {} 0 R] >> >>",
+            icc, palette, icc, negative, two));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let spaces = pdf.page(0).unwrap().color_spaces().unwrap();
+
+        let cmyk_icc = ColorSpace::ICCBased {
+            components: 4, alternate: Some(Box::new(ColorSpace::DeviceCMYK)), profile: Some(ObjectId::new(icc, 0))
+        };
+        assert_eq!(spaces["CS0"].as_ref().unwrap(), &cmyk_icc);
+        assert_eq!(spaces["CS1"].as_ref().unwrap(), &ColorSpace::Indexed {
+            base: Box::new(ColorSpace::DeviceRGB), high: 1, lookup: vec![255, 0, 0, 0, 0, 255]
+        });
+        let separation = spaces["CS2"].as_ref().unwrap();
+        assert_eq!((separation.family(), separation.components()), ("Separation", 1));
+        assert_eq!(separation, &ColorSpace::Separation {
+            colorant: "PANTONE185".to_string(), alternate: Box::new(ColorSpace::DeviceCMYK)
+        });
+        assert_eq!(spaces["CS3"].as_ref().unwrap(), &ColorSpace::DeviceN {
+            colorants: vec!["Cyan".to_string(), "Spot".to_string()], alternate: Box::new(cmyk_icc)
+        });
+        assert_eq!(spaces["CS4"].as_ref().unwrap(), &ColorSpace::Indexed {
+            base: Box::new(ColorSpace::DeviceGray), high: 2, lookup: vec![0, 128, 255]
+        });
+        assert_eq!(spaces["CS5"].as_ref().unwrap().components(), 3);
+        assert_eq!(spaces["CS6"].as_ref().unwrap(), &ColorSpace::Lab {
+            white_point: [0.95, 1.0, 1.09], range: [-100.0, 100.0, -100.0, 100.0]
+        });
+        assert!(spaces["CS7"].is_err());
+        // An /N no profile can have
+        assert_eq!(spaces["CS8"].as_ref().unwrap(), &ColorSpace::DeviceRGB);
+        assert!(spaces["CS9"].is_err());
+
+        let inline = CommandStream::parse(b"BI /CS [/I /RGB 0 <FF0000>] /W 1 /H 1 /BPC 8 ID \x00 EI").unwrap();
+        let dict = inline.commands[0].operands[0].try_into_map().unwrap();
+        assert_eq!(ColorSpace::parse(&dict["CS"]).unwrap(), ColorSpace::Indexed {
+            base: Box::new(ColorSpace::DeviceRGB), high: 0, lookup: vec![255, 0, 0]
+        });
+    }
+}
@@ -0,0 +1,500 @@
+mod cmap;
+mod color_space;
+mod coverage;
+mod encoding;
+mod font;
+mod geometry;
+mod image;
+mod interpreter;
+mod layout;
+#[cfg(feature = "render")]
+mod render;
+mod resources;
+mod search;
+mod standard_fonts;
+mod svg;
+mod table;
+
+use std::fmt;
+use std::rc::Rc;
+use std::str;
+
+use super::*;
+use crate::errors::*;
+use crate::doc_tree::pdf_file::util::*;
+pub use cmap::*;
+pub use color_space::*;
+pub use coverage::*;
+pub use encoding::*;
+pub use font::*;
+pub use geometry::*;
+pub use image::*;
+pub use interpreter::*;
+pub use layout::*;
+#[cfg(feature = "render")]
+pub use render::*;
+pub use resources::*;
+pub use search::*;
+pub use standard_fonts::*;
+pub use svg::*;
+pub use table::*;
+
+/// A content stream operator together with the operands that precede it (spec 7.8.2).
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub operator: String,
+    pub operands: Vec<PdfObject>,
+}
+
+/// The sequence of commands making up a content stream.
+#[derive(Debug, Clone, Default)]
+pub struct CommandStream {
+    pub commands: Vec<Command>,
+    /// For content read with parse_partial, why the commands stop short of the end of the stream
+    pub error: Option<ContentParseError>,
+}
+
+/// Where and why parsing a content stream stopped, the parts of a ContentError.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentParseError {
+    pub problem: String,
+    /// The byte offset in the decoded stream of the token that couldn't be read
+    pub offset: usize,
+    /// The index and operator of the last command read before it
+    pub after: Option<(usize, String)>,
+}
+
+impl ContentParseError {
+    /// As an error, for the content of the page at page_index if it's given.
+    pub fn to_error(&self, page_index: Option<usize>) -> Error {
+        ErrorKind::ContentError(self.problem.clone(), self.offset, self.after.clone(), page_index).into()
+    }
+}
+
+impl CommandStream {
+    /// Split a decoded content stream into commands.  Inline images (BI ... ID ... EI) become a single BI command
+    /// whose operands are the image dictionary and the image data as a hex string.  Errors are ContentErrors
+    /// giving the byte offset of the token that failed and the last operator read before it.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let commands = CommandStream::parse_partial(data);
+        match commands.error {
+            Some(error) => Err(error.to_error(None)),
+            None => Ok(commands)
+        }
+    }
+
+    /// As parse, but keeping the commands read before any error, which goes in the error field.  Operands
+    /// waiting for an operator when the error is met are dropped.
+    pub fn parse_partial(data: &[u8]) -> Self {
+        let mut lexer = ContentLexer { data, index: 0 };
+        let mut commands = Vec::new();
+        let mut token_start = 0;
+        let error = CommandStream::parse_commands(&mut lexer, &mut commands, &mut token_start).err().map(|error| {
+            ContentParseError {
+                problem: error.to_string(),
+                offset: token_start,
+                after: commands.last().map(|command| (commands.len() - 1, command.operator.clone())),
+            }
+        });
+        CommandStream { commands, error }
+    }
+
+    /// Fail with the error that stopped parsing, if there was one, for uses that need the whole stream.
+    pub fn check_complete(&self, page_index: Option<usize>) -> Result<()> {
+        match &self.error {
+            Some(error) => Err(error.to_error(page_index)),
+            None => Ok(())
+        }
+    }
+
+    fn parse_commands(lexer: &mut ContentLexer<'_>, commands: &mut Vec<Command>, token_start: &mut usize)
+                      -> Result<()> {
+        let mut operands = Vec::new();
+        loop {
+            lexer.skip_whitespace_and_comments();
+            *token_start = lexer.index;
+            let token = match lexer.next_token()? {
+                Some(token) => token,
+                None => break
+            };
+            match token {
+                ContentToken::Operand(obj) => operands.push(obj),
+                ContentToken::Operator(op) if op == "BI" => {
+                    if !operands.is_empty() {
+                        Err(ErrorKind::ParsingError(format!("Operands before inline image at {}", lexer.index)))?
+                    };
+                    commands.push(lexer.read_inline_image()?);
+                }
+                ContentToken::Operator(op) => {
+                    commands.push(Command { operator: op, operands });
+                    operands = Vec::new();
+                }
+                ContentToken::Close(c) => Err(ErrorKind::ParsingError(format!(
+                    "Unexpected {} at {}", c as char, lexer.index)))?,
+            }
+        }
+        if !operands.is_empty() {
+            Err(ErrorKind::ParsingError(format!("Content stream ends with {} unused operands", operands.len())))?
+        };
+        Ok(())
+    }
+
+    /// The commands as content stream data, which parses back to the same commands.  Inline images are written
+    /// out as BI, their dictionary entries, ID, the image data and EI.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let options = WriteOptions::default();
+        for command in &self.commands {
+            match (command.operator.as_str(), &command.operands[..]) {
+                ("BI", [dict, data]) => {
+                    output.extend_from_slice(b"BI");
+                    for (key, value) in dict.try_into_map()?.iter() {
+                        output.push(b' ');
+                        write_object(&mut output, &PdfObject::new_name(key.as_str()), &None, &options)?;
+                        output.push(b' ');
+                        write_object(&mut output, value, &None, &options)?;
+                    }
+                    output.extend_from_slice(b" ID ");
+                    output.extend(data.try_into_bytes()?);
+                    output.extend_from_slice(b"\nEI\n");
+                }
+                _ => {
+                    for operand in &command.operands {
+                        write_object(&mut output, operand, &None, &options)?;
+                        output.push(b' ');
+                    }
+                    output.extend_from_slice(command.operator.as_bytes());
+                    output.push(b'\n');
+                }
+            };
+        }
+        Ok(output)
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = Vec::new();
+        for operand in &self.operands {
+            write_object(&mut line, operand, &None, &WriteOptions::default()).map_err(|_| fmt::Error)?;
+            line.push(b' ');
+        }
+        line.extend_from_slice(self.operator.as_bytes());
+        // Only literal strings can hold bytes that aren't UTF-8, so they're written as octal escapes, which read
+        // back as the same string, rather than all becoming U+FFFD
+        for chunk in line.utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            for byte in chunk.invalid() {
+                write!(f, "\\{:03o}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One command per line, with operands in normalized form, suitable for diffing.
+impl fmt::Display for CommandStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for command in &self.commands {
+            writeln!(f, "{}", command)?
+        }
+        Ok(())
+    }
+}
+
+enum ContentToken {
+    Operand(PdfObject),
+    Operator(String),
+    /// ']' or '>>' closing an array or dictionary operand
+    Close(u8),
+}
+
+struct ContentLexer<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> ContentLexer<'a> {
+    fn peek(&self, n: usize) -> Option<u8> {
+        self.data.get(self.index + n).cloned()
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == b'%' {
+                while let Some(c) = self.peek(0) {
+                    if is_eol(c) { break };
+                    self.index += 1;
+                }
+            } else if is_whitespace(c) {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.index;
+        while let Some(c) = self.peek(0) {
+            if is_whitespace(c) || is_delimiter(c) { break };
+            self.index += 1;
+        }
+        &self.data[start..self.index]
+    }
+
+    fn next_token(&mut self) -> Result<Option<ContentToken>> {
+        self.skip_whitespace_and_comments();
+        let c = match self.peek(0) {
+            None => return Ok(None),
+            Some(c) => c,
+        };
+        let token = match c {
+            b'/' => {
+                self.index += 1;
+                let name = self.read_regular();
+                ContentToken::Operand(PdfObject::new_name(String::from_utf8_lossy(&decode_name(name))))
+            }
+            b'(' => ContentToken::Operand(self.read_literal_string()?),
+            b'<' if self.peek(1) == Some(b'<') => {
+                self.index += 2;
+                ContentToken::Operand(self.read_dictionary()?)
+            }
+            b'<' => ContentToken::Operand(self.read_hex_string()?),
+            b'[' => {
+                self.index += 1;
+                let mut items = Vec::new();
+                loop {
+                    match self.next_token()? {
+                        Some(ContentToken::Operand(obj)) => items.push(Rc::new(obj)),
+                        Some(ContentToken::Close(b']')) => break,
+                        _ => Err(ErrorKind::ParsingError(format!("Unterminated array at {}", self.index)))?,
+                    }
+                }
+                ContentToken::Operand(PdfObject::new_array(Rc::new(items)))
+            }
+            b']' => {
+                self.index += 1;
+                ContentToken::Close(b']')
+            }
+            b'>' if self.peek(1) == Some(b'>') => {
+                self.index += 2;
+                ContentToken::Close(b'>')
+            }
+            b'{' | b'}' | b')' | b'>' => Err(ErrorKind::ParsingError(format!(
+                "Invalid character at {}: {}", self.index, c as char)))?,
+            b'0'..=b'9' | b'+' | b'-' | b'.' => {
+                let start = self.index;
+                let word = str::from_utf8(self.read_regular())
+                    .chain_err(|| ErrorKind::ParsingError(format!("Invalid number at {}", start)))?;
+                ContentToken::Operand(parse_number(word)
+                    .chain_err(|| ErrorKind::ParsingError(format!("Invalid number at {}: {}", start, word)))?)
+            }
+            _ => {
+                let word = String::from_utf8_lossy(self.read_regular()).to_string();
+                match &word[..] {
+                    "true" => ContentToken::Operand(PdfObject::new_boolean(true)),
+                    "false" => ContentToken::Operand(PdfObject::new_boolean(false)),
+                    "null" => ContentToken::Operand(PdfObject::Actual(Null)),
+                    _ => ContentToken::Operator(word),
+                }
+            }
+        };
+        Ok(Some(token))
+    }
+
+    fn read_dictionary(&mut self) -> Result<PdfObject> {
+        let mut map = PdfMap::new();
+        loop {
+            let key = match self.next_token()? {
+                Some(ContentToken::Close(b'>')) => return Ok(PdfObject::new_dictionary(Rc::new(map))),
+                Some(ContentToken::Operand(obj)) if obj.is_name() => obj.try_into_string()?.to_string(),
+                _ => Err(ErrorKind::ParsingError(format!("Invalid dictionary key at {}", self.index)))?,
+            };
+            match self.next_token()? {
+                Some(ContentToken::Operand(value)) => map.insert(key, Rc::new(value)),
+                _ => Err(ErrorKind::ParsingError(format!("No value for key {} at {}", key, self.index)))?,
+            };
+        }
+    }
+
+    fn read_literal_string(&mut self) -> Result<PdfObject> {
+        let start = self.index;
+        self.index += 1;
+        let mut depth = 0;
+        let mut buffer = Vec::new();
+        loop {
+            let c = self.peek(0).ok_or_else(|| ErrorKind::ParsingError(format!("Unterminated string at {}", start)))?;
+            self.index += 1;
+            match c {
+                b'(' => {
+                    depth += 1;
+                    buffer.push(c);
+                }
+                b')' if depth == 0 => break,
+                b')' => {
+                    depth -= 1;
+                    buffer.push(c);
+                }
+                b'\\' => {
+                    let (byte, next) = read_string_escape(self.data, self.index - 1);
+                    buffer.extend(byte);
+                    self.index = next;
+                }
+                // An end-of-line marker that isn't escaped stands for a line feed, however it's written
+                b'\r' => {
+                    if self.peek(0) == Some(b'\n') {
+                        self.index += 1;
+                    };
+                    buffer.push(b'\n');
+                }
+                _ => buffer.push(c),
+            }
+        }
+        Ok(PdfObject::new_char_string(buffer))
+    }
+
+    fn read_hex_string(&mut self) -> Result<PdfObject> {
+        let start = self.index;
+        self.index += 1;
+        let mut digits = Vec::new();
+        loop {
+            let c = self.peek(0).ok_or_else(|| ErrorKind::ParsingError(format!("Unterminated hex string at {}", start)))?;
+            self.index += 1;
+            match c {
+                b'>' => break,
+                _ if c.is_ascii_hexdigit() => digits.push(c.to_ascii_uppercase()),
+                _ if is_whitespace(c) => {},
+                _ => Err(ErrorKind::ParsingError(format!("Invalid character in hex string at {}: {}", self.index - 1, c as char)))?,
+            }
+        }
+        Ok(PdfObject::new_hex_string(digits))
+    }
+
+    /// Read an inline image after the BI operator, through the EI operator.
+    fn read_inline_image(&mut self) -> Result<Command> {
+        let start = self.index;
+        let mut map = PdfMap::new();
+        loop {
+            let key = match self.next_token()? {
+                Some(ContentToken::Operator(op)) if op == "ID" => break,
+                Some(ContentToken::Operand(obj)) if obj.is_name() => obj.try_into_string()?.to_string(),
+                _ => Err(ErrorKind::ParsingError(format!("Invalid inline image dictionary at {}", start)))?,
+            };
+            match self.next_token()? {
+                Some(ContentToken::Operand(value)) => map.insert(key, Rc::new(value)),
+                _ => Err(ErrorKind::ParsingError(format!("No value for inline image key {} at {}", key, self.index)))?,
+            };
+        }
+        // A single whitespace byte separates ID from the data, which ends at whitespace + EI + whitespace/EOF
+        self.index += 1;
+        let data_start = self.index.min(self.data.len());
+        let mut data_end = None;
+        let mut i = data_start;
+        while i + 2 <= self.data.len() {
+            if &self.data[i..i + 2] == b"EI"
+                && (i == data_start || is_whitespace(self.data[i - 1]))
+                && !matches!(self.data.get(i + 2), Some(c) if !is_whitespace(*c) && !is_delimiter(*c)) {
+                data_end = Some(i);
+                break;
+            };
+            i += 1;
+        }
+        let data_end = data_end.ok_or_else(|| ErrorKind::ParsingError(format!("Inline image at {} missing EI", start)))?;
+        self.index = data_end + 2;
+        let mut data = &self.data[data_start..data_end];
+        if let Some((last, rest)) = data.split_last() {
+            if is_whitespace(*last) { data = rest };
+        };
+        let digits = data.iter().flat_map(|c| format!("{:02X}", c).into_bytes()).collect();
+        Ok(Command {
+            operator: "BI".to_string(),
+            operands: vec![PdfObject::new_dictionary(Rc::new(map)), PdfObject::new_hex_string(digits)],
+        })
+    }
+}
+
+fn parse_number(word: &str) -> Result<PdfObject> {
+    if word.contains('.') {
+        let value: f32 = word.parse()?;
+        if value.fract() == 0.0 && value.abs() < i32::MAX as f32 {
+            return Ok(PdfObject::new_number_int(value as i32));
+        };
+        Ok(PdfObject::new_number_float(value))
+    } else {
+        Ok(PdfObject::new_number_int(word.parse::<i32>()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let stream = CommandStream::parse(b"q 1 0 0 1 72.0 720 cm\nBT /F1 12 Tf (Hello \\(World\\)) Tj ET Q").unwrap();
+        let operators: Vec<&str> = stream.commands.iter().map(|c| &c.operator[..]).collect();
+        assert_eq!(operators, vec!["q", "cm", "BT", "Tf", "Tj", "ET", "Q"]);
+        assert_eq!(stream.commands[1].operands.len(), 6);
+        assert_eq!(stream.commands[1].operands[4].try_into_int().unwrap(), 72);
+        assert_eq!(*stream.commands[4].operands[0].try_into_string().unwrap(), "Hello (World)");
+        // Names are decoded, as they are in the file's objects, so /F#31 finds the resource F1
+        let stream = CommandStream::parse(b"/F#31 12 Tf /Span#20A BMC").unwrap();
+        assert_eq!(*stream.commands[0].operands[0].try_into_string().unwrap(), "F1");
+        assert_eq!(format!("{}", stream), "/F1 12 Tf\n/Span#20A BMC\n");
+    }
+
+    #[test]
+    fn test_literal_strings() {
+        let stream = CommandStream::parse(b"(a\\tb\\101\\\r\nc) Tj (two\r\nlines\rhere) Tj").unwrap();
+        assert_eq!(*stream.commands[0].operands[0].try_into_string().unwrap(), "a\tbAc");
+        assert_eq!(*stream.commands[1].operands[0].try_into_string().unwrap(), "two\nlines\nhere");
+        let stream = CommandStream::parse(b"(\\377\x80) Tj").unwrap();
+        assert_eq!(stream.commands[0].operands[0].try_into_bytes().unwrap(), [0xFF, 0x80]);
+        assert_eq!(stream.to_bytes().unwrap(), b"(\xFF\x80) Tj\n");
+        // Strings that aren't UTF-8 stay distinct when normalized
+        let stream = CommandStream::parse(b"(\\200) Tj (\\201) Tj (caf\xC3\xA9 \\\\200) Tj").unwrap();
+        assert_eq!(format!("{}", stream), "(\\200) Tj\n(\\201) Tj\n(caf\u{E9} \\\\200) Tj\n");
+    }
+
+    #[test]
+    fn test_normalized_output() {
+        let stream = CommandStream::parse(
+            b"  0.50 0 0   .5 -0.0 10.250 cm % scale\r\n[(A) -120.0 <4142>] TJ /P <</MCID 0>> BDC EMC").unwrap();
+        assert_eq!(format!("{}", stream),
+                   "0.5 0 0 0.5 0 10.25 cm\n[(A) -120 <4142>] TJ\n/P <</MCID 0>> BDC\nEMC\n");
+    }
+
+    #[test]
+    fn test_inline_image() {
+        let stream = CommandStream::parse(b"q BI /W 2 /H 1 /BPC 8 /CS /G ID \x00\xFF EI Q").unwrap();
+        let operators: Vec<&str> = stream.commands.iter().map(|c| &c.operator[..]).collect();
+        assert_eq!(operators, vec!["q", "BI", "Q"]);
+        assert_eq!(format!("{}", stream.commands[1]), "<</W 2 /H 1 /BPC 8 /CS /G>> <00FF> BI");
+        let bytes = stream.to_bytes().unwrap();
+        assert_eq!(bytes, b"q\nBI /W 2 /H 1 /BPC 8 /CS /G ID \x00\xFF\nEI\nQ\n");
+        assert_eq!(format!("{}", CommandStream::parse(&bytes).unwrap()), format!("{}", stream));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(CommandStream::parse(b"1 2").is_err());
+        assert!(CommandStream::parse(b"[1 2 Tj").is_err());
+        assert!(CommandStream::parse(b"(unterminated Tj").is_err());
+        assert!(CommandStream::parse(b"BI /W 1 ID abc").is_err());
+
+        // Errors say where the token that failed starts and what came before it
+        let error = CommandStream::parse(b"q 1 0 0 1 5 5 cm\n0 g 10 10 m <4z> Tj Q").unwrap_err();
+        match error.kind() {
+            ErrorKind::ContentError(problem, offset, after, page) => {
+                assert_eq!(problem, "Invalid character in hex string at 31: z");
+                assert_eq!((*offset, after, *page), (29, &Some((3, "m".to_string())), None));
+            },
+            kind => panic!("Unexpected error {:?}", kind)
+        };
+        match CommandStream::parse(b") Tj").unwrap_err().kind() {
+            ErrorKind::ContentError(_, offset, after, _) => assert_eq!((*offset, after), (0, &None)),
+            kind => panic!("Unexpected error {:?}", kind)
+        };
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How much of an operator's effect the interpreter takes into account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OperatorSupport {
+    /// Interpreted for text and path extraction
+    Supported,
+    /// Recorded, but not drawn: shadings and patterns are listed by PdfDoc::page_patterns only
+    Partial,
+    /// A content stream operator the interpreter skips, like colours, images and marked content
+    Ignored,
+    /// Not an operator in the PDF specification
+    Unknown,
+}
+
+/// One operator seen on a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperatorCount {
+    pub operator: String,
+    pub count: usize,
+    pub support: OperatorSupport,
+}
+
+/// What a page's content streams use, from PdfDoc::page_operators, for seeing which features of a document
+/// the interpreter leaves out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperatorReport {
+    pub page: usize,
+    /// Every operator run, in name order
+    pub operators: Vec<OperatorCount>,
+    /// Resource names of Type3 fonts text was shown in.  Their text is extracted, but the glyph procedures
+    /// aren't run, so whatever they draw is missing from paths and renders.
+    pub type3_fonts: BTreeSet<String>,
+}
+
+impl OperatorReport {
+    pub fn new(page: usize, counts: &BTreeMap<String, usize>, type3_fonts: BTreeSet<String>) -> Self {
+        let operators = counts.iter().map(|(operator, count)| OperatorCount {
+            operator: operator.clone(),
+            count: *count,
+            support: operator_support(operator),
+        }).collect();
+        OperatorReport { page, operators, type3_fonts }
+    }
+
+    /// The operators whose effects are partly or wholly left out.
+    pub fn unsupported(&self) -> impl Iterator<Item = &OperatorCount> {
+        self.operators.iter().filter(|operator| operator.support != OperatorSupport::Supported)
+    }
+}
+
+/// How the interpreter handles an operator (spec Annex A).
+pub fn operator_support(operator: &str) -> OperatorSupport {
+    match operator {
+        "q" | "Q" | "cm" | "w" | "m" | "l" | "c" | "v" | "y" | "re" | "h" | "W" | "W*" | "S" | "s" | "f" | "F"
+        | "f*" | "B" | "B*" | "b" | "b*" | "n" | "BT" | "ET" | "Tc" | "Tw" | "Tz" | "TL" | "Ts" | "Tr" | "Tf"
+        | "Td" | "TD" | "Tm" | "T*" | "Tj" | "'" | "\"" | "TJ" => OperatorSupport::Supported,
+        "sh" | "scn" | "SCN" => OperatorSupport::Partial,
+        "d" | "J" | "j" | "M" | "ri" | "i" | "gs" | "CS" | "cs" | "SC" | "sc" | "G" | "g" | "RG" | "rg" | "K"
+        | "k" | "Do" | "BI" | "ID" | "EI" | "BMC" | "BDC" | "EMC" | "MP" | "DP" | "BX" | "EX" | "d0" | "d1" => {
+            OperatorSupport::Ignored
+        },
+        _ => OperatorSupport::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_support() {
+        let support: Vec<OperatorSupport> = ["Tj", "sh", "Do", "BI", "foo"].iter()
+                                                                         .map(|op| operator_support(op))
+                                                                         .collect();
+        assert_eq!(support, vec![OperatorSupport::Supported, OperatorSupport::Partial, OperatorSupport::Ignored,
+                                 OperatorSupport::Ignored, OperatorSupport::Unknown]);
+    }
+}
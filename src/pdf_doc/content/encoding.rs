@@ -0,0 +1,307 @@
+/// A base encoding named by a simple font's /Encoding (spec 9.6.5).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseEncoding {
+    Standard,
+    WinAnsi,
+    MacRoman,
+}
+
+impl BaseEncoding {
+    /// The encoding for a name like /WinAnsiEncoding.  MacExpertEncoding isn't supported, since its glyphs
+    /// are mostly small capitals and old-style figures without Unicode equivalents of their own.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "StandardEncoding" => Some(BaseEncoding::Standard),
+            "WinAnsiEncoding" => Some(BaseEncoding::WinAnsi),
+            "MacRomanEncoding" => Some(BaseEncoding::MacRoman),
+            _ => None
+        }
+    }
+
+    /// The character a code stands for in this encoding, if any.
+    pub fn decode(&self, code: u8) -> Option<char> {
+        let table = match self {
+            BaseEncoding::Standard => &STANDARD,
+            BaseEncoding::WinAnsi => &WIN_ANSI,
+            BaseEncoding::MacRoman => &MAC_ROMAN,
+        };
+        table[code as usize]
+    }
+}
+
+/// The text for a glyph name, following the Adobe Glyph List Specification: anything after a period is
+/// dropped, underscores separate the components of ligatures, and names like uni20AC, u1F600 and the
+/// common names of Latin, Greek and punctuation glyphs are recognized.
+pub fn glyph_name_to_unicode(name: &str) -> Option<String> {
+    let name = name.split('.').next().unwrap_or("");
+    if name.is_empty() {
+        return None;
+    };
+    let mut text = String::new();
+    for component in name.split('_') {
+        text.push_str(&component_to_unicode(component)?);
+    }
+    Some(text)
+}
+
+fn component_to_unicode(component: &str) -> Option<String> {
+    if let Ok(index) = GLYPH_LIST.binary_search_by_key(&component, |(name, _)| name) {
+        return Some(GLYPH_LIST[index].1.to_string());
+    };
+    let hex_chars = |digits: &str| -> Option<char> {
+        let code = u32::from_str_radix(digits, 16).ok()?;
+        // Surrogates are excluded by from_u32
+        std::char::from_u32(code)
+    };
+    if let Some(digits) = component.strip_prefix("uni") {
+        // Any number of four-digit codes
+        if digits.is_empty() || digits.len() % 4 != 0 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        };
+        return (0..digits.len()).step_by(4).map(|i| hex_chars(&digits[i..i + 4])).collect();
+    };
+    if let Some(digits) = component.strip_prefix('u') {
+        if (4..=6).contains(&digits.len()) && digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return hex_chars(digits).map(|c| c.to_string());
+        };
+    };
+    None
+}
+
+/// Glyph names and their characters, sorted by name for binary search.
+const GLYPH_LIST: &[(&str, char)] = &[
+    ("A", 'A'), ("AE", '\u{00C6}'), ("Aacute", '\u{00C1}'), ("Abreve", '\u{0102}'), ("Acaron", '\u{01CD}'),
+    ("Acircumflex", '\u{00C2}'), ("Adieresis", '\u{00C4}'), ("Adotaccent", '\u{0226}'), ("Agrave", '\u{00C0}'),
+    ("Alpha", '\u{0391}'), ("Amacron", '\u{0100}'), ("Aogonek", '\u{0104}'), ("Aring", '\u{00C5}'),
+    ("Atilde", '\u{00C3}'), ("B", 'B'), ("Beta", '\u{0392}'), ("C", 'C'), ("Cacute", '\u{0106}'),
+    ("Ccaron", '\u{010C}'), ("Ccedilla", '\u{00C7}'), ("Ccircumflex", '\u{0108}'), ("Cdot", '\u{010A}'),
+    ("Cdotaccent", '\u{010A}'), ("Chi", '\u{03A7}'), ("D", 'D'), ("Dcaron", '\u{010E}'), ("Dcroat", '\u{0110}'),
+    ("Delta", '\u{2206}'), ("E", 'E'), ("Eacute", '\u{00C9}'), ("Ebreve", '\u{0114}'), ("Ecaron", '\u{011A}'),
+    ("Ecedilla", '\u{0228}'), ("Ecircumflex", '\u{00CA}'), ("Edieresis", '\u{00CB}'), ("Edot", '\u{0116}'),
+    ("Edotaccent", '\u{0116}'), ("Egrave", '\u{00C8}'), ("Emacron", '\u{0112}'), ("Eng", '\u{014A}'),
+    ("Eogonek", '\u{0118}'), ("Epsilon", '\u{0395}'), ("Eta", '\u{0397}'), ("Eth", '\u{00D0}'), ("Euro", '\u{20AC}'),
+    ("F", 'F'), ("G", 'G'), ("Gacute", '\u{01F4}'), ("Gamma", '\u{0393}'), ("Gbreve", '\u{011E}'),
+    ("Gcaron", '\u{01E6}'), ("Gcedilla", '\u{0122}'), ("Gcircumflex", '\u{011C}'), ("Gdot", '\u{0120}'),
+    ("Gdotaccent", '\u{0120}'), ("H", 'H'), ("Hbar", '\u{0126}'), ("Hcaron", '\u{021E}'),
+    ("Hcircumflex", '\u{0124}'), ("I", 'I'), ("IJ", '\u{0132}'), ("Iacute", '\u{00CD}'), ("Ibreve", '\u{012C}'),
+    ("Icaron", '\u{01CF}'), ("Icircumflex", '\u{00CE}'), ("Idieresis", '\u{00CF}'), ("Idot", '\u{0130}'),
+    ("Idotaccent", '\u{0130}'), ("Igrave", '\u{00CC}'), ("Imacron", '\u{012A}'), ("Iogonek", '\u{012E}'),
+    ("Iota", '\u{0399}'), ("Itilde", '\u{0128}'), ("J", 'J'), ("Jcircumflex", '\u{0134}'), ("K", 'K'),
+    ("Kappa", '\u{039A}'), ("Kcaron", '\u{01E8}'), ("Kcedilla", '\u{0136}'), ("L", 'L'), ("Lacute", '\u{0139}'),
+    ("Lambda", '\u{039B}'), ("Lcaron", '\u{013D}'), ("Lcedilla", '\u{013B}'), ("Ldot", '\u{013F}'),
+    ("Lslash", '\u{0141}'), ("M", 'M'), ("Mu", '\u{039C}'), ("N", 'N'), ("Nacute", '\u{0143}'),
+    ("Ncaron", '\u{0147}'), ("Ncedilla", '\u{0145}'), ("Ngrave", '\u{01F8}'), ("Ntilde", '\u{00D1}'),
+    ("Nu", '\u{039D}'), ("O", 'O'), ("OE", '\u{0152}'), ("Oacute", '\u{00D3}'), ("Obreve", '\u{014E}'),
+    ("Ocaron", '\u{01D1}'), ("Ocircumflex", '\u{00D4}'), ("Odieresis", '\u{00D6}'), ("Odotaccent", '\u{022E}'),
+    ("Ograve", '\u{00D2}'), ("Ohungarumlaut", '\u{0150}'), ("Omacron", '\u{014C}'), ("Omega", '\u{2126}'),
+    ("Omicron", '\u{039F}'), ("Oogonek", '\u{01EA}'), ("Oslash", '\u{00D8}'), ("Otilde", '\u{00D5}'), ("P", 'P'),
+    ("Phi", '\u{03A6}'), ("Pi", '\u{03A0}'), ("Psi", '\u{03A8}'), ("Q", 'Q'), ("R", 'R'), ("Racute", '\u{0154}'),
+    ("Rcaron", '\u{0158}'), ("Rcedilla", '\u{0156}'), ("Rho", '\u{03A1}'), ("S", 'S'), ("Sacute", '\u{015A}'),
+    ("Scaron", '\u{0160}'), ("Scedilla", '\u{015E}'), ("Scircumflex", '\u{015C}'), ("Sigma", '\u{03A3}'), ("T", 'T'),
+    ("Tau", '\u{03A4}'), ("Tbar", '\u{0166}'), ("Tcaron", '\u{0164}'), ("Tcedilla", '\u{0162}'),
+    ("Theta", '\u{0398}'), ("Thorn", '\u{00DE}'), ("U", 'U'), ("Uacute", '\u{00DA}'), ("Ubreve", '\u{016C}'),
+    ("Ucaron", '\u{01D3}'), ("Ucircumflex", '\u{00DB}'), ("Udieresis", '\u{00DC}'), ("Ugrave", '\u{00D9}'),
+    ("Uhungarumlaut", '\u{0170}'), ("Umacron", '\u{016A}'), ("Uogonek", '\u{0172}'), ("Upsilon", '\u{03A5}'),
+    ("Uring", '\u{016E}'), ("Utilde", '\u{0168}'), ("V", 'V'), ("W", 'W'), ("Wcircumflex", '\u{0174}'), ("X", 'X'),
+    ("Xi", '\u{039E}'), ("Y", 'Y'), ("Yacute", '\u{00DD}'), ("Ycircumflex", '\u{0176}'), ("Ydieresis", '\u{0178}'),
+    ("Ymacron", '\u{0232}'), ("Z", 'Z'), ("Zacute", '\u{0179}'), ("Zcaron", '\u{017D}'), ("Zdot", '\u{017B}'),
+    ("Zdotaccent", '\u{017B}'), ("Zeta", '\u{0396}'), ("a", 'a'), ("aacute", '\u{00E1}'), ("abreve", '\u{0103}'),
+    ("acaron", '\u{01CE}'), ("acircumflex", '\u{00E2}'), ("acute", '\u{00B4}'), ("adieresis", '\u{00E4}'),
+    ("adotaccent", '\u{0227}'), ("ae", '\u{00E6}'), ("agrave", '\u{00E0}'), ("alpha", '\u{03B1}'),
+    ("amacron", '\u{0101}'), ("ampersand", '&'), ("aogonek", '\u{0105}'), ("apple", '\u{F8FF}'),
+    ("approxequal", '\u{2248}'), ("aring", '\u{00E5}'), ("arrowboth", '\u{2194}'), ("arrowdown", '\u{2193}'),
+    ("arrowleft", '\u{2190}'), ("arrowright", '\u{2192}'), ("arrowup", '\u{2191}'), ("asciicircum", '^'),
+    ("asciitilde", '~'), ("asterisk", '*'), ("at", '@'), ("atilde", '\u{00E3}'), ("b", 'b'), ("backslash", '\\'),
+    ("bar", '|'), ("beta", '\u{03B2}'), ("braceleft", '{'), ("braceright", '}'), ("bracketleft", '['),
+    ("bracketright", ']'), ("breve", '\u{02D8}'), ("brokenbar", '\u{00A6}'), ("bullet", '\u{2022}'), ("c", 'c'),
+    ("cacute", '\u{0107}'), ("caron", '\u{02C7}'), ("ccaron", '\u{010D}'), ("ccedilla", '\u{00E7}'),
+    ("ccircumflex", '\u{0109}'), ("cdot", '\u{010B}'), ("cdotaccent", '\u{010B}'), ("cedilla", '\u{00B8}'),
+    ("cent", '\u{00A2}'), ("checkmark", '\u{2713}'), ("chi", '\u{03C7}'), ("circumflex", '\u{02C6}'), ("colon", ':'),
+    ("comma", ','), ("commaaccent", '\u{F6C3}'), ("copyright", '\u{00A9}'), ("currency", '\u{00A4}'),
+    ("cwm", '\u{200C}'), ("d", 'd'), ("dagger", '\u{2020}'), ("daggerdbl", '\u{2021}'), ("dcaron", '\u{010F}'),
+    ("dcroat", '\u{0111}'), ("degree", '\u{00B0}'), ("delta", '\u{03B4}'), ("dieresis", '\u{00A8}'),
+    ("divide", '\u{00F7}'), ("dollar", '$'), ("dotaccent", '\u{02D9}'), ("dotlessi", '\u{0131}'),
+    ("dotlessj", '\u{0237}'), ("e", 'e'), ("eacute", '\u{00E9}'), ("ebreve", '\u{0115}'), ("ecaron", '\u{011B}'),
+    ("ecedilla", '\u{0229}'), ("ecircumflex", '\u{00EA}'), ("edieresis", '\u{00EB}'), ("edot", '\u{0117}'),
+    ("edotaccent", '\u{0117}'), ("egrave", '\u{00E8}'), ("eight", '8'), ("ellipsis", '\u{2026}'),
+    ("emacron", '\u{0113}'), ("emdash", '\u{2014}'), ("endash", '\u{2013}'), ("eng", '\u{014B}'),
+    ("eogonek", '\u{0119}'), ("epsilon", '\u{03B5}'), ("equal", '='), ("estimated", '\u{212E}'), ("eta", '\u{03B7}'),
+    ("eth", '\u{00F0}'), ("exclam", '!'), ("exclamdbl", '\u{203C}'), ("exclamdown", '\u{00A1}'), ("f", 'f'),
+    ("ff", '\u{FB00}'), ("ffi", '\u{FB03}'), ("ffl", '\u{FB04}'), ("fi", '\u{FB01}'), ("figuredash", '\u{2012}'),
+    ("five", '5'), ("fiveeighths", '\u{215D}'), ("fl", '\u{FB02}'), ("florin", '\u{0192}'), ("four", '4'),
+    ("foursuperior", '\u{2074}'), ("fraction", '\u{2044}'), ("g", 'g'), ("gacute", '\u{01F5}'),
+    ("gamma", '\u{03B3}'), ("gbreve", '\u{011F}'), ("gcaron", '\u{01E7}'), ("gcedilla", '\u{0123}'),
+    ("gcircumflex", '\u{011D}'), ("gdot", '\u{0121}'), ("gdotaccent", '\u{0121}'), ("germandbls", '\u{00DF}'),
+    ("grave", '`'), ("greater", '>'), ("greaterequal", '\u{2265}'), ("guillemotleft", '\u{00AB}'),
+    ("guillemotright", '\u{00BB}'), ("guilsinglleft", '\u{2039}'), ("guilsinglright", '\u{203A}'), ("h", 'h'),
+    ("hbar", '\u{0127}'), ("hcaron", '\u{021F}'), ("hcircumflex", '\u{0125}'), ("hungarumlaut", '\u{02DD}'),
+    ("hyphen", '-'), ("i", 'i'), ("iacute", '\u{00ED}'), ("ibreve", '\u{012D}'), ("icaron", '\u{01D0}'),
+    ("icircumflex", '\u{00EE}'), ("idieresis", '\u{00EF}'), ("igrave", '\u{00EC}'), ("ij", '\u{0133}'),
+    ("imacron", '\u{012B}'), ("infinity", '\u{221E}'), ("integral", '\u{222B}'), ("iogonek", '\u{012F}'),
+    ("iota", '\u{03B9}'), ("itilde", '\u{0129}'), ("j", 'j'), ("jcaron", '\u{01F0}'), ("jcircumflex", '\u{0135}'),
+    ("k", 'k'), ("kappa", '\u{03BA}'), ("kcaron", '\u{01E9}'), ("kcedilla", '\u{0137}'),
+    ("kgreenlandic", '\u{0138}'), ("l", 'l'), ("lacute", '\u{013A}'), ("lambda", '\u{03BB}'), ("lcaron", '\u{013E}'),
+    ("lcedilla", '\u{013C}'), ("ldot", '\u{0140}'), ("less", '<'), ("lessequal", '\u{2264}'),
+    ("logicalnot", '\u{00AC}'), ("longs", '\u{017F}'), ("lozenge", '\u{25CA}'), ("lslash", '\u{0142}'), ("m", 'm'),
+    ("macron", '\u{00AF}'), ("minus", '\u{2212}'), ("minute", '\u{2032}'), ("mu", '\u{00B5}'),
+    ("multiply", '\u{00D7}'), ("n", 'n'), ("nacute", '\u{0144}'), ("napostrophe", '\u{0149}'),
+    ("nbspace", '\u{00A0}'), ("ncaron", '\u{0148}'), ("ncedilla", '\u{0146}'), ("ngrave", '\u{01F9}'), ("nine", '9'),
+    ("notequal", '\u{2260}'), ("ntilde", '\u{00F1}'), ("nu", '\u{03BD}'), ("numbersign", '#'),
+    ("numero", '\u{2116}'), ("o", 'o'), ("oacute", '\u{00F3}'), ("obreve", '\u{014F}'), ("ocaron", '\u{01D2}'),
+    ("ocircumflex", '\u{00F4}'), ("odieresis", '\u{00F6}'), ("odotaccent", '\u{022F}'), ("oe", '\u{0153}'),
+    ("ogonek", '\u{02DB}'), ("ograve", '\u{00F2}'), ("ohungarumlaut", '\u{0151}'), ("omacron", '\u{014D}'),
+    ("omega", '\u{03C9}'), ("omicron", '\u{03BF}'), ("one", '1'), ("oneeighth", '\u{215B}'), ("onehalf", '\u{00BD}'),
+    ("onequarter", '\u{00BC}'), ("onesuperior", '\u{00B9}'), ("onethird", '\u{2153}'), ("oogonek", '\u{01EB}'),
+    ("ordfeminine", '\u{00AA}'), ("ordmasculine", '\u{00BA}'), ("oslash", '\u{00F8}'), ("otilde", '\u{00F5}'),
+    ("p", 'p'), ("paragraph", '\u{00B6}'), ("parenleft", '('), ("parenright", ')'), ("partialdiff", '\u{2202}'),
+    ("percent", '%'), ("period", '.'), ("periodcentered", '\u{00B7}'), ("perthousand", '\u{2030}'),
+    ("phi", '\u{03C6}'), ("pi", '\u{03C0}'), ("plus", '+'), ("plusminus", '\u{00B1}'), ("product", '\u{220F}'),
+    ("psi", '\u{03C8}'), ("q", 'q'), ("question", '?'), ("questiondown", '\u{00BF}'), ("quotedbl", '"'),
+    ("quotedblbase", '\u{201E}'), ("quotedblleft", '\u{201C}'), ("quotedblright", '\u{201D}'),
+    ("quoteleft", '\u{2018}'), ("quotereversed", '\u{201B}'), ("quoteright", '\u{2019}'),
+    ("quotesinglbase", '\u{201A}'), ("quotesingle", '\''), ("r", 'r'), ("racute", '\u{0155}'),
+    ("radical", '\u{221A}'), ("rcaron", '\u{0159}'), ("rcedilla", '\u{0157}'), ("registered", '\u{00AE}'),
+    ("rho", '\u{03C1}'), ("ring", '\u{02DA}'), ("s", 's'), ("sacute", '\u{015B}'), ("scaron", '\u{0161}'),
+    ("scedilla", '\u{015F}'), ("scircumflex", '\u{015D}'), ("second", '\u{2033}'), ("section", '\u{00A7}'),
+    ("semicolon", ';'), ("seven", '7'), ("seveneighths", '\u{215E}'), ("sfthyphen", '\u{00AD}'),
+    ("sigma", '\u{03C3}'), ("sigma1", '\u{03C2}'), ("six", '6'), ("slash", '/'), ("space", ' '),
+    ("sterling", '\u{00A3}'), ("summation", '\u{2211}'), ("t", 't'), ("tau", '\u{03C4}'), ("tbar", '\u{0167}'),
+    ("tcaron", '\u{0165}'), ("tcedilla", '\u{0163}'), ("theta", '\u{03B8}'), ("thorn", '\u{00FE}'), ("three", '3'),
+    ("threeeighths", '\u{215C}'), ("threequarters", '\u{00BE}'), ("threequartersemdash", '\u{2015}'),
+    ("threesuperior", '\u{00B3}'), ("tilde", '\u{02DC}'), ("trademark", '\u{2122}'), ("two", '2'),
+    ("twosuperior", '\u{00B2}'), ("twothirds", '\u{2154}'), ("u", 'u'), ("uacute", '\u{00FA}'),
+    ("ubreve", '\u{016D}'), ("ucaron", '\u{01D4}'), ("ucircumflex", '\u{00FB}'), ("udieresis", '\u{00FC}'),
+    ("ugrave", '\u{00F9}'), ("uhungarumlaut", '\u{0171}'), ("umacron", '\u{016B}'), ("underscore", '_'),
+    ("underscoredbl", '\u{2017}'), ("uni00A0", '\u{00A0}'), ("uogonek", '\u{0173}'), ("upsilon", '\u{03C5}'),
+    ("uring", '\u{016F}'), ("utilde", '\u{0169}'), ("v", 'v'), ("w", 'w'), ("wcircumflex", '\u{0175}'), ("x", 'x'),
+    ("xi", '\u{03BE}'), ("y", 'y'), ("yacute", '\u{00FD}'), ("ycircumflex", '\u{0177}'), ("ydieresis", '\u{00FF}'),
+    ("yen", '\u{00A5}'), ("ymacron", '\u{0233}'), ("z", 'z'), ("zacute", '\u{017A}'), ("zcaron", '\u{017E}'),
+    ("zdot", '\u{017C}'), ("zdotaccent", '\u{017C}'), ("zero", '0'), ("zeroinferior", '\u{2080}'),
+    ("zerosuperior", '\u{2070}'), ("zeta", '\u{03B6}'),
+];
+
+/// The characters of each base encoding by code (spec Annex D.2), with None for unused codes
+const STANDARD: [Option<char>; 256] = [
+    None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, None, None, None, None, None, None, None, None, None, None, Some(' '), Some('!'), Some('"'),
+    Some('#'), Some('$'), Some('%'), Some('&'), Some('\u{2019}'), Some('('), Some(')'), Some('*'), Some('+'),
+    Some(','), Some('-'), Some('.'), Some('/'), Some('0'), Some('1'), Some('2'), Some('3'), Some('4'), Some('5'),
+    Some('6'), Some('7'), Some('8'), Some('9'), Some(':'), Some(';'), Some('<'), Some('='), Some('>'), Some('?'),
+    Some('@'), Some('A'), Some('B'), Some('C'), Some('D'), Some('E'), Some('F'), Some('G'), Some('H'), Some('I'),
+    Some('J'), Some('K'), Some('L'), Some('M'), Some('N'), Some('O'), Some('P'), Some('Q'), Some('R'), Some('S'),
+    Some('T'), Some('U'), Some('V'), Some('W'), Some('X'), Some('Y'), Some('Z'), Some('['), Some('\\'), Some(']'),
+    Some('^'), Some('_'), Some('\u{2018}'), Some('a'), Some('b'), Some('c'), Some('d'), Some('e'), Some('f'),
+    Some('g'), Some('h'), Some('i'), Some('j'), Some('k'), Some('l'), Some('m'), Some('n'), Some('o'), Some('p'),
+    Some('q'), Some('r'), Some('s'), Some('t'), Some('u'), Some('v'), Some('w'), Some('x'), Some('y'), Some('z'),
+    Some('{'), Some('|'), Some('}'), Some('~'), None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, None, Some('\u{00A1}'), Some('\u{00A2}'), Some('\u{00A3}'), Some('\u{2044}'), Some('\u{00A5}'),
+    Some('\u{0192}'), Some('\u{00A7}'), Some('\u{00A4}'), Some('\''), Some('\u{201C}'), Some('\u{00AB}'),
+    Some('\u{2039}'), Some('\u{203A}'), Some('\u{FB01}'), Some('\u{FB02}'), None, Some('\u{2013}'), Some('\u{2020}'),
+    Some('\u{2021}'), Some('\u{00B7}'), None, Some('\u{00B6}'), Some('\u{2022}'), Some('\u{201A}'), Some('\u{201E}'),
+    Some('\u{201D}'), Some('\u{00BB}'), Some('\u{2026}'), Some('\u{2030}'), None, Some('\u{00BF}'), None, Some('`'),
+    Some('\u{00B4}'), Some('\u{02C6}'), Some('\u{02DC}'), Some('\u{00AF}'), Some('\u{02D8}'), Some('\u{02D9}'),
+    Some('\u{00A8}'), None, Some('\u{02DA}'), Some('\u{00B8}'), None, Some('\u{02DD}'), Some('\u{02DB}'),
+    Some('\u{02C7}'), Some('\u{2014}'), None, None, None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, Some('\u{00C6}'), None, Some('\u{00AA}'), None, None, None, None, Some('\u{0141}'),
+    Some('\u{00D8}'), Some('\u{0152}'), Some('\u{00BA}'), None, None, None, None, None, Some('\u{00E6}'), None, None,
+    None, Some('\u{0131}'), None, None, Some('\u{0142}'), Some('\u{00F8}'), Some('\u{0153}'), Some('\u{00DF}'), None,
+    None, None, None,
+];
+const WIN_ANSI: [Option<char>; 256] = [
+    None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, None, None, None, None, None, None, None, None, None, None, Some(' '), Some('!'), Some('"'),
+    Some('#'), Some('$'), Some('%'), Some('&'), Some('\''), Some('('), Some(')'), Some('*'), Some('+'), Some(','),
+    Some('-'), Some('.'), Some('/'), Some('0'), Some('1'), Some('2'), Some('3'), Some('4'), Some('5'), Some('6'),
+    Some('7'), Some('8'), Some('9'), Some(':'), Some(';'), Some('<'), Some('='), Some('>'), Some('?'), Some('@'),
+    Some('A'), Some('B'), Some('C'), Some('D'), Some('E'), Some('F'), Some('G'), Some('H'), Some('I'), Some('J'),
+    Some('K'), Some('L'), Some('M'), Some('N'), Some('O'), Some('P'), Some('Q'), Some('R'), Some('S'), Some('T'),
+    Some('U'), Some('V'), Some('W'), Some('X'), Some('Y'), Some('Z'), Some('['), Some('\\'), Some(']'), Some('^'),
+    Some('_'), Some('`'), Some('a'), Some('b'), Some('c'), Some('d'), Some('e'), Some('f'), Some('g'), Some('h'),
+    Some('i'), Some('j'), Some('k'), Some('l'), Some('m'), Some('n'), Some('o'), Some('p'), Some('q'), Some('r'),
+    Some('s'), Some('t'), Some('u'), Some('v'), Some('w'), Some('x'), Some('y'), Some('z'), Some('{'), Some('|'),
+    Some('}'), Some('~'), None, Some('\u{20AC}'), None, Some('\u{201A}'), Some('\u{0192}'), Some('\u{201E}'),
+    Some('\u{2026}'), Some('\u{2020}'), Some('\u{2021}'), Some('\u{02C6}'), Some('\u{2030}'), Some('\u{0160}'),
+    Some('\u{2039}'), Some('\u{0152}'), None, Some('\u{017D}'), None, None, Some('\u{2018}'), Some('\u{2019}'),
+    Some('\u{201C}'), Some('\u{201D}'), Some('\u{2022}'), Some('\u{2013}'), Some('\u{2014}'), Some('\u{02DC}'),
+    Some('\u{2122}'), Some('\u{0161}'), Some('\u{203A}'), Some('\u{0153}'), None, Some('\u{017E}'), Some('\u{0178}'),
+    Some('\u{00A0}'), Some('\u{00A1}'), Some('\u{00A2}'), Some('\u{00A3}'), Some('\u{00A4}'), Some('\u{00A5}'),
+    Some('\u{00A6}'), Some('\u{00A7}'), Some('\u{00A8}'), Some('\u{00A9}'), Some('\u{00AA}'), Some('\u{00AB}'),
+    Some('\u{00AC}'), Some('\u{00AD}'), Some('\u{00AE}'), Some('\u{00AF}'), Some('\u{00B0}'), Some('\u{00B1}'),
+    Some('\u{00B2}'), Some('\u{00B3}'), Some('\u{00B4}'), Some('\u{00B5}'), Some('\u{00B6}'), Some('\u{00B7}'),
+    Some('\u{00B8}'), Some('\u{00B9}'), Some('\u{00BA}'), Some('\u{00BB}'), Some('\u{00BC}'), Some('\u{00BD}'),
+    Some('\u{00BE}'), Some('\u{00BF}'), Some('\u{00C0}'), Some('\u{00C1}'), Some('\u{00C2}'), Some('\u{00C3}'),
+    Some('\u{00C4}'), Some('\u{00C5}'), Some('\u{00C6}'), Some('\u{00C7}'), Some('\u{00C8}'), Some('\u{00C9}'),
+    Some('\u{00CA}'), Some('\u{00CB}'), Some('\u{00CC}'), Some('\u{00CD}'), Some('\u{00CE}'), Some('\u{00CF}'),
+    Some('\u{00D0}'), Some('\u{00D1}'), Some('\u{00D2}'), Some('\u{00D3}'), Some('\u{00D4}'), Some('\u{00D5}'),
+    Some('\u{00D6}'), Some('\u{00D7}'), Some('\u{00D8}'), Some('\u{00D9}'), Some('\u{00DA}'), Some('\u{00DB}'),
+    Some('\u{00DC}'), Some('\u{00DD}'), Some('\u{00DE}'), Some('\u{00DF}'), Some('\u{00E0}'), Some('\u{00E1}'),
+    Some('\u{00E2}'), Some('\u{00E3}'), Some('\u{00E4}'), Some('\u{00E5}'), Some('\u{00E6}'), Some('\u{00E7}'),
+    Some('\u{00E8}'), Some('\u{00E9}'), Some('\u{00EA}'), Some('\u{00EB}'), Some('\u{00EC}'), Some('\u{00ED}'),
+    Some('\u{00EE}'), Some('\u{00EF}'), Some('\u{00F0}'), Some('\u{00F1}'), Some('\u{00F2}'), Some('\u{00F3}'),
+    Some('\u{00F4}'), Some('\u{00F5}'), Some('\u{00F6}'), Some('\u{00F7}'), Some('\u{00F8}'), Some('\u{00F9}'),
+    Some('\u{00FA}'), Some('\u{00FB}'), Some('\u{00FC}'), Some('\u{00FD}'), Some('\u{00FE}'), Some('\u{00FF}'),
+];
+const MAC_ROMAN: [Option<char>; 256] = [
+    None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+    None, None, None, None, None, None, None, None, None, None, None, None, None, Some(' '), Some('!'), Some('"'),
+    Some('#'), Some('$'), Some('%'), Some('&'), Some('\''), Some('('), Some(')'), Some('*'), Some('+'), Some(','),
+    Some('-'), Some('.'), Some('/'), Some('0'), Some('1'), Some('2'), Some('3'), Some('4'), Some('5'), Some('6'),
+    Some('7'), Some('8'), Some('9'), Some(':'), Some(';'), Some('<'), Some('='), Some('>'), Some('?'), Some('@'),
+    Some('A'), Some('B'), Some('C'), Some('D'), Some('E'), Some('F'), Some('G'), Some('H'), Some('I'), Some('J'),
+    Some('K'), Some('L'), Some('M'), Some('N'), Some('O'), Some('P'), Some('Q'), Some('R'), Some('S'), Some('T'),
+    Some('U'), Some('V'), Some('W'), Some('X'), Some('Y'), Some('Z'), Some('['), Some('\\'), Some(']'), Some('^'),
+    Some('_'), Some('`'), Some('a'), Some('b'), Some('c'), Some('d'), Some('e'), Some('f'), Some('g'), Some('h'),
+    Some('i'), Some('j'), Some('k'), Some('l'), Some('m'), Some('n'), Some('o'), Some('p'), Some('q'), Some('r'),
+    Some('s'), Some('t'), Some('u'), Some('v'), Some('w'), Some('x'), Some('y'), Some('z'), Some('{'), Some('|'),
+    Some('}'), Some('~'), None, Some('\u{00C4}'), Some('\u{00C5}'), Some('\u{00C7}'), Some('\u{00C9}'),
+    Some('\u{00D1}'), Some('\u{00D6}'), Some('\u{00DC}'), Some('\u{00E1}'), Some('\u{00E0}'), Some('\u{00E2}'),
+    Some('\u{00E4}'), Some('\u{00E3}'), Some('\u{00E5}'), Some('\u{00E7}'), Some('\u{00E9}'), Some('\u{00E8}'),
+    Some('\u{00EA}'), Some('\u{00EB}'), Some('\u{00ED}'), Some('\u{00EC}'), Some('\u{00EE}'), Some('\u{00EF}'),
+    Some('\u{00F1}'), Some('\u{00F3}'), Some('\u{00F2}'), Some('\u{00F4}'), Some('\u{00F6}'), Some('\u{00F5}'),
+    Some('\u{00FA}'), Some('\u{00F9}'), Some('\u{00FB}'), Some('\u{00FC}'), Some('\u{2020}'), Some('\u{00B0}'),
+    Some('\u{00A2}'), Some('\u{00A3}'), Some('\u{00A7}'), Some('\u{2022}'), Some('\u{00B6}'), Some('\u{00DF}'),
+    Some('\u{00AE}'), Some('\u{00A9}'), Some('\u{2122}'), Some('\u{00B4}'), Some('\u{00A8}'), Some('\u{2260}'),
+    Some('\u{00C6}'), Some('\u{00D8}'), Some('\u{221E}'), Some('\u{00B1}'), Some('\u{2264}'), Some('\u{2265}'),
+    Some('\u{00A5}'), Some('\u{00B5}'), Some('\u{2202}'), Some('\u{2211}'), Some('\u{220F}'), Some('\u{03C0}'),
+    Some('\u{222B}'), Some('\u{00AA}'), Some('\u{00BA}'), Some('\u{03A9}'), Some('\u{00E6}'), Some('\u{00F8}'),
+    Some('\u{00BF}'), Some('\u{00A1}'), Some('\u{00AC}'), Some('\u{221A}'), Some('\u{0192}'), Some('\u{2248}'),
+    Some('\u{2206}'), Some('\u{00AB}'), Some('\u{00BB}'), Some('\u{2026}'), Some('\u{00A0}'), Some('\u{00C0}'),
+    Some('\u{00C3}'), Some('\u{00D5}'), Some('\u{0152}'), Some('\u{0153}'), Some('\u{2013}'), Some('\u{2014}'),
+    Some('\u{201C}'), Some('\u{201D}'), Some('\u{2018}'), Some('\u{2019}'), Some('\u{00F7}'), Some('\u{25CA}'),
+    Some('\u{00FF}'), Some('\u{0178}'), Some('\u{2044}'), Some('\u{20AC}'), Some('\u{2039}'), Some('\u{203A}'),
+    Some('\u{FB01}'), Some('\u{FB02}'), Some('\u{2021}'), Some('\u{00B7}'), Some('\u{201A}'), Some('\u{201E}'),
+    Some('\u{2030}'), Some('\u{00C2}'), Some('\u{00CA}'), Some('\u{00C1}'), Some('\u{00CB}'), Some('\u{00C8}'),
+    Some('\u{00CD}'), Some('\u{00CE}'), Some('\u{00CF}'), Some('\u{00CC}'), Some('\u{00D3}'), Some('\u{00D4}'),
+    Some('\u{F8FF}'), Some('\u{00D2}'), Some('\u{00DA}'), Some('\u{00DB}'), Some('\u{00D9}'), Some('\u{0131}'),
+    Some('\u{02C6}'), Some('\u{02DC}'), Some('\u{00AF}'), Some('\u{02D8}'), Some('\u{02D9}'), Some('\u{02DA}'),
+    Some('\u{00B8}'), Some('\u{02DD}'), Some('\u{02DB}'), Some('\u{02C7}'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_names() {
+        assert!(GLYPH_LIST.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let names = ["A", "eacute", "quoteright", "fi", "f_f_i", "uni20AC", "uni00410042", "u1F600", "a.sc",
+                     "Lslash", "sigma1"];
+        let text: Vec<String> = names.iter().map(|name| glyph_name_to_unicode(name).unwrap()).collect();
+        assert_eq!(text, vec!["A", "é", "\u{2019}", "\u{FB01}", "ffi", "€", "AB", "😀", "a", "Ł", "ς"]);
+        for name in [".notdef", "g123", "uniD800", "uni41", "foo_bar"] {
+            assert_eq!(glyph_name_to_unicode(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_base_encodings() {
+        let decode = |encoding: BaseEncoding, codes: &[u8]| -> String {
+            codes.iter().map(|code| encoding.decode(*code).unwrap_or('?')).collect()
+        };
+        assert_eq!(decode(BaseEncoding::Standard, b"It\x27s \xAE\xD0\x80"), "It\u{2019}s \u{FB01}\u{2014}?");
+        assert_eq!(decode(BaseEncoding::WinAnsi, b"\x80\x93\x81\xE9"), "€\u{201C}?é");
+        assert_eq!(decode(BaseEncoding::MacRoman, b"\x8E\xD2\xA5"), "é\u{201C}•");
+        assert_eq!(BaseEncoding::from_name("MacExpertEncoding"), None);
+    }
+}
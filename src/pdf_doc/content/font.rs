@@ -0,0 +1,525 @@
+use std::convert::TryFrom;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::*;
+use crate::errors::*;
+use crate::doc_tree::decode::PdfBinaryStream;
+
+/// Glyph width used when a simple font gives no width for a code, in thousandths of text space units.  Only
+/// an approximation.
+const DEFAULT_SIMPLE_WIDTH: f32 = 500.0;
+/// Default glyph width for composite fonts without a /DW entry (spec 9.7.4.3).
+const DEFAULT_CID_WIDTH: f32 = 1000.0;
+/// The font descriptor flag for fonts using glyphs outside the standard Latin set (spec 9.8.2).
+const SYMBOLIC_FLAG: i32 = 1 << 2;
+/// Maps glyph space to text space for every font type but Type3, which gives its own /FontMatrix.
+const STANDARD_FONT_MATRIX: Matrix = Matrix { a: 0.001, b: 0.0, c: 0.0, d: 0.001, e: 0.0, f: 0.0 };
+
+/// The parts of a font dictionary needed to turn shown strings into text and positions (spec 9.6-9.10).
+#[derive(Debug, Clone)]
+pub struct Font {
+    pub subtype: String,
+    pub base_font: Option<String>,
+    /// How string bytes split into character codes.  Simple fonts always use single bytes.
+    encoding: CMap,
+    to_unicode: Option<CMap>,
+    /// Widths in glyph space units, by character code
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+    font_matrix: Matrix,
+    /// The base encoding of a simple font, if it has a known one
+    base_encoding: Option<BaseEncoding>,
+    /// Glyph names from /Differences, replacing the base encoding for their codes
+    glyph_names: HashMap<u32, String>,
+    /// The content stream drawing each named glyph of a Type3 font
+    glyph_procedures: HashMap<String, Vec<u8>>,
+    /// Composite font widths from the descendant's /W array, taking precedence over `widths`
+    cid_widths: CidWidths,
+    font_file: Option<(FontFormat, Rc<PdfBinaryStream>)>,
+}
+
+/// The format of a font program embedded in a font descriptor (spec 9.9).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontFormat {
+    /// From /FontFile
+    Type1,
+    /// From /FontFile2
+    TrueType,
+    /// Compact font format, from /FontFile3 with /Subtype Type1C or CIDFontType0C
+    Cff,
+    /// From /FontFile3 with /Subtype OpenType
+    OpenType,
+}
+
+impl FontFormat {
+    /// The usual file extension for a font program in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FontFormat::Type1 => "pfa",
+            FontFormat::TrueType => "ttf",
+            FontFormat::Cff => "cff",
+            FontFormat::OpenType => "otf",
+        }
+    }
+}
+
+/// A font program found in a document by PdfDoc::embedded_fonts.
+#[derive(Debug, Clone)]
+pub struct EmbeddedFont {
+    /// The font dictionary using the program
+    pub id: ObjectId,
+    pub base_font: Option<String>,
+    /// The six capital letters before the + in the name of a subset font
+    pub subset_tag: Option<String>,
+    pub format: FontFormat,
+    pub program: Vec<u8>,
+}
+
+/// The widths of a CIDFont's /W array (spec 9.7.4.3), as sorted, non-overlapping ranges of CIDs.  Each
+/// range holds one width per CID, or a single width shared by the whole range.
+#[derive(Debug, Clone, Default)]
+struct CidWidths {
+    ranges: Vec<(u32, u32, Vec<f32>)>,
+}
+
+impl CidWidths {
+    fn parse(array: &[Rc<PdfObject>]) -> Result<Self> {
+        let mut ranges = Vec::new();
+        let mut index = 0;
+        while index < array.len() {
+            // Ranges with negative or overflowing CIDs can't match any code, so they are skipped
+            let first = u32::try_from(array[index].try_into_int()?).ok();
+            match array.get(index + 1) {
+                Some(widths) if widths.is_array() => {
+                    let widths = widths.try_into_array()?.iter()
+                                       .map(|width| width.try_into_number())
+                                       .collect::<Result<Vec<f32>>>()?;
+                    let last = first.zip(u32::try_from(widths.len()).ok())
+                                    .and_then(|(first, count)| first.checked_add(count.checked_sub(1)?));
+                    if let (Some(first), Some(last)) = (first, last) {
+                        ranges.push((first, last, widths));
+                    };
+                    index += 2;
+                },
+                Some(last) => {
+                    let width = array.get(index + 2)
+                                     .ok_or_else(|| ErrorKind::ParsingError("Truncated /W array".to_string()))?;
+                    let last = u32::try_from(last.try_into_int()?).ok();
+                    if let (Some(first), Some(last)) = (first, last) {
+                        if first <= last {
+                            ranges.push((first, last, vec![width.try_into_number()?]));
+                        };
+                    };
+                    index += 3;
+                },
+                None => Err(ErrorKind::ParsingError("Truncated /W array".to_string()))?
+            }
+        }
+        ranges.sort_by_key(|(first, _, _)| *first);
+        Ok(CidWidths { ranges })
+    }
+
+    fn get(&self, cid: u32) -> Option<f32> {
+        // The last range starting at or before cid is the only one that can contain it
+        let position = self.ranges.partition_point(|(first, _, _)| *first <= cid);
+        let (first, last, widths) = self.ranges.get(position.checked_sub(1)?)?;
+        if cid > *last {
+            return None;
+        };
+        if widths.len() == 1 { Some(widths[0]) } else { widths.get((cid - first) as usize).cloned() }
+    }
+}
+
+impl Font {
+    pub fn from_dict(dict: &PdfMap) -> Result<Self> {
+        let subtype = match dict.get("Subtype") {
+            Some(subtype) => subtype.try_into_string()?.to_string(),
+            None => Err(ErrorKind::ParsingError("Font dictionary missing /Subtype".to_string()))?
+        };
+        let base_font = match dict.get("BaseFont") {
+            Some(name) => Some(name.try_into_string()?.to_string()),
+            None => None
+        };
+        let to_unicode = match dict.get("ToUnicode") {
+            Some(stream) if stream.is_stream() => {
+                let stream = stream.try_into_binary_stream()?;
+                Some(CMap::parse(stream.data()?).chain_err(|| ErrorKind::ParsingError(
+                    format!("Invalid ToUnicode map in font {:?}", base_font)))?)
+            },
+            _ => None
+        };
+        let mut font = Font::new(subtype, base_font, to_unicode);
+        if font.is_composite() {
+            font.read_composite_entries(dict)?;
+        } else {
+            if font.is_type3() {
+                font.read_type3_entries(dict)?;
+            };
+            font.read_simple_encoding(dict)?;
+            font.read_simple_widths(dict)?;
+        }
+        let descriptor = match font.descendant(dict)? {
+            Some(descendant) => descendant.get("FontDescriptor").cloned(),
+            None => dict.get("FontDescriptor").cloned()
+        };
+        if let Some(descriptor) = descriptor {
+            let descriptor = descriptor.try_into_map()?;
+            font.font_file = Font::read_font_file(&descriptor)?;
+        };
+        Ok(font)
+    }
+
+    /// A Type1 standard font used without a font dictionary, with its standard encoding and AFM widths.
+    pub fn standard(base_font: &str) -> Self {
+        let mut font = Font::new("Type1".to_string(), Some(base_font.to_string()), None);
+        font.base_encoding = Some(BaseEncoding::Standard);
+        font.widths.extend(standard_font_widths(base_font).into_iter().flatten());
+        font
+    }
+
+    fn new(subtype: String, base_font: Option<String>, to_unicode: Option<CMap>) -> Self {
+        Font {
+            subtype,
+            base_font,
+            encoding: CMap::fixed_width(1),
+            to_unicode,
+            widths: HashMap::new(),
+            default_width: DEFAULT_SIMPLE_WIDTH,
+            font_matrix: STANDARD_FONT_MATRIX,
+            base_encoding: None,
+            glyph_names: HashMap::new(),
+            glyph_procedures: HashMap::new(),
+            cid_widths: CidWidths::default(),
+            font_file: None,
+        }
+    }
+
+    /// Whether this is a Type0 font, whose strings may use multi-byte codes.
+    pub fn is_composite(&self) -> bool {
+        self.subtype == "Type0"
+    }
+
+    /// Whether this is a Type3 font, whose glyphs are content streams in /CharProcs.
+    pub fn is_type3(&self) -> bool {
+        self.subtype == "Type3"
+    }
+
+    /// The transformation from glyph space to text space.
+    pub fn font_matrix(&self) -> Matrix {
+        self.font_matrix
+    }
+
+    /// The tag naming a subset font, like EOODIA in EOODIA+Poetica (spec 9.6.4).
+    pub fn subset_tag(&self) -> Option<&str> {
+        let (tag, _) = self.base_font.as_ref()?.split_once('+')?;
+        if tag.len() == 6 && tag.bytes().all(|c| c.is_ascii_uppercase()) { Some(tag) } else { None }
+    }
+
+    /// The decoded font program embedded in the font descriptor, with its format.
+    pub fn embedded_program(&self) -> Result<Option<(FontFormat, &[u8])>> {
+        self.font_file.as_ref().map(|(format, stream)| Ok((*format, stream.data()?))).transpose()
+    }
+
+    fn read_font_file(descriptor: &PdfMap) -> Result<Option<(FontFormat, Rc<PdfBinaryStream>)>> {
+        if let Some(file) = descriptor.get("FontFile") {
+            return Ok(Some((FontFormat::Type1, file.try_into_binary_stream()?)));
+        };
+        if let Some(file) = descriptor.get("FontFile2") {
+            return Ok(Some((FontFormat::TrueType, file.try_into_binary_stream()?)));
+        };
+        if let Some(file) = descriptor.get("FontFile3") {
+            let file = file.try_into_binary_stream()?;
+            let format = match file.attributes().get("Subtype") {
+                Some(subtype) if subtype.try_into_string()?.as_str() == "OpenType" => FontFormat::OpenType,
+                _ => FontFormat::Cff
+            };
+            return Ok(Some((format, file)));
+        };
+        Ok(None)
+    }
+
+    /// The CIDFont under a Type0 font.
+    fn descendant(&self, dict: &PdfMap) -> Result<Option<Rc<PdfMap>>> {
+        if !self.is_composite() {
+            return Ok(None);
+        };
+        match dict.get("DescendantFonts") {
+            Some(fonts) => fonts.try_into_array()?.first().map(|font| font.try_into_map()).transpose(),
+            None => Ok(None)
+        }
+    }
+
+    /// The decoded content stream drawing a Type3 glyph, if the code is mapped to one.
+    pub fn glyph_procedure(&self, code: u32) -> Option<&[u8]> {
+        self.glyph_names.get(&code)
+            .and_then(|name| self.glyph_procedures.get(name))
+            .map(|procedure| &procedure[..])
+    }
+
+    fn read_type3_entries(&mut self, dict: &PdfMap) -> Result<()> {
+        let values = match dict.get("FontMatrix") {
+            Some(matrix) => matrix.try_into_array()?.iter()
+                                  .map(|value| value.try_into_number())
+                                  .collect::<Result<Vec<f32>>>()?,
+            None => Err(ErrorKind::ParsingError("Type3 font missing /FontMatrix".to_string()))?
+        };
+        if values.len() != 6 {
+            Err(ErrorKind::ParsingError(format!("Invalid /FontMatrix {:?}", values)))?
+        };
+        self.font_matrix = Matrix::new(values[0], values[1], values[2], values[3], values[4], values[5]);
+        // Glyphs missing from /Widths draw nothing, so they shouldn't move the pen either
+        self.default_width = 0.0;
+        if let Some(procedures) = dict.get("CharProcs") {
+            for (name, procedure) in procedures.try_into_map()?.iter() {
+                self.glyph_procedures.insert(name.to_string(), procedure.try_into_binary_stream()?.data()?.to_vec());
+            }
+        };
+        Ok(())
+    }
+
+    fn read_composite_entries(&mut self, dict: &PdfMap) -> Result<()> {
+        self.encoding = match dict.get("Encoding") {
+            Some(encoding) if encoding.is_stream() => {
+                let cmap = CMap::parse(encoding.try_into_binary_stream()?.data()?)?;
+                if cmap.has_codespace() { cmap } else { CMap::fixed_width(2) }
+            },
+            // Identity-H/V and the predefined CJK CMaps other than a few legacy ones use two-byte codes
+            _ => match &self.to_unicode {
+                Some(to_unicode) if to_unicode.has_codespace() => to_unicode.clone(),
+                _ => CMap::fixed_width(2)
+            }
+        };
+        self.default_width = DEFAULT_CID_WIDTH;
+        if let Some(descendant) = self.descendant(dict)? {
+            if let Some(width) = descendant.get("DW") {
+                self.default_width = width.try_into_number()?;
+            };
+            if let Some(widths) = descendant.get("W") {
+                self.cid_widths = CidWidths::parse(&widths.try_into_array()?)
+                    .chain_err(|| ErrorKind::ParsingError(format!("Invalid /W array in font {:?}", self.base_font)))?;
+            };
+        };
+        Ok(())
+    }
+
+    /// Read /Encoding as a base encoding name, or a dictionary with a base encoding and /Differences
+    /// (spec 9.6.5).  Nonsymbolic Type1 fonts fall back on StandardEncoding, while other fonts are left to
+    /// their built-in encodings, which aren't read from font programs.
+    fn read_simple_encoding(&mut self, dict: &PdfMap) -> Result<()> {
+        let symbolic = match dict.get("FontDescriptor") {
+            Some(descriptor) => match descriptor.try_into_map()?.get("Flags") {
+                Some(flags) => flags.try_into_int()? & SYMBOLIC_FLAG != 0,
+                None => false
+            },
+            None => matches!(self.base_font.as_deref(), Some("Symbol") | Some("ZapfDingbats")),
+        };
+        if !symbolic && (self.subtype == "Type1" || self.subtype == "MMType1") {
+            self.base_encoding = Some(BaseEncoding::Standard);
+        };
+        let encoding = match dict.get("Encoding") {
+            Some(encoding) => encoding,
+            None => return Ok(())
+        };
+        if !encoding.is_map() {
+            self.base_encoding = BaseEncoding::from_name(&encoding.try_into_string()?).or(self.base_encoding);
+            return Ok(());
+        };
+        let encoding = encoding.try_into_map()?;
+        if let Some(name) = encoding.get("BaseEncoding") {
+            self.base_encoding = BaseEncoding::from_name(&name.try_into_string()?).or(self.base_encoding);
+        };
+        if let Some(differences) = encoding.get("Differences") {
+            // Names after an out-of-range code have no code of their own, so they're skipped up to the next code
+            let mut code = Some(0);
+            for entry in differences.try_into_array()?.iter() {
+                match entry.try_into_int() {
+                    Ok(start) => {
+                        code = u32::try_from(start).ok();
+                        if code.is_none() {
+                            warn!("Skipping /Differences from invalid code {}", start);
+                        };
+                    },
+                    Err(_) => {
+                        let name = entry.try_into_string()?;
+                        if let Some(current) = code {
+                            self.glyph_names.insert(current, name.to_string());
+                            code = current.checked_add(1);
+                        };
+                    }
+                }
+            }
+        };
+        Ok(())
+    }
+
+    /// Read /Widths, or for a standard font without them, use the font's AFM widths.
+    fn read_simple_widths(&mut self, dict: &PdfMap) -> Result<()> {
+        if let (Some(first_char), Some(widths)) = (dict.get("FirstChar"), dict.get("Widths")) {
+            let first_char = first_char.try_into_int()?;
+            let widths = widths.try_into_array()?;
+            for (offset, width) in widths.iter().enumerate() {
+                let code = u32::try_from(first_char).ok()
+                    .and_then(|first| u32::try_from(offset).ok().and_then(|offset| first.checked_add(offset)));
+                match code {
+                    Some(code) => { self.widths.insert(code, width.try_into_number()?); },
+                    None => {
+                        warn!("Skipping {} /Widths from invalid /FirstChar {}", widths.len() - offset, first_char);
+                        break;
+                    }
+                };
+            }
+        } else if !self.is_type3() {
+            if let Some(widths) = self.base_font.as_deref().and_then(standard_font_widths) {
+                self.widths.extend(widths);
+            };
+        };
+        if let Some(descriptor) = dict.get("FontDescriptor") {
+            if let Some(width) = descriptor.try_into_map()?.get("MissingWidth") {
+                self.default_width = width.try_into_number()?;
+            };
+        };
+        Ok(())
+    }
+
+    /// Split the bytes of a shown string into character codes, each with its byte length.
+    pub fn split_codes(&self, bytes: &[u8]) -> Vec<(u32, usize)> {
+        self.encoding.split_codes(bytes)
+    }
+
+    /// The text for a character code.  Without a ToUnicode map, simple fonts use their glyph names and base
+    /// encodings.  Failing those, single-byte codes are read as Latin-1; multi-byte codes are CIDs with no
+    /// fixed meaning, so they come out as U+FFFD rather than as made-up text.
+    pub fn decode(&self, code: u32, byte_length: usize) -> String {
+        if let Some(text) = self.to_unicode.as_ref().and_then(|cmap| cmap.lookup(code)) {
+            return text.to_string();
+        };
+        if let Some(text) = self.glyph_names.get(&code).and_then(|name| glyph_name_to_unicode(name)) {
+            return text;
+        };
+        if let Some(c) = self.base_encoding.and_then(|encoding| encoding.decode(code as u8)) {
+            return c.to_string();
+        };
+        match std::char::from_u32(code) {
+            Some(c) if byte_length == 1 && !c.is_control() => c.to_string(),
+            _ => '\u{FFFD}'.to_string()
+        }
+    }
+
+    /// Glyph width for a character code, in thousandths of text space units.  Composite font codes are
+    /// taken as CIDs, which holds for the Identity CMaps that nearly all embedded fonts use.
+    pub fn width(&self, code: u32) -> f32 {
+        if self.is_composite() {
+            return self.cid_widths.get(code).unwrap_or(self.default_width);
+        };
+        let width = *self.widths.get(&code).unwrap_or(&self.default_width);
+        if self.is_type3() { width * self.font_matrix.a * 1000.0 } else { width }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn font_from(bodies: &[&str]) -> Font {
+        let mut builder = MiniPdfBuilder::new();
+        let ids: Vec<u32> = bodies.iter().map(|body| builder.add_object(body)).collect();
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        Font::from_dict(&pdf.get_dict(ObjectId::new(ids[0], 0)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_simple_font() {
+        let font = font_from(&["<< /Type /Font /Subtype /TrueType /BaseFont /Arial /FirstChar 65 /Widths [722 667] >>"]);
+        assert!(!font.is_composite());
+        assert_eq!(font.base_font.as_deref(), Some("Arial"));
+        assert_eq!(font.split_codes(b"AB"), vec![(65, 1), (66, 1)]);
+        assert_eq!(font.decode(65, 1), "A");
+        assert_eq!(font.width(66), 667.0);
+        assert_eq!(font.width(67), DEFAULT_SIMPLE_WIDTH);
+    }
+
+    #[test]
+    fn test_standard_font() {
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold >>"]);
+        assert_eq!((font.width(b'W' as u32), font.width(b'i' as u32), font.width(0xE9)), (944.0, 278.0, 500.0));
+        // /Widths take precedence over the built-in metrics
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Courier /FirstChar 65 /Widths [250] >>"]);
+        assert_eq!((font.width(65), font.width(66)), (250.0, DEFAULT_SIMPLE_WIDTH));
+        // Widths that fall outside the code range are skipped rather than wrapping round
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Custom /FirstChar -1 /Widths [500 600] >>"]);
+        assert_eq!((font.width(0), font.width(u32::MAX)), (DEFAULT_SIMPLE_WIDTH, DEFAULT_SIMPLE_WIDTH));
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Custom /FirstChar 2147483647 \
+                                  /Widths [500 600] >>"]);
+        assert_eq!((font.width(0x7FFF_FFFF), font.width(0x8000_0000)), (500.0, 600.0));
+        let font = Font::standard("Times-Roman");
+        assert_eq!((font.decode(0x27, 1).as_str(), font.width(0x27)), ("\u{2019}", 333.0));
+    }
+
+    #[test]
+    fn test_encodings() {
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Times-Roman >>"]);
+        assert_eq!(font.decode(0x27, 1), "\u{2019}");
+        let font = font_from(&["<< /Type /Font /Subtype /TrueType /BaseFont /Arial /Encoding /WinAnsiEncoding >>"]);
+        assert_eq!(font.decode(0x80, 1), "€");
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Custom /Encoding << /Type /Encoding \
+                                  /BaseEncoding /MacRomanEncoding /Differences [1 /f_i /Euro 65 /uni00E9] >> >>"]);
+        let text: Vec<String> = [1, 2, 3, 65, 66, 0x8E].iter().map(|&code| font.decode(code, 1)).collect();
+        assert_eq!(text, vec!["fi", "€", "\u{FFFD}", "é", "B", "é"]);
+        // Names after an out-of-range code are dropped rather than wrapping round
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Custom /Encoding << /Type /Encoding \
+                                  /Differences [-1 /f_i 2147483647 /Euro /Euro 66 /uni00E9] >> >>"]);
+        let text: Vec<String> = [0, 65, 66].iter().map(|&code| font.decode(code, 1)).collect();
+        assert_eq!(text, vec!["\u{FFFD}", "A", "é"]);
+        // Symbolic fonts keep their built-in encoding, which isn't known here
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>"]);
+        assert_eq!(font.decode(0x27, 1), "'");
+    }
+
+    #[test]
+    fn test_composite_font() {
+        let font = font_from(&["<< /Type /Font /Subtype /Type0 /BaseFont /MSMincho /Encoding /Identity-H \
+                                  /DescendantFonts [4 0 R] >>",
+                               "<< /Type /Font /Subtype /CIDFontType2 /DW 900 >>"]);
+        assert!(font.is_composite());
+        assert_eq!(font.split_codes(b"\x30\x42\x4E\x2D"), vec![(0x3042, 2), (0x4E2D, 2)]);
+        // A CID says nothing about the character without a ToUnicode map
+        assert_eq!(font.decode(0x3042, 2), "\u{FFFD}");
+        assert_eq!(font.width(0x3042), 900.0);
+    }
+
+    #[test]
+    fn test_embedded_program() {
+        let mut builder = MiniPdfBuilder::new();
+        let program = builder.add_stream(b"OTTO font data", TestFilter::Flate, "/Subtype /OpenType");
+        let font = builder.add_object(format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /ABCDEF+Minion /Encoding /Identity-H /DescendantFonts [<< \
+               /Subtype /CIDFontType0 /FontDescriptor << /Type /FontDescriptor /FontFile3 {} 0 R >> >>] >>", program));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let font = Font::from_dict(&pdf.get_dict(ObjectId::new(font, 0)).unwrap()).unwrap();
+        assert_eq!(font.embedded_program().unwrap(), Some((FontFormat::OpenType, &b"OTTO font data"[..])));
+        assert_eq!(font.subset_tag(), Some("ABCDEF"));
+
+        let font = font_from(&["<< /Type /Font /Subtype /Type1 /BaseFont /Abc+Helvetica >>"]);
+        assert_eq!((font.embedded_program().unwrap(), font.subset_tag()), (None, None));
+    }
+
+    #[test]
+    fn test_cid_widths() {
+        let font = font_from(&["<< /Type /Font /Subtype /Type0 /BaseFont /Subset /Encoding /Identity-H \
+                                  /DescendantFonts [4 0 R] >>",
+                               "<< /Type /Font /Subtype /CIDFontType2 /W [120 [400 410 420] 1 10 250 200 200 0] >>"]);
+        let widths: Vec<f32> = [0, 1, 10, 11, 119, 120, 122, 123, 200, 201].iter().map(|&cid| font.width(cid)).collect();
+        assert_eq!(widths, vec![1000.0, 250.0, 250.0, 1000.0, 1000.0, 400.0, 420.0, 1000.0, 0.0, 1000.0]);
+        assert!(CidWidths::parse(&[Rc::new(PdfObject::new_number_int(1))]).is_err());
+
+        // Negative and reversed ranges are skipped without disturbing the rest
+        let font = font_from(&["<< /Type /Font /Subtype /Type0 /BaseFont /Subset /Encoding /Identity-H \
+                                  /DescendantFonts [4 0 R] >>",
+                               "<< /Type /Font /Subtype /CIDFontType2 /W [-5 [300] -5 5 300 20 10 300 \
+                                  1 [500]] >>"]);
+        let widths: Vec<f32> = [0, 1, 5, 15].iter().map(|&cid| font.width(cid)).collect();
+        assert_eq!(widths, vec![1000.0, 500.0, 1000.0, 1000.0]);
+    }
+}
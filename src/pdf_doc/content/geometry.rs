@@ -0,0 +1,253 @@
+use std::ops::Mul;
+
+/// An affine transformation [a b c d e f], mapping (x, y) to (ax + cy + e, bx + dy + f) (spec 8.3.4).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Matrix {
+    pub fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Matrix { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Matrix::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        Matrix::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub fn scale(x: f32, y: f32) -> Self {
+        Matrix::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// A counterclockwise rotation by angle radians about the origin.
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Matrix::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// A horizontal skew, moving each point right by factor times its height.
+    pub fn skew(factor: f32) -> Self {
+        Matrix::new(1.0, 0.0, factor, 1.0, 0.0, 0.0)
+    }
+
+    pub fn transform_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y + self.e, self.b * x + self.d * y + self.f)
+    }
+
+    /// Map a displacement rather than a position, so the translation is left out.
+    pub fn transform_vector(&self, dx: f32, dy: f32) -> (f32, f32) {
+        (self.a * dx + self.c * dy, self.b * dx + self.d * dy)
+    }
+
+    /// The smallest axis-aligned rectangle containing the image of rect, which is larger than the image itself
+    /// when the matrix rotates or skews.
+    pub fn transform_rect(&self, rect: &Rect) -> Rect {
+        let corners = [(rect.x0, rect.y0), (rect.x1, rect.y0), (rect.x1, rect.y1), (rect.x0, rect.y1)]
+            .map(|(x, y)| self.transform_point(x, y));
+        let (xs, ys) = (corners.map(|(x, _)| x), corners.map(|(_, y)| y));
+        let min = |values: [f32; 4]| values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = |values: [f32; 4]| values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        Rect::new(min(xs), min(ys), max(xs), max(ys))
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// The matrix undoing this one, or None if it's singular, as when it scales to zero width or collapses the
+    /// plane onto a line.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let determinant = self.determinant();
+        if determinant.abs() <= f32::EPSILON * self.a.abs().max(self.b.abs()).max(self.c.abs()).max(self.d.abs()) {
+            return None;
+        };
+        let (a, b, c, d) = (self.d / determinant, -self.b / determinant, -self.c / determinant, self.a / determinant);
+        Some(Matrix::new(a, b, c, d, -(self.e * a + self.f * c), -(self.e * b + self.f * d)))
+    }
+
+    /// Split the matrix into a skew, a scale, a rotation and a translation, applied in that order.  A
+    /// reflection shows up as a negative vertical scale.
+    pub fn decompose(&self) -> Decomposition {
+        let scale_x = self.a.hypot(self.b);
+        if scale_x == 0.0 {
+            // Everything lands on a line through the second column, so treat it as a vertical scale alone
+            return Decomposition {
+                scale_y: self.vertical_scale(),
+                rotation: (-self.c).atan2(self.d),
+                translate_x: self.e,
+                translate_y: self.f,
+                ..Decomposition::default()
+            };
+        };
+        Decomposition {
+            scale_x,
+            scale_y: self.determinant() / scale_x,
+            rotation: self.b.atan2(self.a),
+            skew: (self.a * self.c + self.b * self.d) / (scale_x * scale_x),
+            translate_x: self.e,
+            translate_y: self.f,
+        }
+    }
+
+    /// How much the matrix stretches vertical distances, ignoring rotation.
+    pub fn vertical_scale(&self) -> f32 {
+        self.c.hypot(self.d)
+    }
+}
+
+/// The parts of a transformation, from Matrix::decompose.  The rotation is counterclockwise in radians, and
+/// the skew moves points right by that factor times their height.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Decomposition {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub rotation: f32,
+    pub skew: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+}
+
+impl Decomposition {
+    /// The matrix with these parts, so that decompose followed by compose gives back the original matrix.
+    pub fn compose(&self) -> Matrix {
+        Matrix::skew(self.skew) * Matrix::scale(self.scale_x, self.scale_y) * Matrix::rotation(self.rotation)
+            * Matrix::translation(self.translate_x, self.translate_y)
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Matrix::identity()
+    }
+}
+
+/// self * other applies self first, then other, matching the spec's notation for concatenation.
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: Matrix) -> Matrix {
+        Matrix::new(self.a * other.a + self.b * other.c,
+                    self.a * other.b + self.b * other.d,
+                    self.c * other.a + self.d * other.c,
+                    self.c * other.b + self.d * other.d,
+                    self.e * other.a + self.f * other.c + other.e,
+                    self.e * other.b + self.f * other.d + other.f)
+    }
+}
+
+/// An axis-aligned rectangle, with (x0, y0) the lower left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+impl Rect {
+    pub fn new(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        Rect { x0: x0.min(x1), y0: y0.min(y1), x1: x0.max(x1), y1: y0.max(y1) }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y1 - self.y0
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.x0 <= x && x <= self.x1 && self.y0 <= y && y <= self.y1
+    }
+
+    /// Whether other lies entirely within this rectangle, edges included.
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        self.contains(other.x0, other.y0) && self.contains(other.x1, other.y1)
+    }
+
+    /// The smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new(self.x0.min(other.x0), self.y0.min(other.y0), self.x1.max(other.x1), self.y1.max(other.y1))
+    }
+
+    /// The overlap of the two rectangles, or None if they don't meet.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let (x0, y0) = (self.x0.max(other.x0), self.y0.max(other.y0));
+        let (x1, y1) = (self.x1.min(other.x1), self.y1.min(other.y1));
+        if x0 > x1 || y0 > y1 {
+            return None;
+        };
+        Some(Rect { x0, y0, x1, y1 })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let scale = Matrix::new(2.0, 0.0, 0.0, 2.0, 0.0, 0.0);
+        let translate = Matrix::translation(10.0, 5.0);
+        assert_eq!((scale * translate).transform_point(1.0, 1.0), (12.0, 7.0));
+        assert_eq!((translate * scale).transform_point(1.0, 1.0), (22.0, 12.0));
+        assert_eq!(Matrix::identity() * translate, translate);
+    }
+
+
+    fn assert_close(left: Matrix, right: Matrix) {
+        let values = |m: Matrix| [m.a, m.b, m.c, m.d, m.e, m.f];
+        let (left, right) = (values(left), values(right));
+        assert!(left.iter().zip(right.iter()).all(|(l, r)| (l - r).abs() < 1e-4), "{:?} != {:?}", left, right);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let matrix = Matrix::new(2.0, 1.0, -1.0, 3.0, 10.0, -4.0);
+        let inverse = matrix.inverse().unwrap();
+        assert_close(matrix * inverse, Matrix::identity());
+        assert_close(inverse * matrix, Matrix::identity());
+        let (x, y) = matrix.transform_point(5.0, 7.0);
+        let (x, y) = inverse.transform_point(x, y);
+        assert!((x - 5.0).abs() < 1e-4 && (y - 7.0).abs() < 1e-4);
+        assert_eq!(Matrix::scale(0.0, 1.0).inverse(), None);
+        assert_eq!(Matrix::new(1.0, 2.0, 2.0, 4.0, 0.0, 0.0).inverse(), None);
+    }
+
+    #[test]
+    fn test_decompose() {
+        let parts = (Matrix::scale(2.0, 3.0) * Matrix::rotation(0.5) * Matrix::translation(7.0, 8.0)).decompose();
+        assert!((parts.scale_x - 2.0).abs() < 1e-5 && (parts.scale_y - 3.0).abs() < 1e-5);
+        assert!((parts.rotation - 0.5).abs() < 1e-5 && parts.skew.abs() < 1e-5);
+        assert_eq!((parts.translate_x, parts.translate_y), (7.0, 8.0));
+        for matrix in [Matrix::new(2.0, 1.0, -1.0, 3.0, 10.0, -4.0), Matrix::new(1.0, 0.0, 0.0, -1.0, 0.0, 792.0),
+                       Matrix::new(0.0, 0.0, 1.0, 2.0, 3.0, 4.0)] {
+            assert_close(matrix.decompose().compose(), matrix);
+        }
+        assert!(Matrix::new(1.0, 0.0, 0.0, -1.0, 0.0, 0.0).decompose().scale_y < 0.0);
+    }
+
+    #[test]
+    fn test_mapping() {
+        let matrix = Matrix::rotation(std::f32::consts::FRAC_PI_2) * Matrix::translation(100.0, 0.0);
+        assert_eq!(matrix.transform_vector(1.0, 0.0).1, 1.0);
+        let rect = matrix.transform_rect(&Rect::new(0.0, 0.0, 10.0, 20.0));
+        assert!((rect.x0 - 80.0).abs() < 1e-4 && (rect.x1 - 100.0).abs() < 1e-4);
+        assert!((rect.y0).abs() < 1e-4 && (rect.y1 - 10.0).abs() < 1e-4);
+        // Rotating a square by 45 degrees gives a bounding box larger than the square
+        let rect = Matrix::rotation(std::f32::consts::FRAC_PI_4).transform_rect(&Rect::new(0.0, 0.0, 1.0, 1.0));
+        assert!((rect.width() - 2f32.sqrt()).abs() < 1e-5);
+        assert!(rect.contains(0.0, 0.5) && !rect.contains(1.0, 0.0));
+    }
+}
@@ -0,0 +1,299 @@
+use std::convert::TryFrom;
+
+use super::*;
+
+/// An image XObject from a page's resources, from PdfDoc::page_images, with its samples ready to export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImage {
+    /// The image's name in the page's /XObject resources
+    pub name: String,
+    pub id: ObjectId,
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_component: u8,
+    /// None for stencil masks (/ImageMask), which are one bit per pixel and painted in the current colour, and
+    /// for JPX images that leave their colour space to the codestream
+    pub color_space: Option<ColorSpace>,
+    /// The samples, a row at a time from the top, with each row starting on a byte boundary; or if encoding is
+    /// set, the image in that format
+    pub data: Vec<u8>,
+    /// The filter the data is still encoded with, like DCTDecode for a JPEG, which can be saved as it is
+    pub encoding: Option<String>,
+    /// The image's /SMask, a grayscale image giving the opacity of each pixel, from transparent at 0
+    pub soft_mask: Option<Box<PageImage>>,
+}
+
+impl PageImage {
+    /// Read an image XObject.  The /Decode array isn't applied.
+    pub fn new(name: &str, id: ObjectId, image: &PdfObject) -> Result<Self> {
+        PageImage::read(name, id, image, false)
+    }
+
+    /// Read an image XObject, or with is_mask its soft mask, whose own /SMask is ignored (spec 11.6.5.2), which
+    /// also stops a mask that names itself from being read forever.
+    fn read(name: &str, id: ObjectId, image: &PdfObject, is_mask: bool) -> Result<Self> {
+        let stream = image.try_into_binary_stream()?;
+        let dict = stream.attributes();
+        let dimension = |key: &str| -> Result<u32> {
+            match dict.get(key) {
+                Some(value) => Ok(u32::try_from(value.try_into_int()?).map_err(|_| ErrorKind::ParsingError(
+                    format!("Invalid /{} in image {}", key, name)))?),
+                None => Err(ErrorKind::ParsingError(format!("Image {} has no /{}", name, key)))?
+            }
+        };
+        let image_mask = match dict.get("ImageMask") {
+            Some(mask) => mask.try_into_bool()?,
+            None => false
+        };
+        let color_space = match dict.get("ColorSpace") {
+            Some(space) if !image_mask => Some(ColorSpace::parse(space)?),
+            _ => None
+        };
+        let bits_per_component = match dict.get("BitsPerComponent") {
+            _ if image_mask => 1,
+            Some(bits) => match bits.try_into_int()? {
+                bits @ (1 | 2 | 4 | 8 | 16) => bits as u8,
+                bits => Err(ErrorKind::ParsingError(format!("Invalid /BitsPerComponent {} in image {}", bits, name)))?
+            },
+            // Only JPX images may leave it out
+            None => 8
+        };
+        let (data, encoding) = stream.image_data()?;
+        let soft_mask = match dict.get("SMask").and_then(|mask| mask.reference_id().map(|id| (id, mask))) {
+            Some((mask_id, mask)) if !is_mask => Some(Box::new(PageImage::read(name, mask_id, mask, true)?)),
+            _ => None
+        };
+        Ok(PageImage {
+            name: name.to_string(),
+            id,
+            width: dimension("Width")?,
+            height: dimension("Height")?,
+            bits_per_component,
+            color_space,
+            data,
+            encoding,
+            soft_mask,
+        })
+    }
+
+    /// The image as 8-bit RGB, three bytes a pixel, row by row from the top.  Indexed images have their palette
+    /// looked up.  CMYK is converted without a colour profile, and ICC-based colours are taken as the device
+    /// colours with the same number of components, so the result is fit for viewing rather than colour exact.
+    /// Images still in an image encoding, and colour spaces needing more than that, like Separation and Lab,
+    /// aren't converted.
+    pub fn to_rgb(&self) -> Result<Vec<u8>> {
+        if let Some(encoding) = &self.encoding {
+            Err(ErrorKind::ParsingError(format!("Image {} is {} encoded", self.name, encoding)))?
+        };
+        let space = match &self.color_space {
+            Some(space) => space,
+            // A stencil mask: 0 paints, and what isn't painted shows white
+            None => return Ok(self.samples()?.into_iter().flat_map(|sample| {
+                let value = if sample == 0 { 0 } else { 255 };
+                vec![value; 3]
+            }).collect())
+        };
+        let samples = self.samples()?;
+        if let Some(palette) = self.palette()? {
+            return Ok(samples.into_iter()
+                             .flat_map(|index| palette.get(index as usize).copied().unwrap_or([0, 0, 0]).to_vec())
+                             .collect());
+        };
+        let max = ((1u32 << self.bits_per_component) - 1) as f32;
+        let mut rgb = Vec::with_capacity(self.width as usize * self.height as usize * 3);
+        for pixel in samples.chunks(space.components()) {
+            let values = pixel.iter().map(|sample| *sample as f32 / max).collect::<Vec<_>>();
+            rgb.extend_from_slice(&device_rgb(space, &values)?);
+        }
+        Ok(rgb)
+    }
+
+    /// The image as 8-bit RGBA, four bytes a pixel, with the soft mask as the alpha channel, or opaque if
+    /// there's none.  A soft mask of another size is stretched over the image.  Colours are converted as by
+    /// to_rgb, and the /Matte colour of premultiplied images isn't taken out.
+    pub fn to_rgba(&self) -> Result<Vec<u8>> {
+        let rgb = self.to_rgb()?;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let alpha = match &self.soft_mask {
+            Some(mask) => {
+                let (mask_width, mask_height) = (mask.width as usize, mask.height as usize);
+                if mask_width == 0 || mask_height == 0 {
+                    Err(ErrorKind::ParsingError(format!("The soft mask of image {} is empty", self.name)))?
+                };
+                let gray = mask.to_rgb()?;
+                (0..width * height).map(|pixel| {
+                    let (x, y) = (pixel % width * mask_width / width, pixel / width * mask_height / height);
+                    gray[(y * mask_width + x) * 3]
+                }).collect()
+            },
+            None => vec![255; width * height]
+        };
+        Ok(rgb.chunks(3).zip(alpha).flat_map(|(color, alpha)| vec![color[0], color[1], color[2], alpha]).collect())
+    }
+
+    /// The colours of an Indexed image's palette as RGB, one for each index from 0, for keeping the samples as
+    /// indices.  None for other images.
+    pub fn palette(&self) -> Result<Option<Vec<[u8; 3]>>> {
+        let (base, high, lookup) = match &self.color_space {
+            Some(ColorSpace::Indexed { base, high, lookup }) => (base, *high, lookup),
+            _ => return Ok(None)
+        };
+        let components = base.components();
+        let mut palette = Vec::with_capacity(high as usize + 1);
+        for index in 0..=high as usize {
+            // A short lookup table leaves the missing colours black
+            let values = match lookup.get(index * components..(index + 1) * components) {
+                Some(entry) => entry.iter().map(|value| *value as f32 / 255.0).collect::<Vec<_>>(),
+                None => vec![0.0; components]
+            };
+            palette.push(device_rgb(base, &values)?);
+        }
+        Ok(Some(palette))
+    }
+
+    /// Each sample as a number, row by row, dropping the padding at the end of each row.
+    fn samples(&self) -> Result<Vec<u16>> {
+        let components = self.color_space.as_ref().map_or(1, |space| space.components());
+        if components == 0 || self.width == 0 || self.height == 0 {
+            Err(ErrorKind::ParsingError(format!("Image {} has no samples: {}x{} with {} components", self.name,
+                                                self.width, self.height, components)))?
+        };
+        let bits = self.bits_per_component as usize;
+        let too_large = || ErrorKind::ParsingError(format!("Image {} is too large", self.name));
+        let row_samples = (self.width as usize).checked_mul(components).ok_or_else(too_large)?;
+        let row_bytes = row_samples.checked_mul(bits).ok_or_else(too_large)?.div_ceil(8);
+        let needed = row_bytes.checked_mul(self.height as usize).ok_or_else(too_large)?;
+        if self.data.len() < needed {
+            Err(ErrorKind::ParsingError(format!("Image {} has {} bytes of samples, not {}", self.name,
+                                                self.data.len(), needed)))?
+        };
+        let mut samples = Vec::with_capacity(row_samples * self.height as usize);
+        for row in self.data[..needed].chunks(row_bytes) {
+            for index in 0..row_samples {
+                samples.push(match bits {
+                    16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+                    8 => row[index] as u16,
+                    _ => {
+                        let bit = index * bits;
+                        ((row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8) as u16
+                    }
+                });
+            }
+        }
+        Ok(samples)
+    }
+}
+
+/// A colour given as values from 0 to 1 in space, as 8-bit RGB.
+fn device_rgb(space: &ColorSpace, values: &[f32]) -> Result<[u8; 3]> {
+    let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Ok(match (space, values) {
+        (ColorSpace::DeviceGray, [gray]) | (ColorSpace::CalGray { .. }, [gray]) => [byte(*gray); 3],
+        (ColorSpace::DeviceRGB, [r, g, b]) | (ColorSpace::CalRGB { .. }, [r, g, b]) => [byte(*r), byte(*g), byte(*b)],
+        (ColorSpace::DeviceCMYK, [c, m, y, k]) => [byte((1.0 - c) * (1.0 - k)), byte((1.0 - m) * (1.0 - k)),
+                                                   byte((1.0 - y) * (1.0 - k))],
+        (ColorSpace::ICCBased { alternate: Some(alternate), .. }, _) => device_rgb(alternate, values)?,
+        (ColorSpace::ICCBased { components: 1, .. }, _) => device_rgb(&ColorSpace::DeviceGray, values)?,
+        (ColorSpace::ICCBased { components: 3, .. }, _) => device_rgb(&ColorSpace::DeviceRGB, values)?,
+        (ColorSpace::ICCBased { components: 4, .. }, _) => device_rgb(&ColorSpace::DeviceCMYK, values)?,
+        _ => Err(ErrorKind::ParsingError(format!("Can't convert {} colours to RGB", space.family())))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_page_images() {
+        let mut builder = MiniPdfBuilder::new();
+        // 2 bits an index, so the 3 pixels of a row fill 6 bits and the rest of the byte is padding
+        let indexed = builder.add_stream(b"\x1B\x40", TestFilter::Flate,
+                                         "/Type /XObject /Subtype /Image /Width 3 /Height 2 /BitsPerComponent 2 \
+                                          /ColorSpace [/Indexed /DeviceRGB 2 <FF0000 00FF00 0000FF>]");
+        let cmyk = builder.add_stream(b"\x00\x00\x00\x00\xFF\x00\x00\x80", TestFilter::None,
+                                      "/Type /XObject /Subtype /Image /Width 2 /Height 1 /BitsPerComponent 8 \
+                                       /ColorSpace /DeviceCMYK");
+        let jpeg = builder.add_stream(b"\xFF\xD8\xFF", TestFilter::None,
+                                      "/Type /XObject /Subtype /Image /Width 1 /Height 1 /BitsPerComponent 8 \
+                                       /ColorSpace /DeviceGray /Filter /DCTDecode");
+        let mask = builder.add_stream(b"\x40", TestFilter::None,
+                                      "/Type /XObject /Subtype /Image /Width 2 /Height 1 /ImageMask true");
+        let form = builder.add_stream(b"", TestFilter::None, "/Type /XObject /Subtype /Form /BBox [0 0 1 1]");
+        builder.add_page_with_entries(b"", TestFilter::None, &format!(
+            "/Resources << /XObject << /Im1 {} 0 R /Im2 {} 0 R /Im3 {} 0 R /Im4 {} 0 R /Fm1 {} 0 R >> >>",
+            indexed, cmyk, jpeg, mask, form));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let images = pdf.page(0).unwrap().images().unwrap();
+        assert_eq!(images.iter().map(|image| image.name.as_str()).collect::<Vec<_>>(),
+                   vec!["Im1", "Im2", "Im3", "Im4"]);
+
+        // Indices 0 1 2 then 1 0 0
+        let indexed = &images[0];
+        assert_eq!(indexed.data, b"\x1B\x40");
+        assert_eq!(indexed.palette().unwrap().unwrap(), vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+        assert_eq!(indexed.to_rgb().unwrap(), vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 0, 0]);
+
+        assert_eq!(images[1].palette().unwrap(), None);
+        assert_eq!(images[1].to_rgb().unwrap(), vec![255, 255, 255, 0, 127, 127]);
+        assert_eq!(images[2].encoding.as_deref(), Some("DCTDecode"));
+        assert_eq!(images[2].data, b"\xFF\xD8\xFF");
+        assert!(images[2].to_rgb().is_err());
+        assert_eq!((images[3].color_space.as_ref(), images[3].to_rgb().unwrap()), (None, vec![0, 0, 0, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_images_without_samples() {
+        let image = |width: u32, color_space: ColorSpace| PageImage {
+            name: "Im1".to_string(), id: ObjectId::new(1, 0), width, height: 1, bits_per_component: 8,
+            color_space: Some(color_space), data: vec![0; 4], encoding: None, soft_mask: None,
+        };
+        let no_colorants = ColorSpace::DeviceN { colorants: Vec::new(), alternate: Box::new(ColorSpace::DeviceGray) };
+        for image in &[image(1, ColorSpace::Pattern { underlying: None }), image(1, no_colorants),
+                       image(0, ColorSpace::DeviceGray), image(u32::MAX, ColorSpace::DeviceCMYK)] {
+            assert!(image.to_rgb().is_err());
+            assert!(image.to_rgba().is_err());
+        }
+        assert_eq!(image(1, ColorSpace::DeviceGray).to_rgb().unwrap(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_soft_mask() {
+        let mut builder = MiniPdfBuilder::new();
+        // A 1x2 mask stretched over a 2x2 image: the top row transparent, the bottom half opaque
+        let mask = builder.add_stream(b"\x00\x80", TestFilter::None,
+                                      "/Type /XObject /Subtype /Image /Width 1 /Height 2 /BitsPerComponent 8 \
+                                       /ColorSpace /DeviceGray");
+        let image = builder.add_stream(b"\xFF\x00\x00\x00\xFF\x00\x00\x00\xFF\xFF\xFF\xFF", TestFilter::Flate,
+                                       &format!("/Type /XObject /Subtype /Image /Width 2 /Height 2 \
+                                                 /BitsPerComponent 8 /ColorSpace /DeviceRGB /SMask {} 0 R", mask));
+        builder.add_page_with_entries(b"", TestFilter::None,
+                                      &format!("/Resources << /XObject << /Im1 {} 0 R >> >>", image));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let images = pdf.page_images(0).unwrap();
+        assert_eq!(images.len(), 1);
+        let soft_mask = images[0].soft_mask.as_ref().unwrap();
+        assert_eq!((soft_mask.id, soft_mask.height), (ObjectId::new(mask, 0), 2));
+        assert_eq!(images[0].to_rgba().unwrap(), vec![255, 0, 0, 0, 0, 255, 0, 0, 0, 0, 255, 128, 255, 255, 255, 128]);
+
+        // A mask's own /SMask is ignored, even when it's the mask itself, and an empty mask can't be stretched
+        let mut builder = MiniPdfBuilder::new();
+        let looped = 3;
+        builder.add_stream(b"\x80", TestFilter::None,
+                           &format!("/Type /XObject /Subtype /Image /Width 1 /Height 1 /BitsPerComponent 8 \
+                                     /ColorSpace /DeviceGray /SMask {} 0 R", looped));
+        let empty = builder.add_stream(b"", TestFilter::None, "/Type /XObject /Subtype /Image /Width 0 /Height 1 \
+                                                              /BitsPerComponent 8 /ColorSpace /DeviceGray");
+        let image = builder.add_stream(b"\xFF", TestFilter::None,
+                                       &format!("/Type /XObject /Subtype /Image /Width 1 /Height 1 \
+                                                 /BitsPerComponent 8 /ColorSpace /DeviceGray /SMask {} 0 R", empty));
+        builder.add_page_with_entries(b"", TestFilter::None, &format!(
+            "/Resources << /XObject << /Im1 {} 0 R /Im2 {} 0 R >> >>", looped, image));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let images = pdf.page_images(0).unwrap();
+        let soft_mask = images[0].soft_mask.as_ref().unwrap();
+        assert_eq!((soft_mask.id, soft_mask.soft_mask.as_ref()), (ObjectId::new(looped, 0), None));
+        assert!(images[1].to_rgba().is_err());
+    }
+}
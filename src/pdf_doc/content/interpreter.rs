@@ -0,0 +1,727 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+use super::*;
+use crate::errors::*;
+
+impl Matrix {
+    fn from_operands(operands: &[PdfObject]) -> Result<Self> {
+        let values = numbers(operands, 6)?;
+        Ok(Matrix::new(values[0], values[1], values[2], values[3], values[4], values[5]))
+    }
+}
+
+/// A run of text shown by one string operand, positioned in default user space.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextBlock {
+    pub text: String,
+    pub font: String,
+    /// Where the run starts and ends on the baseline
+    pub x: f32,
+    pub y: f32,
+    pub end_x: f32,
+    pub end_y: f32,
+    /// The font size after scaling by the text and current transformation matrices
+    pub font_size: f32,
+    pub clip: Option<Rc<ClipPath>>,
+    /// The text rendering mode set with Tr (spec 9.3.6): 0 to fill, 1 to stroke, 3 for invisible text and so on
+    pub render_mode: u8,
+    /// Whether the run lies entirely outside the clipping region, so that none of it can be seen.  This is judged
+    /// from the bounding boxes of the clipping paths, so a run in a corner cut off by a curved clip isn't flagged.
+    pub clipped: bool,
+}
+
+impl TextBlock {
+    /// Whether the run is drawn neither filled nor stroked, as in the text layer OCR adds over scanned images.
+    /// Mode 7 only adds the text to the clipping path, so it's invisible too.
+    pub fn is_invisible(&self) -> bool {
+        self.render_mode == 3 || self.render_mode == 7
+    }
+
+    /// The area the run covers, taking the font's ascent and descent as fractions of its size.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(self.x.min(self.end_x), self.y.min(self.end_y) - 0.2 * self.font_size,
+                  self.x.max(self.end_x), self.y.max(self.end_y) + 0.8 * self.font_size)
+    }
+}
+
+/// A piece of a path, with coordinates in default user space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    /// Two control points, then the end point
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// A painted path (spec 8.5).  Paths ended with n are clipping-only and not recorded.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+    pub stroke: bool,
+    pub fill: bool,
+    /// Whether filling uses the even-odd rule rather than nonzero winding
+    pub even_odd: bool,
+    /// Line width in default user space
+    pub line_width: f32,
+    pub clip: Option<Rc<ClipPath>>,
+}
+
+/// A clipping path set with W or W* (spec 8.5.4).  The clip region is the intersection of this path with its
+/// parent's region.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipPath {
+    pub segments: Vec<PathSegment>,
+    pub even_odd: bool,
+    pub parent: Option<Rc<ClipPath>>,
+}
+
+impl ClipPath {
+    /// A rectangle containing the clip region: the intersection of the bounding boxes of this path and its
+    /// parents, which is exact when they're all rectangles.  None if the region is empty.
+    pub fn bounds(&self) -> Option<Rect> {
+        // Curves lie within the hull of their control points, so the points' bounding box contains the path
+        let points = self.segments.iter().flat_map(|segment| match *segment {
+            PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => vec![(x, y)],
+            PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => vec![(x1, y1), (x2, y2), (x3, y3)],
+            PathSegment::Close => vec![],
+        });
+        let own = points.map(|(x, y)| Rect::new(x, y, x, y)).reduce(|bounds, point| bounds.union(&point))?;
+        match &self.parent {
+            Some(parent) => own.intersection(&parent.bounds()?),
+            None => Some(own)
+        }
+    }
+}
+
+/// How a shading or pattern resource is painted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternPaint {
+    /// Painted over the clip region with sh
+    Shading,
+    /// Set as the fill colour with scn
+    Fill,
+    /// Set as the stroke colour with SCN
+    Stroke,
+}
+
+/// A shading painted with sh, or a pattern set as a colour (spec 8.7).  The types are None where the resource is
+/// missing or lacks them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternUse {
+    /// The resource name, under /Shading for sh and under /Pattern otherwise
+    pub name: String,
+    pub paint: PatternPaint,
+    /// 1 for tiling patterns and 2 for shading patterns; None for sh
+    pub pattern_type: Option<i32>,
+    /// The /ShadingType of the shading painted by sh or by a shading pattern: 2 and 3 are axial and radial
+    /// gradients, 1 a function of position, and 4 to 7 meshes
+    pub shading_type: Option<i32>,
+}
+
+/// Graphics state parameters that the interpreter tracks (spec 8.4 and 9.3).
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    ctm: Matrix,
+    font: Option<(String, Rc<Font>)>,
+    font_size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scaling: f32,
+    leading: f32,
+    rise: f32,
+    render_mode: u8,
+    line_width: f32,
+    clip: Option<Rc<ClipPath>>,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            ctm: Matrix::identity(),
+            font: None,
+            font_size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            render_mode: 0,
+            line_width: 1.0,
+            clip: None,
+        }
+    }
+}
+
+/// Runs content stream commands against a resource dictionary, collecting the text shown and the paths
+/// painted.  Other operators are ignored.
+pub struct Interpreter {
+    resources: Option<Rc<PdfMap>>,
+    fonts: HashMap<String, Rc<Font>>,
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    /// The path under construction, and its current point in user space
+    current_path: Vec<PathSegment>,
+    current_point: (f32, f32),
+    /// Set by W or W*, with whether the even-odd rule applies, until the path is painted
+    pending_clip: Option<bool>,
+    /// Whether a BT has been seen without its ET
+    in_text_object: bool,
+    pub text_blocks: Vec<TextBlock>,
+    pub paths: Vec<Path>,
+    pub patterns: Vec<PatternUse>,
+    /// Problems in the content that were worked around, like unbalanced BT and ET operators.  Each is also
+    /// logged as a warning.
+    pub warnings: Vec<String>,
+    /// How many times each operator was run
+    pub operator_counts: BTreeMap<String, usize>,
+    /// Resource names of the Type3 fonts text was shown in
+    pub type3_fonts: BTreeSet<String>,
+}
+
+impl Interpreter {
+    pub fn new(resources: Option<Rc<PdfMap>>) -> Self {
+        Interpreter {
+            resources,
+            fonts: HashMap::new(),
+            state: GraphicsState::default(),
+            saved_states: Vec::new(),
+            text_matrix: Matrix::identity(),
+            line_matrix: Matrix::identity(),
+            current_path: Vec::new(),
+            current_point: (0.0, 0.0),
+            pending_clip: None,
+            in_text_object: false,
+            text_blocks: Vec::new(),
+            paths: Vec::new(),
+            patterns: Vec::new(),
+            warnings: Vec::new(),
+            operator_counts: BTreeMap::new(),
+            type3_fonts: BTreeSet::new(),
+        }
+    }
+
+    /// An interpreter for content drawn through a transformation and clipped to a rectangle of the space it
+    /// draws in, like a form XObject placed on a page with its /Matrix and /BBox (spec 8.10.1).
+    pub fn with_transform(resources: Option<Rc<PdfMap>>, ctm: Matrix, clip: Option<Rect>) -> Self {
+        let mut interpreter = Interpreter::new(resources);
+        interpreter.state.ctm = ctm;
+        interpreter.state.clip = clip.map(|rect| {
+            let corners = [(rect.x0, rect.y0), (rect.x1, rect.y0), (rect.x1, rect.y1), (rect.x0, rect.y1)]
+                .map(|(x, y)| ctm.transform_point(x, y));
+            let mut segments = vec![PathSegment::MoveTo(corners[0].0, corners[0].1)];
+            segments.extend(corners[1..].iter().map(|&(x, y)| PathSegment::LineTo(x, y)));
+            segments.push(PathSegment::Close);
+            Rc::new(ClipPath { segments, even_odd: false, parent: None })
+        });
+        interpreter
+    }
+
+    /// Run the commands of a content stream.  A text object left open at the end of the stream is closed, with a
+    /// warning, so it doesn't carry over into the next stream run.
+    pub fn run(&mut self, stream: &CommandStream) -> Result<()> {
+        for command in &stream.commands {
+            *self.operator_counts.entry(command.operator.clone()).or_default() += 1;
+            self.execute(command).chain_err(|| ErrorKind::ParsingError(format!("Invalid command: {}", command)))?;
+        }
+        if self.in_text_object {
+            self.warn("BT without ET at the end of the content stream");
+            self.in_text_object = false;
+        };
+        Ok(())
+    }
+
+    fn warn(&mut self, message: &str) {
+        warn!("{}", message);
+        self.warnings.push(message.to_string());
+    }
+
+    fn execute(&mut self, command: &Command) -> Result<()> {
+        let operands = &command.operands[..];
+        match &command.operator[..] {
+            "q" => self.saved_states.push(self.state.clone()),
+            "Q" => if let Some(state) = self.saved_states.pop() {
+                self.state = state;
+            },
+            "cm" => self.state.ctm = Matrix::from_operands(operands)? * self.state.ctm,
+            "w" => self.state.line_width = numbers(operands, 1)?[0],
+            "m" => {
+                let values = numbers(operands, 2)?;
+                let (x, y) = self.state.ctm.transform_point(values[0], values[1]);
+                self.current_path.push(PathSegment::MoveTo(x, y));
+                self.current_point = (x, y);
+            },
+            "l" => {
+                let values = numbers(operands, 2)?;
+                let (x, y) = self.state.ctm.transform_point(values[0], values[1]);
+                self.current_path.push(PathSegment::LineTo(x, y));
+                self.current_point = (x, y);
+            },
+            "c" | "v" | "y" => {
+                let count = if command.operator == "c" { 6 } else { 4 };
+                let values = numbers(operands, count)?;
+                let ctm = self.state.ctm;
+                let points: Vec<(f32, f32)> = values.chunks(2).map(|pair| ctm.transform_point(pair[0], pair[1])).collect();
+                // v repeats the current point as the first control point, y the end point as the second
+                let (first, second, end) = match &command.operator[..] {
+                    "c" => (points[0], points[1], points[2]),
+                    "v" => (self.current_point, points[0], points[1]),
+                    _ => (points[0], points[1], points[1]),
+                };
+                self.current_path.push(PathSegment::CurveTo(first.0, first.1, second.0, second.1, end.0, end.1));
+                self.current_point = end;
+            },
+            "re" => {
+                let values = numbers(operands, 4)?;
+                let (x, y, width, height) = (values[0], values[1], values[2], values[3]);
+                let corners = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+                let corners: Vec<(f32, f32)> = corners.iter().map(|(x, y)| self.state.ctm.transform_point(*x, *y)).collect();
+                self.current_path.push(PathSegment::MoveTo(corners[0].0, corners[0].1));
+                for corner in &corners[1..] {
+                    self.current_path.push(PathSegment::LineTo(corner.0, corner.1));
+                }
+                self.current_path.push(PathSegment::Close);
+                self.current_point = corners[0];
+            },
+            "h" => self.current_path.push(PathSegment::Close),
+            "W" => self.pending_clip = Some(false),
+            "W*" => self.pending_clip = Some(true),
+            "S" => self.paint_path(true, false, false, false),
+            "s" => self.paint_path(true, false, false, true),
+            "f" | "F" => self.paint_path(false, true, false, false),
+            "f*" => self.paint_path(false, true, true, false),
+            "B" => self.paint_path(true, true, false, false),
+            "B*" => self.paint_path(true, true, true, false),
+            "b" => self.paint_path(true, true, false, true),
+            "b*" => self.paint_path(true, true, true, true),
+            "n" => self.paint_path(false, false, false, false),
+            // Text objects can't nest (spec 9.4.1), so a BT inside one starts a new one, as if the ET were missing
+            "BT" => {
+                if self.in_text_object {
+                    self.warn("BT inside a text object");
+                };
+                self.in_text_object = true;
+                self.text_matrix = Matrix::identity();
+                self.line_matrix = Matrix::identity();
+            },
+            "ET" => if self.in_text_object {
+                self.in_text_object = false;
+            } else {
+                self.warn("ET outside a text object");
+            },
+            "Tc" => self.state.char_spacing = numbers(operands, 1)?[0],
+            "Tw" => self.state.word_spacing = numbers(operands, 1)?[0],
+            "Tz" => self.state.horizontal_scaling = numbers(operands, 1)?[0] / 100.0,
+            "TL" => self.state.leading = numbers(operands, 1)?[0],
+            "Ts" => self.state.rise = numbers(operands, 1)?[0],
+            "Tr" => match operand(operands, 0, 1)?.try_into_int()? {
+                mode @ 0..=7 => self.state.render_mode = mode as u8,
+                mode => self.warn(&format!("Ignoring invalid text rendering mode {}", mode))
+            },
+            "Tf" => {
+                let name = operand(operands, 0, 2)?.try_into_string()?;
+                self.state.font = Some((name.to_string(), self.load_font(&name)?));
+                self.state.font_size = operands[1].try_into_number()?;
+            },
+            "Td" => {
+                let values = numbers(operands, 2)?;
+                self.move_to_next_line(values[0], values[1]);
+            },
+            "TD" => {
+                let values = numbers(operands, 2)?;
+                self.state.leading = -values[1];
+                self.move_to_next_line(values[0], values[1]);
+            },
+            "Tm" => {
+                self.text_matrix = Matrix::from_operands(operands)?;
+                self.line_matrix = self.text_matrix;
+            },
+            "T*" => self.move_to_next_line(0.0, -self.state.leading),
+            "Tj" => self.show_string(&operand(operands, 0, 1)?.try_into_bytes()?)?,
+            "'" => {
+                self.move_to_next_line(0.0, -self.state.leading);
+                self.show_string(&operand(operands, 0, 1)?.try_into_bytes()?)?;
+            },
+            "\"" => {
+                let values = numbers(&operands[..operands.len().min(2)], 2)?;
+                self.state.word_spacing = values[0];
+                self.state.char_spacing = values[1];
+                self.move_to_next_line(0.0, -self.state.leading);
+                self.show_string(&operand(operands, 2, 3)?.try_into_bytes()?)?;
+            },
+            "TJ" => for item in operand(operands, 0, 1)?.try_into_array()?.iter() {
+                if item.is_number() {
+                    let adjustment = -item.try_into_number()? / 1000.0 * self.state.font_size
+                        * self.state.horizontal_scaling;
+                    self.text_matrix = Matrix::translation(adjustment, 0.0) * self.text_matrix;
+                } else {
+                    self.show_string(&item.try_into_bytes()?)?;
+                }
+            },
+            "sh" => {
+                let name = operand(operands, 0, 1)?.try_into_string()?.to_string();
+                let shading_type = self.resource("Shading", &name)
+                                       .and_then(|shading| int_entry(&shading, "ShadingType"));
+                self.patterns.push(PatternUse { name, paint: PatternPaint::Shading, pattern_type: None, shading_type });
+            },
+            // A name operand is only allowed when the colour space is /Pattern, and comes after any components
+            // of the underlying colour space for uncoloured tiling patterns
+            "scn" | "SCN" => if let Some(PdfObject::Actual(Name(name))) = operands.last() {
+                let pattern = self.resource("Pattern", name);
+                let shading = pattern.as_ref()
+                                     .and_then(|pattern| pattern.try_into_map().ok())
+                                     .and_then(|pattern| pattern.get("Shading").cloned());
+                self.patterns.push(PatternUse {
+                    name: name.to_string(),
+                    paint: if command.operator == "scn" { PatternPaint::Fill } else { PatternPaint::Stroke },
+                    pattern_type: pattern.and_then(|pattern| int_entry(&pattern, "PatternType")),
+                    shading_type: shading.and_then(|shading| int_entry(&shading, "ShadingType")),
+                });
+            },
+            _ => {}
+        };
+        Ok(())
+    }
+
+    /// A named entry in one category of the resource dictionary, like /Pattern or /Shading.
+    fn resource(&self, category: &str, name: &str) -> Option<SharedObject> {
+        self.resources.as_ref()?.get(category)?.try_into_map().ok()?.get(name).cloned()
+    }
+
+    /// End the current path, recording it if it's stroked or filled.  A pending clip applies after painting.
+    fn paint_path(&mut self, stroke: bool, fill: bool, even_odd: bool, close: bool) {
+        if close {
+            self.current_path.push(PathSegment::Close);
+        };
+        let segments = std::mem::take(&mut self.current_path);
+        let pending_clip = self.pending_clip.take();
+        if segments.is_empty() {
+            return;
+        };
+        if stroke || fill {
+            // Approximate the width for non-uniform scaling with the average of the axis scales
+            let scale = (self.state.ctm.a.hypot(self.state.ctm.b) + self.state.ctm.vertical_scale()) / 2.0;
+            self.paths.push(Path {
+                segments: segments.clone(),
+                stroke,
+                fill,
+                even_odd,
+                line_width: self.state.line_width * scale,
+                clip: self.state.clip.clone(),
+            });
+        };
+        if let Some(even_odd) = pending_clip {
+            self.state.clip = Some(Rc::new(ClipPath { segments, even_odd, parent: self.state.clip.take() }));
+        };
+    }
+
+    fn move_to_next_line(&mut self, x: f32, y: f32) {
+        self.line_matrix = Matrix::translation(x, y) * self.line_matrix;
+        self.text_matrix = self.line_matrix;
+    }
+
+    fn load_font(&mut self, name: &str) -> Result<Rc<Font>> {
+        if let Some(font) = self.fonts.get(name) {
+            return Ok(Rc::clone(font));
+        };
+        let font_dict = self.resources.as_ref()
+                                      .and_then(|resources| resources.get("Font"))
+                                      .map(|fonts| fonts.try_into_map())
+                                      .transpose()?
+                                      .and_then(|fonts| fonts.get(name).cloned());
+        // A missing font shouldn't cost the whole page, so its text is laid out with standard metrics instead
+        let font = match font_dict {
+            Some(font_dict) => Font::from_dict(font_dict.try_into_map()?.as_ref())?,
+            None => {
+                self.warn(&format!("Font {} not in resources, using {} metrics", name, FALLBACK_FONT));
+                Font::standard(FALLBACK_FONT)
+            }
+        };
+        let font = Rc::new(font);
+        self.fonts.insert(name.to_string(), Rc::clone(&font));
+        Ok(font)
+    }
+
+    /// Decode a string with the current font, record it as a text block and advance the text matrix past it
+    /// (spec 9.4.4).
+    fn show_string(&mut self, bytes: &[u8]) -> Result<()> {
+        let (font_name, font) = self.state.font.clone()
+                                    .ok_or_else(|| ErrorKind::ParsingError("Text shown with no font set".to_string()))?;
+        if font.is_type3() {
+            self.type3_fonts.insert(font_name.clone());
+        };
+        let state = &self.state;
+        let render_matrix = |text_matrix: Matrix| {
+            Matrix::new(state.font_size * state.horizontal_scaling, 0.0, 0.0, state.font_size, 0.0, state.rise)
+                * text_matrix * state.ctm
+        };
+        let start = render_matrix(self.text_matrix);
+        let mut text = String::new();
+        for (code, length) in font.split_codes(bytes) {
+            text.push_str(&font.decode(code, length));
+            // Word spacing applies to the single-byte code 32 only, even in composite fonts
+            let word_spacing = if code == 32 && length == 1 { state.word_spacing } else { 0.0 };
+            let advance = (font.width(code) / 1000.0 * state.font_size + state.char_spacing + word_spacing)
+                * state.horizontal_scaling;
+            self.text_matrix = Matrix::translation(advance, 0.0) * self.text_matrix;
+        }
+        let (x, y) = start.transform_point(0.0, 0.0);
+        let (end_x, end_y) = render_matrix(self.text_matrix).transform_point(0.0, 0.0);
+        let mut block = TextBlock {
+            text,
+            font: font_name,
+            x,
+            y,
+            end_x,
+            end_y,
+            font_size: start.vertical_scale(),
+            clip: self.state.clip.clone(),
+            render_mode: state.render_mode,
+            clipped: false,
+        };
+        if let Some(clip) = &block.clip {
+            block.clipped = clip.bounds().and_then(|region| region.intersection(&block.bounds())).is_none();
+        };
+        self.text_blocks.push(block);
+        Ok(())
+    }
+}
+
+fn operand(operands: &[PdfObject], index: usize, expected: usize) -> Result<&PdfObject> {
+    if operands.len() != expected {
+        Err(ErrorKind::ParsingError(format!("Expected {} operands, found {}", expected, operands.len())))?
+    };
+    Ok(&operands[index])
+}
+
+/// An integer entry of a dictionary or of a stream's dictionary.
+fn int_entry(object: &PdfObject, key: &str) -> Option<i32> {
+    let value = match object.try_into_binary_stream() {
+        Ok(stream) => stream.attributes().get(key).cloned(),
+        Err(_) => object.try_into_map().ok()?.get(key).cloned()
+    };
+    value?.try_into_int().ok()
+}
+
+fn numbers(operands: &[PdfObject], expected: usize) -> Result<Vec<f32>> {
+    if operands.len() != expected {
+        Err(ErrorKind::ParsingError(format!("Expected {} operands, found {}", expected, operands.len())))?
+    };
+    operands.iter().map(|operand| operand.try_into_number()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn blocks_for(font: &str, content: &[u8]) -> Vec<TextBlock> {
+        let mut builder = MiniPdfBuilder::new();
+        let font_id = builder.add_object(font);
+        let resources_id = builder.add_object(format!("<< /Font << /F1 {} 0 R >> >>", font_id));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let mut interpreter = Interpreter::new(Some(pdf.get_dict(ObjectId::new(resources_id, 0)).unwrap()));
+        interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        interpreter.text_blocks
+    }
+
+    #[test]
+    fn test_patterns() {
+        let mut builder = MiniPdfBuilder::new();
+        let axial = "<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 0 1 0] /Function << /FunctionType 2 \
+                     /Domain [0 1] /C0 [1 0 0] /C1 [0 0 1] /N 1 >> >>";
+        let axial_id = builder.add_object(axial);
+        let mesh = builder.add_stream(b"", TestFilter::None, "/ShadingType 4 /ColorSpace /DeviceGray \
+                                      /BitsPerCoordinate 8 /BitsPerComponent 8 /BitsPerFlag 8 \
+                                      /Decode [0 1 0 1 0 1]");
+        let tiling = builder.add_stream(b"0 0 5 5 re f", TestFilter::None,
+                                        "/PatternType 1 /PaintType 2 /TilingType 1 /BBox [0 0 10 10] \
+                                         /XStep 10 /YStep 10 /Resources << >>");
+        let resources = builder.add_object(format!(
+            "<< /Shading << /Sh0 {} 0 R /Sh1 {} 0 R >> /Pattern << /P0 << /PatternType 2 /Shading {} 0 R >> \
+             /P1 {} 0 R >> >>", axial_id, mesh, axial_id, tiling));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let mut interpreter = Interpreter::new(Some(pdf.get_dict(ObjectId::new(resources, 0)).unwrap()));
+        let content = b"/Sh0 sh /Sh1 sh /Pattern cs /P0 scn 0 0 10 10 re f \
+                        /CS0 CS 0.5 0.2 0.1 /P1 SCN 0.5 g 0 0 m 10 10 l S /Missing sh";
+        interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        let found: Vec<(&str, PatternPaint, Option<i32>, Option<i32>)> = interpreter.patterns.iter()
+            .map(|used| (used.name.as_str(), used.paint, used.pattern_type, used.shading_type))
+            .collect();
+        assert_eq!(found, vec![("Sh0", PatternPaint::Shading, None, Some(2)),
+                               ("Sh1", PatternPaint::Shading, None, Some(4)),
+                               ("P0", PatternPaint::Fill, Some(2), Some(2)),
+                               ("P1", PatternPaint::Stroke, Some(1), None),
+                               ("Missing", PatternPaint::Shading, None, None)]);
+        assert_eq!(interpreter.paths.len(), 2);
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut interpreter = Interpreter::new(None);
+        let content = b"2 w 10 10 m 100 10 l S q 2 0 0 2 0 0 cm 5 5 10 20 re f Q 0 0 m 1 1 2 2 v h n";
+        interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        assert_eq!(interpreter.paths.len(), 2);
+        assert_eq!(interpreter.paths[0], Path {
+            segments: vec![PathSegment::MoveTo(10.0, 10.0), PathSegment::LineTo(100.0, 10.0)],
+            stroke: true,
+            line_width: 2.0,
+            ..Default::default()
+        });
+        assert!(interpreter.paths[1].fill);
+        assert_eq!(interpreter.paths[1].segments[2], PathSegment::LineTo(30.0, 50.0));
+        assert_eq!(interpreter.paths[1].segments[4], PathSegment::Close);
+    }
+
+    #[test]
+    fn test_clipping() {
+        let mut interpreter = Interpreter::new(None);
+        let content = b"q 0 0 100 100 re W n 10 10 m 20 20 l S 50 0 100 100 re W* f Q 0 0 m 5 5 l S";
+        interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        let clip = interpreter.paths[0].clip.as_ref().unwrap();
+        assert_eq!((clip.segments.len(), clip.even_odd, clip.parent.is_none()), (5, false, true));
+        // The second clip only takes effect after its path is painted
+        assert_eq!(interpreter.paths[1].clip.as_ref(), Some(clip));
+        assert!(interpreter.paths[1].fill && !interpreter.paths[1].even_odd);
+        assert_eq!(interpreter.paths[2].clip, None);
+
+        // An empty clipping path is discarded rather than applied to the next painted path
+        let mut interpreter = Interpreter::new(None);
+        interpreter.run(&CommandStream::parse(b"W n 0 0 m 5 5 l S").unwrap()).unwrap();
+        assert_eq!(interpreter.paths[0].clip, None);
+    }
+
+    #[test]
+    fn test_clipped_text() {
+        let font = "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /FirstChar 65 /Widths [600] >>";
+        let blocks = blocks_for(font, b"BT /F1 10 Tf 10 10 Td (A) Tj ET \
+                                        q 0 0 50 50 re W n BT /F1 10 Tf 10 10 Td (A) Tj 100 0 Td (A) Tj ET \
+                                        q 200 0 50 50 re W n BT /F1 10 Tf 10 10 Td (A) Tj ET Q Q \
+                                        q 0 0 m 0 100 l 100 100 l 100 0 l h W n BT /F1 10 Tf 110 10 Td (A) Tj ET Q");
+        let clipped: Vec<bool> = blocks.iter().map(|block| block.clipped).collect();
+        // Outside a rectangle, outside the empty intersection of two, and outside a closed polygon
+        assert_eq!(clipped, vec![false, false, true, true, true]);
+        assert_eq!(blocks[0].bounds(), Rect::new(10.0, 8.0, 16.0, 18.0));
+
+        let clip = ClipPath { segments: vec![PathSegment::MoveTo(0.0, 0.0), PathSegment::CurveTo(0.0, 10.0, 20.0, 10.0,
+                                                                                                 20.0, 0.0)],
+                              even_odd: false, parent: None };
+        assert_eq!(clip.bounds(), Some(Rect::new(0.0, 0.0, 20.0, 10.0)));
+    }
+
+    #[test]
+    fn test_render_mode() {
+        let font = "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>";
+        let blocks = blocks_for(font, b"BT /F1 10 Tf (a) Tj 3 Tr (b) Tj q 7 Tr (c) Tj Q (d) Tj ET BT (e) Tj ET");
+        let modes: Vec<(u8, bool)> = blocks.iter().map(|block| (block.render_mode, block.is_invisible())).collect();
+        // The mode is part of the graphics state, so it survives ET but not Q
+        assert_eq!(modes, vec![(0, false), (3, true), (7, true), (3, true), (3, true)]);
+
+        let mut interpreter = Interpreter::new(None);
+        interpreter.run(&CommandStream::parse(b"BT 3 Tr 9 Tr ET").unwrap()).unwrap();
+        assert_eq!((interpreter.state.render_mode, interpreter.warnings.as_slice()),
+                   (3, ["Ignoring invalid text rendering mode 9".to_string()].as_slice()));
+    }
+
+    #[test]
+    fn test_unbalanced_text_objects() {
+        let font = "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>";
+        let mut builder = MiniPdfBuilder::new();
+        let font_id = builder.add_object(font);
+        let resources_id = builder.add_object(format!("<< /Font << /F1 {} 0 R >> >>", font_id));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let mut interpreter = Interpreter::new(Some(pdf.get_dict(ObjectId::new(resources_id, 0)).unwrap()));
+        let run = |interpreter: &mut Interpreter, content: &[u8]| {
+            interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        };
+        run(&mut interpreter, b"BT /F1 10 Tf 10 10 Td (a) Tj ET BT 20 20 Td (b) Tj");
+        assert_eq!(interpreter.warnings, vec!["BT without ET at the end of the content stream"]);
+        // The open text object was closed, so this ET is unmatched
+        run(&mut interpreter, b"ET BT 30 30 Td (c) Tj BT (d) Tj ET");
+        assert_eq!(&interpreter.warnings[1..], ["ET outside a text object", "BT inside a text object"]);
+        let starts: Vec<(f32, f32)> = interpreter.text_blocks.iter().map(|block| (block.x, block.y)).collect();
+        assert_eq!(starts, vec![(10.0, 10.0), (20.0, 20.0), (30.0, 30.0), (0.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_missing_font() {
+        let mut interpreter = Interpreter::new(None);
+        interpreter.run(&CommandStream::parse(b"BT /F9 10 Tf 10 10 Td (Hi) Tj (!) Tj ET").unwrap()).unwrap();
+        assert_eq!(interpreter.warnings, vec!["Font F9 not in resources, using Helvetica metrics"]);
+        let texts: Vec<&str> = interpreter.text_blocks.iter().map(|block| &block.text[..]).collect();
+        assert_eq!(texts, vec!["Hi", "!"]);
+        // H and i are 722 and 222 thousandths wide in Helvetica
+        assert!((interpreter.text_blocks[0].end_x - 19.44).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_simple_text() {
+        let blocks = blocks_for("<< /Type /Font /Subtype /Type1 /BaseFont /Courier /FirstChar 32 /Widths [600 600 600] >>",
+                                b"BT /F1 10 Tf 72 700 Td (! !) Tj 0 -12 Td [(!) -1000 ( )] TJ ET");
+        let texts: Vec<&str> = blocks.iter().map(|b| &b.text[..]).collect();
+        assert_eq!(texts, vec!["! !", "!", " "]);
+        assert_eq!((blocks[0].x, blocks[0].y, blocks[0].end_x), (72.0, 700.0, 90.0));
+        assert_eq!(blocks[0].font_size, 10.0);
+        // The TJ adjustment moves the second string 10 units right
+        assert_eq!((blocks[1].y, blocks[2].x), (688.0, 88.0));
+    }
+
+    #[test]
+    fn test_scaled_text() {
+        let blocks = blocks_for("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+                                b"q 2 0 0 2 100 100 cm BT /F1 6 Tf 1 0 0 1 5 5 Tm 2 Tc (ab) Tj ET Q");
+        assert_eq!((blocks[0].x, blocks[0].y, blocks[0].font_size), (110.0, 110.0, 12.0));
+        // Two glyphs of 556/1000 * 6 plus 2 spacing each, doubled by the CTM
+        assert!((blocks[0].end_x - (110.0 + 2.0 * 2.0 * 5.336)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_type3_font() {
+        let mut builder = MiniPdfBuilder::new();
+        let square = builder.add_stream(b"10 0 0 0 10 10 d1 0 0 10 10 re f", TestFilter::None, "");
+        let font = builder.add_object(format!(
+            "<< /Type /Font /Subtype /Type3 /FontBBox [0 0 10 10] /FontMatrix [0.1 0 0 0.1 0 0] \
+               /CharProcs << /square {} 0 R >> /Encoding << /Type /Encoding /Differences [97 /square] >> \
+               /FirstChar 97 /LastChar 98 /Widths [10 5] >>", square));
+        builder.add_page_with_entries(b"BT /F1 2 Tf 10 10 Td (abc) Tj ET", TestFilter::None,
+                                      &format!("/Resources << /Font << /F1 {} 0 R >> >>", font));
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let blocks = doc.page_text_blocks(0).unwrap();
+        // Widths of 10 and 5 glyph units are 1 and 0.5 text space units; c has no width
+        assert_eq!((blocks[0].x, blocks[0].end_x), (10.0, 13.0));
+        assert_eq!(blocks[0].font_size, 2.0);
+        let font = Font::from_dict(&doc.get_dict(ObjectId::new(font, 0)).unwrap()).unwrap();
+        assert!(font.is_type3());
+        assert_eq!(font.glyph_procedure(97), Some(&b"10 0 0 0 10 10 d1 0 0 10 10 re f"[..]));
+        assert_eq!(font.glyph_procedure(98), None);
+    }
+
+    #[test]
+    fn test_two_byte_codes() {
+        // Without the font's code width, <3042 4E2D> would be read as four one-byte codes
+        let blocks = blocks_for("<< /Type /Font /Subtype /Type0 /BaseFont /MSMincho /Encoding /Identity-H \
+                                   /DescendantFonts [<< /Subtype /CIDFontType2 >>] >>",
+                                b"BT /F1 10 Tf (\x30\x42\x4E\x2D) Tj <0020> Tj ET");
+        assert_eq!(blocks[0].text, "\u{FFFD}\u{FFFD}");
+        assert_eq!(blocks[0].end_x, 20.0);
+        assert_eq!(blocks[1].text, "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_to_unicode_font() {
+        let mut builder = MiniPdfBuilder::new();
+        let cmap = builder.add_stream(b"1 begincodespacerange <0000> <FFFF> endcodespacerange \
+                                        1 beginbfrange <0001> <0003> <0048> endbfrange", TestFilter::Flate, "");
+        let font = builder.add_object(format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /Subset /Encoding /Identity-H /ToUnicode {} 0 R \
+               /DescendantFonts [<< /Subtype /CIDFontType2 >>] >>", cmap));
+        builder.add_page_with_entries(b"BT /F1 12 Tf <00010002000300FF> Tj ET", TestFilter::None,
+                                      &format!("/Resources << /Font << /F1 {} 0 R >> >>", font));
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        // Codes the map leaves out have no text of their own
+        assert_eq!(doc.page_text(0).unwrap(), "HIJ\u{FFFD}\n");
+    }
+}
@@ -0,0 +1,414 @@
+use std::fmt;
+
+use super::*;
+
+/// Fragments whose baselines differ by less than this fraction of the font size are on the same line.
+const SAME_LINE_TOLERANCE: f32 = 0.5;
+/// Horizontal gaps wider than this fraction of the font size separate words.  A typical space is 0.25 em,
+/// while kerning rarely exceeds 0.1 em.
+const WORD_GAP: f32 = 0.15;
+/// Column gutters must be at least this many ems wide, to tell them from gaps between words.
+const GUTTER_WIDTH: f32 = 1.0;
+/// Histogram bins with at most this fraction of the peak coverage, or covered by a single fragment, can be part
+/// of a gutter, so that a heading crossing the gutter doesn't hide it.
+const GUTTER_COVERAGE: f32 = 0.05;
+/// Each column needs text on at least this many baselines, so that label/value pairs aren't read as columns.
+const MIN_COLUMN_LINES: usize = 5;
+
+/// Which text PdfDoc::page_text_with_options and text_with_options extract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextOptions {
+    /// Include text drawn with an invisible rendering mode, like the OCR layer of a scanned document.  Leave it
+    /// out to get only what a reader sees printed.
+    pub include_invisible: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions { include_invisible: true }
+    }
+}
+
+/// A word of text positioned on its baseline, in default user space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Word {
+    pub text: String,
+    pub x: f32,
+    pub end_x: f32,
+    pub y: f32,
+    pub font_size: f32,
+}
+
+/// Words sharing a baseline, ordered left to right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub words: Vec<Word>,
+    pub y: f32,
+    /// Whether most of the line's letters are from right-to-left scripts like Arabic and Hebrew, so that its
+    /// words are read right to left
+    pub rtl: bool,
+}
+
+impl Line {
+    pub fn x(&self) -> f32 {
+        self.words.first().map_or(0.0, |word| word.x)
+    }
+
+    pub fn end_x(&self) -> f32 {
+        self.words.last().map_or(0.0, |word| word.end_x)
+    }
+
+    /// The words in the order they're read, each with whether its characters are read right to left.
+    pub fn words_in_reading_order(&self) -> Vec<(&Word, bool)> {
+        let words = self.words.iter().map(|word| (word, is_right_to_left_word(&word.text)));
+        if self.rtl { words.rev().collect() } else { words.collect() }
+    }
+
+    /// The words in reading order.  Glyphs are shown left to right whatever the script, so words in
+    /// right-to-left scripts have their characters reversed to put them in the order they're read.
+    pub fn text(&self) -> String {
+        self.words_in_reading_order()
+            .into_iter()
+            .map(|(word, rtl)| if rtl { reverse_letters(&word.text) } else { word.text.clone() })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Whether c is a letter of a right-to-left script: Hebrew, Arabic, Syriac, Thaana, N'Ko and the others in
+/// U+0590 to U+08FF, or the Hebrew and Arabic presentation forms.
+fn is_right_to_left_char(c: char) -> bool {
+    matches!(c, '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}')
+}
+
+/// Whether c is a Hebrew point or cantillation mark or an Arabic vowel mark, which combine with the letter
+/// before them.
+fn is_right_to_left_mark(c: char) -> bool {
+    matches!(c, '\u{0591}'..='\u{05BD}' | '\u{05BF}' | '\u{05C1}'..='\u{05C2}' | '\u{05C4}'..='\u{05C5}' | '\u{05C7}'
+                | '\u{064B}'..='\u{065F}')
+}
+
+/// The characters of text in reverse order, keeping each combining mark after the letter it belongs to.
+fn reverse_letters(text: &str) -> String {
+    let mut clusters: Vec<String> = Vec::new();
+    for c in text.chars() {
+        match clusters.last_mut() {
+            Some(cluster) if is_right_to_left_mark(c) => cluster.push(c),
+            _ => clusters.push(c.to_string())
+        };
+    }
+    clusters.into_iter().rev().collect()
+}
+
+fn is_right_to_left_word(text: &str) -> bool {
+    text.chars().any(is_right_to_left_char)
+}
+
+/// Whether more of the letters in the words are from right-to-left scripts than from left-to-right ones.
+fn is_right_to_left_line(words: &[Word]) -> bool {
+    let (rtl, ltr) = words.iter().flat_map(|word| word.text.chars()).fold((0, 0), |(rtl, ltr), c| {
+        if is_right_to_left_char(c) {
+            (rtl + 1, ltr)
+        } else if c.is_alphabetic() {
+            (rtl, ltr + 1)
+        } else {
+            (rtl, ltr)
+        }
+    });
+    rtl > ltr
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+/// A run of non-whitespace text from one text block.
+struct Fragment {
+    word: Word,
+    /// Whether whitespace in the same block precedes or follows the fragment
+    space_before: bool,
+    space_after: bool,
+}
+
+/// Cluster text blocks into words and lines, with lines ordered top to bottom.  Only horizontal text is
+/// handled; glyphs are assumed to share their block's width equally.
+pub fn group_into_lines(blocks: &[TextBlock]) -> Vec<Line> {
+    lines_from_fragments(blocks.iter().flat_map(split_block).collect())
+}
+
+/// Cluster text blocks into lines in reading order.  Columns found by looking for empty vertical strips in a
+/// histogram of text coverage are read one after the other; text crossing a gutter, like a heading over both
+/// columns, separates sections that are each read column by column.
+pub fn group_into_reading_order(blocks: &[TextBlock]) -> Vec<Line> {
+    let fragments: Vec<Fragment> = blocks.iter().flat_map(split_block).collect();
+    let gutters = find_gutters(&fragments);
+    if gutters.is_empty() {
+        return lines_from_fragments(fragments);
+    };
+    // Split lines where a gap between words contains a gutter
+    let mut spanning = Vec::new();
+    let mut columns: Vec<Vec<Line>> = (0..=gutters.len()).map(|_| Vec::new()).collect();
+    for line in lines_from_fragments(fragments) {
+        let mut segments: Vec<Line> = Vec::new();
+        for word in line.words {
+            match segments.last_mut() {
+                Some(segment) if !gutters.iter().any(|(start, end)| segment.end_x() <= *start && word.x >= *end) => {
+                    segment.words.push(word)
+                },
+                _ => segments.push(Line { words: vec![word], y: line.y, rtl: line.rtl }),
+            }
+        }
+        for segment in segments {
+            if gutters.iter().any(|(start, end)| segment.x() < *start && segment.end_x() > *end) {
+                spanning.push(segment);
+            } else {
+                let center = (segment.x() + segment.end_x()) / 2.0;
+                columns[gutters.iter().filter(|(start, _)| *start < center).count()].push(segment);
+            }
+        }
+    }
+
+    let mut ordered = Vec::new();
+    for section_end in spanning.into_iter().map(Some).chain(std::iter::once(None)) {
+        let section_bottom = section_end.as_ref().map_or(f32::NEG_INFINITY, |line| line.y);
+        for column in columns.iter_mut() {
+            let split = column.iter().position(|line| line.y <= section_bottom).unwrap_or(column.len());
+            ordered.extend(column.drain(..split));
+        }
+        ordered.extend(section_end);
+    }
+    ordered
+}
+
+/// The x ranges of the gaps between columns, left to right.
+fn find_gutters(fragments: &[Fragment]) -> Vec<(f32, f32)> {
+    // Words in text turned round run from right to left, so x can be past end_x
+    let extent = |fragment: &Fragment| {
+        (fragment.word.x.min(fragment.word.end_x), fragment.word.x.max(fragment.word.end_x))
+    };
+    let left = fragments.iter().map(|fragment| extent(fragment).0).fold(f32::INFINITY, f32::min);
+    let right = fragments.iter().map(|fragment| extent(fragment).1).fold(f32::NEG_INFINITY, f32::max);
+    if fragments.is_empty() || right - left > 100_000.0 {
+        return Vec::new();
+    };
+    let mut font_sizes: Vec<f32> = fragments.iter().map(|fragment| fragment.word.font_size).collect();
+    font_sizes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let min_width = GUTTER_WIDTH * font_sizes[font_sizes.len() / 2];
+
+    // One bin per unit of x
+    let mut coverage = vec![0usize; (right - left).ceil() as usize + 1];
+    for fragment in fragments {
+        let (low, high) = extent(fragment);
+        let end = ((high - left).ceil() as usize).min(coverage.len());
+        let start = ((low - left).floor() as usize).min(end);
+        coverage[start..end].iter_mut().for_each(|count| *count += 1);
+    }
+    let threshold = ((*coverage.iter().max().unwrap() as f32 * GUTTER_COVERAGE) as usize).max(1);
+
+    let mut gutters = Vec::new();
+    let mut bin = 0;
+    while bin < coverage.len() {
+        if coverage[bin] > threshold {
+            bin += 1;
+            continue;
+        };
+        let run_end = (bin..coverage.len()).find(|&i| coverage[i] > threshold).unwrap_or(coverage.len());
+        let (start, end) = (left + bin as f32, left + run_end as f32);
+        if bin > 0 && run_end < coverage.len() && end - start >= min_width {
+            gutters.push((start, end));
+        };
+        bin = run_end;
+    }
+    // Both sides of every gutter need enough lines of text
+    let column_lines = |low: f32, high: f32| {
+        let mut baselines: Vec<i64> = fragments.iter()
+                                               .filter(|fragment| fragment.word.x >= low && fragment.word.end_x <= high)
+                                               .map(|fragment| fragment.word.y.round() as i64)
+                                               .collect();
+        baselines.sort_unstable();
+        baselines.dedup();
+        baselines.len()
+    };
+    let mut index = 0;
+    while index < gutters.len() {
+        let low = if index == 0 { f32::NEG_INFINITY } else { gutters[index - 1].1 };
+        let high = gutters.get(index + 1).map_or(f32::INFINITY, |gutter| gutter.0);
+        if column_lines(low, gutters[index].0) < MIN_COLUMN_LINES || column_lines(gutters[index].1, high) < MIN_COLUMN_LINES {
+            gutters.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+    gutters
+}
+
+fn lines_from_fragments(fragments: Vec<Fragment>) -> Vec<Line> {
+    let mut lines: Vec<(f32, f32, Vec<Fragment>)> = Vec::new();
+    for fragment in fragments {
+        let tolerance = SAME_LINE_TOLERANCE * fragment.word.font_size;
+        match lines.iter_mut().find(|(y, _, _)| (y - fragment.word.y).abs() <= tolerance) {
+            Some((_, _, fragments)) => fragments.push(fragment),
+            None => lines.push((fragment.word.y, fragment.word.x, vec![fragment])),
+        }
+    }
+    lines.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.partial_cmp(&b.1).unwrap()));
+    lines.into_iter().map(|(y, _, mut fragments)| {
+        fragments.sort_by(|a, b| a.word.x.partial_cmp(&b.word.x).unwrap());
+        let mut words: Vec<Word> = Vec::new();
+        let mut space_after_last = false;
+        for fragment in fragments {
+            match words.last_mut() {
+                Some(last) if !fragment.space_before && !space_after_last
+                    && fragment.word.x - last.end_x < WORD_GAP * fragment.word.font_size.max(last.font_size) => {
+                    last.text.push_str(&fragment.word.text);
+                    last.end_x = last.end_x.max(fragment.word.end_x);
+                },
+                _ => words.push(fragment.word),
+            }
+            space_after_last = fragment.space_after;
+        }
+        let rtl = is_right_to_left_line(&words);
+        Line { words, y, rtl }
+    }).collect()
+}
+
+/// Text with one line per output line, as pdftotext produces without its layout option.
+pub fn lines_to_text(lines: &[Line]) -> String {
+    lines.iter().map(|line| line.text() + "\n").collect()
+}
+
+fn split_block(block: &TextBlock) -> Vec<Fragment> {
+    let chars: Vec<char> = block.text.chars().collect();
+    let char_width = if chars.is_empty() { 0.0 } else { (block.end_x - block.x) / chars.len() as f32 };
+    let mut fragments = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        if chars[start].is_whitespace() {
+            start += 1;
+            continue;
+        };
+        let end = (start..chars.len()).find(|&i| chars[i].is_whitespace()).unwrap_or(chars.len());
+        fragments.push(Fragment {
+            word: Word {
+                text: chars[start..end].iter().collect(),
+                x: block.x + start as f32 * char_width,
+                end_x: block.x + end as f32 * char_width,
+                y: block.y,
+                font_size: block.font_size,
+            },
+            space_before: start > 0,
+            space_after: end < chars.len(),
+        });
+        start = end;
+    }
+    fragments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str, x: f32, end_x: f32, y: f32) -> TextBlock {
+        TextBlock { text: text.to_string(), font: "F1".to_string(), x, y, end_x, end_y: y, font_size: 10.0,
+                    ..Default::default() }
+    }
+
+    #[test]
+    fn test_words_and_lines() {
+        let blocks = vec![
+            // The second line is shown first, and contains an explicit space
+            block("Second line", 72.0, 127.0, 688.0),
+            // Kerned pieces of one word, then a word placed by a wide TJ gap
+            block("Hel", 72.0, 87.0, 700.0), block("lo", 87.5, 97.5, 700.0), block("world", 100.5, 125.5, 700.0),
+            // A subscript stays on the first line
+            block("2", 126.0, 129.0, 698.0),
+        ];
+        let lines = group_into_lines(&blocks);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text(), "Hello world2");
+        assert_eq!(lines[1].text(), "Second line");
+        assert_eq!(lines[1].words[1], Word { text: "line".to_string(), x: 107.0, end_x: 127.0, y: 688.0, font_size: 10.0 });
+        assert_eq!(lines_to_text(&lines), "Hello world2\nSecond line\n");
+    }
+
+    fn two_columns(rows: usize) -> Vec<TextBlock> {
+        let mut blocks = Vec::new();
+        for row in 0..rows {
+            let y = 700.0 - 12.0 * row as f32;
+            blocks.push(block(&format!("left {}", row), 72.0, 122.0 + row as f32, y));
+            blocks.push(block(&format!("right {}", row), 320.0, 375.0, y));
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut blocks = vec![block("A heading across both columns", 72.0, 375.0, 730.0)];
+        blocks.extend(two_columns(5));
+        blocks.push(block("Footnote", 72.0, 112.0, 600.0));
+        let text = lines_to_text(&group_into_reading_order(&blocks));
+        // The footnote is read as the end of the left column
+        assert_eq!(text, "A heading across both columns\nleft 0\nleft 1\nleft 2\nleft 3\nleft 4\nFootnote\n\
+                          right 0\nright 1\nright 2\nright 3\nright 4\n");
+        // A full-width line between rows splits the page into sections, each read column by column
+        blocks.push(block("A figure caption spanning the page", 72.0, 375.0, 676.0 - 6.0));
+        let lines = group_into_reading_order(&blocks);
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(&texts[1..5], &["left 0", "left 1", "left 2", "right 0"]);
+        assert_eq!(texts[7], "A figure caption spanning the page");
+        assert_eq!(texts[8], "left 3");
+    }
+
+    #[test]
+    fn test_too_few_lines_for_columns() {
+        // Label/value pairs stay on their lines
+        let lines = group_into_reading_order(&two_columns(2));
+        let texts: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(texts, vec!["left 0 right 0", "left 1 right 1"]);
+    }
+
+    #[test]
+    fn test_reversed_words_in_columns() {
+        // Text turned half round ends to the left of where it starts, here past the left edge of the page text
+        let mut blocks = two_columns(5);
+        blocks.push(block("upside down", 122.0, 20.0, 620.0));
+        let texts: Vec<String> = group_into_reading_order(&blocks).iter().map(|line| line.text()).collect();
+        assert_eq!(&texts[..2], &["left 0", "left 1"]);
+        assert!(texts.iter().any(|text| text.contains("upside")));
+    }
+
+    #[test]
+    fn test_trailing_space_separates_blocks() {
+        let lines = group_into_lines(&[block("one ", 0.0, 20.0, 0.0), block("two", 20.0, 35.0, 0.0)]);
+        assert_eq!(lines[0].text(), "one two");
+        assert_eq!((lines[0].x(), lines[0].end_x()), (0.0, 35.0));
+    }
+
+    #[test]
+    fn test_right_to_left_lines() {
+        // Glyphs are shown left to right, so the Hebrew comes in the reverse of reading order: "shalom olam" as
+        // one block, then "year 2024" in two blocks, and an English name in a Hebrew line
+        let blocks = vec![
+            block("\u{5DD}\u{5DC}\u{5D5}\u{5E2} \u{5DD}\u{5D5}\u{5DC}\u{5E9}", 72.0, 117.0, 700.0),
+            block("2024", 72.0, 92.0, 680.0), block("\u{5EA}\u{5E0}\u{5E9}", 97.0, 112.0, 680.0),
+            block("PDF \u{5E8}\u{5D5}\u{5D1}\u{5E2} \u{5D9}\u{5DC}\u{5DB}", 72.0, 132.0, 660.0),
+            block("Left to right", 72.0, 137.0, 640.0),
+        ];
+        let lines = group_into_lines(&blocks);
+        assert_eq!(lines.iter().map(|line| line.rtl).collect::<Vec<_>>(), vec![true, true, true, false]);
+        assert_eq!(lines_to_text(&lines), "\u{5E9}\u{5DC}\u{5D5}\u{5DD} \u{5E2}\u{5D5}\u{5DC}\u{5DD}\n\
+                                           \u{5E9}\u{5E0}\u{5EA} 2024\n\
+                                           \u{5DB}\u{5DC}\u{5D9} \u{5E2}\u{5D1}\u{5D5}\u{5E8} PDF\n\
+                                           Left to right\n");
+        // Positions stay left to right
+        assert_eq!(lines[1].words[0].text, "2024");
+
+        // Points and vowel marks stay after the letters they sit on
+        let lines = group_into_lines(&[block("\u{5DD}\u{5D5}\u{5B9}\u{5DC}\u{5E9}\u{5C1}", 72.0, 92.0, 700.0),
+                                       block("\u{628}\u{627}\u{62A}\u{64E}\u{643}\u{650}", 72.0, 92.0, 680.0)]);
+        assert_eq!(lines_to_text(&lines), "\u{5E9}\u{5C1}\u{5DC}\u{5D5}\u{5B9}\u{5DD}\n\
+                                           \u{643}\u{650}\u{62A}\u{64E}\u{627}\u{628}\n");
+    }
+}
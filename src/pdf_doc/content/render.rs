@@ -0,0 +1,248 @@
+use std::io::Write;
+
+use super::*;
+use crate::errors::*;
+use crate::doc_tree::pdf_file::decode::flate_encode;
+
+/// Line segments used to approximate each Bézier curve.
+const CURVE_STEPS: usize = 16;
+/// The most pixels a raster may have, a gigabyte of RGBA, so that a huge page box or resolution fails rather
+/// than exhausting memory.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// Settings for render_page.  Colors are RGBA.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub dpi: f32,
+    pub background: [u8; 4],
+    pub path_color: [u8; 4],
+    /// Text is drawn as boxes covering each run of text, since glyphs aren't rendered
+    pub text_color: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            dpi: 72.0,
+            background: [255, 255, 255, 255],
+            path_color: [0, 0, 0, 255],
+            text_color: [128, 128, 128, 255],
+        }
+    }
+}
+
+/// An RGBA image, stored row by row from the top left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    fn new(width: u32, height: u32, color: [u8; 4]) -> Self {
+        let pixels = color.iter().cloned().cycle().take(width as usize * height as usize * 4).collect();
+        Raster { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        [self.pixels[index], self.pixels[index + 1], self.pixels[index + 2], self.pixels[index + 3]]
+    }
+
+    /// Fill the area inside polygons by the nonzero winding or even-odd rule, sampling at pixel centers.
+    fn fill_polygons(&mut self, polygons: &[Vec<(f32, f32)>], even_odd: bool, color: [u8; 4]) {
+        let edges: Vec<((f32, f32), (f32, f32))> = polygons.iter().flat_map(|polygon| {
+            (0..polygon.len()).map(move |i| (polygon[i], polygon[(i + 1) % polygon.len()]))
+        }).filter(|(a, b)| a.1 != b.1).collect();
+        for row in 0..self.height {
+            let y = row as f32 + 0.5;
+            let mut crossings: Vec<(f32, i32)> = edges.iter().filter_map(|&((x0, y0), (x1, y1))| {
+                if (y0 <= y && y < y1) || (y1 <= y && y < y0) {
+                    Some((x0 + (y - y0) / (y1 - y0) * (x1 - x0), if y1 > y0 { 1 } else { -1 }))
+                } else {
+                    None
+                }
+            }).collect();
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += if even_odd { 1 } else { pair[0].1 };
+                if winding == 0 || (even_odd && winding % 2 == 0) { continue };
+                let start = (pair[0].0 - 0.5).ceil().max(0.0) as u32;
+                let end = ((pair[1].0 - 0.5).ceil().max(0.0) as u32).min(self.width);
+                for column in start..end {
+                    let index = (row as usize * self.width as usize + column as usize) * 4;
+                    self.pixels[index..index + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    /// Encode as a PNG file with 8-bit RGBA pixels.
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut output = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut header = Vec::new();
+        header.write_all(&self.width.to_be_bytes())?;
+        header.write_all(&self.height.to_be_bytes())?;
+        // Bit depth 8, color type 6 (RGBA), default compression, filtering and no interlacing
+        header.write_all(&[8, 6, 0, 0, 0])?;
+        write_png_chunk(&mut output, b"IHDR", &header);
+        let mut scanlines = Vec::with_capacity(self.pixels.len() + self.height as usize);
+        for row in self.pixels.chunks(self.width as usize * 4) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_png_chunk(&mut output, b"IDAT", &flate_encode(&scanlines, 6)?);
+        write_png_chunk(&mut output, b"IEND", &[]);
+        Ok(output)
+    }
+}
+
+fn write_png_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = output.len();
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(data);
+    let crc = crc32(&output[start..]);
+    output.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Rasterize painted paths, and boxes standing in for text, over the part of user space in page_box.  Rasters
+/// of more than MAX_PIXELS pixels are refused.
+pub fn render_page(paths: &[Path], text_blocks: &[TextBlock], page_box: &Rect, options: &RenderOptions)
+                   -> Result<Raster> {
+    let scale = options.dpi / 72.0;
+    // Casts saturate, so a box or resolution too large for u32, or infinite, gives u32::MAX
+    let width = (page_box.width() * scale).round().max(1.0) as u32;
+    let height = (page_box.height() * scale).round().max(1.0) as u32;
+    let pixels = (width as u64 * height as u64).min(usize::MAX as u64) as usize;
+    if pixels > MAX_PIXELS {
+        Err(ErrorKind::LimitExceeded("raster pixels", pixels, MAX_PIXELS))?
+    };
+    let mut raster = Raster::new(width, height, options.background);
+    let to_pixels = |(x, y): (f32, f32)| ((x - page_box.x0) * scale, (page_box.y1 - y) * scale);
+
+    for path in paths {
+        let subpaths: Vec<(Vec<(f32, f32)>, bool)> = flatten(&path.segments).into_iter()
+            .map(|(points, closed)| (points.into_iter().map(to_pixels).collect(), closed))
+            .collect();
+        if path.fill {
+            let polygons: Vec<Vec<(f32, f32)>> = subpaths.iter().map(|(points, _)| points.clone()).collect();
+            raster.fill_polygons(&polygons, path.even_odd, options.path_color);
+        };
+        if path.stroke {
+            let half_width = (path.line_width * scale).max(1.0) / 2.0;
+            let mut quads = Vec::new();
+            for (points, closed) in &subpaths {
+                let segment_count = if *closed { points.len() } else { points.len().saturating_sub(1) };
+                for i in 0..segment_count {
+                    let (a, b) = (points[i], points[(i + 1) % points.len()]);
+                    let length = (b.0 - a.0).hypot(b.1 - a.1);
+                    if length == 0.0 { continue };
+                    let (nx, ny) = (-(b.1 - a.1) / length * half_width, (b.0 - a.0) / length * half_width);
+                    quads.push(vec![(a.0 + nx, a.1 + ny), (b.0 + nx, b.1 + ny), (b.0 - nx, b.1 - ny), (a.0 - nx, a.1 - ny)]);
+                }
+            }
+            // Each quad is filled on its own so that overlapping segments don't cancel out
+            for quad in quads {
+                raster.fill_polygons(&[quad], false, options.path_color);
+            }
+        };
+    }
+    for block in text_blocks {
+        let (x0, y0) = to_pixels((block.x, block.y - 0.2 * block.font_size));
+        let (x1, y1) = to_pixels((block.end_x, block.y + 0.8 * block.font_size));
+        raster.fill_polygons(&[vec![(x0, y0), (x1, y0), (x1, y1), (x0, y1)]], false, options.text_color);
+    }
+    Ok(raster)
+}
+
+/// Split a path into subpaths of points, approximating curves, with whether each subpath was closed.
+fn flatten(segments: &[PathSegment]) -> Vec<(Vec<(f32, f32)>, bool)> {
+    let mut subpaths: Vec<(Vec<(f32, f32)>, bool)> = Vec::new();
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo(x, y) => subpaths.push((vec![(x, y)], false)),
+            PathSegment::LineTo(x, y) => match subpaths.last_mut() {
+                Some((points, _)) => points.push((x, y)),
+                None => subpaths.push((vec![(x, y)], false)),
+            },
+            PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                if let Some((points, _)) = subpaths.last_mut() {
+                    let (x0, y0) = *points.last().unwrap();
+                    for step in 1..=CURVE_STEPS {
+                        let t = step as f32 / CURVE_STEPS as f32;
+                        let u = 1.0 - t;
+                        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                        points.push((a * x0 + b * x1 + c * x2 + d * x3, a * y0 + b * y1 + c * y2 + d * y3));
+                    }
+                };
+            },
+            PathSegment::Close => if let Some((points, closed)) = subpaths.last_mut() {
+                *closed = true;
+                // Later segments start a new subpath at the same point
+                let start = points[0];
+                subpaths.push((vec![start], false));
+            },
+        }
+    }
+    subpaths.retain(|(points, _)| points.len() > 1);
+    subpaths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let paths = vec![
+            Path { segments: vec![PathSegment::MoveTo(10.0, 10.0), PathSegment::LineTo(30.0, 10.0),
+                                  PathSegment::LineTo(30.0, 30.0), PathSegment::LineTo(10.0, 30.0), PathSegment::Close],
+                   fill: true, line_width: 1.0, ..Default::default() },
+            Path { segments: vec![PathSegment::MoveTo(0.0, 50.0), PathSegment::LineTo(100.0, 50.0)],
+                   stroke: true, line_width: 2.0, ..Default::default() },
+        ];
+        let text = vec![TextBlock { text: "Hi".to_string(), font: "F1".to_string(), x: 60.0, y: 10.0,
+                                    end_x: 80.0, end_y: 10.0, font_size: 10.0, ..Default::default() }];
+        let options = RenderOptions { dpi: 144.0, ..Default::default() };
+        let raster = render_page(&paths, &text, &Rect::new(0.0, 0.0, 100.0, 100.0), &options).unwrap();
+        assert_eq!((raster.width, raster.height), (200, 200));
+        // Inside and outside the filled square, which covers rows 140-180
+        assert_eq!(raster.pixel(40, 160), [0, 0, 0, 255]);
+        assert_eq!(raster.pixel(10, 160), [255, 255, 255, 255]);
+        // The stroke covers 4 rows, from 98 to 101
+        assert_eq!(raster.pixel(50, 97), [255, 255, 255, 255]);
+        assert_eq!(raster.pixel(50, 98), [0, 0, 0, 255]);
+        assert_eq!(raster.pixel(50, 101), [0, 0, 0, 255]);
+        assert_eq!(raster.pixel(50, 102), [255, 255, 255, 255]);
+        assert_eq!(raster.pixel(140, 170), [128, 128, 128, 255]);
+
+        // Too many pixels, at this resolution or any
+        let options = RenderOptions { dpi: 72_000.0, ..Default::default() };
+        assert!(render_page(&paths, &text, &Rect::new(0.0, 0.0, 612.0, 792.0), &options).is_err());
+        let huge = Rect::new(0.0, 0.0, f32::MAX, f32::MAX);
+        assert!(render_page(&paths, &text, &huge, &RenderOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_png() {
+        let raster = Raster::new(2, 1, [255, 0, 0, 255]);
+        let png = raster.to_png().unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x02\x00\x00\x00\x01\x08\x06"));
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
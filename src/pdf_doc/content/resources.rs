@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+
+use super::*;
+
+/// The subdictionaries of /Resources that content streams name resources from (spec 7.8.3).
+pub const RESOURCE_CATEGORIES: [&str; 7] =
+    ["ExtGState", "ColorSpace", "Pattern", "Shading", "XObject", "Font", "Properties"];
+
+/// Colour space names that are operands in their own right rather than resource names (spec 8.6.3 and 8.9.7).
+const DEVICE_COLOR_SPACES: [&str; 8] = ["DeviceGray", "DeviceRGB", "DeviceCMYK", "Pattern", "G", "RGB", "CMYK", "I"];
+
+/// One resource a page declares or uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUse {
+    /// The /Resources subdictionary it's in, such as Font or XObject
+    pub category: String,
+    pub name: String,
+    /// The resource's /Subtype, such as Image or Form for an XObject, if it's declared and has one
+    pub subtype: Option<String>,
+    /// Whether it's in the page's /Resources
+    pub declared: bool,
+    /// Whether the page's content names it
+    pub used: bool,
+}
+
+/// Which resources a page declares and which its content uses, from PdfDoc::page_resource_usage, for finding
+/// resources that can be dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub page: usize,
+    /// Every resource declared or used, in category then name order
+    pub resources: Vec<ResourceUse>,
+}
+
+impl ResourceUsage {
+    /// Resources declared but never used, which the page can do without.
+    pub fn unused(&self) -> impl Iterator<Item = &ResourceUse> {
+        self.resources.iter().filter(|resource| !resource.used)
+    }
+
+    /// Resources the content uses that aren't declared, which viewers can't draw.
+    pub fn missing(&self) -> impl Iterator<Item = &ResourceUse> {
+        self.resources.iter().filter(|resource| !resource.declared)
+    }
+
+    /// The resources of one category, such as Font.
+    pub fn category<'a>(&'a self, category: &'a str) -> impl Iterator<Item = &'a ResourceUse> {
+        self.resources.iter().filter(move |resource| resource.category == category)
+    }
+
+    /// The image XObjects, declared or used.
+    pub fn images(&self) -> impl Iterator<Item = &ResourceUse> {
+        self.category("XObject").filter(|resource| resource.subtype.as_deref() == Some("Image"))
+    }
+}
+
+/// The resources the commands name, as (category, name) pairs.  Forms drawn with Do aren't looked into.
+pub fn used_resources(commands: &CommandStream) -> BTreeSet<(String, String)> {
+    let mut used = BTreeSet::new();
+    let mut add = |category: &str, operand: Option<&PdfObject>| {
+        if let Some(name) = operand.filter(|operand| operand.is_name()).and_then(|name| name.try_into_string().ok()) {
+            if category != "ColorSpace" || !DEVICE_COLOR_SPACES.contains(&name.as_str()) {
+                used.insert((category.to_string(), name.as_ref().clone()));
+            };
+        };
+    };
+    for command in &commands.commands {
+        let operands = &command.operands;
+        match command.operator.as_str() {
+            "Tf" => add("Font", operands.first()),
+            "Do" => add("XObject", operands.first()),
+            "gs" => add("ExtGState", operands.first()),
+            "sh" => add("Shading", operands.first()),
+            "cs" | "CS" => add("ColorSpace", operands.first()),
+            "scn" | "SCN" => add("Pattern", operands.last()),
+            "BDC" | "DP" => add("Properties", operands.get(1)),
+            "BI" => {
+                if let Some(dict) = operands.first().and_then(|dict| dict.try_into_map().ok()) {
+                    add("ColorSpace", dict.get("CS").or_else(|| dict.get("ColorSpace")).map(|cs| cs.as_ref()));
+                };
+            }
+            _ => {}
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_resources() {
+        let commands = CommandStream::parse(b"/GS1 gs /CS0 cs /DeviceRGB CS /P1 scn 0.5 /P2 SCN 1 0 0 sc \
+            BT /F1 12 Tf (Hi) Tj ET /Im1 Do /Span /MC0 BDC EMC /OC <</MCID 1>> BDC EMC /Sh1 sh \
+            BI /W 1 /H 1 /BPC 8 /CS /G ID \x00 EI BI /W 1 /H 1 /BPC 8 /CS /Cs1 ID \x00 EI").unwrap();
+        let used = used_resources(&commands).into_iter()
+                                            .map(|(category, name)| format!("{}/{}", category, name))
+                                            .collect::<Vec<_>>();
+        assert_eq!(used, vec!["ColorSpace/CS0", "ColorSpace/Cs1", "ExtGState/GS1", "Font/F1", "Pattern/P1",
+                              "Pattern/P2", "Properties/MC0", "Shading/Sh1", "XObject/Im1"]);
+    }
+}
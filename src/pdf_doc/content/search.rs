@@ -0,0 +1,107 @@
+use regex::Regex;
+
+use super::*;
+
+/// Share of the font size that glyph boxes extend below the baseline, and above it.  Fonts' real ascent
+/// and descent aren't known, so these are typical values.
+const DESCENT: f32 = 0.2;
+const ASCENT: f32 = 0.8;
+
+/// A match found by PdfDoc::search, with one rectangle per line the match covers, in default user space.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    /// Page index, counting from 0
+    pub page: usize,
+    pub text: String,
+    pub rects: Vec<Rect>,
+}
+
+impl SearchMatch {
+    /// The rectangles as the /QuadPoints of a text markup annotation (spec 12.5.6.10): for each, the corners
+    /// top left, top right, bottom left and bottom right, the order viewers expect whatever the spec says.
+    pub fn quad_points(&self) -> Vec<f32> {
+        self.rects.iter().flat_map(|rect| [rect.x0, rect.y1, rect.x1, rect.y1, rect.x0, rect.y0, rect.x1, rect.y0])
+                  .collect()
+    }
+}
+
+/// The text of a page as produced by lines_to_text, with the box and line number of every character.
+/// Spaces and newlines added between words have no box.
+pub struct PageText {
+    pub text: String,
+    /// (byte offset in text, line number, glyph box) for each character with a box
+    boxes: Vec<(usize, usize, Rect)>,
+}
+
+impl PageText {
+    pub fn new(lines: &[Line]) -> Self {
+        let mut text = String::new();
+        let mut boxes = Vec::new();
+        for (line_number, line) in lines.iter().enumerate() {
+            for (word_index, (word, rtl)) in line.words_in_reading_order().into_iter().enumerate() {
+                if word_index > 0 { text.push(' ') };
+                // Glyphs share the word's width equally, as in the layout module
+                let chars: Vec<char> = word.text.chars().collect();
+                let char_width = (word.end_x - word.x) / chars.len().max(1) as f32;
+                for i in 0..chars.len() {
+                    // Right-to-left words are read from their last glyph back
+                    let position = if rtl { chars.len() - 1 - i } else { i };
+                    let x = word.x + position as f32 * char_width;
+                    boxes.push((text.len(), line_number, Rect::new(
+                        x, word.y - DESCENT * word.font_size, x + char_width, word.y + ASCENT * word.font_size)));
+                    text.push(chars[position]);
+                }
+            }
+            text.push('\n');
+        }
+        PageText { text, boxes }
+    }
+
+    /// Matches of pattern in the text, each with its text and one rectangle per line it covers.
+    pub fn find(&self, pattern: &Regex) -> Vec<(String, Vec<Rect>)> {
+        pattern.find_iter(&self.text).filter(|m| !m.as_str().is_empty()).map(|m| {
+            let start = self.boxes.partition_point(|(offset, _, _)| *offset < m.start());
+            let end = self.boxes.partition_point(|(offset, _, _)| *offset < m.end());
+            let mut rects: Vec<(usize, Rect)> = Vec::new();
+            for (_, line, rect) in &self.boxes[start..end] {
+                match rects.last_mut() {
+                    Some((last_line, last)) if last_line == line => *last = last.union(rect),
+                    _ => rects.push((*line, *rect)),
+                }
+            }
+            (m.as_str().to_string(), rects.into_iter().map(|(_, rect)| rect).collect())
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: f32, y: f32) -> Word {
+        Word { text: text.to_string(), x, end_x: x + 5.0 * text.len() as f32, y, font_size: 10.0 }
+    }
+
+    #[test]
+    fn test_find() {
+        let lines = vec![
+            Line { words: vec![word("Hello", 10.0, 100.0), word("big", 45.0, 100.0)], y: 100.0, rtl: false },
+            Line { words: vec![word("world", 10.0, 88.0)], y: 88.0, rtl: false },
+        ];
+        let page = PageText::new(&lines);
+        assert_eq!(page.text, "Hello big\nworld\n");
+        let matches = page.find(&Regex::new("llo").unwrap());
+        assert_eq!(matches, vec![("llo".to_string(), vec![Rect::new(20.0, 98.0, 35.0, 108.0)])]);
+        // Matches across lines get a rectangle per line
+        let matches = page.find(&Regex::new(r"big\s+wor").unwrap());
+        assert_eq!(matches[0].1, vec![Rect::new(45.0, 98.0, 60.0, 108.0), Rect::new(10.0, 86.0, 25.0, 96.0)]);
+        assert!(page.find(&Regex::new("xyz").unwrap()).is_empty());
+
+        // Right-to-left words are searched in reading order, and boxed where their glyphs are shown
+        let lines = vec![Line { words: vec![word("\u{5DD}\u{5D5}\u{5DC}\u{5E9}", 10.0, 100.0)], y: 100.0, rtl: true }];
+        let page = PageText::new(&lines);
+        assert_eq!(page.text, "\u{5E9}\u{5DC}\u{5D5}\u{5DD}\n");
+        let matches = page.find(&Regex::new("\u{5E9}\u{5DC}").unwrap());
+        assert_eq!(matches[0].1, vec![Rect::new(30.0, 98.0, 50.0, 108.0)]);
+    }
+}
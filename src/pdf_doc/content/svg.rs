@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use super::*;
+
+/// An SVG document showing the painted paths and the text of a page.  Coordinates stay in PDF user space
+/// under a single flipping transform, so the output lines up with the page box; clipping paths become
+/// nested clipPath elements.  Colors aren't tracked yet, so everything is drawn in black.
+pub fn to_svg(paths: &[Path], text_blocks: &[TextBlock], page_box: &Rect) -> String {
+    let mut clips = ClipIds::default();
+    let mut body = String::new();
+    for path in paths {
+        let clip = clips.attribute(&path.clip);
+        let fill = if path.fill { "black" } else { "none" };
+        let fill_rule = if path.even_odd { " fill-rule=\"evenodd\"" } else { "" };
+        let stroke = if path.stroke {
+            format!(" stroke=\"black\" stroke-width=\"{}\"", number(path.line_width))
+        } else {
+            String::new()
+        };
+        writeln!(body, "<path d=\"{}\" fill=\"{}\"{}{}{}/>", path_data(&path.segments), fill, fill_rule, stroke, clip).unwrap();
+    }
+    for block in text_blocks {
+        let clip = clips.attribute(&block.clip);
+        // Undo the page flip for the glyphs themselves, and turn the text to follow its baseline
+        let angle = (block.end_y - block.y).atan2(block.end_x - block.x).to_degrees();
+        let length = (block.end_x - block.x).hypot(block.end_y - block.y);
+        write!(body, "<text transform=\"translate({} {}) rotate({}) scale(1 -1)\" font-size=\"{}\"",
+               number(block.x), number(block.y), number(angle), number(block.font_size)).unwrap();
+        if length > 0.0 {
+            write!(body, " textLength=\"{}\" lengthAdjust=\"spacingAndGlyphs\"", number(length)).unwrap();
+        };
+        writeln!(body, "{}>{}</text>", clip, escape(&block.text)).unwrap();
+    }
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}pt\" height=\"{h}pt\" viewBox=\"0 0 {w} {h}\">\n",
+                          w = number(page_box.width()), h = number(page_box.height()));
+    if !clips.definitions.is_empty() {
+        svg.push_str("<defs>\n");
+        svg.push_str(&clips.definitions);
+        svg.push_str("</defs>\n");
+    };
+    writeln!(svg, "<g transform=\"matrix(1 0 0 -1 {} {})\">", number(-page_box.x0), number(page_box.y1)).unwrap();
+    svg.push_str(&body);
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// Assigns ids to clipping paths as they're used, writing a clipPath definition for each.
+#[derive(Default)]
+struct ClipIds {
+    ids: HashMap<*const ClipPath, usize>,
+    definitions: String,
+}
+
+impl ClipIds {
+    fn attribute(&mut self, clip: &Option<Rc<ClipPath>>) -> String {
+        match clip {
+            None => String::new(),
+            Some(clip) => format!(" clip-path=\"url(#clip{})\"", self.id(clip)),
+        }
+    }
+
+    fn id(&mut self, clip: &Rc<ClipPath>) -> usize {
+        if let Some(id) = self.ids.get(&Rc::as_ptr(clip)) {
+            return *id;
+        };
+        // Intersections are expressed by clipping the clipPath itself
+        let parent = self.attribute(&clip.parent);
+        let id = self.ids.len();
+        self.ids.insert(Rc::as_ptr(clip), id);
+        let rule = if clip.even_odd { " clip-rule=\"evenodd\"" } else { "" };
+        writeln!(self.definitions, "<clipPath id=\"clip{}\"{}><path d=\"{}\"{}/></clipPath>",
+                 id, parent, path_data(&clip.segments), rule).unwrap();
+        id
+    }
+}
+
+fn path_data(segments: &[PathSegment]) -> String {
+    let parts: Vec<String> = segments.iter().map(|segment| match *segment {
+        PathSegment::MoveTo(x, y) => format!("M{} {}", number(x), number(y)),
+        PathSegment::LineTo(x, y) => format!("L{} {}", number(x), number(y)),
+        PathSegment::CurveTo(x1, y1, x2, y2, x3, y3) => format!(
+            "C{} {} {} {} {} {}", number(x1), number(y1), number(x2), number(y2), number(x3), number(y3)),
+        PathSegment::Close => "Z".to_string(),
+    }).collect();
+    parts.join(" ")
+}
+
+/// Format a coordinate with at most three decimal places and no trailing zeros.
+fn number(value: f32) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svg() {
+        let mut interpreter = Interpreter::new(None);
+        let content = b"q 0 0 50 50 re W n 0.5 w 1 1 m 10 1 l 10 10 20 20 30 10 c S Q 5 5 10 10 re f*";
+        interpreter.run(&CommandStream::parse(content).unwrap()).unwrap();
+        let text = vec![TextBlock { text: "a<b".to_string(), x: 10.0, y: 20.0, end_x: 10.0, end_y: 40.0, font_size: 12.0,
+                                    clip: interpreter.paths[0].clip.clone(), ..Default::default() }];
+        let svg = to_svg(&interpreter.paths, &text, &Rect::new(0.0, 0.0, 100.0, 200.0));
+        assert_eq!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100pt\" height=\"200pt\" viewBox=\"0 0 100 200\">\n\
+                         <defs>\n\
+                         <clipPath id=\"clip0\"><path d=\"M0 0 L50 0 L50 50 L0 50 Z\"/></clipPath>\n\
+                         </defs>\n\
+                         <g transform=\"matrix(1 0 0 -1 0 200)\">\n\
+                         <path d=\"M1 1 L10 1 C10 10 20 20 30 10\" fill=\"none\" stroke=\"black\" stroke-width=\"0.5\" clip-path=\"url(#clip0)\"/>\n\
+                         <path d=\"M5 5 L15 5 L15 15 L5 15 Z\" fill=\"black\" fill-rule=\"evenodd\"/>\n\
+                         <text transform=\"translate(10 20) rotate(90) scale(1 -1)\" font-size=\"12\" textLength=\"20\" \
+                         lengthAdjust=\"spacingAndGlyphs\" clip-path=\"url(#clip0)\">a&lt;b</text>\n\
+                         </g>\n</svg>\n");
+    }
+
+    #[test]
+    fn test_nested_clips() {
+        let mut interpreter = Interpreter::new(None);
+        interpreter.run(&CommandStream::parse(b"0 0 10 10 re W n 5 5 10 10 re W* n 0 0 m 1 1 l S").unwrap()).unwrap();
+        let svg = to_svg(&interpreter.paths, &[], &Rect::new(0.0, 0.0, 10.0, 10.0));
+        assert!(svg.contains("<clipPath id=\"clip0\"><path d=\"M0 0 L10 0 L10 10 L0 10 Z\"/></clipPath>"));
+        assert!(svg.contains("<clipPath id=\"clip1\" clip-path=\"url(#clip0)\"><path d=\"M5 5 L15 5 L15 15 L5 15 Z\" \
+                              clip-rule=\"evenodd\"/></clipPath>"));
+        assert!(svg.contains("clip-path=\"url(#clip1)\"/>"));
+    }
+}
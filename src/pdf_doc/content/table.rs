@@ -0,0 +1,311 @@
+use std::fmt::Write;
+
+use super::*;
+
+/// Positions within this distance are treated as the same ruling or cell edge.
+const SNAP_TOLERANCE: f32 = 2.0;
+/// Path edges shorter than this can't be table rulings; this also skips the ends of thin filled rectangles.
+const MIN_RULING_LENGTH: f32 = 5.0;
+/// Gaps between words wider than this many ems separate the cells of an unruled table.
+const CELL_GAP: f32 = 1.0;
+/// Unruled tables need at least this many rows, so that a label and value on one line isn't a table.
+const MIN_TABLE_ROWS: usize = 3;
+/// Rows of an unruled table are at most this many ems apart.
+const MAX_ROW_SPACING: f32 = 3.0;
+
+/// A table found on a page, as rows of cell text, top to bottom and left to right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub rows: Vec<Vec<String>>,
+    /// Whether the table was found from ruling lines, rather than from aligned text
+    pub ruled: bool,
+}
+
+impl Table {
+    /// The table as comma-separated values (RFC 4180), quoting cells where needed.
+    pub fn to_csv(&self) -> String {
+        let mut output = String::new();
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(|cell| {
+                if cell.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.clone()
+                }
+            }).collect();
+            output.push_str(&cells.join(","));
+            output.push('\n');
+        }
+        output
+    }
+
+    /// The table as a JSON array of rows, each an array of cell strings.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self.rows.iter().map(|row| {
+            let cells: Vec<String> = row.iter().map(|cell| json_string(cell)).collect();
+            format!("[{}]", cells.join(","))
+        }).collect();
+        format!("[{}]", rows.join(","))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut output = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(output, "\\u{:04x}", c as u32).unwrap(),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// A horizontal or vertical line segment that may bound table cells.
+#[derive(Debug, Clone, Copy)]
+struct Ruling {
+    horizontal: bool,
+    /// y for horizontal rulings, x for vertical ones
+    position: f32,
+    start: f32,
+    end: f32,
+}
+
+impl Ruling {
+    fn crosses(&self, other: &Ruling) -> bool {
+        if self.horizontal == other.horizontal {
+            return false;
+        };
+        other.position >= self.start - SNAP_TOLERANCE && other.position <= self.end + SNAP_TOLERANCE
+            && self.position >= other.start - SNAP_TOLERANCE && self.position <= other.end + SNAP_TOLERANCE
+    }
+}
+
+/// Find tables on a page: grids of ruling lines from the painted paths, then runs of lines whose text falls
+/// into the same aligned columns.  Text inside a ruled table isn't considered for unruled ones.
+pub fn find_tables(blocks: &[TextBlock], paths: &[Path]) -> Vec<Table> {
+    let mut words: Vec<Word> = group_into_lines(blocks).into_iter().flat_map(|line| line.words).collect();
+    let mut tables = Vec::new();
+    for grid in ruling_grids(&rulings_from_paths(paths)) {
+        let (xs, ys) = grid;
+        let (inside, outside): (Vec<Word>, Vec<Word>) = words.into_iter().partition(|word| {
+            let center = (word.x + word.end_x) / 2.0;
+            center > xs[0] && center < xs[xs.len() - 1] && word.y < ys[0] && word.y > ys[ys.len() - 1]
+        });
+        words = outside;
+        let mut rows = vec![vec![String::new(); xs.len() - 1]; ys.len() - 1];
+        // Words are already in reading order
+        for word in inside {
+            let center = (word.x + word.end_x) / 2.0;
+            let column = xs.iter().filter(|x| **x < center).count() - 1;
+            let row = ys.iter().filter(|y| **y > word.y).count() - 1;
+            let cell = &mut rows[row][column];
+            if !cell.is_empty() { cell.push(' ') };
+            cell.push_str(&word.text);
+        }
+        tables.push(Table { rows, ruled: true });
+    }
+    tables.extend(aligned_text_tables(words));
+    tables
+}
+
+fn rulings_from_paths(paths: &[Path]) -> Vec<Ruling> {
+    let mut rulings = Vec::new();
+    for path in paths {
+        let mut current = (0.0, 0.0);
+        let mut subpath_start = (0.0, 0.0);
+        for segment in &path.segments {
+            let (from, to) = match *segment {
+                PathSegment::MoveTo(x, y) => {
+                    current = (x, y);
+                    subpath_start = current;
+                    continue;
+                },
+                PathSegment::LineTo(x, y) => (current, (x, y)),
+                PathSegment::Close => (current, subpath_start),
+                PathSegment::CurveTo(_, _, _, _, x, y) => {
+                    current = (x, y);
+                    continue;
+                },
+            };
+            current = to;
+            let (dx, dy) = ((to.0 - from.0).abs(), (to.1 - from.1).abs());
+            if dy <= SNAP_TOLERANCE && dx >= MIN_RULING_LENGTH {
+                rulings.push(Ruling { horizontal: true, position: (from.1 + to.1) / 2.0,
+                                      start: from.0.min(to.0), end: from.0.max(to.0) });
+            } else if dx <= SNAP_TOLERANCE && dy >= MIN_RULING_LENGTH {
+                rulings.push(Ruling { horizontal: false, position: (from.0 + to.0) / 2.0,
+                                      start: from.1.min(to.1), end: from.1.max(to.1) });
+            };
+        }
+    }
+    rulings
+}
+
+/// Group rulings into connected grids, returning each grid's cell edges: x positions left to right, and y
+/// positions top to bottom.  Grids need at least two rulings in each direction.
+fn ruling_grids(rulings: &[Ruling]) -> Vec<(Vec<f32>, Vec<f32>)> {
+    // Union-find over crossing rulings
+    let mut parents: Vec<usize> = (0..rulings.len()).collect();
+    fn root(parents: &mut [usize], mut index: usize) -> usize {
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+    for i in 0..rulings.len() {
+        for j in i + 1..rulings.len() {
+            if rulings[i].crosses(&rulings[j]) {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+            };
+        }
+    }
+    let mut components: Vec<(usize, Vec<Ruling>)> = Vec::new();
+    for (index, ruling) in rulings.iter().enumerate() {
+        let component = root(&mut parents, index);
+        match components.iter_mut().find(|(root, _)| *root == component) {
+            Some((_, members)) => members.push(*ruling),
+            None => components.push((component, vec![*ruling])),
+        }
+    }
+
+    let mut grids: Vec<(Vec<f32>, Vec<f32>)> = components.into_iter().filter_map(|(_, members)| {
+        let xs = snap(members.iter().filter(|r| !r.horizontal).map(|r| r.position).collect());
+        let mut ys = snap(members.iter().filter(|r| r.horizontal).map(|r| r.position).collect());
+        ys.reverse();
+        if xs.len() >= 2 && ys.len() >= 2 { Some((xs, ys)) } else { None }
+    }).collect();
+    grids.sort_by(|a, b| b.1[0].partial_cmp(&a.1[0]).unwrap());
+    grids
+}
+
+/// Sort positions and merge those within SNAP_TOLERANCE of each other.
+fn snap(mut positions: Vec<f32>) -> Vec<f32> {
+    positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut snapped: Vec<f32> = Vec::new();
+    for position in positions {
+        match snapped.last() {
+            Some(last) if position - last <= SNAP_TOLERANCE => {},
+            _ => snapped.push(position),
+        }
+    }
+    snapped
+}
+
+/// Find runs of consecutive lines that split into the same number of cells, with each column of cells
+/// aligned on its left edge, right edge or center.
+fn aligned_text_tables(words: Vec<Word>) -> Vec<Table> {
+    let blocks: Vec<TextBlock> = words.into_iter().map(|word| TextBlock {
+        text: word.text, font: String::new(), x: word.x, y: word.y, end_x: word.end_x, end_y: word.y,
+        font_size: word.font_size, ..Default::default()
+    }).collect();
+    let rows: Vec<(f32, f32, Vec<Line>)> = group_into_lines(&blocks).into_iter().map(|line| {
+        let font_size = line.words.iter().map(|word| word.font_size).fold(0.0, f32::max);
+        let mut cells: Vec<Line> = Vec::new();
+        for word in line.words {
+            match cells.last_mut() {
+                Some(cell) if word.x - cell.end_x() <= CELL_GAP * font_size => cell.words.push(word),
+                _ => cells.push(Line { words: vec![word], y: line.y, rtl: line.rtl }),
+            }
+        }
+        (line.y, font_size, cells)
+    }).collect();
+
+    let aligned = |a: &Line, b: &Line| {
+        (a.x() - b.x()).abs() <= SNAP_TOLERANCE
+            || (a.end_x() - b.end_x()).abs() <= SNAP_TOLERANCE
+            || ((a.x() + a.end_x()) - (b.x() + b.end_x())).abs() / 2.0 <= SNAP_TOLERANCE
+    };
+    let mut tables = Vec::new();
+    let mut start = 0;
+    while start < rows.len() {
+        let (_, _, first) = &rows[start];
+        let mut end = start + 1;
+        while first.len() >= 2 && end < rows.len() {
+            let (previous_y, font_size, _) = &rows[end - 1];
+            let (y, _, cells) = &rows[end];
+            if cells.len() != first.len() || previous_y - y > MAX_ROW_SPACING * font_size
+                || !first.iter().zip(cells).all(|(a, b)| aligned(a, b)) {
+                break;
+            };
+            end += 1;
+        }
+        if end - start >= MIN_TABLE_ROWS {
+            let table_rows = rows[start..end].iter()
+                                             .map(|(_, _, cells)| cells.iter().map(|cell| cell.text()).collect())
+                                             .collect();
+            tables.push(Table { rows: table_rows, ruled: false });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(text: &str, x: f32, y: f32) -> TextBlock {
+        let end_x = x + 5.0 * text.chars().count() as f32;
+        TextBlock { text: text.to_string(), font: "F1".to_string(), x, y, end_x, end_y: y, font_size: 10.0,
+                    ..Default::default() }
+    }
+
+    fn line(x1: f32, y1: f32, x2: f32, y2: f32) -> Path {
+        Path { segments: vec![PathSegment::MoveTo(x1, y1), PathSegment::LineTo(x2, y2)],
+               stroke: true, line_width: 1.0, ..Default::default() }
+    }
+
+    #[test]
+    fn test_ruled_table() {
+        // A 2x2 grid from stroked lines, except that one ruling is a thin filled rectangle
+        let mut paths = vec![line(100.0, 700.0, 300.0, 700.0), line(100.0, 680.0, 300.0, 680.0),
+                             line(100.0, 700.0, 100.0, 660.0), line(200.0, 700.0, 200.0, 660.0),
+                             line(300.0, 700.0, 300.0, 660.0)];
+        paths.push(Path {
+            segments: vec![PathSegment::MoveTo(100.0, 659.5), PathSegment::LineTo(300.0, 659.5),
+                           PathSegment::LineTo(300.0, 660.5), PathSegment::LineTo(100.0, 660.5), PathSegment::Close],
+            fill: true, line_width: 1.0, ..Default::default() });
+        let blocks = vec![block("Name", 105.0, 685.0), block("Price", 205.0, 685.0),
+                          block("Tea,", 105.0, 665.0), block("green", 130.0, 665.0), block("\"3\"", 205.0, 665.0),
+                          block("Outside", 105.0, 600.0)];
+        let tables = find_tables(&blocks, &paths);
+        assert_eq!(tables.len(), 1);
+        assert!(tables[0].ruled);
+        assert_eq!(tables[0].rows, vec![vec!["Name", "Price"], vec!["Tea, green", "\"3\""]]);
+        assert_eq!(tables[0].to_csv(), "Name,Price\n\"Tea, green\",\"\"\"3\"\"\"\n");
+        assert_eq!(tables[0].to_json(), r#"[["Name","Price"],["Tea, green","\"3\""]]"#);
+    }
+
+    #[test]
+    fn test_aligned_text_table() {
+        let mut blocks = vec![block("A paragraph of text before the table", 72.0, 720.0)];
+        for (row, (item, amount)) in [("Item", "Amount"), ("Apples", "12"), ("Pears", "7"), ("Plums", "130")].iter().enumerate() {
+            let y = 700.0 - 12.0 * row as f32;
+            blocks.push(block(item, 72.0, y));
+            // Right-aligned numbers
+            blocks.push(block(amount, 230.0 - 5.0 * amount.len() as f32, y));
+        }
+        let tables = find_tables(&blocks, &[]);
+        assert_eq!(tables.len(), 1);
+        assert!(!tables[0].ruled);
+        assert_eq!(tables[0].rows[0], vec!["Item", "Amount"]);
+        assert_eq!(tables[0].rows[3], vec!["Plums", "130"]);
+    }
+
+    #[test]
+    fn test_no_table() {
+        let blocks = vec![block("Name:", 72.0, 700.0), block("Ada", 150.0, 700.0),
+                          block("Just a line", 72.0, 688.0)];
+        assert!(find_tables(&blocks, &[line(72.0, 650.0, 300.0, 650.0)]).is_empty());
+    }
+}
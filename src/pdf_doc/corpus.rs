@@ -0,0 +1,73 @@
+//! Golden-output harness over the PDFs in data/corpus.  Each file is summarized (version, objects, page
+//! content) and compared with the .golden file next to it.  Run with UPDATE_GOLDEN=1 to regenerate the golden
+//! files after an intended change in output, and review the diff before committing.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::*;
+
+const CORPUS_DIR: &str = "data/corpus";
+
+fn summarize(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    let file = PdfFileHandler::create_pdf_from_bytes(bytes.clone())?;
+    let mut summary = String::new();
+    writeln!(summary, "version: {}", file.version)?;
+    writeln!(summary, "objects:")?;
+    for id in file.object_map.object_ids() {
+        let object = file.get_object(id)?;
+        let type_name = match file.get_dict(id) {
+            Ok(dict) => match dict.get("Type") {
+                Some(name) => format!(" /{}", name.try_into_string()?),
+                None => String::new()
+            },
+            Err(_) => String::new()
+        };
+        writeln!(summary, "  {} {} {:?}{}", id.number(), id.generation(), object.get_pdf_primitive_type()?, type_name)?;
+    }
+    let mut reachable = 0;
+    file.walk(|_, _| reachable += 1)?;
+    writeln!(summary, "reachable: {}", reachable)?;
+
+    let doc = PdfDoc::create_pdf_from_bytes(bytes)?;
+    writeln!(summary, "pages: {}", doc.page_count())?;
+    for page_index in 0..doc.page_count() {
+        writeln!(summary, "page {}:", page_index)?;
+        for line in doc.normalized_page_content(page_index)?.lines() {
+            writeln!(summary, "  {}", line)?;
+        }
+    }
+    Ok(summary)
+}
+
+#[test]
+fn corpus_matches_golden() {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut paths: Vec<_> = fs::read_dir(CORPUS_DIR).unwrap()
+                                                    .map(|entry| entry.unwrap().path())
+                                                    .filter(|path| path.extension().is_some_and(|ext| ext == "pdf"))
+                                                    .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "No PDFs found in {}", CORPUS_DIR);
+    let mut failures = Vec::new();
+    for path in paths {
+        let summary = match summarize(&path) {
+            Ok(summary) => summary,
+            Err(e) => format!("error: {}\n", e)
+        };
+        let golden_path = path.with_extension("golden");
+        if update {
+            fs::write(&golden_path, &summary).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden_path) {
+            Ok(golden) if golden == summary => {},
+            Ok(golden) => failures.push(format!("{}: expected\n{}\nbut got\n{}", path.display(), golden, summary)),
+            Err(_) => failures.push(format!("{}: missing {}", path.display(), golden_path.display()))
+        }
+    }
+    assert!(failures.is_empty(), "Corpus mismatches (set UPDATE_GOLDEN=1 to regenerate):\n{}", failures.join("\n"));
+}
@@ -0,0 +1,180 @@
+use super::*;
+
+/// How two documents differ, from PdfDoc::diff.  Each list is in page, key or object order, so comparing the
+/// same two files always gives the same report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentDiff {
+    /// The page counts of the two documents, if they differ
+    pub page_count: Option<(usize, usize)>,
+    /// Pages in both documents whose text differs
+    pub pages: Vec<PageDiff>,
+    /// Document information entries that differ, or are only in one document
+    pub metadata: Vec<MetadataDiff>,
+    pub objects: Vec<ObjectDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageDiff {
+    pub index: usize,
+    pub left: String,
+    pub right: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataDiff {
+    pub key: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectChange {
+    /// Only in the second document
+    Added,
+    /// Only in the first document
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectDiff {
+    pub id: ObjectId,
+    pub change: ObjectChange,
+}
+
+impl DocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.page_count.is_none() && self.pages.is_empty() && self.metadata.is_empty() && self.objects.is_empty()
+    }
+}
+
+/// One line per difference, for test failure messages and for tools that compare reports as text.
+impl fmt::Display for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((left, right)) = self.page_count {
+            writeln!(f, "page count: {} -> {}", left, right)?;
+        };
+        for page in &self.pages {
+            writeln!(f, "page {} text: {:?} -> {:?}", page.index, page.left, page.right)?;
+        }
+        for entry in &self.metadata {
+            writeln!(f, "metadata /{}: {:?} -> {:?}", entry.key, entry.left, entry.right)?;
+        }
+        for object in &self.objects {
+            writeln!(f, "object {} {}: {:?}", object.id.number(), object.id.generation(), object.change)?;
+        }
+        Ok(())
+    }
+}
+
+impl PdfDoc {
+    /// Compare this document with other: page counts, the text of each page, the document information
+    /// entries, and each object by id.  Objects are compared as the writer would serialize them, with streams
+    /// decoded and dictionary keys sorted, so differences in compression or key order don't count.  A document with
+    /// its objects renumbered differs in every renumbered object.
+    pub fn diff(&self, other: &PdfDoc) -> Result<DocumentDiff> {
+        let mut diff = DocumentDiff::default();
+        if self.page_count() != other.page_count() {
+            diff.page_count = Some((self.page_count(), other.page_count()));
+        };
+        for index in 0..self.page_count().min(other.page_count()) {
+            let (left, right) = (self.page_text(index)?, other.page_text(index)?);
+            if left != right {
+                diff.pages.push(PageDiff { index, left, right });
+            };
+        }
+
+        let (mut left, mut right) = (self.info()?, other.info()?);
+        let keys: BTreeSet<String> = left.keys().chain(right.keys()).cloned().collect();
+        for key in keys {
+            let (left, right) = (left.remove(&key), right.remove(&key));
+            if left != right {
+                diff.metadata.push(MetadataDiff { key, left, right });
+            };
+        }
+
+        let ids: BTreeSet<ObjectId> = self.file.object_map.object_ids().into_iter()
+                                          .chain(other.file.object_map.object_ids())
+                                          .collect();
+        for id in ids {
+            let change = match (self.serialized_object(id)?, other.serialized_object(id)?) {
+                (Some(left), Some(right)) if left == right => continue,
+                (Some(_), Some(_)) => ObjectChange::Changed,
+                (Some(_), None) => ObjectChange::Removed,
+                (None, _) => ObjectChange::Added,
+            };
+            diff.objects.push(ObjectDiff { id, change });
+        }
+        Ok(diff)
+    }
+
+    /// The object with the given id as the writer would write it, or None if there is no such object.
+    fn serialized_object(&self, id: ObjectId) -> Result<Option<Vec<u8>>> {
+        if !self.file.object_map.contains(id) {
+            return Ok(None);
+        };
+        let mut output = Vec::new();
+        let options = WriteOptions { sort_keys: true, ..Default::default() };
+        write_object(&mut output, self.get_object(id)?.as_ref(), &None, &options)?;
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn build(text: &str, title: &str, extra_page: bool) -> PdfDoc {
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+        let info = builder.add_object(format!("<< /Title ({}) /Producer (test) >>", title));
+        let mut builder = builder.with_trailer_entries(&format!("/Info {} 0 R", info));
+        let resources = format!("/Resources << /Font << /F1 {} 0 R >> >>", font);
+        let content = format!("BT /F1 12 Tf ({}) Tj ET", text);
+        builder.add_page_with_entries(content.as_bytes(), TestFilter::Flate, &resources);
+        if extra_page {
+            builder.add_page_with_entries(b"", TestFilter::None, &resources);
+        };
+        PdfDoc::create_pdf_from_bytes(builder.build()).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let doc = build("Hello", "Report", false);
+        assert!(doc.diff(&build("Hello", "Report", false)).unwrap().is_empty());
+
+        let diff = doc.diff(&build("Goodbye", "Summary", true)).unwrap();
+        assert_eq!(diff.page_count, Some((1, 2)));
+        assert_eq!(diff.pages, vec![PageDiff {
+            index: 0,
+            left: "Hello\n".to_string(),
+            right: "Goodbye\n".to_string(),
+        }]);
+        assert_eq!(diff.metadata, vec![MetadataDiff {
+            key: "Title".to_string(),
+            left: Some("Report".to_string()),
+            right: Some("Summary".to_string()),
+        }]);
+        // The info dictionary, the first page's content and the page tree change; the second page is added
+        let changes: Vec<ObjectChange> = diff.objects.iter().map(|object| object.change).collect();
+        assert_eq!(changes.iter().filter(|change| **change == ObjectChange::Changed).count(), 3);
+        assert!(changes.contains(&ObjectChange::Added));
+        assert!(!changes.contains(&ObjectChange::Removed));
+        assert!(diff.to_string().starts_with("page count: 1 -> 2\npage 0 text: \"Hello\\n\" -> \"Goodbye\\n\"\n"));
+
+        let reverse = build("Goodbye", "Summary", true).diff(&doc).unwrap();
+        assert_eq!(reverse.page_count, Some((2, 1)));
+        assert!(reverse.objects.iter().any(|object| object.change == ObjectChange::Removed));
+
+        // Key order doesn't count
+        let font = |entries: &str| {
+            let mut builder = MiniPdfBuilder::new();
+            builder.add_object(format!("<< {} >>", entries));
+            PdfDoc::create_pdf_from_bytes(builder.build()).unwrap()
+        };
+        let (left, right) = (font("/Type /Font /Subtype /Type1"), font("/Subtype /Type1 /Type /Font"));
+        assert!(left.diff(&right).unwrap().is_empty());
+        assert!(!left.diff(&font("/Type /Font /Subtype /TrueType")).unwrap().is_empty());
+    }
+}
@@ -2,16 +2,62 @@
 mod pdf_file;
 #[path = "pdf_objects/pdf_objects.rs"]
 mod pdf_objects;
+#[path = "action.rs"]
+mod action;
+#[path = "annotation.rs"]
+mod annotation;
+#[path = "builder.rs"]
+mod builder;
+#[path = "content/content.rs"]
+mod content;
+#[cfg(all(test, feature = "fs"))]
+#[path = "corpus.rs"]
+mod corpus;
+#[path = "diff.rs"]
+mod diff;
+#[path = "form.rs"]
+mod form;
+#[path = "impose.rs"]
+mod impose;
+#[path = "language.rs"]
+mod language;
+#[path = "metadata.rs"]
+mod metadata;
+#[path = "optimize.rs"]
+mod optimize;
+#[path = "outline.rs"]
+mod outline;
+#[path = "page.rs"]
+mod page;
+#[path = "page_cache.rs"]
+mod page_cache;
+#[path = "security.rs"]
+mod security;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt;
 use std::rc::Rc;
+use std::time::Duration;
 
 use crate::errors::*;
+use regex::Regex;
 use vec_tree::VecTree;
 
 pub use pdf_file::*;
 use pdf_objects::*;
+pub use action::*;
+pub use annotation::*;
+pub use builder::*;
+pub use content::*;
+pub use diff::*;
+pub use form::*;
+pub use language::*;
+pub use outline::*;
+pub use page::*;
+pub use page_cache::DEFAULT_PAGE_CACHE_BUDGET;
+use page_cache::*;
+pub use security::*;
 
 type TreeIndex = vec_tree::Index;
 struct DocTree {}
@@ -21,15 +67,43 @@ pub struct PdfDoc {
     file: PdfFileHandler,
     page_tree: PageTree,
     root: SharedObject,
+    page_cache: RefCell<PageCache<CachedPage>>,
+}
+
+/// How opening a document went, for logging diagnostics about each file processed.  Returned by
+/// PdfDoc::create_pdf_from_bytes_with_report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenReport {
+    /// The version in the file header
+    pub version: PDFVersion,
+    /// Finding and reading the cross-reference tables
+    pub xref_time: Duration,
+    /// Parsing the trailer dictionaries, one per revision
+    pub trailer_time: Duration,
+    /// Reading the catalog and the page tree
+    pub page_tree_time: Duration,
+    /// Problems worked around by lenient parsing while opening, by kind; see ObjectCache::repairs
+    pub repairs: BTreeMap<&'static str, usize>,
+    /// Whether the trailer has an /Encrypt entry
+    pub encrypted: bool,
+}
+
+impl OpenReport {
+    /// The number of problems worked around, of all kinds.
+    pub fn repair_count(&self) -> usize {
+        self.repairs.values().sum()
+    }
 }
 
 // ----------Node-------------
 
 #[derive(Debug, Clone)]
 struct Node {
+    /// None for a node written directly into its parent
+    id: Option<ObjectId>,
     node_type: NodeType,
     contents: Option<SharedObject>,
-    attributes: HashMap<String, SharedObject>,
+    attributes: PdfMap,
 }
 
 
@@ -80,6 +154,7 @@ impl PageTree {
                                 ))??;
         let kids = node_map.get("Kids");
         let new_node = Node{
+            id: new_node.reference_id(),
             contents: node_map.get("Contents").map(|rc_ref| Rc::clone(rc_ref)),
             node_type,
             attributes: node_map.as_ref().clone()
@@ -113,6 +188,18 @@ impl PageTree {
         }
     }
 
+    /// Page nodes in document order.
+    fn pages(&self) -> Vec<&Node> {
+        let root = match self.tree.get_root_index() {
+            None => return Vec::new(),
+            Some(root) => root
+        };
+        self.tree.descendants(root)
+                 .map(|index| &self.tree[index])
+                 .filter(|node| matches!(node.node_type, NodeType::Page))
+                 .collect()
+    }
+
     fn _get_node_type(name: &PdfObject) -> Result<NodeType> {
         use NodeType::*;
         match &name.try_into_string()?[..] {
@@ -142,19 +229,637 @@ impl fmt::Display for PageTree {
 }
 
 impl PdfDoc {
-    pub fn create_pdf_from_file(path: &str) -> Result<Self> {
-        let file = PdfFileHandler::create_pdf_from_file(path)?;
+    #[cfg(feature = "fs")]
+    pub fn create_pdf_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        PdfDoc::from_file_handler(PdfFileHandler::create_pdf_from_file(path)?)
+    }
+
+    pub fn create_pdf_from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        PdfDoc::from_file_handler(PdfFileHandler::create_pdf_from_bytes(bytes)?)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn create_pdf_from_file_with_options<P: AsRef<std::path::Path>>(path: P, options: ParserOptions)
+                                                                         -> Result<Self> {
+        PdfDoc::from_file_handler(PdfFileHandler::create_pdf_from_file_with_options(path, options)?)
+    }
+
+    pub fn create_pdf_from_bytes_with_options(bytes: Vec<u8>, options: ParserOptions) -> Result<Self> {
+        PdfDoc::from_file_handler(PdfFileHandler::create_pdf_from_bytes_with_options(bytes, options)?)
+    }
+
+    /// As create_pdf_from_file_with_options, along with a report on how opening the file went.
+    #[cfg(feature = "fs")]
+    pub fn create_pdf_from_file_with_report<P: AsRef<std::path::Path>>(path: P, options: ParserOptions)
+                                                                        -> Result<(Self, OpenReport)> {
+        PdfDoc::from_file_handler_with_report(PdfFileHandler::create_pdf_from_file_with_options(path, options)?)
+    }
+
+    /// As create_pdf_from_bytes_with_options, along with a report on how opening the file went.
+    pub fn create_pdf_from_bytes_with_report(bytes: Vec<u8>, options: ParserOptions) -> Result<(Self, OpenReport)> {
+        PdfDoc::from_file_handler_with_report(PdfFileHandler::create_pdf_from_bytes_with_options(bytes, options)?)
+    }
+
+    fn from_file_handler(file: PdfFileHandler) -> Result<Self> {
+        Ok(PdfDoc::from_file_handler_with_report(file)?.0)
+    }
+
+    fn from_file_handler_with_report(file: PdfFileHandler) -> Result<(Self, OpenReport)> {
+        let start = Timer::start();
         let trailer_dict = file.retrieve_trailer()?
                                .try_into_map()
                                .unwrap();
         let root = trailer_dict.get("Root").ok_or(ErrorKind::ParsingError("Root not present in trailer!".to_string()))?;
         let pdf = PdfDoc {
-            file: file,
             page_tree: PageTree::new(&root)?,
             root: Rc::clone(root),
+            page_cache: RefCell::new(PageCache::new(DEFAULT_PAGE_CACHE_BUDGET)),
+            file,
+        };
+        let load_times = pdf.file.load_times();
+        let report = OpenReport {
+            version: pdf.file.version,
+            xref_time: load_times.xref,
+            trailer_time: load_times.trailer,
+            page_tree_time: start.elapsed(),
+            repairs: pdf.file.object_map.repairs(),
+            encrypted: trailer_dict.contains_key("Encrypt"),
+        };
+        Ok((pdf, report))
+    }
+
+    pub fn get_object(&self, id: ObjectId) -> Result<SharedObject> {
+        self.file.get_object(id)
+    }
+
+    /// The text entries of the document information dictionary (spec 14.3.3), such as Title, Author and
+    /// CreationDate, which is empty if the trailer has no /Info.  Entries that aren't strings are left out.
+    pub fn info(&self) -> Result<BTreeMap<String, String>> {
+        let trailer = self.file.retrieve_trailer()?.try_into_map()?;
+        let info = match trailer.get("Info") {
+            Some(info) => info.try_into_map()?,
+            None => return Ok(BTreeMap::new())
+        };
+        Ok(info.iter()
+               .filter_map(|(key, value)| value.try_into_text().ok().map(|text| (key.clone(), text)))
+               .collect())
+    }
+
+    /// Memory held by the file buffer, decoded streams and parsed objects; see ObjectCache::memory_report.
+    pub fn memory_report(&self) -> MemoryReport {
+        self.file.object_map.memory_report()
+    }
+
+    /// The file's revisions, oldest first: the original file and then each incremental update.
+    pub fn revisions(&self) -> &[Revision] {
+        self.file.revisions()
+    }
+
+    /// The document as it was when the given revision was saved, such as the version a signature covers.
+    pub fn at_revision(&self, revision: usize) -> Result<PdfDoc> {
+        PdfDoc::from_file_handler(self.file.at_revision(revision)?)
+    }
+
+    pub fn get_dict(&self, id: ObjectId) -> Result<Rc<PdfMap>> {
+        self.file.get_dict(id)
+    }
+
+    pub fn get_array(&self, id: ObjectId) -> Result<Rc<PdfArray>> {
+        self.file.get_array(id)
+    }
+
+    pub fn get_int(&self, id: ObjectId) -> Result<i32> {
+        self.file.get_int(id)
+    }
+
+    pub fn get_number(&self, id: ObjectId) -> Result<f32> {
+        self.file.get_number(id)
+    }
+
+    pub fn get_string(&self, id: ObjectId) -> Result<String> {
+        self.file.get_string(id)
+    }
+
+    pub fn get_stream_data(&self, id: ObjectId) -> Result<Vec<u8>> {
+        self.file.get_stream_data(id)
+    }
+
+    /// Visit every indirect object reachable from the trailer exactly once.  See PdfFileHandler::walk.
+    pub fn walk<F>(&self, visitor: F) -> Result<()>
+    where
+        F: FnMut(ObjectId, &SharedObject),
+    {
+        self.file.walk(visitor)
+    }
+
+    /// Every font in the document with an embedded font program, in the order the fonts are reached from the
+    /// trailer.  CIDFonts are reported through the Type0 fonts that use them, and fonts written as direct
+    /// dictionaries through the indirect object holding them.  Fonts that can't be read are logged and skipped.
+    pub fn embedded_fonts(&self) -> Result<Vec<EmbeddedFont>> {
+        let mut font_dicts = Vec::new();
+        self.walk(|id, obj| collect_font_dicts(id, obj, &mut font_dicts))?;
+        let mut fonts = Vec::new();
+        for (id, dict) in font_dicts {
+            let embedded = Font::from_dict(&dict).and_then(|font| {
+                Ok(font.embedded_program()?.map(|(format, program)| EmbeddedFont {
+                    id,
+                    base_font: font.base_font.clone(),
+                    subset_tag: font.subset_tag().map(|tag| tag.to_string()),
+                    format,
+                    program: program.to_vec(),
+                }))
+            });
+            match embedded {
+                Ok(Some(font)) => fonts.push(font),
+                Ok(None) => {},
+                Err(e) => warn!("Skipping unreadable font in object {}: {}", id, e)
+            };
+        }
+        Ok(fonts)
+    }
+
+    /// Serialize the document, with any edits, to bytes.  With WriteOptions::remove_unused_resources, resources
+    /// no page uses are left out of the written page tree, though the document itself keeps them.  Pages whose
+    /// boxes don't nest, as PdfDoc::page_box_warnings finds, are logged and listed in WriteReport::warnings.
+    pub fn write(&self, options: &WriteOptions) -> Result<(Vec<u8>, WriteReport)> {
+        let (bytes, mut report) = if options.remove_unused_resources {
+            let (replacements, removed) = self.unused_resource_edits()?;
+            let options = WriteOptions { garbage_collect: true, ..options.clone() };
+            let (bytes, mut report) = self.file.write_with_replacements(&options, &replacements)?;
+            report.resources_removed = removed;
+            (bytes, report)
+        } else {
+            self.file.write(options)?
+        };
+        // Pages whose boxes can't be read are written as they are, but reported too
+        for page_index in 0..self.page_count() {
+            match self.page_box_warnings(page_index) {
+                Ok(warnings) => report.warnings.extend(warnings),
+                Err(error) => report.warnings.push(error.to_string())
+            };
+        }
+        for warning in &report.warnings {
+            warn!("{}", warning);
+        }
+        Ok((bytes, report))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<std::path::Path>>(&self, path: P, options: &WriteOptions) -> Result<WriteReport> {
+        let (bytes, report) = self.write(options)?;
+        std::fs::write(path, bytes)?;
+        Ok(report)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.page_tree.pages().len()
+    }
+
+    /// The index of the page with the given object id.
+    pub fn page_index_of(&self, id: ObjectId) -> Option<usize> {
+        self.page_tree.pages().iter().position(|page| page.id == Some(id))
+    }
+
+    /// The page at page_index, counting from 0.
+    pub fn page(&self, page_index: usize) -> Result<Page<'_>> {
+        if page_index >= self.page_count() {
+            Err(ErrorKind::DocTreeError(
+                format!("Page {} requested from document with {} pages", page_index, self.page_count())))?
+        };
+        Ok(Page::new(self, page_index))
+    }
+
+    /// The page at page_index, counting from 0, for editing.
+    pub fn page_mut(&mut self, page_index: usize) -> Result<PageMut<'_>> {
+        self.page(page_index)?;
+        Ok(PageMut::new(self, page_index))
+    }
+
+    /// The decoded content of the page at page_index (counting from 0).  Multiple content streams are joined
+    /// with newlines, per spec 7.8.2.
+    pub fn page_content(&self, page_index: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for stream in self.page_content_streams(page_index)? {
+            if !data.is_empty() { data.push(b'\n') };
+            data.extend_from_slice(stream.data());
+        }
+        Ok(data)
+    }
+
+    /// The streams making up a page's /Contents, in order, each with the page's resources attached.
+    pub fn page_content_streams(&self, page_index: usize) -> Result<Vec<Rc<PdfContentStream>>> {
+        let page = self.page_tree.pages().into_iter().nth(page_index).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("Page {} requested from document with {} pages", page_index, self.page_count())))?;
+        let contents = match &page.contents {
+            None => return Ok(Vec::new()),
+            Some(contents) => contents
+        };
+        let streams = if contents.is_array() {
+            contents.try_into_array()?.as_ref().clone()
+        } else {
+            vec![Rc::clone(contents)]
+        };
+        let resources = self.inherited_page_attribute(page_index, "Resources")?;
+        streams.iter().map(|stream| {
+            // Streams are always indirect objects (spec 7.3.8)
+            let id = stream.reference_id().ok_or_else(|| ErrorKind::DocTreeError(
+                format!("Invalid /Contents on page {}", page_index)))?;
+            self.file.object_map.content_stream(id, resources.clone())
+                .chain_err(|| ErrorKind::DocTreeError(format!("Invalid /Contents on page {}", page_index)))
+        }).collect()
+    }
+
+    /// A page attribute, looking up the page tree for inheritable attributes like /Resources (spec 7.7.3.4).
+    fn inherited_page_attribute(&self, page_index: usize, key: &str) -> Result<Option<SharedObject>> {
+        let page = self.page_tree.pages().into_iter().nth(page_index).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("Page {} requested from document with {} pages", page_index, self.page_count())))?;
+        if let Some(value) = page.attributes.get(key) {
+            return Ok(Some(Rc::clone(value)));
         };
-        Ok(pdf)
+        let mut parent = page.attributes.get("Parent").cloned();
+        // Bound the search in case of a cyclic tree
+        for _ in 0..64 {
+            let node = match parent {
+                Some(node) => node.try_into_map()?,
+                None => return Ok(None)
+            };
+            if let Some(value) = node.get(key) {
+                return Ok(Some(Rc::clone(value)));
+            };
+            parent = node.get("Parent").cloned();
+        }
+        Ok(None)
+    }
+
+    /// The object id of the page at page_index.
+    fn page_id(&self, page_index: usize) -> Result<ObjectId> {
+        let page = self.page_tree.pages().into_iter().nth(page_index).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("Page {} requested from document with {} pages", page_index, self.page_count())))?;
+        page.id.ok_or_else(|| ErrorKind::DocTreeError(format!("Page {} is not an indirect object", page_index)).into())
+    }
+
+    fn catalog_id(&self) -> Result<ObjectId> {
+        self.root.reference_id()
+                 .ok_or_else(|| ErrorKind::DocTreeError("Catalog is not an indirect object".to_string()).into())
+    }
+
+    /// Replace a dictionary object with an edited copy.
+    fn update_dict<F: FnOnce(&mut PdfMap)>(&self, id: ObjectId, edit: F) -> Result<()> {
+        let mut dict = self.get_dict(id)?.as_ref().clone();
+        edit(&mut dict);
+        self.file.object_map.set_object(id, PdfObject::new_dictionary(Rc::new(dict)));
+        Ok(())
     }
+
+    /// Read the page tree again, after edits to pages or the catalog.
+    fn reload_page_tree(&mut self) -> Result<()> {
+        self.page_tree = PageTree::new(&self.root)?;
+        self.page_cache.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// How many pages to keep parsed and interpreted content for, most recently used first.  Edits to the
+    /// document make the kept pages stale, so they're worked out again on next use.  A budget of 0 turns the
+    /// cache off.
+    pub fn set_page_cache_budget(&self, pages: usize) {
+        self.page_cache.borrow_mut().set_budget(pages);
+    }
+
+    /// The parsed content of the page at page_index, from the page cache if it's there.  Content that can't be
+    /// read to the end gives the commands before the error, with the error in CommandStream::error, so that
+    /// text and the rest can still be had from what comes before it.
+    fn page_commands(&self, page_index: usize) -> Result<Rc<CommandStream>> {
+        let edit_count = self.file.object_map.edit_count();
+        let cached = self.page_cache.borrow_mut().get_mut(page_index, edit_count).map(|page| Rc::clone(&page.commands));
+        if let Some(commands) = cached {
+            return Ok(commands);
+        };
+        let commands = Rc::new(CommandStream::parse_partial(&self.page_content(page_index)?));
+        let page = CachedPage { commands: Rc::clone(&commands), interpreter: None };
+        self.page_cache.borrow_mut().insert(page_index, edit_count, page);
+        Ok(commands)
+    }
+
+    /// The error that stopped the content of the page at page_index being read to the end, if any.  Text,
+    /// tables and the like come from the commands before it.
+    pub fn page_content_error(&self, page_index: usize) -> Result<Option<Error>> {
+        Ok(self.page_commands(page_index)?.error.as_ref().map(|error| error.to_error(Some(page_index))))
+    }
+
+    /// Run the content of the page at page_index against the page's resources, or take the result of doing so
+    /// from the page cache.
+    fn interpret_page(&self, page_index: usize) -> Result<Rc<Interpreter>> {
+        self.file.object_map.options().check_cancelled()?;
+        let edit_count = self.file.object_map.edit_count();
+        let cached = self.page_cache.borrow_mut().get_mut(page_index, edit_count)
+                                                 .and_then(|page| page.interpreter.clone());
+        if let Some(interpreter) = cached {
+            return Ok(interpreter);
+        };
+        let commands = self.page_commands(page_index)?;
+        let resources = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => Some(resources.try_into_map()?),
+            None => None
+        };
+        let mut interpreter = Interpreter::new(resources);
+        interpreter.run(&commands)?;
+        let interpreter = Rc::new(interpreter);
+        let page = CachedPage { commands, interpreter: Some(Rc::clone(&interpreter)) };
+        self.page_cache.borrow_mut().insert(page_index, edit_count, page);
+        Ok(interpreter)
+    }
+
+    /// The page's /MediaBox, defaulting to US Letter if missing.
+    pub fn media_box(&self, page_index: usize) -> Result<Rect> {
+        Ok(self.page_rect(page_index, "MediaBox")?.unwrap_or_else(|| Rect::new(0.0, 0.0, 612.0, 792.0)))
+    }
+
+    /// The page's /CropBox, the region viewers show, clipped to the media box.  It defaults to the media box.
+    pub fn crop_box(&self, page_index: usize) -> Result<Rect> {
+        let media_box = self.media_box(page_index)?;
+        Ok(match self.page_rect(page_index, "CropBox")? {
+            Some(crop_box) => crop_box.intersection(&media_box).unwrap_or(media_box),
+            None => media_box
+        })
+    }
+
+    /// How far the page is turned clockwise when shown, from /Rotate: 0, 90, 180 or 270 degrees.
+    pub fn rotation(&self, page_index: usize) -> Result<i32> {
+        let rotate = match self.inherited_page_attribute(page_index, "Rotate")? {
+            Some(rotate) => rotate.try_into_int()?,
+            None => return Ok(0)
+        };
+        if rotate % 90 != 0 {
+            Err(ErrorKind::DocTreeError(format!("Invalid /Rotate on page {}: {}", page_index, rotate)))?
+        };
+        Ok(rotate.rem_euclid(360))
+    }
+
+    /// A rectangle attribute of the page, such as /MediaBox, which may be inherited.
+    fn page_rect(&self, page_index: usize, key: &str) -> Result<Option<Rect>> {
+        let values = match self.inherited_page_attribute(page_index, key)? {
+            None => return Ok(None),
+            Some(array) => array.try_into_array()?
+                                .iter()
+                                .map(|value| value.try_into_number())
+                                .collect::<Result<Vec<f32>>>()?
+        };
+        if values.len() != 4 {
+            Err(ErrorKind::DocTreeError(format!("Invalid /{} on page {}: {:?}", key, page_index, values)))?
+        };
+        Ok(Some(Rect::new(values[0], values[1], values[2], values[3])))
+    }
+
+    /// The size of a default user space unit on the page at page_index, in points (spec 14.11.2).  /UserUnit
+    /// isn't inheritable, so only the page itself is checked.
+    pub fn user_unit(&self, page_index: usize) -> Result<f32> {
+        let page = self.page_tree.pages().into_iter().nth(page_index).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("Page {} requested from document with {} pages", page_index, self.page_count())))?;
+        let unit = match page.attributes.get("UserUnit") {
+            Some(unit) => unit.try_into_number()?,
+            None => return Ok(1.0)
+        };
+        if !(unit > 0.0 && unit.is_finite()) {
+            Err(ErrorKind::DocTreeError(format!("Invalid /UserUnit on page {}: {}", page_index, unit)))?
+        };
+        Ok(unit)
+    }
+
+    /// Rasterize the paths on the page at page_index, with boxes standing in for text, and then those of the
+    /// annotation appearances over them.  The resolution is in pixels per inch of the printed page, so pages
+    /// with a /UserUnit come out at their true size.
+    #[cfg(feature = "render")]
+    pub fn render_page(&self, page_index: usize, options: &RenderOptions) -> Result<Raster> {
+        let interpreter = self.interpret_page(page_index)?;
+        let (mut paths, mut text_blocks) = (interpreter.paths.clone(), interpreter.text_blocks.clone());
+        for appearance in self.annotation_appearances(page_index)? {
+            paths.extend(appearance.paths);
+            text_blocks.extend(appearance.text_blocks);
+        }
+        let options = RenderOptions { dpi: options.dpi * self.user_unit(page_index)?, ..options.clone() };
+        render_page(&paths, &text_blocks, &self.media_box(page_index)?, &options)
+    }
+
+    /// The text runs shown on the page at page_index, in content stream order.
+    pub fn page_text_blocks(&self, page_index: usize) -> Result<Vec<TextBlock>> {
+        Ok(self.interpret_page(page_index)?.text_blocks.clone())
+    }
+
+    /// Find text on any page, matching across the spaces and line breaks of the extracted text.  Pages are
+    /// interpreted one at a time as the iterator advances; a page that can't be read yields an error.
+    pub fn search<'a>(&'a self, needle: &str) -> impl Iterator<Item = Result<SearchMatch>> + 'a {
+        let pattern = Regex::new(&regex::escape(needle)).expect("Escaped pattern is valid");
+        self.search_regex(pattern)
+    }
+
+    /// As search, for matches of a regular expression.  Text that is clipped out of view isn't searched.
+    pub fn search_regex<'a>(&'a self, pattern: Regex) -> impl Iterator<Item = Result<SearchMatch>> + 'a {
+        (0..self.page_count()).flat_map(move |page| {
+            let matches = self.page_text_blocks(page).map(|mut blocks| {
+                blocks.retain(|block| !block.clipped);
+                PageText::new(&group_into_reading_order(&blocks)).find(&pattern)
+            });
+            let results: Vec<Result<SearchMatch>> = match matches {
+                Ok(matches) => matches.into_iter()
+                                      .map(|(text, rects)| Ok(SearchMatch { page, text, rects }))
+                                      .collect(),
+                Err(e) => vec![Err(e)],
+            };
+            results
+        })
+    }
+
+    /// Shadings and patterns painted on the page at page_index, in content stream order, for spotting gradients
+    /// and other fills that need more than flat colour.
+    pub fn page_patterns(&self, page_index: usize) -> Result<Vec<PatternUse>> {
+        Ok(self.interpret_page(page_index)?.patterns.clone())
+    }
+
+    /// The operators run on the page at page_index, with counts and how well each is supported, and the Type3
+    /// fonts used.
+    pub fn page_operators(&self, page_index: usize) -> Result<OperatorReport> {
+        let interpreter = self.interpret_page(page_index)?;
+        Ok(OperatorReport::new(page_index, &interpreter.operator_counts, interpreter.type3_fonts.clone()))
+    }
+
+    /// The image XObjects in the resources of the page at page_index, in name order.
+    pub fn page_images(&self, page_index: usize) -> Result<Vec<PageImage>> {
+        let xobjects = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => resources.try_into_map()?.get("XObject").cloned(),
+            None => None
+        };
+        let xobjects = match xobjects {
+            Some(xobjects) => xobjects.try_into_map()?,
+            None => return Ok(Vec::new())
+        };
+        let mut images = Vec::new();
+        for (name, xobject) in xobjects.iter() {
+            let id = match xobject.reference_id() {
+                Some(id) => id,
+                None => continue
+            };
+            let is_image = self.get_dict(id)?.get("Subtype").map_or(Ok(false), |subtype| subtype.try_into_string()
+                                                                                           .map(|s| *s == "Image"))?;
+            if is_image {
+                images.push(PageImage::new(name, id, xobject)?);
+            };
+        }
+        images.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(images)
+    }
+
+    /// The colour spaces in the /ColorSpace resources of the page at page_index, by resource name, each parsed
+    /// separately so one that can't be read doesn't hide the others.
+    pub fn page_color_spaces(&self, page_index: usize) -> Result<BTreeMap<String, Result<ColorSpace>>> {
+        let spaces = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => resources.try_into_map()?.get("ColorSpace").cloned(),
+            None => None
+        };
+        Ok(match spaces {
+            Some(spaces) => spaces.try_into_map()?
+                                  .iter()
+                                  .map(|(name, space)| (name.clone(), ColorSpace::parse(space)))
+                                  .collect(),
+            None => BTreeMap::new()
+        })
+    }
+
+    /// The resources the page at page_index declares in its /Resources and which of them its content uses,
+    /// counting the content of Form XObjects that take their resources from the page.
+    pub fn page_resource_usage(&self, page_index: usize) -> Result<ResourceUsage> {
+        let resources = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => resources.try_into_map()?,
+            None => Rc::new(PdfMap::new())
+        };
+        let declared = |category: &str, name: &str| -> Result<Option<SharedObject>> {
+            Ok(match resources.get(category) {
+                Some(entries) => entries.try_into_map()?.get(name).cloned(),
+                None => None
+            })
+        };
+        // What comes after an error might use anything, so there's no telling what's unused
+        let commands = self.page_commands(page_index)?;
+        commands.check_complete(Some(page_index))?;
+        let mut used = used_resources(&commands);
+        let mut forms_checked = BTreeSet::new();
+        loop {
+            let forms = used.iter()
+                            .filter(|(category, name)| category == "XObject" && !forms_checked.contains(name))
+                            .map(|(_, name)| name.clone())
+                            .collect::<Vec<_>>();
+            if forms.is_empty() { break };
+            for name in forms {
+                let id = declared("XObject", &name)?.and_then(|form| form.reference_id());
+                forms_checked.insert(name);
+                let id = match id {
+                    Some(id) => id,
+                    None => continue
+                };
+                let dict = self.get_dict(id)?;
+                let is_form = dict.get("Subtype").map_or(Ok(false), |subtype| subtype.try_into_string()
+                                                                                     .map(|s| *s == "Form"))?;
+                if is_form && !dict.contains_key("Resources") {
+                    used.extend(used_resources(&CommandStream::parse(&self.get_stream_data(id)?)?));
+                };
+            }
+        }
+
+        let mut usage = ResourceUsage { page: page_index, resources: Vec::new() };
+        for category in &RESOURCE_CATEGORIES {
+            let entries = match resources.get(category) {
+                Some(entries) => entries.try_into_map()?,
+                None => Rc::new(PdfMap::new())
+            };
+            for (name, value) in entries.iter() {
+                let dict = match value.reference_id() {
+                    Some(id) => self.get_dict(id).ok(),
+                    None => value.try_into_map().ok()
+                };
+                let subtype = dict.and_then(|dict| dict.get("Subtype").and_then(|s| s.try_into_string().ok()));
+                usage.resources.push(ResourceUse {
+                    category: category.to_string(),
+                    name: name.clone(),
+                    subtype: subtype.map(|s| s.as_ref().clone()),
+                    declared: true,
+                    used: used.remove(&(category.to_string(), name.clone())),
+                });
+            }
+        }
+        usage.resources.extend(used.into_iter().map(|(category, name)| ResourceUse {
+            category, name, subtype: None, declared: false, used: true
+        }));
+        usage.resources.sort_by(|a, b| (&a.category, &a.name).cmp(&(&b.category, &b.name)));
+        Ok(usage)
+    }
+
+    /// Tables on the page at page_index, found from ruling lines and aligned text.
+    pub fn page_tables(&self, page_index: usize) -> Result<Vec<Table>> {
+        let interpreter = self.interpret_page(page_index)?;
+        Ok(find_tables(&interpreter.text_blocks, &interpreter.paths))
+    }
+
+    /// The text on the page at page_index in reading order, one line of text per line of output.
+    pub fn page_text(&self, page_index: usize) -> Result<String> {
+        self.page_text_with_options(page_index, &TextOptions::default())
+    }
+
+    /// As page_text, with a choice of which text to include.
+    pub fn page_text_with_options(&self, page_index: usize, text_options: &TextOptions) -> Result<String> {
+        let mut blocks = self.page_text_blocks(page_index)?;
+        if !text_options.include_invisible {
+            blocks.retain(|block| !block.is_invisible());
+        };
+        Ok(lines_to_text(&group_into_reading_order(&blocks)))
+    }
+
+    /// The text of every page, as page_text gives it, with a form feed between pages.  The number of pages done
+    /// is reported to ParserOptions::progress as it goes.
+    pub fn text(&self) -> Result<String> {
+        self.text_with_options(&TextOptions::default())
+    }
+
+    /// As text, with a choice of which text to include.
+    pub fn text_with_options(&self, text_options: &TextOptions) -> Result<String> {
+        let options = self.file.object_map.options();
+        let mut pages = Vec::new();
+        for page_index in 0..self.page_count() {
+            options.report(ProgressPhase::Pages, page_index, Some(self.page_count()));
+            pages.push(self.page_text_with_options(page_index, text_options)?);
+        }
+        options.report(ProgressPhase::Pages, self.page_count(), Some(self.page_count()));
+        Ok(pages.join("\x0C"))
+    }
+
+    /// The content of the page at page_index with one operator per line and normalized operands, for diffing.
+    pub fn normalized_page_content(&self, page_index: usize) -> Result<String> {
+        let commands = self.page_commands(page_index)?;
+        commands.check_complete(Some(page_index))?;
+        Ok(format!("{}", commands))
+    }
+}
+
+/// Push the font dictionaries in obj, without following references, paired with the id of the object holding them.
+fn collect_font_dicts(id: ObjectId, obj: &PdfObject, fonts: &mut Vec<(ObjectId, Rc<PdfMap>)>) {
+    let dict = match obj {
+        PdfObject::Actual(Array(array)) => {
+            array.iter().for_each(|item| collect_font_dicts(id, item, fonts));
+            return;
+        },
+        PdfObject::Actual(Dictionary(dict)) => dict,
+        PdfObject::Actual(BinaryStream(stream)) => stream.attributes(),
+        PdfObject::Actual(ContentStream(stream)) => stream.attributes(),
+        _ => return
+    };
+    let is_font = dict.get("Type").is_some_and(|t| t.try_into_string().is_ok_and(|t| t.as_str() == "Font"));
+    if is_font {
+        let is_cid_font = dict.get("Subtype")
+                              .is_some_and(|t| t.try_into_string().is_ok_and(|t| t.starts_with("CIDFontType")));
+        if !is_cid_font {
+            fonts.push((id, Rc::new(dict.clone())));
+        };
+    } else {
+        dict.values().for_each(|item| collect_font_dicts(id, item, fonts));
+    };
 }
 
 impl fmt::Display for PdfDoc {
@@ -169,6 +874,7 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
+    #[cfg(feature = "fs")]
     fn test_data() -> HashMap<&'static str, PDFVersion> {
         let mut data = HashMap::new();
         data.insert("data/simple_pdf.pdf", PDFVersion::V1_7);
@@ -179,6 +885,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "fs")]
     fn object_imports() {
         let test_pdfs = test_data();
         for (path, version) in test_pdfs {
@@ -195,6 +902,366 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "fs")]
+    fn normalized_content() {
+        let pdf = PdfDoc::create_pdf_from_file("data/simple_pdf.pdf").unwrap();
+        assert_eq!(pdf.page_count(), 1);
+        assert_eq!(pdf.normalized_page_content(0).unwrap(), "");
+        assert!(pdf.page_content(1).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "render")]
+    fn render() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"0 0 50 100 re f", TestFilter::None);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let raster = pdf.render_page(0, &RenderOptions { dpi: 36.0, ..Default::default() }).unwrap();
+        assert_eq!((raster.width, raster.height), (306, 396));
+        assert_eq!(raster.pixel(0, 395), [0, 0, 0, 255]);
+        assert_eq!(raster.pixel(25, 395), [255, 255, 255, 255]);
+        assert!(raster.to_png().unwrap().len() < 2000);
+    }
+
+    #[test]
+    fn embedded_fonts() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let program = builder.add_stream(b"\x00\x01\x00\x00 glyf", TestFilter::Flate, "");
+        let embedded = builder.add_object(format!(
+            "<< /Type /Font /Subtype /TrueType /BaseFont /QWERTY+Georgia /FontDescriptor \
+               << /Type /FontDescriptor /FontName /QWERTY+Georgia /FontFile2 {} 0 R >> >>", program));
+        let standard = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+        let broken = builder.add_object("<< /Type /Font /Subtype /TrueType /FontDescriptor 999 >>");
+        let direct = format!("<< /Type /Font /Subtype /TrueType /BaseFont /ASDFGH+Verdana /FontDescriptor \
+                                << /Type /FontDescriptor /FontFile2 {} 0 R >> >>", program);
+        builder.add_page_with_entries(b"", TestFilter::None,
+                                      &format!("/Resources << /Font << /F1 {} 0 R /F2 {} 0 R /F3 {} 0 R /F4 {} >> >>",
+                                               embedded, standard, broken, direct));
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let fonts = doc.embedded_fonts().unwrap();
+        assert_eq!(fonts.len(), 2);
+        // The direct font is reported through the page holding it, which the walk reaches first
+        assert_eq!((doc.page_index_of(fonts[0].id), fonts[0].subset_tag.as_deref()), (Some(0), Some("ASDFGH")));
+        assert_eq!(fonts[1].id, ObjectId::new(embedded, 0));
+        assert_eq!((fonts[1].subset_tag.as_deref(), fonts[1].format), (Some("QWERTY"), FontFormat::TrueType));
+        assert_eq!(fonts[1].program, b"\x00\x01\x00\x00 glyf");
+        assert_eq!(fonts[0].program, fonts[1].program);
+    }
+
+    #[test]
+    fn text_options() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let resources = format!("/Resources << /Font << /F1 {} 0 R >> >>", font);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 72 700 Td (Printed) Tj 0 -20 Td 3 Tr (Recognized) Tj ET",
+                                      TestFilter::None, &resources);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.page_text(0).unwrap(), "Printed\nRecognized\n");
+        let visible = TextOptions { include_invisible: false };
+        assert_eq!(pdf.page_text_with_options(0, &visible).unwrap(), "Printed\n");
+        assert_eq!(pdf.text_with_options(&visible).unwrap(), "Printed\n");
+    }
+
+    #[test]
+    fn operator_report() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type3 /FontBBox [0 0 1 1] /FontMatrix [1 0 0 1 0 0] \
+                                         /CharProcs << >> >>");
+        let resources = format!("/Resources << /Font << /T3 {} 0 R >> >>", font);
+        builder.add_page_with_entries(b"q 1 0 0 rg BT /T3 1 Tf (a) Tj (b) Tj ET /Sh0 sh Q 1 2 zz", TestFilter::None,
+                                      &resources);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let report = pdf.page_operators(0).unwrap();
+        let count = |operator: &str| report.operators.iter().find(|op| op.operator == operator).map(|op| op.count);
+        assert_eq!((count("Tj"), count("q"), count("re")), (Some(2), Some(1), None));
+        let unsupported: Vec<(&str, OperatorSupport)> = report.unsupported()
+                                                              .map(|op| (op.operator.as_str(), op.support))
+                                                              .collect();
+        assert_eq!(unsupported, vec![("rg", OperatorSupport::Ignored), ("sh", OperatorSupport::Partial),
+                                     ("zz", OperatorSupport::Unknown)]);
+        assert_eq!(report.type3_fonts.into_iter().collect::<Vec<_>>(), vec!["T3"]);
+    }
+
+    #[test]
+    fn resource_usage() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let image = builder.add_stream(b"\x00", TestFilter::None,
+                                       "/Type /XObject /Subtype /Image /Width 1 /Height 1 /BitsPerComponent 8");
+        // A form without resources of its own draws with the page's
+        let form = builder.add_stream(b"BT /F2 10 Tf ET", TestFilter::None,
+                                      "/Type /XObject /Subtype /Form /BBox [0 0 10 10]");
+        let resources = format!("/Resources << /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Courier >> \
+                                 /F2 << /Type /Font /Subtype /Type1 /BaseFont /Symbol >> >> \
+                                 /XObject << /Im1 {} 0 R /Fm1 {} 0 R >> /ExtGState << /GS0 << >> >> >>", image, form);
+        builder.add_page_with_entries(b"BT /F1 12 Tf (a) Tj ET /Fm1 Do /Im2 Do", TestFilter::None, &resources);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let usage = pdf.page(0).unwrap().resource_usage().unwrap();
+        let names = |resources: Vec<&ResourceUse>| resources.iter()
+                                                            .map(|r| format!("{}/{}", r.category, r.name))
+                                                            .collect::<Vec<_>>();
+        assert_eq!(names(usage.unused().collect()), vec!["ExtGState/GS0", "XObject/Im1"]);
+        assert_eq!(names(usage.missing().collect()), vec!["XObject/Im2"]);
+        assert_eq!(names(usage.category("Font").collect()), vec!["Font/F1", "Font/F2"]);
+        assert_eq!(names(usage.images().collect()), vec!["XObject/Im1"]);
+    }
+
+    #[test]
+    fn open_report() {
+        use crate::test_utils::*;
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>", "<< /Type /Pages /Kids [] /Count 0 >>\nendstream"];
+        let bytes = pdf_from_objects(&objects, "<< /Root 1 0 R /Size 3 >>");
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        let (pdf, report) = PdfDoc::create_pdf_from_bytes_with_report(bytes.clone(), lenient.clone()).unwrap();
+        assert_eq!(pdf.page_count(), 0);
+        assert_eq!(report.version, pdf.file.version);
+        assert_eq!(report.repairs.into_iter().collect::<Vec<_>>(), vec![("stray keyword", 1)]);
+        assert!(!report.encrypted);
+
+        let bytes = String::from_utf8(bytes).unwrap().replace("/Size 3", "/Size 3 /Encrypt << >>").into_bytes();
+        let (_, report) = PdfDoc::create_pdf_from_bytes_with_report(bytes, lenient).unwrap();
+        assert!(report.encrypted);
+        assert_eq!(report.repair_count(), 1);
+    }
+
+    #[test]
+    fn page_cache() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let resources = format!("/Resources << /Font << /F1 {} 0 R >> >>", font);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 72 700 Td (First) Tj ET", TestFilter::None, &resources);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 72 700 Td (Second) Tj ET", TestFilter::None, &resources);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.page_text(0).unwrap(), "First\n");
+        assert!(Rc::ptr_eq(&pdf.interpret_page(0).unwrap(), &pdf.interpret_page(0).unwrap()));
+        assert_eq!(pdf.search("Second").count(), 1);
+        assert_eq!(pdf.page_cache.borrow().len(), 2);
+
+        // Replacing the first page's content stream with the second's is seen straight away
+        let contents = |page| pdf.get_dict(pdf.page_id(page).unwrap()).unwrap()["Contents"].reference_id().unwrap();
+        let stream = pdf.get_object(contents(1)).unwrap().as_ref().clone();
+        pdf.file.object_map.set_object(contents(0), stream);
+        assert_eq!(pdf.page_text(0).unwrap(), "Second\n");
+        assert_eq!(pdf.page_cache.borrow().len(), 1);
+
+        pdf.set_page_cache_budget(0);
+        assert_eq!(pdf.page_text(1).unwrap(), "Second\n");
+        assert_eq!(pdf.page_cache.borrow().len(), 0);
+    }
+
+    #[test]
+    fn search() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let resources = format!("/Resources << /Font << /F1 {} 0 R >> >>", font);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 72 700 Td (Hello world) Tj ET", TestFilter::None, &resources);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 1 Td (Bad operands) Tj ET", TestFilter::None, &resources);
+        builder.add_page_with_entries(b"BT /F1 10 Tf 72 700 Td 12 TL (A world of) Tj T* (worlds) Tj ET", TestFilter::Flate,
+                                      &resources);
+        // Clipped out of view, so not found
+        builder.add_page_with_entries(b"q 0 0 10 10 re W n BT /F1 10 Tf 72 700 Td (Hidden world) Tj ET Q",
+                                      TestFilter::None, &resources);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let results: Vec<Result<SearchMatch>> = pdf.search("world").collect();
+        assert_eq!(results.len(), 4);
+        let first = results[0].as_ref().unwrap();
+        assert_eq!((first.page, &first.text[..]), (0, "world"));
+        assert_eq!(first.rects, vec![Rect::new(108.0, 698.0, 138.0, 708.0)]);
+        assert!(results[1].is_err());
+        assert_eq!(results[3].as_ref().unwrap().page, 2);
+
+        // Pages are only read as needed, so the unreadable page isn't reached here
+        assert!(pdf.search("Hello").next().unwrap().is_ok());
+
+        let mut matches = pdf.search_regex(Regex::new(r"of\s+worlds").unwrap());
+        assert!(matches.next().unwrap().is_err());
+        let across_lines = matches.next().unwrap().unwrap();
+        assert_eq!(across_lines.text, "of\nworlds");
+        assert_eq!(across_lines.rects.len(), 2);
+    }
+
+    #[test]
+    fn test_revisions() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let note = builder.add_object("(first)");
+        builder.add_page(b"BT (old) Tj ET", TestFilter::None);
+        let mut bytes = builder.build();
+        let original_length = bytes.len();
+        append_incremental_update(&mut bytes, &[(note, "(second)")], "");
+        let second_length = bytes.len();
+        let content = String::from_utf8(stream_object(b"BT (new) Tj ET", TestFilter::None, "")).unwrap();
+        append_incremental_update(&mut bytes, &[(4, &content), (7, "(added)")], "/Info 7 0 R");
+
+        let pdf = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let revisions = pdf.revisions();
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(revisions[0].byte_range, 0..original_length);
+        assert_eq!(revisions[1].byte_range, original_length..second_length);
+        assert_eq!(revisions[1].changed_objects, vec![ObjectId::new(note, 0)]);
+        assert_eq!(revisions[2].changed_objects, vec![ObjectId::new(4, 0), ObjectId::new(7, 0)]);
+        assert!(revisions[2].trailer.contains_key("Info"));
+        assert_eq!(revisions[0].changed_objects.len(), 5);
+        assert_eq!(pdf.get_string(ObjectId::new(note, 0)).unwrap(), "second");
+        assert_eq!(pdf.get_string(ObjectId::new(7, 0)).unwrap(), "added");
+        assert_eq!(pdf.page_content(0).unwrap(), b"BT (new) Tj ET");
+
+        let original = pdf.at_revision(0).unwrap();
+        assert_eq!(original.revisions().len(), 1);
+        assert_eq!(original.get_string(ObjectId::new(note, 0)).unwrap(), "first");
+        assert!(original.get_object(ObjectId::new(7, 0)).is_err());
+        assert_eq!(original.page_content(0).unwrap(), b"BT (old) Tj ET");
+        assert!(pdf.at_revision(3).is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        use std::cell::RefCell;
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"0 0 m 10 10 l S", TestFilter::None);
+        builder.add_page(b"", TestFilter::None);
+        let mut bytes = builder.build();
+        append_incremental_update(&mut bytes, &[(7, "(added)")], "");
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&reports);
+        let options = ParserOptions {
+            progress: Some(ProgressHook::new(move |progress| log.borrow_mut().push(progress))),
+            ..Default::default()
+        };
+        let pdf = PdfDoc::create_pdf_from_bytes_with_options(bytes, options).unwrap();
+        let steps = |phase| -> Vec<(usize, Option<usize>)> {
+            reports.borrow().iter().filter(|p| p.phase == phase).map(|p| (p.done, p.total)).collect()
+        };
+        assert_eq!(steps(ProgressPhase::XrefScan), vec![(0, None), (1, None), (2, Some(2))]);
+
+        assert_eq!(pdf.text().unwrap(), "\x0C");
+        assert_eq!(steps(ProgressPhase::Pages), vec![(0, Some(2)), (1, Some(2)), (2, Some(2))]);
+        let last = *reports.borrow().last().unwrap();
+        assert_eq!(last.percent(), Some(100.0));
+
+        let cache = &pdf.file.object_map;
+        cache.prefetch(&cache.object_ids()).unwrap();
+        let parsed = steps(ProgressPhase::ObjectParse);
+        assert_eq!(parsed.last(), Some(&(parsed.len() - 1, Some(parsed.len() - 1))));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let note = builder.add_object("(note)");
+        builder.add_page(b"0 0 m 10 10 l S", TestFilter::None);
+        let bytes = builder.build();
+        let token = CancellationToken::new();
+        let options = ParserOptions { cancel: Some(token.clone()), ..Default::default() };
+        let pdf = PdfDoc::create_pdf_from_bytes_with_options(bytes.clone(), options.clone()).unwrap();
+        assert!(pdf.text().is_ok());
+
+        let remote = token.clone();
+        std::thread::spawn(move || remote.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+        let is_cancelled = |result: Result<()>| matches!(result.unwrap_err().kind(), ErrorKind::Cancelled);
+        assert!(is_cancelled(pdf.page_text(0).map(drop)));
+        assert!(is_cancelled(pdf.get_object(ObjectId::new(note, 0)).map(drop)));
+        assert!(is_cancelled(PdfDoc::create_pdf_from_bytes_with_options(bytes, options).map(drop)));
+    }
+
+    #[test]
+    fn test_info() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let info = builder.add_object("<< /Title (Annual report) /Author <FEFF00C9006D0065> /Pages 3 >>");
+        let builder = builder.with_trailer_entries(&format!("/Info {} 0 R", info));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let entries: Vec<(String, String)> = pdf.info().unwrap().into_iter().collect();
+        assert_eq!(entries, vec![("Author".to_string(), "Éme".to_string()),
+                                 ("Title".to_string(), "Annual report".to_string())]);
+        assert!(PdfDoc::create_pdf_from_bytes(MiniPdfBuilder::new().build()).unwrap().info().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_content_streams() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let image = builder.add_stream(b"\xFF\xD8", TestFilter::None,
+                                       "/Type /XObject /Subtype /Image /Filter /DCTDecode");
+        builder.add_page_with_entries(b"BT (caf\xE9) Tj ET", TestFilter::Flate,
+                                      &format!("/Resources << /XObject << /Im1 {} 0 R >> >>", image));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let content_id = ObjectId::new(image + 1, 0);
+        assert!(pdf.get_object(content_id).unwrap().try_into_content_stream().is_err());
+
+        let streams = pdf.page_content_streams(0).unwrap();
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].data(), b"BT (caf\xE9) Tj ET");
+        assert_eq!(streams[0].text(), "BT (caf\u{FFFD}) Tj ET");
+        let resources = streams[0].resources().unwrap().try_into_map().unwrap();
+        assert!(resources.contains_key("XObject"));
+
+        // The cached object is still the binary stream
+        assert!(pdf.get_object(content_id).unwrap().try_into_binary_stream().unwrap().is_loaded());
+        assert_eq!(pdf.get_stream_data(content_id).unwrap(), b"BT (caf\xE9) Tj ET");
+        assert!(pdf.get_dict(content_id).unwrap().contains_key("Length"));
+        assert!(pdf.file.object_map.content_stream(ObjectId::new(image, 0), None).is_err());
+
+        let (bytes, _) = pdf.file.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(reread.page_content(0).unwrap(), b"BT (caf\xE9) Tj ET");
+
+        // Pages sharing a content stream each read it with their own resources
+        let page = |font: &str| format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 3 0 R \
+                                         /Resources << /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /{} \
+                                         >> >> >> >>", font);
+        let objects = vec!["<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                           "<< /Type /Pages /Kids [4 0 R 5 0 R] /Count 2 >>".to_string(),
+                           "<< /Length 9 >>\nstream\n/F1 12 Tf\nendstream".to_string(),
+                           page("Helvetica"), page("Courier")];
+        let pdf = PdfDoc::create_pdf_from_bytes(pdf_from_objects(&objects, "<< /Root 1 0 R >>")).unwrap();
+        let base_fonts: Vec<String> = (0..2).map(|page| {
+            let streams = pdf.page_content_streams(page).unwrap();
+            let resources = streams[0].resources().unwrap().try_into_map().unwrap();
+            let fonts = resources["Font"].try_into_map().unwrap();
+            fonts["F1"].try_into_map().unwrap()["BaseFont"].try_into_string().unwrap().as_ref().clone()
+        }).collect();
+        assert_eq!(base_fonts, vec!["Helvetica", "Courier"]);
+        assert!(pdf.get_object(ObjectId::new(3, 0)).unwrap().try_into_binary_stream().is_ok());
+    }
+
+    #[test]
+    fn content_errors() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"BT /F1 12 Tf (fine) Tj ET", TestFilter::None);
+        builder.add_page(b"BT /F1 12 Tf [(a) 10 (b) TJ ET", TestFilter::Flate);
+        builder.add_page(b"BT /F1 12 Tf 0 0 Td (kept) Tj ET BT 0 20 Td (a) ) Tj ET", TestFilter::None);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(pdf.page_text(0).is_ok());
+        assert!(pdf.page_content_error(0).unwrap().is_none());
+        assert_eq!(pdf.page_text(1).unwrap(), "");
+        let error = pdf.page_content_error(1).unwrap().unwrap();
+        assert!(matches!(error.kind(), ErrorKind::ContentError(_, 13, Some((1, _)), Some(1))), "{:?}", error.kind());
+        // Messages number pages from 1, like viewers do
+        assert_eq!(error.to_string(), "Unterminated array at 27 (content stream byte 13, after operator 1 Tf, page 2)");
+
+        // The text before the error is still there, but uses that need the whole stream fail
+        assert_eq!(pdf.page_text(2).unwrap(), "kept\n");
+        assert!(matches!(pdf.page_content_error(2).unwrap().unwrap().kind(),
+                         ErrorKind::ContentError(_, 48, Some((6, _)), Some(2))));
+        assert!(pdf.normalized_page_content(2).is_err());
+        assert!(pdf.page_resource_usage(2).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
     fn page_trees() {
         let test_pdfs = test_data();
         for (path, _version) in test_pdfs {
@@ -0,0 +1,438 @@
+use std::fmt::Write;
+
+use super::*;
+
+/// Most levels of field hierarchy followed, so that cyclic /Kids or /Parent links end.
+const MAX_FIELD_DEPTH: usize = 32;
+/// Font resource name used for text drawn by flatten.
+const FLATTEN_FONT: &str = "FlattenHelv";
+/// Text size for fields whose /DA gives an automatic (zero) size, capped by the field height.
+const DEFAULT_TEXT_SIZE: f32 = 12.0;
+
+/// An interactive form field with a value (spec 12.7.3).  Only terminal fields are listed; their names are
+/// the partial names of their ancestors joined with periods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    pub id: ObjectId,
+    pub name: String,
+    /// /Btn, /Tx, /Ch or /Sig, inherited from ancestors if needed
+    pub field_type: Option<String>,
+    /// The text of a text or choice field, or the selected state of a button, such as /Yes or /Off
+    pub value: Option<String>,
+    /// The widget annotations showing the field, which may be the field itself
+    pub widgets: Vec<ObjectId>,
+}
+
+/// One part of an XFA form definition: the packet name, like template or datasets, and its XML.  A form given
+/// as a single stream is one packet with an empty name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XfaPacket {
+    pub name: String,
+    pub xml: Vec<u8>,
+}
+
+impl PdfDoc {
+    /// The terminal fields of the document's interactive form, in /Fields order.  Fields written as direct
+    /// objects are skipped, since they can't be edited by id.  XFA forms often have no /Fields at all; check
+    /// has_xfa_form and use xfa_packets for those.
+    pub fn form_fields(&self) -> Result<Vec<FormField>> {
+        let mut fields = Vec::new();
+        if let Some(acro_form) = self.root.try_into_map()?.get("AcroForm") {
+            if let Some(kids) = acro_form.try_into_map()?.get("Fields") {
+                for kid in kids.try_into_array()?.iter() {
+                    self.add_fields(kid, "", None, None, &mut fields, 0)?;
+                }
+            };
+        };
+        Ok(fields)
+    }
+
+    fn add_fields(&self, node: &PdfObject, parent_name: &str, field_type: Option<String>, value: Option<String>,
+                  fields: &mut Vec<FormField>, depth: usize) -> Result<()> {
+        if depth > MAX_FIELD_DEPTH {
+            Err(ErrorKind::DocTreeError("Form field hierarchy too deep or cyclic".to_string()))?
+        };
+        let id = match node.reference_id() {
+            Some(id) => id,
+            None => return Ok(())
+        };
+        let dict = node.try_into_map()?;
+        let name = match dict.get("T") {
+            Some(partial) if parent_name.is_empty() => partial.try_into_text()?,
+            Some(partial) => format!("{}.{}", parent_name, partial.try_into_text()?),
+            None => parent_name.to_string()
+        };
+        let field_type = match dict.get("FT") {
+            Some(field_type) => Some(field_type.try_into_string()?.to_string()),
+            None => field_type
+        };
+        let value = match dict.get("V") {
+            Some(value) => field_value(value)?,
+            None => value
+        };
+        let kids = match dict.get("Kids") {
+            Some(kids) => kids.try_into_array()?.as_ref().clone(),
+            None => Vec::new()
+        };
+        // Kids without partial names are widgets of this field rather than fields of their own
+        let (widgets, child_fields): (Vec<SharedObject>, Vec<SharedObject>) = kids.into_iter().partition(|kid| {
+            kid.try_into_map().map(|kid| !kid.contains_key("T")).unwrap_or(false)
+        });
+        if child_fields.is_empty() {
+            let mut widget_ids: Vec<ObjectId> = widgets.iter().filter_map(|widget| widget.reference_id()).collect();
+            if name_entry_is(&dict, "Subtype", "Widget") {
+                widget_ids.insert(0, id);
+            };
+            fields.push(FormField { id, name, field_type, value, widgets: widget_ids });
+        } else {
+            for kid in child_fields {
+                self.add_fields(&kid, &name, field_type.clone(), value.clone(), fields, depth + 1)?;
+            }
+        };
+        Ok(())
+    }
+
+    /// Whether the interactive form is defined, in whole or in part, by XFA (XML Forms Architecture) data.
+    pub fn has_xfa_form(&self) -> Result<bool> {
+        Ok(self.xfa_entry()?.is_some())
+    }
+
+    fn xfa_entry(&self) -> Result<Option<SharedObject>> {
+        match self.root.try_into_map()?.get("AcroForm") {
+            Some(acro_form) => Ok(acro_form.try_into_map()?.get("XFA").cloned()),
+            None => Ok(None)
+        }
+    }
+
+    /// The packets of an XFA form, in document order, decoded but otherwise uninterpreted.  /XFA is either one
+    /// stream holding the whole XDP document or an array alternating packet names and streams (spec 12.7.8).
+    pub fn xfa_packets(&self) -> Result<Vec<XfaPacket>> {
+        let xfa = match self.xfa_entry()? {
+            Some(xfa) => xfa,
+            None => return Ok(Vec::new())
+        };
+        if !xfa.is_array() {
+            return Ok(vec![XfaPacket { name: String::new(), xml: xfa.try_into_binary_stream()?.data()?.to_vec() }]);
+        };
+        let parts = xfa.try_into_array()?;
+        if parts.len() % 2 != 0 {
+            Err(ErrorKind::DocTreeError(format!("XFA array has odd length {}", parts.len())))?
+        };
+        parts.chunks(2).map(|pair| Ok(XfaPacket {
+            name: pair[0].try_into_text()?,
+            xml: pair[1].try_into_binary_stream()?.data()?.to_vec(),
+        })).collect()
+    }
+
+    /// The complete XDP document of an XFA form: its packets joined in order, which for a split form begins with
+    /// the opening xdp:xdp packet and ends with the closing one.
+    pub fn xfa_xml(&self) -> Result<Option<Vec<u8>>> {
+        if !self.has_xfa_form()? {
+            return Ok(None);
+        };
+        Ok(Some(self.xfa_packets()?.into_iter().flat_map(|packet| packet.xml).collect()))
+    }
+
+    /// Set the value of the field with the given fully qualified name.  For buttons the value is the name of an
+    /// appearance state, like Yes, or Off, and each widget's /AS is switched to match.  Appearance streams aren't
+    /// regenerated for other fields; /NeedAppearances asks viewers to redraw them instead.
+    pub fn set_field_value(&self, name: &str, value: &str) -> Result<()> {
+        let field = self.form_fields()?.into_iter().find(|field| field.name == name)
+                        .ok_or_else(|| ErrorKind::DocTreeError(format!("No form field named {}", name)))?;
+        if field.field_type.as_deref() == Some("Btn") {
+            self.update_dict(field.id, |dict| {
+                dict.insert("V".to_string(), Rc::new(PdfObject::new_name(value)));
+            })?;
+            for widget in &field.widgets {
+                let has_state = self.get_dict(*widget)?.get("AP")
+                                    .map(|ap| ap.try_into_map()).transpose()?
+                                    .and_then(|ap| ap.get("N").cloned())
+                                    .map(|normal| normal.try_into_map()).transpose()?
+                                    .is_some_and(|states| states.contains_key(value));
+                let state = if has_state { value } else { "Off" };
+                self.update_dict(*widget, |dict| {
+                    dict.insert("AS".to_string(), Rc::new(PdfObject::new_name(state)));
+                })?;
+            }
+            return Ok(());
+        };
+        self.update_dict(field.id, |dict| {
+            dict.insert("V".to_string(), Rc::new(PdfObject::new_text_string(value)));
+        })?;
+        self.update_acro_form(|acro_form| {
+            acro_form.insert("NeedAppearances".to_string(), Rc::new(PdfObject::new_boolean(true)));
+        })
+    }
+
+    /// Edit the /AcroForm dictionary, wherever it's stored.
+    fn update_acro_form<F: FnOnce(&mut PdfMap)>(&self, edit: F) -> Result<()> {
+        let catalog_id = self.catalog_id()?;
+        let acro_form = self.get_dict(catalog_id)?.get("AcroForm").cloned()
+                            .ok_or_else(|| ErrorKind::DocTreeError("Document has no /AcroForm".to_string()))?;
+        match acro_form.reference_id() {
+            Some(id) => self.update_dict(id, edit),
+            None => {
+                let mut dict = acro_form.try_into_map()?.as_ref().clone();
+                edit(&mut dict);
+                self.update_dict(catalog_id, |catalog| {
+                    catalog.insert("AcroForm".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(dict))));
+                })
+            }
+        }
+    }
+
+    /// Draw field values into the page content and remove the form: text and choice values become text in
+    /// Helvetica, checked buttons become a tick, and widget annotations and the /AcroForm are dropped.  Field
+    /// appearance streams are ignored, so the result doesn't depend on them being present or up to date.
+    pub fn flatten(&mut self) -> Result<()> {
+        let fields = self.form_fields()?;
+        let mut widget_values = HashMap::new();
+        for field in &fields {
+            for widget in &field.widgets {
+                widget_values.insert(*widget, field);
+            }
+        }
+        for page_index in 0..self.page_count() {
+            let page_id = self.page_id(page_index)?;
+            let annots = match self.get_dict(page_id)?.get("Annots") {
+                Some(annots) => annots.try_into_array()?.as_ref().clone(),
+                None => continue
+            };
+            let annot_count = annots.len();
+            let mut drawing = String::new();
+            let mut kept = Vec::new();
+            for annot in annots {
+                let dict = annot.try_into_map()?;
+                if !name_entry_is(&dict, "Subtype", "Widget") {
+                    kept.push(annot);
+                    continue;
+                };
+                if let Some(field) = annot.reference_id().and_then(|id| widget_values.get(&id)) {
+                    draw_field(&mut drawing, field, &dict)?;
+                };
+            }
+            if drawing.is_empty() && kept.len() == annot_count {
+                continue;
+            };
+            self.add_page_drawing(page_index, page_id, &drawing, kept)?;
+        }
+        let catalog_id = self.catalog_id()?;
+        self.update_dict(catalog_id, |catalog| {
+            catalog.remove("AcroForm");
+        })?;
+        self.reload_page_tree()
+    }
+
+    /// Append drawing to a page's content, isolated from the existing content's graphics state, with the
+    /// flattening font added to its resources and its annotations replaced.
+    fn add_page_drawing(&self, page_index: usize, page_id: ObjectId, drawing: &str, annots: Vec<SharedObject>)
+                        -> Result<()> {
+        let mut resources = match self.inherited_page_attribute(page_index, "Resources")? {
+            Some(resources) => resources.try_into_map()?.as_ref().clone(),
+            None => PdfMap::new()
+        };
+        let mut fonts = match resources.get("Font") {
+            Some(fonts) => fonts.try_into_map()?.as_ref().clone(),
+            None => PdfMap::new()
+        };
+        let mut helvetica = PdfMap::new();
+        helvetica.insert("Type".to_string(), Rc::new(PdfObject::new_name("Font")));
+        helvetica.insert("Subtype".to_string(), Rc::new(PdfObject::new_name("Type1")));
+        helvetica.insert("BaseFont".to_string(), Rc::new(PdfObject::new_name("Helvetica")));
+        helvetica.insert("Encoding".to_string(), Rc::new(PdfObject::new_name("WinAnsiEncoding")));
+        fonts.insert(FLATTEN_FONT.to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(helvetica))));
+        resources.insert("Font".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(fonts))));
+
+        self.wrap_page_content(page_id, b"q\n", format!("Q\n{}", drawing).as_bytes())?;
+        self.update_dict(page_id, |page| {
+            page.insert("Resources".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(resources))));
+            if annots.is_empty() {
+                page.remove("Annots");
+            } else {
+                page.insert("Annots".to_string(), Rc::new(PdfObject::new_array(Rc::new(annots))));
+            };
+        })
+    }
+}
+
+/// Content drawing a field's value inside a widget's /Rect.
+fn draw_field(drawing: &mut String, field: &FormField, widget: &PdfMap) -> Result<()> {
+    let rect = match widget.get("Rect") {
+        Some(rect) => rect.try_into_array()?.iter().map(|n| n.try_into_number()).collect::<Result<Vec<f32>>>()?,
+        None => return Ok(())
+    };
+    if rect.len() != 4 {
+        return Ok(());
+    };
+    let rect = Rect::new(rect[0], rect[1], rect[2], rect[3]);
+    match field.field_type.as_deref() {
+        Some("Btn") => {
+            let state = match widget.get("AS") {
+                Some(state) => state.try_into_string()?.to_string(),
+                None => field.value.clone().unwrap_or_default()
+            };
+            if state.is_empty() || state == "Off" {
+                return Ok(());
+            };
+            // A tick from the left third, down to the bottom center, and up to the top right
+            let (w, h) = (rect.width(), rect.height());
+            writeln!(drawing, "q {} w {} {} m {} {} l {} {} l S Q",
+                     number(h.min(w) / 10.0), number(rect.x0 + 0.2 * w), number(rect.y0 + 0.5 * h),
+                     number(rect.x0 + 0.4 * w), number(rect.y0 + 0.2 * h),
+                     number(rect.x0 + 0.8 * w), number(rect.y0 + 0.8 * h)).unwrap();
+        },
+        Some("Tx") | Some("Ch") => {
+            let text = match &field.value {
+                Some(text) if !text.is_empty() => text,
+                _ => return Ok(())
+            };
+            let size = match widget.get("DA").map(|da| da.try_into_text()).transpose()? {
+                Some(da) => da_font_size(&da).filter(|size| *size > 0.0),
+                None => None
+            }.unwrap_or_else(|| DEFAULT_TEXT_SIZE.min(rect.height() * 0.8));
+            // Vertically centered on the cap height, with the 2 point inset viewers use for field borders
+            let baseline = rect.y0 + (rect.height() - 0.7 * size) / 2.0;
+            writeln!(drawing, "BT /{} {} Tf {} {} Td {} Tj ET", FLATTEN_FONT, number(size), number(rect.x0 + 2.0),
+                     number(baseline), literal_string(text)).unwrap();
+        },
+        _ => {}
+    };
+    Ok(())
+}
+
+/// The font size in a default appearance string like "/Helv 10 Tf 0 g".
+fn da_font_size(da: &str) -> Option<f32> {
+    let words: Vec<&str> = da.split_whitespace().collect();
+    let position = words.iter().position(|word| *word == "Tf")?;
+    words.get(position.checked_sub(1)?)?.parse().ok()
+}
+
+/// A literal string in WinAnsiEncoding, with characters it lacks replaced by question marks.
+pub(super) fn literal_string(text: &str) -> String {
+    let mut literal = String::from("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => { literal.push('\\'); literal.push(c) },
+            ' '..='~' => literal.push(c),
+            '\u{A0}'..='\u{FF}' => write!(literal, "\\{:03o}", c as u32).unwrap(),
+            _ => literal.push('?'),
+        }
+    }
+    literal.push(')');
+    literal
+}
+
+pub(super) fn number(value: f32) -> String {
+    let formatted = format!("{:.2}", value);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// A field value as text: a text string, or a button state name.
+fn field_value(value: &PdfObject) -> Result<Option<String>> {
+    if value.is_name() {
+        return Ok(Some(value.try_into_string()?.to_string()));
+    };
+    if value.is_array() {
+        // Multiple selections in a list box
+        let items = value.try_into_array()?.iter().map(|item| item.try_into_text()).collect::<Result<Vec<String>>>()?;
+        return Ok(Some(items.join(", ")));
+    };
+    value.try_into_text().map(Some).or(Ok(None))
+}
+
+fn name_entry_is(dict: &PdfMap, key: &str, name: &str) -> bool {
+    dict.get(key).is_some_and(|value| value.try_into_string().is_ok_and(|value| value.as_str() == name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn form_pdf() -> PdfDoc {
+        let mut builder = MiniPdfBuilder::new().with_catalog_entries("/AcroForm << /Fields [3 0 R 4 0 R] >>");
+        builder.add_object("<< /T (person) /FT /Tx /Kids [5 0 R] >>");
+        builder.add_object("<< /T (agree) /FT /Btn /V /Off /Type /Annot /Subtype /Widget /Rect [100 100 110 110] \
+                              /AP << /N << /Yes 6 0 R /Off 6 0 R >> >> /AS /Off >>");
+        builder.add_object("<< /T (name) /V <FEFF00C90076006100200042> /Type /Annot /Subtype /Widget \
+                              /Rect [100 700 300 720] /DA (/Helv 10 Tf 0 g) >>");
+        builder.add_stream(b"", TestFilter::None, "/Type /XObject /Subtype /Form /BBox [0 0 10 10]");
+        builder.add_page_with_entries(b"0 0 m 10 10 l S", TestFilter::None,
+                                      "/Annots [4 0 R 5 0 R << /Subtype /Text /Rect [0 0 1 1] >>]");
+        PdfDoc::create_pdf_from_bytes(builder.build()).unwrap()
+    }
+
+    #[test]
+    fn test_form_fields() {
+        let doc = form_pdf();
+        let fields = doc.form_fields().unwrap();
+        assert_eq!(fields, vec![
+            FormField { id: ObjectId::new(5, 0), name: "person.name".to_string(), field_type: Some("Tx".to_string()),
+                        value: Some("Éva B".to_string()), widgets: vec![ObjectId::new(5, 0)] },
+            FormField { id: ObjectId::new(4, 0), name: "agree".to_string(), field_type: Some("Btn".to_string()),
+                        value: Some("Off".to_string()), widgets: vec![ObjectId::new(4, 0)] },
+        ]);
+    }
+
+    #[test]
+    fn test_xfa_packets() {
+        let mut builder = MiniPdfBuilder::new();
+        let preamble = builder.add_stream(b"<xdp:xdp xmlns:xdp=\"http://ns.adobe.com/xdp/\">", TestFilter::None, "");
+        let template = builder.add_stream(b"<template/>", TestFilter::Flate, "");
+        let postamble = builder.add_stream(b"</xdp:xdp>", TestFilter::None, "");
+        let builder = builder.with_catalog_entries(&format!(
+            "/AcroForm << /Fields [] /XFA [(preamble) {} 0 R (template) {} 0 R (postamble) {} 0 R] >>",
+            preamble, template, postamble));
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(doc.has_xfa_form().unwrap());
+        assert!(doc.form_fields().unwrap().is_empty());
+        let packets = doc.xfa_packets().unwrap();
+        assert_eq!(packets.iter().map(|packet| packet.name.as_str()).collect::<Vec<&str>>(),
+                   vec!["preamble", "template", "postamble"]);
+        assert_eq!(packets[1].xml, b"<template/>");
+        assert_eq!(doc.xfa_xml().unwrap().unwrap(),
+                   b"<xdp:xdp xmlns:xdp=\"http://ns.adobe.com/xdp/\"><template/></xdp:xdp>".to_vec());
+
+        let doc = form_pdf();
+        assert!(!doc.has_xfa_form().unwrap());
+        assert_eq!(doc.xfa_xml().unwrap(), None);
+    }
+
+    #[test]
+    fn test_set_field_value() {
+        let doc = form_pdf();
+        doc.set_field_value("person.name", "Zoë").unwrap();
+        doc.set_field_value("agree", "Yes").unwrap();
+        assert!(doc.set_field_value("missing", "x").is_err());
+        let values: Vec<Option<String>> = doc.form_fields().unwrap().into_iter().map(|field| field.value).collect();
+        assert_eq!(values, vec![Some("Zoë".to_string()), Some("Yes".to_string())]);
+        // Stored as UTF-16, since it isn't ASCII
+        let name = doc.form_fields().unwrap().into_iter().find(|field| field.name == "person.name").unwrap();
+        let stored = doc.get_dict(name.id).unwrap()["V"].try_into_bytes().unwrap();
+        assert_eq!(stored, b"\xFE\xFF\x00Z\x00o\x00\xEB");
+        assert_eq!(doc.get_dict(ObjectId::new(4, 0)).unwrap()["AS"].try_into_string().unwrap().as_str(), "Yes");
+        let acro_form = doc.get_dict(ObjectId::new(1, 0)).unwrap()["AcroForm"].try_into_map().unwrap();
+        assert!(acro_form["NeedAppearances"].try_into_bool().unwrap());
+
+        // A state the widget has no appearance for turns it off
+        doc.set_field_value("agree", "Maybe").unwrap();
+        assert_eq!(doc.get_dict(ObjectId::new(4, 0)).unwrap()["AS"].try_into_string().unwrap().as_str(), "Off");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let mut doc = form_pdf();
+        doc.set_field_value("agree", "Yes").unwrap();
+        doc.flatten().unwrap();
+        let (bytes, _) = doc.file.write(&WriteOptions::default()).unwrap();
+        let doc = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert!(doc.form_fields().unwrap().is_empty());
+        let page = doc.get_dict(doc.page_id(0).unwrap()).unwrap();
+        assert_eq!(page["Annots"].try_into_array().unwrap().len(), 1);
+        let content = String::from_utf8(doc.page_content(0).unwrap()).unwrap();
+        assert_eq!(content, "q\n\n0 0 m 10 10 l S\nQ\n\
+                             q 1 w 102 105 m 104 102 l 108 108 l S Q\n\
+                             BT /FlattenHelv 10 Tf 102 706.5 Td (\\311va B) Tj ET\n");
+        assert_eq!(doc.page_text(0).unwrap(), "Éva B\n");
+    }
+}
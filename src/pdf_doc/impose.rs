@@ -0,0 +1,200 @@
+use std::fmt::Write;
+
+use super::*;
+use super::form::number;
+use crate::doc_tree::decode::PdfBinaryStream;
+
+impl PdfDoc {
+    /// Replace the pages with sheets each holding n of them in a grid, read left to right and top to bottom.
+    /// Sheets are the size of the first page, turned to landscape when the grid has more columns than rows, as
+    /// for 2-up.  Each page is drawn as a Form XObject, scaled to fit its cell and centered in it, and turned
+    /// by its /Rotate.  Annotations on the pages are left behind.
+    pub fn impose_nup(&mut self, n: usize) -> Result<()> {
+        if n == 0 {
+            Err(ErrorKind::DocTreeError("Can't put 0 pages on a sheet".to_string()))?
+        };
+        let rows = (1..=n).take_while(|rows| rows * rows <= n).filter(|rows| n.is_multiple_of(*rows)).last().unwrap();
+        let columns = n / rows;
+        let (width, height) = self.shown_size(0)?;
+        let (width, height) = if columns > rows { (width.max(height), width.min(height)) } else { (width, height) };
+        let sheets = (0..self.page_count()).map(Some)
+                                           .collect::<Vec<_>>()
+                                           .chunks(n)
+                                           .map(|pages| pages.to_vec())
+                                           .collect();
+        self.impose(sheets, columns, rows, width, height)
+    }
+
+    /// Reorder the pages for a saddle-stitched booklet: two pages side by side on each side of a sheet, so the
+    /// printed sheets folded in half and nested read in order.  Sheets are twice as wide as the first page, and
+    /// blank pages pad the end of the document to a multiple of 4.
+    pub fn booklet(&mut self) -> Result<()> {
+        let count = self.page_count();
+        let padded = count.div_ceil(4) * 4;
+        let page = |index: usize| if index < count { Some(index) } else { None };
+        let mut sides = Vec::new();
+        for sheet in 0..padded / 4 {
+            sides.push(vec![page(padded - 1 - 2 * sheet), page(2 * sheet)]);
+            sides.push(vec![page(2 * sheet + 1), page(padded - 2 - 2 * sheet)]);
+        }
+        let (width, height) = self.shown_size(0)?;
+        self.impose(sides, 2, 1, width * 2.0, height)
+    }
+
+    /// The width and height of a page's crop box as shown, after /Rotate.
+    fn shown_size(&self, page_index: usize) -> Result<(f32, f32)> {
+        let crop_box = self.crop_box(page_index)?;
+        Ok(match self.rotation(page_index)? {
+            90 | 270 => (crop_box.height(), crop_box.width()),
+            _ => (crop_box.width(), crop_box.height())
+        })
+    }
+
+    /// Replace the pages with new ones of the given size, each drawing the listed pages into a grid of cells,
+    /// with None leaving a cell blank.
+    fn impose(&mut self, sheets: Vec<Vec<Option<usize>>>, columns: usize, rows: usize, width: f32, height: f32)
+              -> Result<()> {
+        let pages_id = self.root.try_into_map()?.get("Pages").and_then(|pages| pages.reference_id()).ok_or_else(
+            || ErrorKind::DocTreeError("Page tree root is not an indirect object".to_string()))?;
+        let (cell_width, cell_height) = (width / columns as f32, height / rows as f32);
+        let cache = Rc::clone(&self.file.object_map);
+        let mut kids = Vec::new();
+        for sheet in sheets {
+            let mut content = String::new();
+            let mut xobjects = PdfMap::new();
+            for (cell, page_index) in sheet.into_iter().enumerate() {
+                let page_index = match page_index {
+                    Some(page_index) => page_index,
+                    None => continue
+                };
+                let crop_box = self.crop_box(page_index)?;
+                let (page_width, page_height) = self.shown_size(page_index)?;
+                let scale = (cell_width / page_width).min(cell_height / page_height);
+                let (column, row) = ((cell % columns) as f32, (rows - 1 - cell / columns) as f32);
+                let turned = Matrix::translation(-crop_box.x0, -crop_box.y0) * rotation(self.rotation(page_index)?);
+                let turned_box = turned.transform_rect(&crop_box);
+                let matrix = turned
+                    * Matrix::translation(-turned_box.x0, -turned_box.y0)
+                    * Matrix::scale(scale, scale)
+                    * Matrix::translation(column * cell_width + (cell_width - page_width * scale) / 2.0,
+                                          row * cell_height + (cell_height - page_height * scale) / 2.0);
+                let name = format!("P{}", cell + 1);
+                let form_id = cache.add_object(self.page_form(page_index)?);
+                xobjects.insert(name.clone(), Rc::new(cache.reference(form_id)));
+                writeln!(content, "q {} {} {} {} {} {} cm /{} Do Q", number(matrix.a), number(matrix.b),
+                         number(matrix.c), number(matrix.d), number(matrix.e), number(matrix.f), name).unwrap();
+            }
+            let contents = PdfBinaryStream::new(PdfMap::new(), content.into_bytes());
+            let contents_id = cache.add_object(PdfObject::new_binary_stream(contents));
+            let mut resources = PdfMap::new();
+            resources.insert("XObject".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(xobjects))));
+            let mut page = PdfMap::new();
+            page.insert("Type".to_string(), Rc::new(PdfObject::new_name("Page")));
+            page.insert("Parent".to_string(), Rc::new(cache.reference(pages_id)));
+            page.insert("MediaBox".to_string(), Rc::new(rect_object(&Rect::new(0.0, 0.0, width, height))));
+            page.insert("Resources".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(resources))));
+            page.insert("Contents".to_string(), Rc::new(cache.reference(contents_id)));
+            kids.push(Rc::new(cache.reference(cache.add_object(PdfObject::new_dictionary(Rc::new(page))))));
+        }
+        self.update_dict(pages_id, |pages| {
+            pages.insert("Count".to_string(), Rc::new(PdfObject::new_number_int(kids.len() as i64)));
+            pages.insert("Kids".to_string(), Rc::new(PdfObject::new_array(Rc::new(kids))));
+            // Attributes the old pages inherited would otherwise apply to the sheets
+            for key in &["MediaBox", "CropBox", "Rotate", "Resources"] {
+                pages.remove(key);
+            }
+        })?;
+        self.reload_page_tree()
+    }
+
+    /// A Form XObject drawing the crop box of a page, with the page's content and resources.
+    fn page_form(&self, page_index: usize) -> Result<PdfObject> {
+        let mut dict = PdfMap::new();
+        dict.insert("Type".to_string(), Rc::new(PdfObject::new_name("XObject")));
+        dict.insert("Subtype".to_string(), Rc::new(PdfObject::new_name("Form")));
+        dict.insert("BBox".to_string(), Rc::new(rect_object(&self.crop_box(page_index)?)));
+        if let Some(resources) = self.inherited_page_attribute(page_index, "Resources")? {
+            dict.insert("Resources".to_string(), resources);
+        };
+        Ok(PdfObject::new_binary_stream(PdfBinaryStream::new(dict, self.page_content(page_index)?)))
+    }
+}
+
+/// The clockwise turn of /Rotate, which is always a multiple of 90 degrees, without rounding errors.
+fn rotation(degrees: i32) -> Matrix {
+    match degrees {
+        90 => Matrix::new(0.0, -1.0, 1.0, 0.0, 0.0, 0.0),
+        180 => Matrix::new(-1.0, 0.0, 0.0, -1.0, 0.0, 0.0),
+        270 => Matrix::new(0.0, 1.0, -1.0, 0.0, 0.0, 0.0),
+        _ => Matrix::identity()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn sheet_content(doc: &PdfDoc, sheet: usize) -> String {
+        String::from_utf8(doc.page_content(sheet).unwrap()).unwrap()
+    }
+
+    /// The content of the pages drawn on a sheet, by resource name.
+    fn sheet_pages(doc: &PdfDoc, sheet: usize) -> Vec<(String, Vec<u8>)> {
+        let resources = doc.get_dict(doc.page_id(sheet).unwrap()).unwrap()["Resources"].try_into_map().unwrap();
+        resources["XObject"].try_into_map().unwrap().iter()
+                            .map(|(name, form)| (name.clone(), doc.get_stream_data(form.reference_id().unwrap())
+                                                                  .unwrap()))
+                            .collect()
+    }
+
+    #[test]
+    fn test_impose_nup() {
+        let mut builder = MiniPdfBuilder::new();
+        for page in 1..=5 {
+            builder.add_page(format!("{0} {0} m S", page).as_bytes(), TestFilter::None);
+        }
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        doc.impose_nup(4).unwrap();
+        assert_eq!(doc.page_count(), 2);
+        assert_eq!(doc.media_box(0).unwrap(), Rect::new(0.0, 0.0, 612.0, 792.0));
+        assert_eq!(sheet_content(&doc, 0), "q 0.5 0 0 0.5 0 396 cm /P1 Do Q\nq 0.5 0 0 0.5 306 396 cm /P2 Do Q\n\
+                                            q 0.5 0 0 0.5 0 0 cm /P3 Do Q\nq 0.5 0 0 0.5 306 0 cm /P4 Do Q\n");
+        assert_eq!(sheet_pages(&doc, 1), vec![("P1".to_string(), b"5 5 m S".to_vec())]);
+
+        // 2-up turns the sheet to landscape, and a rotated page is turned back upright in its cell
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page_with_entries(b"", TestFilter::None, "/Rotate 90");
+        builder.add_page(b"", TestFilter::None);
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        doc.impose_nup(2).unwrap();
+        assert_eq!(doc.media_box(0).unwrap(), Rect::new(0.0, 0.0, 792.0, 612.0));
+        assert_eq!(doc.rotation(0).unwrap(), 0);
+        assert_eq!(sheet_content(&doc, 0).lines().next().unwrap(), "q 0 -0.5 0.5 0 0 459 cm /P1 Do Q");
+        assert!(doc.impose_nup(0).is_err());
+    }
+
+    #[test]
+    fn test_booklet() {
+        let mut builder = MiniPdfBuilder::new();
+        for page in 1..=5 {
+            builder.add_page(format!("{0} {0} m S", page).as_bytes(), TestFilter::None);
+        }
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        doc.booklet().unwrap();
+        assert_eq!(doc.page_count(), 4);
+        assert_eq!(doc.media_box(0).unwrap(), Rect::new(0.0, 0.0, 1224.0, 792.0));
+        // Padded to 8 pages, the sides hold 8 and 1, 2 and 7, 6 and 3, then 4 and 5
+        let drawn = (0..4).map(|side| sheet_pages(&doc, side).into_iter().map(|(name, data)| format!(
+            "{}:{}", name, String::from_utf8(data).unwrap())).collect::<Vec<_>>()).collect::<Vec<_>>();
+        assert_eq!(drawn, vec![vec!["P2:1 1 m S"], vec!["P1:2 2 m S"], vec!["P2:3 3 m S"],
+                               vec!["P1:4 4 m S", "P2:5 5 m S"]]);
+        assert_eq!(sheet_content(&doc, 3), "q 1 0 0 1 0 0 cm /P1 Do Q\nq 1 0 0 1 612 0 cm /P2 Do Q\n");
+
+        let options = WriteOptions { garbage_collect: true, ..Default::default() };
+        let (bytes, report) = doc.write(&options).unwrap();
+        assert!(report.objects_dropped > 0);
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(reread.page_count(), 4);
+    }
+}
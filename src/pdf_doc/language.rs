@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use super::*;
+
+/// Primary language subtags of languages written right to left, to tell from a /Lang tag.
+const RIGHT_TO_LEFT_LANGUAGES: [&str; 11] = ["ar", "arc", "dv", "fa", "he", "iw", "ks", "ps", "syr", "ur", "yi"];
+/// Structure trees nested deeper than this are taken to be broken.
+const MAX_STRUCTURE_DEPTH: usize = 256;
+
+/// A structure element (spec 14.7.2) giving the language of its content with /Lang (spec 14.9.2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureLanguage {
+    /// The element's object, unless it's a direct dictionary
+    pub id: Option<ObjectId>,
+    /// The structure type, such as P or Span
+    pub structure_type: String,
+    /// A BCP 47 language tag, such as en-US or ar
+    pub language: String,
+    /// The page the element's content is on, from its /Pg or an ancestor's
+    pub page: Option<usize>,
+}
+
+impl StructureLanguage {
+    /// Whether the language is written right to left.
+    pub fn is_right_to_left(&self) -> bool {
+        is_right_to_left_language(&self.language)
+    }
+}
+
+/// Whether a language tag like he-IL names a language written right to left, such as Arabic or Hebrew.
+pub fn is_right_to_left_language(tag: &str) -> bool {
+    let primary = tag.split(['-', '_']).next().unwrap_or("").to_ascii_lowercase();
+    RIGHT_TO_LEFT_LANGUAGES.contains(&primary.as_str())
+}
+
+impl PdfDoc {
+    /// The natural language of the document, from the catalog's /Lang (spec 14.9.2.1).
+    pub fn language(&self) -> Result<Option<String>> {
+        match self.root.try_into_map()?.get("Lang") {
+            Some(lang) => Ok(Some(lang.try_into_text()?)),
+            None => Ok(None)
+        }
+    }
+
+    /// The structure elements of the document's structure tree that set a /Lang, in document order.  Elements
+    /// without one aren't listed: their language is that of the nearest listed ancestor, or failing that the
+    /// document's language.
+    pub fn structure_languages(&self) -> Result<Vec<StructureLanguage>> {
+        let mut languages = Vec::new();
+        if let Some(tree) = self.root.try_into_map()?.get("StructTreeRoot") {
+            let mut visited = HashSet::new();
+            self.collect_languages(tree, None, 0, &mut visited, &mut languages)?;
+        };
+        Ok(languages)
+    }
+
+    fn collect_languages(&self, element: &SharedObject, page: Option<usize>, depth: usize,
+                         visited: &mut HashSet<ObjectId>, languages: &mut Vec<StructureLanguage>) -> Result<()> {
+        if depth > MAX_STRUCTURE_DEPTH {
+            Err(ErrorKind::DocTreeError("Structure tree nests too deeply".to_string()))?
+        };
+        let id = element.reference_id();
+        if let Some(id) = id {
+            if !visited.insert(id) {
+                return Ok(());
+            };
+        };
+        // Kids are also marked-content sequences, given by MCID alone or as a dictionary, and object references
+        let dict = match element.try_into_map() {
+            Ok(dict) => dict,
+            Err(_) => return Ok(())
+        };
+        let page = match dict.get("Pg").and_then(|page| page.reference_id()) {
+            Some(page_id) => self.page_index_of(page_id).or(page),
+            None => page
+        };
+        if let (Some(structure_type), Some(lang)) = (dict.get("S"), dict.get("Lang")) {
+            languages.push(StructureLanguage {
+                id,
+                structure_type: structure_type.try_into_string()?.as_ref().clone(),
+                language: lang.try_into_text()?,
+                page,
+            });
+        };
+        match dict.get("K") {
+            Some(kids) if kids.is_array() => {
+                for kid in kids.try_into_array()?.iter() {
+                    self.collect_languages(kid, page, depth + 1, visited, languages)?;
+                }
+            },
+            Some(kid) => self.collect_languages(kid, page, depth + 1, visited, languages)?,
+            None => {}
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_languages() {
+        let mut builder = MiniPdfBuilder::new();
+        let page = builder.add_page(b"/P <</MCID 0>> BDC EMC /Span <</MCID 1>> BDC EMC", TestFilter::None);
+        let tree = builder.add_object("<< /Type /StructTreeRoot /K 6 0 R >>");
+        // The document element is listed twice, and the paragraph names it as a kid too
+        let document = builder.add_object("<< /Type /StructElem /S /Document /P 5 0 R /K [7 0 R 6 0 R] >>");
+        let paragraph = builder.add_object(format!(
+            "<< /Type /StructElem /S /P /P 6 0 R /Pg {} 0 R /Lang <FEFF00610072> /K [0 8 0 R 6 0 R] >>", page));
+        let span = builder.add_object("<< /Type /StructElem /S /Span /P 7 0 R /Lang (en-GB) \
+                                          /K << /Type /MCR /MCID 1 >> >>");
+        assert_eq!((tree, document, paragraph, span), (5, 6, 7, 8));
+        let builder = builder.with_catalog_entries("/Lang (he-IL) /StructTreeRoot 5 0 R");
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.language().unwrap().as_deref(), Some("he-IL"));
+        let languages = pdf.structure_languages().unwrap();
+        assert_eq!(languages, vec![
+            StructureLanguage { id: Some(ObjectId::new(7, 0)), structure_type: "P".to_string(),
+                                language: "ar".to_string(), page: Some(0) },
+            StructureLanguage { id: Some(ObjectId::new(8, 0)), structure_type: "Span".to_string(),
+                                language: "en-GB".to_string(), page: Some(0) },
+        ]);
+        assert!(languages[0].is_right_to_left() && !languages[1].is_right_to_left());
+        assert!(is_right_to_left_language("HE") && is_right_to_left_language("fa_IR"));
+        assert!(PdfDoc::create_pdf_from_bytes(MiniPdfBuilder::new().build()).unwrap().language().unwrap().is_none());
+    }
+}
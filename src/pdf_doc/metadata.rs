@@ -0,0 +1,287 @@
+use super::*;
+use crate::doc_tree::decode::PdfBinaryStream;
+
+/// Document information entries (spec 14.3.3) and the XMP properties (spec 14.3.2) that carry the same value.
+const XMP_PROPERTIES: [(&str, &str); 5] = [
+    ("Title", "dc:title"),
+    ("Author", "dc:creator"),
+    ("Subject", "dc:description"),
+    ("Keywords", "pdf:Keywords"),
+    ("ModDate", "xmp:ModifyDate"),
+];
+/// An XMP packet without properties, for documents that have no /Metadata yet.
+const EMPTY_XMP_PACKET: &str = "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+                                <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+                                <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+                                </rdf:RDF>\n\
+                                </x:xmpmeta>\n\
+                                <?xpacket end=\"w\"?>";
+
+impl PdfDoc {
+    /// Set the document's title, in both the information dictionary and the XMP metadata.
+    pub fn set_title(&mut self, title: &str) -> Result<()> {
+        self.set_info_text("Title", title)
+    }
+
+    /// Set the name of the person who wrote the document, in both the information dictionary and the XMP
+    /// metadata.
+    pub fn set_author(&mut self, author: &str) -> Result<()> {
+        self.set_info_text("Author", author)
+    }
+
+    /// Set what the document is about, in both the information dictionary and the XMP metadata.
+    pub fn set_subject(&mut self, subject: &str) -> Result<()> {
+        self.set_info_text("Subject", subject)
+    }
+
+    /// Set the keywords for the document, in both the information dictionary and the XMP metadata.
+    pub fn set_keywords(&mut self, keywords: &str) -> Result<()> {
+        self.set_info_text("Keywords", keywords)
+    }
+
+    /// Set when the document was last changed, as a PDF date (spec 7.9.4) such as D:20240131093000+01'00'.  The
+    /// XMP metadata gets the same time in its own form, 2024-01-31T09:30:00+01:00.
+    pub fn set_modification_date(&mut self, date: &str) -> Result<()> {
+        xmp_date(date)?;
+        self.set_info_text("ModDate", date)
+    }
+
+    /// The text of the XMP metadata stream the catalog's /Metadata gives, if there is one.
+    pub fn xmp_metadata(&self) -> Result<Option<String>> {
+        match self.root.try_into_map()?.get("Metadata").and_then(|metadata| metadata.reference_id()) {
+            Some(id) => Ok(Some(String::from_utf8_lossy(&self.get_stream_data(id)?).into_owned())),
+            None => Ok(None)
+        }
+    }
+
+    /// Set an entry of the information dictionary, adding the dictionary if the trailer has none, and bring the
+    /// XMP metadata into line with it.  The new metadata is made first, so that nothing is changed if that fails.
+    fn set_info_text(&mut self, key: &str, value: &str) -> Result<()> {
+        let mut info = self.info()?;
+        info.insert(key.to_string(), value.to_string());
+        let (metadata_id, metadata) = self.updated_metadata_stream(&info)?;
+        let catalog_id = self.catalog_id()?;
+        let value = Rc::new(PdfObject::new_text_string(value));
+        let info = self.file.retrieve_trailer()?.try_into_map()?.get("Info").cloned();
+        match info.as_ref().and_then(|info| info.reference_id()) {
+            Some(id) => self.update_dict(id, |info| { info.insert(key.to_string(), value); })?,
+            None => {
+                // A direct dictionary is moved into an object of its own, as the spec requires
+                let mut dict = match info {
+                    Some(info) => info.try_into_map()?.as_ref().clone(),
+                    None => PdfMap::new()
+                };
+                dict.insert(key.to_string(), value);
+                let id = self.file.object_map.add_object(PdfObject::new_dictionary(Rc::new(dict)));
+                let reference = Rc::new(self.file.object_map.reference(id));
+                self.file.set_trailer_entry("Info", reference)?;
+            }
+        };
+        let cache = &self.file.object_map;
+        match metadata_id {
+            Some(id) => cache.set_object(id, PdfObject::new_binary_stream(metadata)),
+            None => {
+                let reference = Rc::new(cache.reference(cache.add_object(PdfObject::new_binary_stream(metadata))));
+                self.update_dict(catalog_id, |catalog| { catalog.insert("Metadata".to_string(), reference); })?;
+            }
+        };
+        Ok(())
+    }
+
+    /// The catalog's XMP metadata stream with the properties that mirror information dictionary entries taken
+    /// from info, and its id, or a new stream if the catalog has none.
+    fn updated_metadata_stream(&self, info: &BTreeMap<String, String>) -> Result<(Option<ObjectId>, PdfBinaryStream)> {
+        match self.root.try_into_map()?.get("Metadata").and_then(|metadata| metadata.reference_id()) {
+            Some(id) => {
+                let attributes = self.get_object(id)?.try_into_binary_stream()?.attributes().clone();
+                let xmp = updated_xmp(&String::from_utf8_lossy(&self.get_stream_data(id)?), info);
+                Ok((Some(id), PdfBinaryStream::new(attributes, xmp.into_bytes())))
+            },
+            None => {
+                let mut attributes = PdfMap::new();
+                attributes.insert("Type".to_string(), Rc::new(PdfObject::new_name("Metadata")));
+                attributes.insert("Subtype".to_string(), Rc::new(PdfObject::new_name("XML")));
+                Ok((None, PdfBinaryStream::new(attributes, updated_xmp(EMPTY_XMP_PACKET, info).into_bytes())))
+            }
+        }
+    }
+}
+
+/// An XMP packet with the properties that mirror information dictionary entries taken out wherever they are,
+/// and given again from info in a description of their own.  Other properties are kept as they are.  A packet
+/// without an rdf:RDF element is replaced by a new one.  A /ModDate that isn't a valid PDF date is left out, since
+/// files in the wild often have one and it shouldn't stop the other properties being updated.
+fn updated_xmp(xmp: &str, info: &BTreeMap<String, String>) -> String {
+    let mut xmp = if xmp.contains("</rdf:RDF>") { xmp.to_string() } else { EMPTY_XMP_PACKET.to_string() };
+    let mut properties = String::new();
+    for (key, property) in XMP_PROPERTIES.iter() {
+        let name = regex::escape(property);
+        for pattern in [format!(r"(?s)\s*<{0}(?:\s[^>]*[^/>])?\s*>.*?</{0}\s*>", name),
+                        format!(r"\s*<{}(?:\s[^>]*)?/>", name),
+                        format!(r#"\s{}\s*=\s*(?:"[^"]*"|'[^']*')"#, name)] {
+            xmp = Regex::new(&pattern).unwrap().replace_all(&xmp, "").into_owned();
+        }
+        let value = match info.get(*key) {
+            Some(value) => value,
+            None => continue
+        };
+        let value = match *key {
+            "ModDate" => match xmp_date(value) {
+                Ok(date) => date,
+                Err(e) => {
+                    warn!("Leaving the modification date out of the XMP metadata: {}", e);
+                    continue;
+                }
+            },
+            _ => escape_xml(value)
+        };
+        let value = match *key {
+            "Title" | "Subject" => format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>", value),
+            "Author" => format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", value),
+            _ => value
+        };
+        properties.push_str(&format!("<{0}>{1}</{0}>\n", property, value));
+    }
+    // Descriptions left with nothing in them, including ones written here before
+    let empty = Regex::new(concat!(r"(?s)\s*<rdf:Description(?:\s[^>]*[^/>])?\s*>\s*</rdf:Description\s*>|",
+                                   r"\s*<rdf:Description(?:\s+(?:rdf:about|xmlns:[\w.-]+)\s*=\s*",
+                                   r#"(?:"[^"]*"|'[^']*'))*\s*/>"#)).unwrap();
+    xmp = empty.replace_all(&xmp, "").into_owned();
+    if !properties.is_empty() {
+        let description = format!("<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+                                   xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+                                   xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n{}</rdf:Description>\n", properties);
+        let end = xmp.rfind("</rdf:RDF>").unwrap();
+        xmp.insert_str(end, &description);
+    };
+    xmp
+}
+
+/// A PDF date such as D:20240131093000+01'00' in the ISO 8601 form XMP uses, 2024-01-31T09:30:00+01:00.  Only
+/// the year is required, and the time zone is left out if the date doesn't give one.
+fn xmp_date(date: &str) -> Result<String> {
+    let pattern = Regex::new(concat!(r"^(?:D:)?(\d{4})(?:(\d{2})(?:(\d{2})(?:(\d{2})(?:(\d{2})(\d{2})?)?)?)?)?",
+                                     r"(Z(?:00'?(?:00'?)?)?|([+-])(\d{2})(?:'(\d{2})'?)?)?$")).unwrap();
+    let invalid = || Error::from(ErrorKind::DocTreeError(format!("Invalid date {}", date)));
+    let captures = pattern.captures(date).ok_or_else(invalid)?;
+    let field = |i: usize| captures.get(i).map(|field| field.as_str().parse::<u32>().unwrap());
+    let ranges = [(2, 1, 12), (3, 1, 31), (4, 0, 23), (5, 0, 59), (6, 0, 59), (9, 0, 23), (10, 0, 59)];
+    if ranges.iter().any(|&(i, min, max)| matches!(field(i), Some(value) if value < min || value > max)) {
+        return Err(invalid());
+    };
+    let mut xmp = captures[1].to_string();
+    if let Some(month) = captures.get(2) {
+        xmp.push_str(&format!("-{}", month.as_str()));
+    };
+    if let Some(day) = captures.get(3) {
+        xmp.push_str(&format!("-{}", day.as_str()));
+    };
+    if let Some(hour) = captures.get(4) {
+        // XMP has no time without minutes
+        xmp.push_str(&format!("T{}:{}", hour.as_str(), captures.get(5).map_or("00", |minute| minute.as_str())));
+        if let Some(second) = captures.get(6) {
+            xmp.push_str(&format!(":{}", second.as_str()));
+        };
+        match (captures.get(7), captures.get(8)) {
+            (_, Some(sign)) => xmp.push_str(&format!("{}{}:{}", sign.as_str(), &captures[9],
+                                                     captures.get(10).map_or("00", |minute| minute.as_str()))),
+            (Some(_), None) => xmp.push('Z'),
+            (None, None) => {}
+        };
+    };
+    Ok(xmp)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_xmp_dates() {
+        assert_eq!(xmp_date("D:20240131093000+01'00'").unwrap(), "2024-01-31T09:30:00+01:00");
+        assert_eq!(xmp_date("D:20240131093000Z00'00'").unwrap(), "2024-01-31T09:30:00Z");
+        assert_eq!(xmp_date("D:2024013109-05").unwrap(), "2024-01-31T09:00-05:00");
+        assert_eq!(xmp_date("202401").unwrap(), "2024-01");
+        assert_eq!(xmp_date("D:20240131093000").unwrap(), "2024-01-31T09:30:00");
+        for invalid in ["", "D:24", "D:20241301", "D:20240131250000", "January 2024"] {
+            assert!(xmp_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_metadata_setters() {
+        // A direct information dictionary and no XMP metadata
+        let builder = MiniPdfBuilder::new().with_trailer_entries("/Info << /Creator (Tests) /Title (Old) >>");
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        pdf.set_title("Caf\u{E9} & <Bar>").unwrap();
+        pdf.set_author("A. Writer").unwrap();
+        pdf.set_modification_date("D:20240131093000+01'00'").unwrap();
+        assert!(pdf.set_modification_date("yesterday").is_err());
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let pdf = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let info = pdf.info().unwrap();
+        assert_eq!(info["Title"], "Caf\u{E9} & <Bar>");
+        assert_eq!(info["Author"], "A. Writer");
+        assert_eq!(info["Creator"], "Tests");
+        assert_eq!(info["ModDate"], "D:20240131093000+01'00'");
+        let xmp = pdf.xmp_metadata().unwrap().unwrap();
+        assert!(xmp.contains("<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">Caf\u{E9} &amp; &lt;Bar&gt;</rdf:li>"));
+        assert!(xmp.contains("<dc:creator><rdf:Seq><rdf:li>A. Writer</rdf:li></rdf:Seq></dc:creator>"));
+        assert!(xmp.contains("<xmp:ModifyDate>2024-01-31T09:30:00+01:00</xmp:ModifyDate>"));
+        assert_eq!(xmp.matches("<rdf:Description").count(), 1);
+
+        // An indirect dictionary, and XMP giving the title both as an attribute and an element
+        let mut builder = MiniPdfBuilder::new();
+        let info = builder.add_object("<< /Title (Old) /Keywords (a, b) >>");
+        let xmp = concat!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\" xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" pdf:Keywords=\"a, b\"/>",
+            "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" ",
+            "xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" xmp:CreatorTool=\"Tool\">",
+            "<dc:title>\n<rdf:Alt><rdf:li xml:lang=\"x-default\">Old</rdf:li></rdf:Alt></dc:title>",
+            "<dc:format>application/pdf</dc:format></rdf:Description></rdf:RDF></x:xmpmeta>");
+        let metadata = builder.add_stream(xmp.as_bytes(), TestFilter::Flate, "/Type /Metadata /Subtype /XML");
+        let builder = builder.with_trailer_entries(&format!("/Info {} 0 R", info))
+                             .with_catalog_entries(&format!("/Metadata {} 0 R", metadata));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        pdf.set_title("New").unwrap();
+        pdf.set_subject("Tests").unwrap();
+        pdf.set_keywords("c").unwrap();
+        let info_id = ObjectId::new(info, 0);
+        let trailer = pdf.file.retrieve_trailer().unwrap().try_into_map().unwrap();
+        assert_eq!(trailer["Info"].reference_id(), Some(info_id));
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let pdf = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let info = pdf.info().unwrap();
+        assert_eq!((info["Title"].as_str(), info["Subject"].as_str(), info["Keywords"].as_str()),
+                   ("New", "Tests", "c"));
+        let xmp = pdf.xmp_metadata().unwrap().unwrap();
+        assert!(!xmp.contains("Old") && !xmp.contains("a, b"));
+        assert!(xmp.contains("xmp:CreatorTool=\"Tool\"") && xmp.contains("<dc:format>application/pdf</dc:format>"));
+        assert!(xmp.contains("<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">Tests</rdf:li>"));
+        assert!(xmp.contains("<pdf:Keywords>c</pdf:Keywords>"));
+        assert_eq!(xmp.matches("<rdf:Description").count(), 2);
+
+        // A date in the file that isn't a PDF date is left out of the XMP rather than stopping the setters
+        let builder = MiniPdfBuilder::new().with_trailer_entries("/Info << /ModDate (Tue Jan 30 2024) >>");
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        pdf.set_title("Dated").unwrap();
+        let xmp = pdf.xmp_metadata().unwrap().unwrap();
+        assert!(xmp.contains("Dated") && !xmp.contains("ModifyDate"));
+        assert_eq!(pdf.info().unwrap()["ModDate"], "Tue Jan 30 2024");
+
+        // Metadata that can't be updated leaves the information dictionary as it was
+        let mut builder = MiniPdfBuilder::new();
+        let metadata = builder.add_object("<< /Type /Metadata >>");
+        let builder = builder.with_trailer_entries("/Info << /Title (Old) >>")
+                             .with_catalog_entries(&format!("/Metadata {} 0 R", metadata));
+        let mut pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(pdf.set_title("New").is_err());
+        assert_eq!(pdf.info().unwrap()["Title"], "Old");
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::btree_map::Entry;
+use std::collections::HashSet;
+
+use super::*;
+
+/// A dictionary to edit: an object, and the keys leading from it through direct dictionaries to the one holding
+/// the resource names.
+type Target = (ObjectId, Vec<String>);
+/// The paths to dictionaries within an object and the names to remove from each.
+type Edits = Vec<(Vec<String>, BTreeSet<String>)>;
+
+impl PdfDoc {
+    /// Copies of the page tree nodes and resource dictionaries with the resources that no page uses removed, by
+    /// object id, and how many resource entries that removes.  A resource dictionary shared by several pages
+    /// keeps whatever any of them uses, and one that something other than the page tree refers to, like a form
+    /// or an annotation appearance, is left alone.
+    pub(super) fn unused_resource_edits(&self) -> Result<(HashMap<ObjectId, SharedObject>, usize)> {
+        let mut unused: BTreeMap<Target, BTreeSet<String>> = BTreeMap::new();
+        // Who refers to the indirect resource dictionaries among the targets, as far as the page tree goes
+        let mut referrers: HashMap<ObjectId, HashSet<ObjectId>> = HashMap::new();
+        for page_index in 0..self.page_count() {
+            let (node_id, resources) = match self.resources_owner(self.page_id(page_index)?)? {
+                Some(owner) => owner,
+                None => continue
+            };
+            let usage = self.page_resource_usage(page_index)?;
+            let (container, prefix) = match resources.reference_id() {
+                Some(id) => {
+                    referrers.entry(id).or_default().insert(node_id);
+                    (id, Vec::new())
+                },
+                None => (node_id, vec!["Resources".to_string()])
+            };
+            let resources = resources.try_into_map()?;
+            for category in &RESOURCE_CATEGORIES {
+                let entries = match resources.get(category) {
+                    Some(entries) => entries,
+                    None => continue
+                };
+                let target = match entries.reference_id() {
+                    Some(id) => {
+                        referrers.entry(id).or_default().insert(container);
+                        (id, Vec::new())
+                    },
+                    None => (container, prefix.iter().cloned().chain(Some(category.to_string())).collect())
+                };
+                let names = usage.category(category)
+                                 .filter(|resource| !resource.used)
+                                 .map(|resource| resource.name.clone())
+                                 .collect::<BTreeSet<_>>();
+                match unused.entry(target) {
+                    Entry::Vacant(entry) => { entry.insert(names); },
+                    Entry::Occupied(mut entry) => entry.get_mut().retain(|name| names.contains(name))
+                };
+            }
+        }
+
+        let mut reference_counts: HashMap<ObjectId, usize> = HashMap::new();
+        self.walk(|_id, obj| {
+            for id in obj.references().into_iter().collect::<HashSet<_>>() {
+                *reference_counts.entry(id).or_default() += 1;
+            }
+        })?;
+        let mut edits: BTreeMap<ObjectId, Edits> = BTreeMap::new();
+        for ((id, path), names) in unused {
+            let shared_elsewhere = referrers.get(&id).is_some_and(|known| {
+                reference_counts.get(&id).copied().unwrap_or(0) > known.len()
+            });
+            if !names.is_empty() && !shared_elsewhere {
+                edits.entry(id).or_default().push((path, names));
+            };
+        }
+        let mut replacements = HashMap::new();
+        let mut removed = 0;
+        for (id, paths) in edits {
+            let mut dict = self.get_dict(id)?.as_ref().clone();
+            for (path, names) in paths {
+                removed += remove_entries(&mut dict, &path, &names)?;
+            }
+            replacements.insert(id, Rc::new(PdfObject::new_dictionary(Rc::new(dict))));
+        }
+        Ok((replacements, removed))
+    }
+
+    /// The page tree node a page takes its /Resources from, the page itself or an ancestor, and the resources.
+    fn resources_owner(&self, page_id: ObjectId) -> Result<Option<(ObjectId, SharedObject)>> {
+        let mut node_id = page_id;
+        // Bound the search in case of a cyclic tree
+        for _ in 0..64 {
+            let node = self.get_dict(node_id)?;
+            if let Some(resources) = node.get("Resources") {
+                return Ok(Some((node_id, Rc::clone(resources))));
+            };
+            node_id = match node.get("Parent").and_then(|parent| parent.reference_id()) {
+                Some(parent_id) => parent_id,
+                None => return Ok(None)
+            };
+        }
+        Ok(None)
+    }
+}
+
+/// Remove names from the dictionary found by following path from dict, returning how many were there.
+fn remove_entries(dict: &mut PdfMap, path: &[String], names: &BTreeSet<String>) -> Result<usize> {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Ok(names.iter().filter(|name| dict.remove(name).is_some()).count())
+    };
+    let mut inner = match dict.get(key) {
+        Some(inner) => inner.try_into_map()?.as_ref().clone(),
+        None => return Ok(0)
+    };
+    let removed = remove_entries(&mut inner, rest, names)?;
+    dict.insert(key.clone(), Rc::new(PdfObject::new_dictionary(Rc::new(inner))));
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_remove_unused_resources() {
+        let mut builder = MiniPdfBuilder::new();
+        let used_font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>");
+        let unused_font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Symbol >>");
+        let image = builder.add_stream(&[0; 64], TestFilter::None,
+                                       "/Type /XObject /Subtype /Image /Width 8 /Height 8 /BitsPerComponent 8");
+        // Both pages share one resource dictionary, so only what neither uses can go
+        let resources = builder.add_object(format!("<< /Font << /F1 {} 0 R /F2 {} 0 R >> /XObject << /Im1 {} 0 R >> \
+                                                    /ExtGState << /GS0 << /CA 0.5 >> >> >>",
+                                                   used_font, unused_font, image));
+        let entries = format!("/Resources {} 0 R", resources);
+        builder.add_page_with_entries(b"BT /F1 12 Tf (a) Tj ET", TestFilter::None, &entries);
+        builder.add_page_with_entries(b"/GS0 gs", TestFilter::None, &entries);
+        // A direct resource dictionary on the page itself
+        builder.add_page_with_entries(b"", TestFilter::None, &format!("/Resources << /XObject << /Im1 {} 0 R >> >>",
+                                                                      image));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let options = WriteOptions { remove_unused_resources: true, ..Default::default() };
+        let (bytes, report) = pdf.write(&options).unwrap();
+        assert_eq!(report.resources_removed, 3);
+        // The unused font and the image go with the references to them
+        assert_eq!(report.objects_dropped, 2);
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let names = |page_index: usize| reread.page_resource_usage(page_index).unwrap().resources.iter()
+                                              .map(|resource| format!("{}/{}", resource.category, resource.name))
+                                              .collect::<Vec<_>>();
+        assert_eq!(names(0), vec!["ExtGState/GS0", "Font/F1"]);
+        assert_eq!(names(2), Vec::<String>::new());
+        assert_eq!(reread.page_text(0).unwrap(), "a\n");
+
+        // Resources a form's content shares with the page are kept
+        let mut builder = MiniPdfBuilder::new();
+        let states = builder.add_object("<< /GS0 << /CA 0.5 >> >>");
+        let form = builder.add_stream(b"/GS0 gs", TestFilter::None,
+                                      &format!("/Type /XObject /Subtype /Form /BBox [0 0 1 1] \
+                                                /Resources << /ExtGState {} 0 R >>", states));
+        builder.add_page_with_entries(b"/Fm1 Do", TestFilter::None,
+                                      &format!("/Resources << /XObject << /Fm1 {} 0 R >> /ExtGState {} 0 R >>", form,
+                                               states));
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let (_, report) = pdf.write(&options).unwrap();
+        assert_eq!(report.resources_removed, 0);
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashSet;
+
+use super::*;
+use super::action::name_tree_entries;
+
+/// Most levels of outline followed, so that cyclic /First links end.
+const MAX_OUTLINE_DEPTH: usize = 64;
+
+/// An item of the document outline (spec 12.3.3), the bookmarks viewers list beside the pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub id: ObjectId,
+    pub title: String,
+    /// Where the item goes, from its /Dest or a GoTo action, if it goes anywhere in this document
+    pub destination: Option<Destination>,
+    /// Whether the item's children are shown
+    pub open: bool,
+    pub children: Vec<Bookmark>,
+}
+
+impl PdfDoc {
+    /// The top-level items of the document outline, with their children.
+    pub fn outline(&self) -> Result<Vec<Bookmark>> {
+        match self.outline_root()? {
+            Some(root) => self.bookmarks(root, 0),
+            None => Ok(Vec::new())
+        }
+    }
+
+    fn bookmarks(&self, parent: ObjectId, depth: usize) -> Result<Vec<Bookmark>> {
+        if depth > MAX_OUTLINE_DEPTH {
+            Err(ErrorKind::DocTreeError("Outline too deep or cyclic".to_string()))?
+        };
+        let mut bookmarks = Vec::new();
+        for id in self.outline_children(parent)? {
+            let dict = self.get_dict(id)?;
+            let destination = match (dict.get("Dest"), dict.get("A")) {
+                (Some(destination), _) => Some(self.destination(destination)?),
+                (None, Some(action)) => match self.action(&*action.try_into_map()?)? {
+                    Action::GoTo(destination) => Some(destination),
+                    _ => None
+                },
+                (None, None) => None
+            };
+            bookmarks.push(Bookmark {
+                id,
+                title: dict.get("Title").map_or(Ok(String::new()), |title| title.try_into_text())?,
+                destination,
+                open: outline_count(&dict)? > 0,
+                children: self.bookmarks(id, depth + 1)?,
+            });
+        }
+        Ok(bookmarks)
+    }
+
+    /// Add an item at the end of the top level of the outline, going to the page at page_index scrolled to
+    /// put y at the top of the window, and return its id.  The outline is made if the document has none.
+    pub fn add_bookmark(&self, title: &str, page_index: usize, y: f32) -> Result<ObjectId> {
+        let root = match self.outline_root()? {
+            Some(root) => root,
+            None => {
+                let mut outlines = PdfMap::new();
+                outlines.insert("Type".to_string(), Rc::new(PdfObject::new_name("Outlines")));
+                let root = self.file.object_map.add_object(PdfObject::new_dictionary(Rc::new(outlines)));
+                let reference = Rc::new(self.file.object_map.reference(root));
+                self.update_dict(self.catalog_id()?, |catalog| { catalog.insert("Outlines".to_string(), reference); })?;
+                root
+            }
+        };
+        self.add_child_bookmark(root, title, page_index, y)
+    }
+
+    /// As add_bookmark, adding the item after the children of another item, which is shown open if it had
+    /// none before.
+    pub fn add_child_bookmark(&self, parent: ObjectId, title: &str, page_index: usize, y: f32) -> Result<ObjectId> {
+        let cache = &self.file.object_map;
+        let last = self.get_dict(parent)?.get("Last").and_then(|last| last.reference_id());
+        let mut item = PdfMap::new();
+        item.insert("Title".to_string(), Rc::new(PdfObject::new_text_string(title)));
+        item.insert("Parent".to_string(), Rc::new(cache.reference(parent)));
+        item.insert("Dest".to_string(), Rc::new(self.page_destination(page_index, y)?));
+        if let Some(last) = last {
+            item.insert("Prev".to_string(), Rc::new(cache.reference(last)));
+        };
+        let id = cache.add_object(PdfObject::new_dictionary(Rc::new(item)));
+        self.update_dict(last.unwrap_or(parent), |dict| {
+            let key = if last.is_some() { "Next" } else { "First" };
+            dict.insert(key.to_string(), Rc::new(cache.reference(id)));
+        })?;
+        self.update_dict(parent, |dict| { dict.insert("Last".to_string(), Rc::new(cache.reference(id))); })?;
+        self.recount_outline()?;
+        Ok(id)
+    }
+
+    /// Take an item and its children out of the outline.
+    pub fn remove_bookmark(&self, id: ObjectId) -> Result<()> {
+        let dict = self.get_dict(id)?;
+        let parent = dict.get("Parent").and_then(|parent| parent.reference_id()).ok_or_else(|| {
+            ErrorKind::DocTreeError(format!("{} is not an outline item", id))
+        })?;
+        let (prev, next) = (dict.get("Prev").cloned(), dict.get("Next").cloned());
+        let link = |target: Option<ObjectId>, key: &str, value: &Option<SharedObject>| -> Result<()> {
+            self.update_dict(target.unwrap_or(parent), |dict| match value {
+                Some(value) => { dict.insert(key.to_string(), Rc::clone(value)); },
+                None => { dict.remove(key); }
+            })
+        };
+        let prev_id = prev.as_ref().and_then(|prev| prev.reference_id());
+        let next_id = next.as_ref().and_then(|next| next.reference_id());
+        link(prev_id, if prev_id.is_some() { "Next" } else { "First" }, &next)?;
+        link(next_id, if next_id.is_some() { "Prev" } else { "Last" }, &prev)?;
+        self.recount_outline()
+    }
+
+    /// Change the title of an outline item.
+    pub fn set_bookmark_title(&self, id: ObjectId, title: &str) -> Result<()> {
+        self.update_dict(id, |dict| { dict.insert("Title".to_string(), Rc::new(PdfObject::new_text_string(title))); })
+    }
+
+    /// Send an outline item to the page at page_index, as for add_bookmark, replacing any action it had.
+    pub fn set_bookmark_destination(&self, id: ObjectId, page_index: usize, y: f32) -> Result<()> {
+        let destination = Rc::new(self.page_destination(page_index, y)?);
+        self.update_dict(id, |dict| {
+            dict.remove("A");
+            dict.insert("Dest".to_string(), destination);
+        })
+    }
+
+    /// Set the named destination name to go to the page at page_index, as for add_bookmark.  Names in the
+    /// catalog's /Dests dictionary are changed there, and others go in the /Names /Dests name tree.
+    pub fn set_named_destination(&self, name: &str, page_index: usize, y: f32) -> Result<()> {
+        let destination = self.page_destination(page_index, y)?;
+        self.edit_named_destinations(name, Some(destination))?;
+        Ok(())
+    }
+
+    /// Remove a named destination, returning whether there was one.
+    pub fn remove_named_destination(&self, name: &str) -> Result<bool> {
+        self.edit_named_destinations(name, None)
+    }
+
+    /// Set or remove a named destination, returning whether the name was there before.  The name tree is
+    /// written out again as a single node.
+    fn edit_named_destinations(&self, name: &str, destination: Option<PdfObject>) -> Result<bool> {
+        let catalog_id = self.catalog_id()?;
+        let catalog = self.get_dict(catalog_id)?;
+        let mut found = false;
+        // Lookups try the /Dests dictionary of PDF 1.1 first
+        if let Some(dests) = catalog.get("Dests") {
+            if dests.try_into_map()?.contains_key(name) {
+                let mut dict = dests.try_into_map()?.as_ref().clone();
+                match &destination {
+                    Some(destination) => {
+                        dict.insert(name.to_string(), Rc::new(destination.clone()));
+                    },
+                    None => { dict.remove(name); }
+                };
+                let dict = PdfObject::new_dictionary(Rc::new(dict));
+                match dests.reference_id() {
+                    Some(id) => self.file.object_map.set_object(id, dict),
+                    None => self.update_dict(catalog_id, |catalog| {
+                        catalog.insert("Dests".to_string(), Rc::new(dict));
+                    })?
+                };
+                if destination.is_some() {
+                    return Ok(true);
+                };
+                found = true;
+            };
+        };
+
+        let names = catalog.get("Names").map(|names| names.try_into_map()).transpose()?;
+        let mut entries = BTreeMap::new();
+        if let Some(tree) = names.as_ref().and_then(|names| names.get("Dests")) {
+            name_tree_entries(tree, &mut entries, 0)?;
+        };
+        let removing = destination.is_none();
+        let existed = match destination {
+            Some(destination) => entries.insert(name.as_bytes().to_vec(), Rc::new(destination)).is_some(),
+            None => entries.remove(name.as_bytes()).is_some()
+        };
+        if removing && !existed {
+            return Ok(found);
+        };
+        let mut array = Vec::new();
+        for (key, value) in entries {
+            array.push(Rc::new(match String::from_utf8(key) {
+                Ok(key) if key.is_ascii() => PdfObject::new_char_string(key),
+                Ok(key) => PdfObject::new_byte_string(key.as_bytes()),
+                Err(error) => PdfObject::new_byte_string(error.as_bytes())
+            }));
+            array.push(value);
+        }
+        let mut tree = PdfMap::new();
+        tree.insert("Names".to_string(), Rc::new(PdfObject::new_array(Rc::new(array))));
+        let cache = &self.file.object_map;
+        let tree = Rc::new(cache.reference(cache.add_object(PdfObject::new_dictionary(Rc::new(tree)))));
+        match catalog.get("Names").and_then(|names| names.reference_id()) {
+            Some(names_id) => self.update_dict(names_id, |names| { names.insert("Dests".to_string(), tree); })?,
+            None => {
+                let mut names = names.map_or_else(PdfMap::new, |names| names.as_ref().clone());
+                names.insert("Dests".to_string(), tree);
+                self.update_dict(catalog_id, |catalog| {
+                    catalog.insert("Names".to_string(), Rc::new(PdfObject::new_dictionary(Rc::new(names))));
+                })?;
+            }
+        };
+        Ok(found || existed)
+    }
+
+    fn outline_root(&self) -> Result<Option<ObjectId>> {
+        Ok(self.root.try_into_map()?.get("Outlines").and_then(|outlines| outlines.reference_id()))
+    }
+
+    /// The items linked from an outline item or the outline root through /First and /Next.
+    fn outline_children(&self, parent: ObjectId) -> Result<Vec<ObjectId>> {
+        let mut children = Vec::new();
+        let mut seen = HashSet::new();
+        let mut next = self.get_dict(parent)?.get("First").and_then(|first| first.reference_id());
+        while let Some(id) = next {
+            if !seen.insert(id) {
+                Err(ErrorKind::DocTreeError(format!("Outline items after {} form a cycle", parent)))?
+            };
+            children.push(id);
+            next = self.get_dict(id)?.get("Next").and_then(|next| next.reference_id());
+        }
+        Ok(children)
+    }
+
+    /// Set /Count throughout the outline after items are added or removed.
+    fn recount_outline(&self) -> Result<()> {
+        if let Some(root) = self.outline_root()? {
+            self.recount(root, true, 0)?;
+        };
+        Ok(())
+    }
+
+    /// Set the /Count of an outline item, or of the root, from its children, keeping whether it's open, and
+    /// return how many of its descendants are shown when it's open.
+    fn recount(&self, id: ObjectId, is_root: bool, depth: usize) -> Result<i64> {
+        if depth > MAX_OUTLINE_DEPTH {
+            Err(ErrorKind::DocTreeError("Outline too deep or cyclic".to_string()))?
+        };
+        let mut shown = 0;
+        let children = self.outline_children(id)?;
+        for child in &children {
+            let below = self.recount(*child, false, depth + 1)?;
+            shown += 1 + if outline_count(&*self.get_dict(*child)?)? > 0 { below } else { 0 };
+        }
+        let dict = self.get_dict(id)?;
+        // Items without a count yet are shown open
+        let count = match (is_root, children.is_empty(), outline_count(&dict)? < 0) {
+            (true, _, _) => Some(shown),
+            (false, true, _) => None,
+            (false, false, closed) => Some(if closed { -shown } else { shown })
+        };
+        let current = dict.get("Count").map(|count| count.try_into_int()).transpose()?.map(i64::from);
+        if current != count {
+            self.update_dict(id, |dict| match count {
+                Some(count) => { dict.insert("Count".to_string(), Rc::new(PdfObject::new_number_int(count))); },
+                None => { dict.remove("Count"); }
+            })?;
+        };
+        Ok(shown)
+    }
+
+    /// An explicit destination showing the page at page_index with y at the top of the window, keeping the
+    /// horizontal position and zoom.
+    pub(super) fn page_destination(&self, page_index: usize, y: f32) -> Result<PdfObject> {
+        let null = || Rc::new(PdfObject::Actual(Null));
+        Ok(PdfObject::new_array(Rc::new(vec![
+            Rc::new(self.file.object_map.reference(self.page_id(page_index)?)),
+            Rc::new(PdfObject::new_name("XYZ")),
+            null(),
+            Rc::new(PdfObject::new_number_float(y)),
+            null(),
+        ])))
+    }
+}
+
+/// An outline item's /Count, or 0 if it has none.
+fn outline_count(dict: &PdfMap) -> Result<i32> {
+    dict.get("Count").map_or(Ok(0), |count| count.try_into_int())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    fn titles(bookmarks: &[Bookmark]) -> Vec<String> {
+        bookmarks.iter().map(|bookmark| {
+            let children = titles(&bookmark.children);
+            if children.is_empty() { bookmark.title.clone() } else { format!("{} {:?}", bookmark.title, children) }
+        }).collect()
+    }
+
+    #[test]
+    fn test_outline_editing() {
+        let mut builder = MiniPdfBuilder::new();
+        for _ in 0..3 {
+            builder.add_page(b"", TestFilter::None);
+        }
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(pdf.outline().unwrap().is_empty());
+
+        let first = pdf.add_bookmark("Chapter 1", 0, 700.0).unwrap();
+        let second = pdf.add_bookmark("Chapter 2", 1, 500.0).unwrap();
+        let section = pdf.add_child_bookmark(first, "Section 1.1", 0, 400.0).unwrap();
+        pdf.add_child_bookmark(first, "Section 1.2", 2, 300.0).unwrap();
+        pdf.add_child_bookmark(section, "Note", 0, 350.0).unwrap();
+        pdf.set_bookmark_title(second, "Résumé").unwrap();
+        pdf.set_bookmark_destination(second, 2, 72.0).unwrap();
+        let outline = pdf.outline().unwrap();
+        assert_eq!(titles(&outline), vec![r#"Chapter 1 ["Section 1.1 [\"Note\"]", "Section 1.2"]"#, "Résumé"]);
+        let root = pdf.outline_root().unwrap().unwrap();
+        assert_eq!(outline_count(&pdf.get_dict(root).unwrap()).unwrap(), 5);
+        assert_eq!(outline_count(&pdf.get_dict(first).unwrap()).unwrap(), 3);
+
+        // Removing the first and the only item of a level
+        pdf.remove_bookmark(section).unwrap();
+        pdf.remove_bookmark(second).unwrap();
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let outline = reread.outline().unwrap();
+        assert_eq!(titles(&outline), vec![r#"Chapter 1 ["Section 1.2"]"#]);
+        assert!(outline[0].open);
+        assert_eq!(outline[0].children[0].destination, Some(Destination::Explicit {
+            page: Some(2), fit: "XYZ".to_string(), params: vec![None, Some(300.0), None]
+        }));
+        let root = reread.outline_root().unwrap().unwrap();
+        assert_eq!(outline_count(&reread.get_dict(root).unwrap()).unwrap(), 2);
+
+        let added = reread.add_bookmark("Ré", 1, 0.0).unwrap();
+        assert_eq!(reread.outline().unwrap()[1].title, "Ré");
+        reread.remove_bookmark(outline[0].id).unwrap();
+        reread.remove_bookmark(added).unwrap();
+        assert!(reread.outline().unwrap().is_empty());
+        assert_eq!(outline_count(&reread.get_dict(root).unwrap()).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_named_destination_editing() {
+        let mut builder = MiniPdfBuilder::new().with_catalog_entries(
+            "/Dests << /old [3 0 R /Fit] >> /Names << /Dests << /Kids [<< /Limits [(a) (m)] \
+             /Names [(intro) [4 0 R /Fit]] >> << /Limits [(n) (z)] /Names [(summary) [6 0 R /FitH 700]] >>] >> >>");
+        builder.add_page(b"", TestFilter::None);
+        builder.add_page(b"", TestFilter::None);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        pdf.set_named_destination("appendix", 1, 100.0).unwrap();
+        pdf.set_named_destination("intro", 1, 200.0).unwrap();
+        pdf.set_named_destination("old", 1, 300.0).unwrap();
+        assert!(pdf.remove_named_destination("summary").unwrap());
+        assert!(!pdf.remove_named_destination("missing").unwrap());
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let at = |y: f32| Some(Destination::Explicit {
+            page: Some(1), fit: "XYZ".to_string(), params: vec![None, Some(y), None]
+        });
+        assert_eq!(reread.named_destination("appendix").unwrap(), at(100.0));
+        assert_eq!(reread.named_destination("intro").unwrap(), at(200.0));
+        assert_eq!(reread.named_destination("old").unwrap(), at(300.0));
+        assert_eq!(reread.named_destination("summary").unwrap(), None);
+
+        // A document with no names gets a name tree
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"", TestFilter::None);
+        let pdf = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(!pdf.remove_named_destination("start").unwrap());
+        assert!(pdf.root.try_into_map().unwrap().get("Names").is_none());
+        pdf.set_named_destination("start", 0, 792.0).unwrap();
+        assert!(matches!(pdf.named_destination("start").unwrap(), Some(Destination::Explicit { page: Some(0), .. })));
+    }
+}
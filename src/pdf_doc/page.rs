@@ -0,0 +1,480 @@
+use super::*;
+use crate::doc_tree::decode::PdfBinaryStream;
+
+/// A unit of length for page sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Points,
+    Millimeters,
+    Inches,
+}
+
+impl LengthUnit {
+    /// The number of points, 1/72 of an inch, in one of this unit.
+    pub fn points(&self) -> f32 {
+        match self {
+            LengthUnit::Points => 1.0,
+            LengthUnit::Millimeters => 72.0 / 25.4,
+            LengthUnit::Inches => 72.0,
+        }
+    }
+
+    /// Convert a length in this unit to another unit.
+    pub fn convert(&self, length: f32, to: LengthUnit) -> f32 {
+        length * self.points() / to.points()
+    }
+}
+
+/// A page of a document, by its index in document order.  A lightweight handle; each method reads what it
+/// needs from the document.
+#[derive(Debug, Clone)]
+pub struct Page<'a> {
+    doc: &'a PdfDoc,
+    index: usize,
+}
+
+impl<'a> Page<'a> {
+    pub(super) fn new(doc: &'a PdfDoc, index: usize) -> Self {
+        Page { doc, index }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn media_box(&self) -> Result<Rect> {
+        self.doc.media_box(self.index)
+    }
+
+    pub fn crop_box(&self) -> Result<Rect> {
+        self.doc.crop_box(self.index)
+    }
+
+    pub fn rotation(&self) -> Result<i32> {
+        self.doc.rotation(self.index)
+    }
+
+    /// The resources the page declares and which of them its content uses.  See PdfDoc::page_resource_usage.
+    pub fn resource_usage(&self) -> Result<ResourceUsage> {
+        self.doc.page_resource_usage(self.index)
+    }
+
+    /// Why the page's content couldn't be read to the end, if it couldn't.  See PdfDoc::page_content_error.
+    pub fn content_error(&self) -> Result<Option<Error>> {
+        self.doc.page_content_error(self.index)
+    }
+
+    /// The image XObjects in the page's resources.  See PdfDoc::page_images.
+    pub fn images(&self) -> Result<Vec<PageImage>> {
+        self.doc.page_images(self.index)
+    }
+
+    /// The page's /ColorSpace resources.  See PdfDoc::page_color_spaces.
+    pub fn color_spaces(&self) -> Result<BTreeMap<String, Result<ColorSpace>>> {
+        self.doc.page_color_spaces(self.index)
+    }
+
+    /// Problems with how the page's boxes nest.  See PdfDoc::page_box_warnings.
+    pub fn box_warnings(&self) -> Result<Vec<String>> {
+        self.doc.page_box_warnings(self.index)
+    }
+
+    /// The size of a user space unit in points, from /UserUnit.
+    pub fn user_unit(&self) -> Result<f32> {
+        self.doc.user_unit(self.index)
+    }
+
+    /// The width and height of the media box as printed, scaled by /UserUnit.
+    pub fn size(&self, unit: LengthUnit) -> Result<(f32, f32)> {
+        let (media_box, scale) = (self.media_box()?, self.user_unit()? / unit.points());
+        Ok((media_box.width() * scale, media_box.height() * scale))
+    }
+
+    /// The size of the page rendered at dpi pixels per inch, rounded as the renderer rounds it.
+    pub fn pixel_size(&self, dpi: f32) -> Result<(u32, u32)> {
+        let (width, height) = self.size(LengthUnit::Inches)?;
+        Ok(((width * dpi).round().max(1.0) as u32, (height * dpi).round().max(1.0) as u32))
+    }
+
+    /// The transformation from user space to the pixels of the page rendered at dpi pixels per inch, with the
+    /// origin at the top left of the media box and y increasing downwards.
+    pub fn pixel_transform(&self, dpi: f32) -> Result<Matrix> {
+        let (media_box, scale) = (self.media_box()?, self.user_unit()? * dpi / LengthUnit::Inches.points());
+        Ok(Matrix::translation(-media_box.x0, -media_box.y1) * Matrix::scale(scale, -scale))
+    }
+
+    pub fn content(&self) -> Result<Vec<u8>> {
+        self.doc.page_content(self.index)
+    }
+
+    pub fn text_blocks(&self) -> Result<Vec<TextBlock>> {
+        self.doc.page_text_blocks(self.index)
+    }
+
+    pub fn text(&self) -> Result<String> {
+        self.doc.page_text(self.index)
+    }
+
+    pub fn tables(&self) -> Result<Vec<Table>> {
+        self.doc.page_tables(self.index)
+    }
+
+    /// The page's paths and text as an SVG document the size of the media box.
+    pub fn to_svg(&self) -> Result<String> {
+        let interpreter = self.doc.interpret_page(self.index)?;
+        Ok(to_svg(&interpreter.paths, &interpreter.text_blocks, &self.media_box()?))
+    }
+}
+
+/// A page of a document open for editing, from PdfDoc::page_mut.  Edits change the document's objects in
+/// memory; write the document out with PdfDoc::write to keep them.
+#[derive(Debug)]
+pub struct PageMut<'a> {
+    doc: &'a mut PdfDoc,
+    index: usize,
+}
+
+impl<'a> PageMut<'a> {
+    pub(super) fn new(doc: &'a mut PdfDoc, index: usize) -> Self {
+        PageMut { doc, index }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The page as it is now, for reading.
+    pub fn page(&self) -> Page<'_> {
+        Page::new(self.doc, self.index)
+    }
+
+    /// Add commands after the page's content, as a new content stream.  The existing content is wrapped in q
+    /// and Q first, so that the graphics state it leaves behind, such as a transformation or clipping path,
+    /// doesn't apply to the new commands.  Resources the commands use must already be in the page's resources.
+    pub fn append_content(&mut self, commands: &CommandStream) -> Result<()> {
+        let page_id = self.doc.page_id(self.index)?;
+        self.doc.wrap_page_content(page_id, b"q\n", &[&b"Q\n"[..], &commands.to_bytes()?].concat())?;
+        self.doc.reload_page_tree()
+    }
+
+    /// Set /Rotate, the clockwise turn applied when the page is shown.  Degrees must be a multiple of 90, and
+    /// are stored normalized to 0, 90, 180 or 270.
+    pub fn set_rotation(&mut self, degrees: i32) -> Result<()> {
+        if degrees % 90 != 0 {
+            Err(ErrorKind::DocTreeError(format!("Rotation must be a multiple of 90 degrees, not {}", degrees)))?
+        };
+        let page_id = self.doc.page_id(self.index)?;
+        self.doc.update_dict(page_id, |page| {
+            page.insert("Rotate".to_string(), Rc::new(PdfObject::new_number_int(degrees.rem_euclid(360))));
+        })?;
+        self.doc.reload_page_tree()
+    }
+
+    /// Scale the page by the given factors: its content is drawn through a cm put before it, and the media box
+    /// and any other boxes are scaled to match, with the media box moved to the origin.  Annotations aren't
+    /// moved.
+    pub fn scale(&mut self, x: f32, y: f32) -> Result<()> {
+        if !(x > 0.0 && x.is_finite() && y > 0.0 && y.is_finite()) {
+            Err(ErrorKind::DocTreeError(format!("Invalid scale factors: {} {}", x, y)))?
+        };
+        let media_box = self.doc.media_box(self.index)?;
+        let matrix = Matrix::translation(-media_box.x0, -media_box.y0) * Matrix::scale(x, y);
+        let page_id = self.doc.page_id(self.index)?;
+        let mut boxes = vec![("MediaBox", media_box)];
+        if let Some(crop_box) = self.doc.page_rect(self.index, "CropBox")? {
+            boxes.push(("CropBox", crop_box));
+        };
+        // These aren't inheritable
+        let page = self.doc.get_dict(page_id)?;
+        for key in &["BleedBox", "TrimBox", "ArtBox"] {
+            if page.contains_key(key) {
+                boxes.push((key, self.doc.page_rect(self.index, key)?.unwrap()));
+            };
+        }
+        let cm = format!("q {} {} {} {} {} {} cm\n", matrix.a, matrix.b, matrix.c, matrix.d, matrix.e, matrix.f);
+        self.doc.wrap_page_content(page_id, cm.as_bytes(), b"Q\n")?;
+        self.doc.update_dict(page_id, |page| {
+            for (key, rect) in boxes {
+                page.insert(key.to_string(), Rc::new(rect_object(&matrix.transform_rect(&rect))));
+            }
+        })?;
+        self.doc.reload_page_tree()
+    }
+
+    /// Set /MediaBox, the extent of the medium the page is printed on.  Other boxes the page has must still fit
+    /// inside it, except the crop box, which is only warned about; see set_crop_box.
+    pub fn set_media_box(&mut self, rect: Rect) -> Result<()> {
+        self.set_box("MediaBox", rect)
+    }
+
+    /// Set /CropBox, the region viewers show.  A crop box extending beyond the media box is allowed, as viewers
+    /// clip it, but is logged as a warning.  Bleed, trim and art boxes must fit inside it.
+    pub fn set_crop_box(&mut self, rect: Rect) -> Result<()> {
+        self.set_box("CropBox", rect)
+    }
+
+    /// Set /BleedBox, the region production clips to, which must lie within the crop box.
+    pub fn set_bleed_box(&mut self, rect: Rect) -> Result<()> {
+        self.set_box("BleedBox", rect)
+    }
+
+    /// Set /TrimBox, the finished page after trimming, which must lie within the bleed box, or the crop box if
+    /// there is none.
+    pub fn set_trim_box(&mut self, rect: Rect) -> Result<()> {
+        self.set_box("TrimBox", rect)
+    }
+
+    /// Set one of the page's boxes on the page itself, after checking that it nests with the others.
+    fn set_box(&mut self, key: &'static str, rect: Rect) -> Result<()> {
+        if !(rect.width() > 0.0 && rect.height() > 0.0 && rect.x0.is_finite() && rect.y0.is_finite()
+             && rect.x1.is_finite() && rect.y1.is_finite()) {
+            Err(ErrorKind::DocTreeError(format!("Invalid /{} for page {}: {:?}", key, self.index, rect)))?
+        };
+        let mut boxes = self.doc.page_boxes(self.index)?;
+        boxes.insert(key, rect);
+        for (inner, outer) in misplaced_boxes(&boxes) {
+            if inner != key && outer != key {
+                continue;
+            };
+            let problem = box_problem(&boxes, self.index, inner, outer);
+            if (inner, outer) == ("CropBox", "MediaBox") {
+                warn!("{}", problem);
+            } else {
+                Err(ErrorKind::DocTreeError(problem))?
+            };
+        }
+        let page_id = self.doc.page_id(self.index)?;
+        self.doc.update_dict(page_id, |page| { page.insert(key.to_string(), Rc::new(rect_object(&rect))); })?;
+        self.doc.reload_page_tree()
+    }
+
+    /// Set /CropBox to the part of rect inside the media box, clipping any bleed, trim and art boxes to it.
+    pub fn crop(&mut self, rect: Rect) -> Result<()> {
+        let crop_box = rect.intersection(&self.doc.media_box(self.index)?).ok_or_else(|| ErrorKind::DocTreeError(
+            format!("Crop box {:?} is outside the media box of page {}", rect, self.index)))?;
+        let page_id = self.doc.page_id(self.index)?;
+        let mut boxes = vec![("CropBox", crop_box)];
+        let page = self.doc.get_dict(page_id)?;
+        for key in &["BleedBox", "TrimBox", "ArtBox"] {
+            if page.contains_key(key) {
+                let clipped = self.doc.page_rect(self.index, key)?.unwrap().intersection(&crop_box);
+                boxes.push((key, clipped.unwrap_or(crop_box)));
+            };
+        }
+        self.doc.update_dict(page_id, |page| {
+            for (key, rect) in boxes {
+                page.insert(key.to_string(), Rc::new(rect_object(&rect)));
+            }
+        })?;
+        self.doc.reload_page_tree()
+    }
+}
+
+pub(super) fn rect_object(rect: &Rect) -> PdfObject {
+    let values = [rect.x0, rect.y0, rect.x1, rect.y1].iter()
+                                                      .map(|n| Rc::new(PdfObject::new_number_float(*n)))
+                                                      .collect();
+    PdfObject::new_array(Rc::new(values))
+}
+
+/// The page boxes other than the media box and, for each, the boxes that should contain it in order of
+/// preference: a box that is missing is passed over for the next (spec 14.11.2).
+const BOX_CONTAINERS: [(&str, &[&str]); 4] = [
+    ("CropBox", &["MediaBox"]),
+    ("BleedBox", &["CropBox", "MediaBox"]),
+    ("TrimBox", &["BleedBox", "CropBox", "MediaBox"]),
+    ("ArtBox", &["BleedBox", "CropBox", "MediaBox"]),
+];
+
+/// The boxes, as (box, container) pairs, that extend beyond the box that should contain them.
+fn misplaced_boxes(boxes: &BTreeMap<&'static str, Rect>) -> Vec<(&'static str, &'static str)> {
+    BOX_CONTAINERS.iter().filter_map(|&(inner, containers)| {
+        let rect = boxes.get(inner)?;
+        let outer = containers.iter().find(|outer| boxes.contains_key(*outer))?;
+        if boxes[outer].contains_rect(rect) { None } else { Some((inner, *outer)) }
+    }).collect()
+}
+
+fn box_problem(boxes: &BTreeMap<&'static str, Rect>, page_index: usize, inner: &str, outer: &str) -> String {
+    format!("/{} {:?} of page {} extends beyond its /{} {:?}", inner, boxes[inner], page_index, outer, boxes[outer])
+}
+
+impl PdfDoc {
+    /// Boxes of the page at page_index that extend beyond the box that should contain them (spec 14.11.2): a
+    /// crop box beyond the media box, a bleed box beyond the crop box, or a trim or art box beyond the bleed
+    /// box.  Viewers clip each box to its container, so these are warnings rather than errors.
+    pub fn page_box_warnings(&self, page_index: usize) -> Result<Vec<String>> {
+        let boxes = self.page_boxes(page_index)?;
+        Ok(misplaced_boxes(&boxes).into_iter().map(|(inner, outer)| box_problem(&boxes, page_index, inner, outer))
+                                  .collect())
+    }
+
+    /// The boxes the page gives, as they are given, along with its media box, which always has a value.
+    fn page_boxes(&self, page_index: usize) -> Result<BTreeMap<&'static str, Rect>> {
+        let mut boxes = BTreeMap::new();
+        boxes.insert("MediaBox", self.media_box(page_index)?);
+        for key in ["CropBox", "BleedBox", "TrimBox", "ArtBox"] {
+            if let Some(rect) = self.page_rect(page_index, key)? {
+                boxes.insert(key, rect);
+            };
+        }
+        Ok(boxes)
+    }
+
+    /// Put new content streams holding before and after around those of the page with the given id.  The page
+    /// tree needs reloading afterwards.
+    pub(super) fn wrap_page_content(&self, page_id: ObjectId, before: &[u8], after: &[u8]) -> Result<()> {
+        let cache = &self.file.object_map;
+        let stream = |data: Vec<u8>| Rc::new(cache.reference(
+            cache.add_object(PdfObject::new_binary_stream(PdfBinaryStream::new(PdfMap::new(), data)))));
+        let mut contents = vec![stream(before.to_vec())];
+        if let Some(existing) = self.get_dict(page_id)?.get("Contents") {
+            if existing.is_array() {
+                contents.extend(existing.try_into_array()?.iter().cloned());
+            } else {
+                contents.push(Rc::clone(existing));
+            };
+        };
+        contents.push(stream(after.to_vec()));
+        self.update_dict(page_id, |page| {
+            page.insert("Contents".to_string(), Rc::new(PdfObject::new_array(Rc::new(contents))));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_page_svg() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"2 0 0 2 0 0 cm 10 10 m 20 10 l S", TestFilter::None);
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let page = doc.page(0).unwrap();
+        assert_eq!(page.index(), 0);
+        let svg = page.to_svg().unwrap();
+        assert!(svg.contains("viewBox=\"0 0 612 792\""));
+        assert!(svg.contains("<path d=\"M20 20 L40 20\" fill=\"none\" stroke=\"black\" stroke-width=\"2\"/>"));
+        assert!(doc.page(1).is_err());
+    }
+
+    #[test]
+    fn test_page_units() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page_with_entries(b"", TestFilter::None, "/MediaBox [0 0 595.28 841.89]");
+        builder.add_page_with_entries(b"", TestFilter::None, "/MediaBox [100 100 172 244] /UserUnit 10");
+        builder.add_page_with_entries(b"", TestFilter::None, "/UserUnit 0");
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let a4 = doc.page(0).unwrap();
+        let (width, height) = a4.size(LengthUnit::Millimeters).unwrap();
+        assert!((width - 210.0).abs() < 0.01 && (height - 297.0).abs() < 0.01);
+        assert_eq!(a4.user_unit().unwrap(), 1.0);
+        assert_eq!(a4.pixel_size(150.0).unwrap(), (1240, 1754));
+
+        // One by two inches of user space, printed ten times larger
+        let large = doc.page(1).unwrap();
+        assert_eq!(large.size(LengthUnit::Inches).unwrap(), (10.0, 20.0));
+        assert_eq!(large.size(LengthUnit::Points).unwrap(), (720.0, 1440.0));
+        assert_eq!(large.pixel_size(10.0).unwrap(), (100, 200));
+        let to_pixels = large.pixel_transform(10.0).unwrap();
+        assert_eq!(to_pixels.transform_point(100.0, 244.0), (0.0, 0.0));
+        assert_eq!(to_pixels.transform_point(172.0, 100.0), (100.0, 200.0));
+
+        assert!(doc.page(2).unwrap().size(LengthUnit::Points).is_err());
+        assert_eq!(LengthUnit::Inches.convert(2.0, LengthUnit::Millimeters), 50.8);
+    }
+
+    #[test]
+    fn test_append_content() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page(b"2 0 0 2 0 0 cm 10 10 m 20 10 l S", TestFilter::None);
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let overlay = CommandStream::parse(b"0 0 m 5 0 l S").unwrap();
+        let mut page = doc.page_mut(0).unwrap();
+        page.append_content(&overlay).unwrap();
+        assert_eq!(page.page().content().unwrap(),
+                   b"q\n\n2 0 0 2 0 0 cm 10 10 m 20 10 l S\nQ\n0 0 m\n5 0 l\nS\n".to_vec());
+        // The overlay isn't scaled by the existing content's cm
+        let svg = page.page().to_svg().unwrap();
+        assert!(svg.contains("M20 20 L40 20") && svg.contains("M0 0 L5 0"), "{}", svg);
+
+        let (bytes, _) = doc.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(reread.page_content_streams(0).unwrap().len(), 3);
+        assert!(doc.page_mut(1).is_err());
+    }
+
+    #[test]
+    fn test_page_transformations() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page_with_entries(b"10 10 m 20 10 l S", TestFilter::None, "/TrimBox [50 50 562 742] /Rotate -90");
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(doc.page(0).unwrap().rotation().unwrap(), 270);
+        assert_eq!(doc.crop_box(0).unwrap(), Rect::new(0.0, 0.0, 612.0, 792.0));
+
+        let mut page = doc.page_mut(0).unwrap();
+        page.set_rotation(450).unwrap();
+        assert_eq!(page.page().rotation().unwrap(), 90);
+        assert!(page.set_rotation(45).is_err());
+
+        // Cropping clips the trim box too
+        page.crop(Rect::new(100.0, 100.0, 700.0, 400.0)).unwrap();
+        assert_eq!(page.page().crop_box().unwrap(), Rect::new(100.0, 100.0, 612.0, 400.0));
+        assert!(page.crop(Rect::new(700.0, 0.0, 800.0, 100.0)).is_err());
+
+        page.scale(0.5, 2.0).unwrap();
+        assert_eq!(page.page().media_box().unwrap(), Rect::new(0.0, 0.0, 306.0, 1584.0));
+        assert_eq!(page.page().crop_box().unwrap(), Rect::new(50.0, 200.0, 306.0, 800.0));
+        let svg = page.page().to_svg().unwrap();
+        assert!(svg.contains("M5 20 L10 20"), "{}", svg);
+        assert!(page.scale(0.0, 1.0).is_err());
+
+        let (bytes, _) = doc.write(&WriteOptions::default()).unwrap();
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let trim_box = reread.get_dict(reread.page_id(0).unwrap()).unwrap()["TrimBox"].try_into_array().unwrap()
+                             .iter().map(|n| n.try_into_number().unwrap()).collect::<Vec<f32>>();
+        assert_eq!(trim_box, vec![50.0, 200.0, 281.0, 800.0]);
+        assert_eq!(reread.rotation(0).unwrap(), 90);
+    }
+
+    #[test]
+    fn test_page_boxes() {
+        let mut builder = MiniPdfBuilder::new();
+        builder.add_page_with_entries(b"", TestFilter::None, "/MediaBox [0 0 600 800] /TrimBox [10 10 590 790]");
+        builder.add_page_with_entries(b"", TestFilter::None, "/CropBox [-10 0 600 800] /ArtBox [0 0 700 100]");
+        let mut doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert!(doc.page(0).unwrap().box_warnings().unwrap().is_empty());
+        assert_eq!(doc.page_box_warnings(1).unwrap().len(), 2);
+
+        let mut page = doc.page_mut(0).unwrap();
+        page.set_bleed_box(Rect::new(5.0, 5.0, 595.0, 795.0)).unwrap();
+        // The trim box would stick out of the bleed box, or the bleed box out of the crop box
+        assert!(page.set_bleed_box(Rect::new(20.0, 5.0, 595.0, 795.0)).is_err());
+        assert!(page.set_trim_box(Rect::new(0.0, 0.0, 100.0, 100.0)).is_err());
+        assert!(page.set_crop_box(Rect::new(100.0, 100.0, 500.0, 700.0)).is_err());
+        assert!(page.set_media_box(Rect::new(0.0, 0.0, 500.0, 500.0)).is_err());
+        assert!(page.set_trim_box(Rect::new(0.0, 0.0, 0.0, 100.0)).is_err());
+        assert!(page.set_trim_box(Rect::new(0.0, 0.0, f32::NAN, 100.0)).is_err());
+        page.set_trim_box(Rect::new(20.0, 20.0, 580.0, 780.0)).unwrap();
+        page.set_media_box(Rect::new(0.0, 0.0, 610.0, 800.0)).unwrap();
+        // A crop box beyond the media box is only warned about
+        page.set_crop_box(Rect::new(-5.0, -5.0, 605.0, 805.0)).unwrap();
+        assert_eq!(page.page().crop_box().unwrap(), Rect::new(0.0, 0.0, 605.0, 800.0));
+        let warnings = page.page().box_warnings().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("/CropBox Rect { x0: -5.0, y0: -5.0, x1: 605.0, y1: 805.0 } of page 0 extends \
+                                         beyond its /MediaBox"), "{}", warnings[0]);
+
+        let (bytes, report) = doc.write(&WriteOptions::default()).unwrap();
+        assert_eq!(report.warnings.len(), 3);
+        let reread = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        let boxes = reread.page_boxes(0).unwrap();
+        assert_eq!(boxes["MediaBox"], Rect::new(0.0, 0.0, 610.0, 800.0));
+        assert_eq!(boxes["BleedBox"], Rect::new(5.0, 5.0, 595.0, 795.0));
+        assert_eq!(boxes["TrimBox"], Rect::new(20.0, 20.0, 580.0, 780.0));
+        assert_eq!(boxes["CropBox"], Rect::new(-5.0, -5.0, 605.0, 805.0));
+    }
+}
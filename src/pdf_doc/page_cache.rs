@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::*;
+
+/// How many pages PdfDoc keeps interpreted content for, unless set otherwise with
+/// PdfDoc::set_page_cache_budget.
+pub const DEFAULT_PAGE_CACHE_BUDGET: usize = 8;
+
+/// The parsed content of a page, and the result of running it once that's been needed.
+pub(crate) struct CachedPage {
+    pub commands: Rc<CommandStream>,
+    pub interpreter: Option<Rc<Interpreter>>,
+}
+
+/// Work done on the most recently used pages, so that extracting text, searching and so on don't run the
+/// content of a page again each time.  Entries are only good for the edit count they were made at, which
+/// ObjectCache::edit_count moves on with every edit to the file.
+pub(crate) struct PageCache<T> {
+    budget: usize,
+    edit_count: usize,
+    /// Page indices and their entries, least recently used first
+    entries: VecDeque<(usize, T)>,
+}
+
+impl<T> PageCache<T> {
+    pub fn new(budget: usize) -> Self {
+        PageCache { budget, edit_count: 0, entries: VecDeque::new() }
+    }
+
+    /// Change how many pages are kept, dropping the least recently used ones over the new budget.  A budget of 0
+    /// turns caching off.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.trim();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entry for the page, as of the given edit count, marking it the most recently used.
+    pub fn get_mut(&mut self, page_index: usize, edit_count: usize) -> Option<&mut T> {
+        self.expire(edit_count);
+        let position = self.entries.iter().position(|(page, _)| *page == page_index)?;
+        let entry = self.entries.remove(position)?;
+        self.entries.push_back(entry);
+        self.entries.back_mut().map(|(_, value)| value)
+    }
+
+    pub fn insert(&mut self, page_index: usize, edit_count: usize, value: T) {
+        self.expire(edit_count);
+        self.entries.retain(|(page, _)| *page != page_index);
+        self.entries.push_back((page_index, value));
+        self.trim();
+    }
+
+    fn expire(&mut self, edit_count: usize) {
+        if edit_count != self.edit_count {
+            self.entries.clear();
+            self.edit_count = edit_count;
+        };
+    }
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.budget {
+            self.entries.pop_front();
+        }
+    }
+}
+
+impl<T> fmt::Debug for PageCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageCache")
+         .field("budget", &self.budget)
+         .field("pages", &self.entries.iter().map(|(page, _)| *page).collect::<Vec<_>>())
+         .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_cache() {
+        let mut cache = PageCache::new(2);
+        cache.insert(0, 0, "zero");
+        cache.insert(1, 0, "one");
+        // Using page 0 makes page 1 the one to go when page 2 is added
+        assert_eq!(cache.get_mut(0, 0).copied(), Some("zero"));
+        cache.insert(2, 0, "two");
+        assert_eq!(cache.get_mut(1, 0), None);
+        assert_eq!((cache.get_mut(0, 0).copied(), cache.get_mut(2, 0).copied()), (Some("zero"), Some("two")));
+
+        // An edit makes every entry stale
+        assert_eq!(cache.get_mut(2, 1), None);
+        assert_eq!(cache.len(), 0);
+        cache.insert(3, 1, "three");
+        cache.set_budget(0);
+        assert_eq!(cache.get_mut(3, 1), None);
+        cache.insert(3, 1, "three");
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -1,4 +1,5 @@
-use std::io::Read;
+use std::cell::{Cell, OnceCell};
+use std::io::{Read, Write};
 use std::fmt::Display;
 
 use flate2;
@@ -7,28 +8,256 @@ use super::*;
 use crate::errors::*;
 use crate::doc_tree::pdf_objects::PdfObjectInterface;
 
+/// A stream of page description operators (spec 7.8.2), such as a page's /Contents, with the resource
+/// dictionary its operands' names refer to.
 #[derive(Debug)]
 pub struct PdfContentStream {
     attributes: PdfMap,
-    data: String
+    data: Vec<u8>,
+    resources: Option<SharedObject>,
+    raw: Option<RawSource>,
+}
+
+impl PdfContentStream {
+    /// A content stream holding decoded data.
+    pub fn new(attributes: PdfMap, data: Vec<u8>, resources: Option<SharedObject>) -> Self {
+        PdfContentStream { attributes, data, resources, raw: None }
+    }
+
+    /// A content stream made from a binary stream read from a file, keeping the location of its raw data.  The
+    /// binary stream is decoded if it hasn't been already.
+    pub(crate) fn from_binary_stream(stream: &PdfBinaryStream, resources: Option<SharedObject>) -> Result<Self> {
+        Ok(PdfContentStream {
+            attributes: stream.attributes.clone(),
+            data: stream.data()?.to_vec(),
+            resources,
+            raw: stream.raw.clone(),
+        })
+    }
+
+    /// The stream dictionary.
+    pub fn attributes(&self) -> &PdfMap {
+        &self.attributes
+    }
+
+    /// The decoded stream contents.  Operators are ASCII, but strings and inline images can hold any bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The decoded stream contents as text, with invalid UTF-8 replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).to_string()
+    }
+
+    /// The resource dictionary in effect for this stream, such as the /Resources of the page it was read for.
+    pub fn resources(&self) -> Option<&SharedObject> {
+        self.resources.as_ref()
+    }
+
+    /// The stream data as it appears in the file, before filters are applied.
+    pub fn raw_data(&self) -> Result<Vec<u8>> {
+        match &self.raw {
+            Some(raw) => raw.read(),
+            None => Ok(self.data.clone())
+        }
+    }
+}
+
+/// Where a stream's data was read from: the bytes between its stream and endstream keywords, which are still
+/// encoded by any filters, and the object they belong to.
+#[derive(Debug, Clone)]
+pub(crate) struct RawSource {
+    file: Weak<ObjectCache>,
+    id: ObjectId,
+    span: Range<usize>,
+}
+
+impl RawSource {
+    pub(crate) fn new(file: &Weak<ObjectCache>, id: ObjectId, span: Range<usize>) -> Self {
+        RawSource { file: Weak::clone(file), id, span }
+    }
+
+    /// Whether the file is still around to read the data from.  It isn't while the file's cross-reference
+    /// streams are being read, or when objects are parsed on their own.
+    fn is_readable(&self) -> bool {
+        self.file.strong_count() > 0
+    }
+
+    fn read(&self) -> Result<Vec<u8>> {
+        let file = self.file.upgrade().ok_or_else(|| ErrorKind::ReferenceError(
+            "Stream's file has been dropped".to_string()))?;
+        Ok(file.get_span(self.span.clone())?.to_vec())
+    }
 }
 
 impl Display for PdfContentStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Attributes: {:#?}, Content: {}", self.attributes, self.data)?;
+        write!(f, "Attributes: {:#?}, Content: {}", self.attributes, self.text())?;
         Ok(())
     }
 }
 
+/// One step in decoding a stream: the filter applied, its position in the /Filter array, and the amount of data
+/// that went in and came out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterStage {
+    pub index: usize,
+    pub filter: String,
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+}
+
+/// A stream other than a content stream.  Streams read from a file hold only the location of their data until
+/// it's first asked for; the filters are applied then and the result kept.
 #[derive(Debug)]
 pub struct PdfBinaryStream {
     attributes: PdfMap,
-    data: Vec<u8>
+    data: OnceCell<Vec<u8>>,
+    /// The filters that produced data, once it has been decoded
+    stages: OnceCell<Vec<FilterStage>>,
+    decoded: bool,
+    stream_type: Cell<StreamType>,
+    raw: Option<RawSource>,
+    /// ParserOptions::max_stream_bytes, checked against the output of each filter as it's applied
+    max_bytes: Option<usize>,
+}
+
+impl PdfBinaryStream {
+    /// A stream holding decoded data.  Any /Filter in the attributes is dropped when the stream is written.
+    pub fn new(attributes: PdfMap, data: Vec<u8>) -> Self {
+        let stream_type = Cell::new(determine_stream_type(&attributes));
+        PdfBinaryStream {
+            attributes,
+            data: OnceCell::from(data),
+            stages: OnceCell::from(Vec::new()),
+            decoded: true,
+            stream_type,
+            raw: None,
+            max_bytes: None,
+        }
+    }
+
+    /// The stream dictionary.
+    pub fn attributes(&self) -> &PdfMap {
+        &self.attributes
+    }
+
+    /// The stream contents, after any filters have been applied.  The first call for a stream read from a file
+    /// reads and decodes its data, so errors in the data or its filters surface here.
+    pub fn data(&self) -> Result<&[u8]> {
+        if let Some(data) = self.data.get() {
+            return Ok(data);
+        };
+        let raw = self.raw.as_ref().expect("Streams without data always have a raw source");
+        let start = Timer::start();
+        self.load(raw.read()?)?;
+        if let Some(file) = raw.file.upgrade() {
+            file.record_decode(start.elapsed());
+        };
+        Ok(self.data.get().unwrap())
+    }
+
+    /// What each filter did in decoding the data, in the order they were applied.  Empty until the data has been
+    /// decoded, and for streams whose data is kept encoded.
+    pub fn filter_stages(&self) -> &[FilterStage] {
+        self.stages.get().map_or(&[], |stages| stages.as_slice())
+    }
+
+    /// The data of an image, with the general-purpose filters applied and any image codec, like DCTDecode for a
+    /// JPEG, left undone.  The codec's filter name comes with the data when it's still encoded.
+    pub fn image_data(&self) -> Result<(Vec<u8>, Option<String>)> {
+        if self.decoded {
+            return Ok((self.data()?.to_vec(), None));
+        };
+        let mut data = self.raw_data()?;
+        for (index, filter) in filters(&self.attributes)?.into_iter().enumerate() {
+            if let Some(codec) = filter.image_codec() {
+                return Ok((data, Some(codec.to_string())));
+            };
+            let name = filter.to_string();
+            data = filter.apply(Ok(data), self.max_bytes).chain_err(|| ErrorKind::FilterError(
+                format!("{} (filter {}) failed on image data in {}", name, index, self.name()),
+                "PdfBinaryStream.image_data",
+            ))?;
+            ParserOptions::check("decoded stream length", data.len(), self.max_bytes)?;
+        }
+        Ok((data, None))
+    }
+
+    /// Whether the data has been read and decoded yet.
+    pub fn is_loaded(&self) -> bool {
+        self.data.get().is_some()
+    }
+
+    /// Whether the filters listed in the stream dictionary have been applied to the data.  Image data is
+    /// kept in its encoded form.
+    pub fn is_decoded(&self) -> bool {
+        self.decoded
+    }
+
+    /// What the stream holds.  Only the stream dictionary is considered until PdfFileHandler::stream_types
+    /// has classified streams by how they're referenced.
+    pub fn stream_type(&self) -> StreamType {
+        self.stream_type.get()
+    }
+
+    /// The stream data as it appears in the file, before filters are applied.  Streams made in memory have no
+    /// encoded form, so their data is returned as is.
+    pub fn raw_data(&self) -> Result<Vec<u8>> {
+        match &self.raw {
+            Some(raw) => raw.read(),
+            None => Ok(self.data()?.to_vec())
+        }
+    }
+
+    /// The offsets of the raw stream data in the file, if it was read from one.
+    pub fn raw_span(&self) -> Option<Range<usize>> {
+        self.raw.as_ref().map(|raw| raw.span.clone())
+    }
+
+    pub(crate) fn set_stream_type(&self, stream_type: StreamType) {
+        self.stream_type.set(stream_type)
+    }
+
+    /// Apply the filters named in the stream dictionary to the raw data, unless it's kept encoded, and keep the
+    /// result.  An error names the stream, the filter that failed and how much data it was given.
+    fn load(&self, bytes: Vec<u8>) -> Result<()> {
+        let mut stages = Vec::new();
+        let mut data = bytes;
+        if self.decoded {
+            for (index, filter) in filters(&self.attributes)?.into_iter().enumerate() {
+                let name = filter.to_string();
+                let input_bytes = data.len();
+                data = filter.apply(Ok(data), self.max_bytes).chain_err(|| ErrorKind::FilterError(
+                    format!("{} (filter {}) failed on {} input bytes in {}", name, index, input_bytes, self.name()),
+                    "PdfBinaryStream.load",
+                ))?;
+                // Checked after each filter, which stops as soon as its output passes the limit
+                ParserOptions::check("decoded stream length", data.len(), self.max_bytes)?;
+                stages.push(FilterStage { index, filter: name, input_bytes, output_bytes: data.len() });
+            }
+        };
+        let _ = self.data.set(data);
+        let _ = self.stages.set(stages);
+        Ok(())
+    }
+
+    /// The stream's object id for messages, if it was read from a file.
+    fn name(&self) -> String {
+        match &self.raw {
+            Some(raw) => format!("stream {} {}", raw.id.number(), raw.id.generation()),
+            None => "stream".to_string(),
+        }
+    }
 }
 
 impl Display for PdfBinaryStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Attributes: {:#?}, Content length: {}", self.attributes, self.data.len())?;
+        match self.data.get() {
+            Some(data) => write!(f, "Attributes: {:#?}, Content length: {}", self.attributes, data.len())?,
+            None => write!(f, "Attributes: {:#?}, Content not yet decoded", self.attributes)?,
+        };
         Ok(())
     }
 }
@@ -67,121 +296,172 @@ impl std::fmt::Display for Filter {
 }
 
 impl Filter {
-    pub fn apply(self, data: Result<Vec<u8>>) -> Result<Vec<u8>> {
+    /// The filter name of an image-specific compression, which leaves data that's an image format of its own.
+    fn image_codec(&self) -> Option<&'static str> {
+        match self {
+            Filter::CCITTFax(_) => Some("CCITTFaxDecode"),
+            Filter::DCT(_) => Some("DCTDecode"),
+            Filter::JPX => Some("JPXDecode"),
+            _ => None
+        }
+    }
+
+    /// Decode data.  Filters that can expand data without bound, like FlateDecode, stop once their output is past
+    /// max_bytes, leaving the caller to check the length; JBIG2Decode, which builds an image in memory, fails.
+    pub fn apply(self, data: Result<Vec<u8>>, max_bytes: Option<usize>) -> Result<Vec<u8>> {
         use Filter::*;
         if data.is_err() {
             return Err(data.unwrap_err());
         };
-        if let Ok(ref v) = data {println!("input data:\nstart: {:?},\nend: {:?},\nlength: {}", &v[..5], &v[(v.len() - 5)..], &v.len());
+        if let Ok(ref v) = data {debug!("{} input length: {}", self, v.len());
         };
         let data = data.unwrap();
         let output_data = match self {
             ASCIIHex => Filter::apply_ascii_hex(data),
             ASCII85 => Filter::apply_ascii_85(data),
             LZW(params) => Filter::apply_lzw(data, params),
-            Flate(params) => Filter::apply_flate(data, params),
+            Flate(params) => Filter::apply_flate(data, params, max_bytes),
+            JBIG2(params) => Filter::apply_jbig2(data, params, max_bytes),
             _ => Err(ErrorKind::FilterError(
                 format!("Unsupported filter: {}", self),
                 "Filter.apply",
             ))?,
         };
-        println!("output data_success: {:?}", !output_data.is_err());
+        debug!("output data_success: {:?}", output_data.is_ok());
         output_data
     }
 
     fn apply_ascii_hex(data: Vec<u8>) -> Result<Vec<u8>> {
-        const END_OF_DATA: u8 = b'<'; // Standard 7.4.2
+        const END_OF_DATA: u8 = b'>'; // Standard 7.4.2
         let mut output = Vec::new();
         let mut buffer = Option::None;
         for c in data {
-            if !is_hex(c) {
-                if !is_whitespace(c) {
-                    return Err(ErrorKind::FilterError(
-                        format!("Invalid character for ASCIIHexDecode: {}", c as char),
-                        "Filter.apply_ascii_hex",
-                    ))?;
-                };
-                if c == END_OF_DATA {
-                    break;
-                };
+            if c == END_OF_DATA {
+                break;
             };
+            if is_whitespace(c) {
+                continue;
+            };
+            let digit = (c as char).to_digit(16).ok_or_else(|| ErrorKind::FilterError(
+                format!("Invalid character for ASCIIHexDecode: {}", c as char),
+                "Filter.apply_ascii_hex",
+            ))? as u8;
             match buffer {
-                None => buffer = Some(c as char),
-                Some(old_c) => {
-                    let hex_pair: String = [old_c, c as char].iter().collect();
-                    let value = u8::from_str_radix(&hex_pair, 16).unwrap(); // Valid hex confirmed already
-                    output.push(value);
+                None => buffer = Some(digit),
+                Some(high) => {
+                    output.push(high * 16 + digit);
+                    buffer = None;
                 }
             }
         }
-        if let Some(final_char) = buffer {
+        if let Some(final_digit) = buffer {
             // Per spec 7.4.2, unpaired digit is followed by an implicit 0
-            output.push(16 * (final_char.to_digit(16).unwrap() as u8));
+            output.push(16 * final_digit);
         }
         Ok(output)
     }
 
     fn apply_ascii_85(data: Vec<u8>) -> Result<Vec<u8>> {
         let mut new_data = Vec::new();
-        for group in AsciiData(data).ascii85_iter() {
-            new_data.extend(Filter::_parse_ascii_85_group(group)?)
+        let mut group = Vec::with_capacity(5);
+        let mut index = 0;
+        while index < data.len() {
+            let c = data[index];
+            index += 1;
+            if is_whitespace(c) {
+                continue;
+            };
+            if c == b'~' {
+                break; // End of data marker is ~>
+            };
+            if c == b'z' && group.is_empty() {
+                new_data.extend_from_slice(&[0, 0, 0, 0]);
+                continue;
+            };
+            group.push(c);
+            if group.len() == 5 {
+                new_data.extend(Filter::_parse_ascii_85_group(&group)?);
+                group.clear();
+            };
         }
+        if !group.is_empty() {
+            new_data.extend(Filter::_parse_ascii_85_group(&group)?);
+        };
         Ok(new_data)
     }
 
-    fn _parse_ascii_85_group(arr: [Option<u8>; 5]) -> Result<Vec<u8>> {
-        let mut base_256_value: u32 = 0;
-        let vec: Vec<u8> = arr.iter()
-                              .filter(|c| c.is_some())
-                              .map(|c| c.unwrap())
-                              .collect();
-        for &c in &vec {
-            if !is_valid_ascii_85_byte(c) {
+    /// Decode a group of up to 5 characters.  A final partial group of n characters is padded with 'u' and
+    /// yields n - 1 bytes (spec 7.4.3).
+    fn _parse_ascii_85_group(group: &[u8]) -> Result<Vec<u8>> {
+        if group.len() == 1 {
+            return Err(ErrorKind::FilterError(
+                "Single character in final Ascii85 group".to_string(),
+                "apply_ascii_85::_parse_ascii_85_group",
+            ))?;
+        };
+        let mut base_256_value: u64 = 0;
+        for i in 0..5 {
+            let c = *group.get(i).unwrap_or(&b'u');
+            if c == b'z' || !is_valid_ascii_85_byte(c) {
                 return Err(ErrorKind::FilterError(
                     format!("Invalid Ascii85 character: {}", c),
                     "apply_ascii_85",
                 ))?;
             };
-            if c == b'z' {
-                if vec.len() > 1 {
-                    return Err(ErrorKind::FilterError(
-                        format!("z in middle of group: {:?}", vec),
-                        "apply_ascii_85::_parse_ascii_85_group",
-                    ))?;
-                }
-                return Ok(vec![0, 0, 0, 0]);
-            }
-            base_256_value = base_256_value * 85 + (c - b'!') as u32; // See spec 7.4.3
-        }
-        let mut data = Vec::new();
-        for exp in (0..3).into_iter().rev() {
-            let place_value = base_256_value.pow(exp);
-            let digit = (base_256_value / place_value) as u8;
-            data.push(digit);
-            base_256_value %= place_value;
+            base_256_value = base_256_value * 85 + (c - b'!') as u64; // See spec 7.4.3
         }
-        Ok(data)
+        if base_256_value > u32::MAX as u64 {
+            return Err(ErrorKind::FilterError(
+                format!("Ascii85 group out of range: {:?}", group),
+                "apply_ascii_85::_parse_ascii_85_group",
+            ))?;
+        };
+        let bytes = (base_256_value as u32).to_be_bytes();
+        Ok(bytes[..(group.len() - 1)].to_vec())
     }
 
     fn apply_lzw(data: Vec<u8>, _params: Option<SharedObject>) -> Result<Vec<u8>> {
         Ok(data)
     }
 
-    fn apply_flate(data: Vec<u8>, _params: Option<SharedObject>) -> Result<Vec<u8>> {
-        let mut decoder = flate2::read::ZlibDecoder::new(&*data);
+    fn apply_flate(data: Vec<u8>, _params: Option<SharedObject>, max_bytes: Option<usize>) -> Result<Vec<u8>> {
+        let decoder = flate2::read::ZlibDecoder::new(&*data);
+        // One byte past the limit is enough to show the data is too long
+        let limit = max_bytes.map_or(u64::MAX, |max| (max as u64).saturating_add(1));
         let mut output = Vec::new();
-        let decode_result = decoder.read_to_end(&mut output);
+        let decode_result = decoder.take(limit).read_to_end(&mut output);
         match decode_result {
-            Ok(_) => Ok(data),
+            Ok(_) => Ok(output),
             Err(e) => Err(ErrorKind::FilterError(
                 format!("Error applying flate filter: {:?}", e),
                 "apply:apply_flate",
             ))?,
         }
     }
+
+    /// JBIG2 data embedded in PDF can leave segments shared between images to a /JBIG2Globals stream.
+    fn apply_jbig2(data: Vec<u8>, params: Option<SharedObject>, max_bytes: Option<usize>) -> Result<Vec<u8>> {
+        let globals = match params.as_ref().map(|params| params.try_into_map()).transpose()? {
+            Some(params) => match params.get("JBIG2Globals") {
+                Some(globals) => Some(globals.try_into_binary_stream()?.data()?.to_vec()),
+                None => None
+            },
+            None => None
+        };
+        super::jbig2::decode_jbig2(&data, globals.as_deref(), max_bytes)
+    }
 }
 
-pub fn decode_stream(map: PdfMap, bytes: Vec<u8>) -> Result<PdfObject> {
+/// Compress data with the zlib format used by FlateDecode.  level runs from 0 (none) to 9 (best).
+pub fn flate_encode(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(level.min(9)));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Build a stream object from its dictionary and raw body.  Filters are applied when the data is first asked
+/// for, or straight away if the file can't be read from later, in which case the body is copied.
+pub(crate) fn decode_stream(map: PdfMap, bytes: &[u8], raw: RawSource, max_bytes: Option<usize>) -> Result<PdfObject> {
     //Check size
     let expected_byte_length = map
         .get("Length")
@@ -191,51 +471,81 @@ pub fn decode_stream(map: PdfMap, bytes: Vec<u8>) -> Result<PdfObject> {
         )))?
         .try_into_int()? as usize;
     assert_eq!(bytes.len(), expected_byte_length);
-    println!("expected byte length: {}, actual: {}", expected_byte_length, bytes.len());
-
-    // Classify stream
-    let type_and_subtype = (map.get("Type"), map.get("Subtype"));
-    let stream_type = determine_stream_type(type_and_subtype);
-    if let StreamType::Image = stream_type {
-        return Ok(PdfObject::new_binary_stream(PdfBinaryStream{
-            attributes: map,
-            data: bytes}))
+    debug!("expected byte length: {}, actual: {}", expected_byte_length, bytes.len());
+
+    // Classify stream; image data is kept encoded
+    let stream_type = determine_stream_type(&map);
+    let stream = PdfBinaryStream {
+        attributes: map,
+        data: OnceCell::new(),
+        stages: OnceCell::new(),
+        decoded: stream_type != StreamType::Image,
+        stream_type: Cell::new(stream_type),
+        raw: Some(raw),
+        max_bytes,
+    };
+    if !stream.raw.as_ref().unwrap().is_readable() {
+        stream.load(bytes.to_vec())?;
     };
+    Ok(PdfObject::new_binary_stream(stream))
+}
 
-    //Extract filters
-    let params = map.get("DecodeParms");
-    let filter_object_array = match map.get("Filter") {
+/// The filters listed in a stream dictionary, in the order they're applied, with their parameters.
+fn filters(map: &PdfMap) -> Result<Vec<Filter>> {
+    let names = match map.get("Filter") {
         None => Vec::new(),
-        Some(obj) if obj.is_string() => vec![Rc::new(obj.as_ref().clone())],
-        Some(obj) if obj.is_array() => (*obj.try_into_array().unwrap()).to_owned(),
+        Some(obj) if obj.is_string() => vec![Rc::clone(obj)],
+        Some(obj) if obj.is_array() => (*obj.try_into_array()?).to_owned(),
         Some(obj) => Err(ErrorKind::FilterError(
             format!("Non-name item in Filter array: {:?}", obj),
             "decode stream",
         ))?,
-    }
-    .into_iter()
-    .collect::<Vec<SharedObject>>();
-    let filter_array = filter_object_array
-        .into_iter()
-        .enumerate()
-        // Collect matching params without throwing error if no filters need params
-        .map(|(index, s)| {
-            filter_from_string_and_params(
-                s.try_into_string()?.as_ref(),
-                params.as_ref()
-                      .map(|arr| {
-                          if arr.is_array() {
-                              arr.try_to_index(index).unwrap()
-                            } else {Rc::clone(arr)}
-                      }))
-        })
-        .collect::<Result<Vec<decode::Filter>>>()?;
-    let filtered_data = filter_array
-        .into_iter()
-        .fold(Ok(bytes.clone()), |data, filter| filter.apply(data))?;
+    };
+    let params = decode_params(map.get("DecodeParms"), names.len())?;
+    names.iter()
+         .zip(params)
+         .map(|(name, params)| filter_from_string_and_params(name.try_into_string()?.as_ref(), params))
+         .collect()
+}
 
-    Ok(PdfObject::new_binary_stream(PdfBinaryStream{
-        attributes: map, data: filtered_data}))
+/// The /DecodeParms entry matched up with filter_count filters (spec 7.3.8.2).  An array holds one entry per
+/// filter, where null means the filter's defaults; a short array leaves the remaining filters at their defaults.
+/// A lone dictionary is accepted only when there's a single filter.
+fn decode_params(params: Option<&SharedObject>, filter_count: usize) -> Result<Vec<Option<SharedObject>>> {
+    let is_null = |obj: &SharedObject| matches!(obj.get_pdf_primitive_type(), Ok(PdfDataType::Null));
+    let entries = match params {
+        None => Vec::new(),
+        Some(obj) if is_null(obj) => Vec::new(),
+        Some(obj) if obj.is_array() => (*obj.try_into_array()?).to_owned(),
+        Some(obj) if obj.is_map() && filter_count <= 1 => vec![Rc::clone(obj)],
+        Some(obj) if obj.is_map() => Err(ErrorKind::FilterError(
+            format!("A single DecodeParms dictionary can't be matched with {} filters", filter_count),
+            "decode_params",
+        ))?,
+        Some(obj) => Err(ErrorKind::FilterError(
+            format!("DecodeParms must be a dictionary or array, not {:?}", obj),
+            "decode_params",
+        ))?,
+    };
+    if entries.len() > filter_count {
+        Err(ErrorKind::FilterError(
+            format!("{} DecodeParms entries for {} filters", entries.len(), filter_count),
+            "decode_params",
+        ))?
+    };
+    let mut matched = Vec::with_capacity(filter_count);
+    for (index, entry) in entries.into_iter().enumerate() {
+        match entry {
+            entry if is_null(&entry) => matched.push(None),
+            entry if entry.is_map() => matched.push(Some(entry)),
+            entry => Err(ErrorKind::FilterError(
+                format!("DecodeParms entry {} is neither a dictionary nor null: {:?}", index, entry),
+                "decode_params",
+            ))?,
+        }
+    }
+    matched.resize(filter_count, None);
+    Ok(matched)
 }
 
 fn filter_from_string_and_params<T: AsRef<str> + Display>(name: T, params: Option<Rc<PdfObject>>) -> Result<Filter> {
@@ -258,88 +568,117 @@ fn filter_from_string_and_params<T: AsRef<str> + Display>(name: T, params: Optio
     }
 }
 
-fn determine_stream_type(tup: (Option<&Rc<PdfObject>>, Option<&Rc<PdfObject>>)) -> StreamType {
+/// Classify a stream by the /Type and /Subtype in its dictionary, or by the /Length1 entry of Type 1 and
+/// TrueType font programs.
+fn determine_stream_type(map: &PdfMap) -> StreamType {
     use StreamType::*;
-    if let Some(object) = tup.1 {
-        match object.try_into_string() {
-            Ok(s) if *s == "Image" => return Image,
-            _ => {}
-        }
+    let name = |key: &str| map.get(key).and_then(|value| value.try_into_string().ok());
+    match name("Type").as_ref().map(|name| name.as_str()) {
+        Some("XRef") => return XRef,
+        Some("ObjStm") => return Object,
+        Some("Metadata") => return Metadata,
+        _ => {}
     };
-    return Unknown
-    
+    match name("Subtype").as_ref().map(|name| name.as_str()) {
+        Some("Image") => Image,
+        Some("Form") => Form,
+        // FontFile3 programs
+        Some("Type1C") | Some("CIDFontType0C") | Some("OpenType") => FontFile,
+        _ if map.contains_key("Length1") => FontFile,
+        _ => Unknown
+    }
 }
 
-struct Ascii85Iterator {
-    data: Vec<u8>,
-    data_cursor: usize,
-    last_index: usize,
-    buffer: [Option<u8>; 5],
-    buffer_cursor: usize,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Iterator for Ascii85Iterator {
-    type Item = [Option<u8>; 5];
-    fn next(&mut self) -> Option<[Option<u8>; 5]> {
-        loop {
-            if self.data_cursor > self.last_index {
-                return None;
-            };
-            let next_char = self.data[self.data_cursor];
-            self.data_cursor += 1;
-            if self.data_cursor == self.last_index {
-                break;
-            };
+    #[test]
+    fn test_ascii_hex() {
+        assert_eq!(Filter::apply_ascii_hex(b"48 65\n6c6C 6F>".to_vec()).unwrap(), b"Hello".to_vec());
+        assert_eq!(Filter::apply_ascii_hex(b"7>".to_vec()).unwrap(), vec![0x70]);
+        assert!(Filter::apply_ascii_hex(b"4G>".to_vec()).is_err());
+    }
 
-            if is_whitespace(next_char) {
-                continue;
-            };
+    #[test]
+    fn test_ascii_85() {
+        assert_eq!(Filter::apply_ascii_85(b"87cURD]i,\"Ebo80~>".to_vec()).unwrap(), b"Hello World!".to_vec());
+        assert_eq!(Filter::apply_ascii_85(b"z87cU\nRD_~>".to_vec()).unwrap(), b"\0\0\0\0Hello".to_vec());
+        assert!(Filter::apply_ascii_85(b"87cUR{~>".to_vec()).is_err());
+    }
 
-            if next_char == b'~'
-                && self.data_cursor < self.last_index
-                && self.data[self.data_cursor + 1] == b'>'
-            {
-                return None;
-            };
+    #[test]
+    fn test_flate_round_trip() {
+        let data = b"BT /F1 12 Tf (Hello) Tj ET ".repeat(20);
+        let encoded = flate_encode(&data, 9).unwrap();
+        assert!(encoded.len() < data.len());
+        assert_eq!(Filter::apply_flate(encoded, None, None).unwrap(), data);
+    }
 
-            self.buffer[self.buffer_cursor] = Some(next_char);
-            self.buffer_cursor += 1;
+    #[test]
+    fn test_decode_params() {
+        let dict = || Rc::new(PdfObject::new_dictionary(Rc::new(PdfMap::new())));
+        let null = || Rc::new(PdfObject::Actual(Null));
+        let array = |items: Vec<SharedObject>| Rc::new(PdfObject::new_array(Rc::new(items)));
+        let present = |params: Vec<Option<SharedObject>>| params.iter().map(Option::is_some).collect::<Vec<_>>();
 
-            if self.buffer_cursor > 4 {
-                debug_assert_eq!(self.buffer_cursor, 5);
-                break;
-            };
-            if next_char == b'z' {
-                break;
-            }
-        }
-        let return_value = self.buffer;
-        self.buffer = [Option::None; 5];
-        self.buffer_cursor = 0;
-        return Some(return_value);
+        assert_eq!(present(decode_params(None, 2).unwrap()), vec![false, false]);
+        assert_eq!(present(decode_params(Some(&null()), 1).unwrap()), vec![false]);
+        assert_eq!(present(decode_params(Some(&dict()), 1).unwrap()), vec![true]);
+        assert!(decode_params(Some(&dict()), 2).is_err());
+        let params = array(vec![null(), dict()]);
+        assert_eq!(present(decode_params(Some(&params), 2).unwrap()), vec![false, true]);
+        let params = array(vec![dict()]);
+        assert_eq!(present(decode_params(Some(&params), 3).unwrap()), vec![true, false, false]);
+        assert!(decode_params(Some(&params), 0).is_err());
+        let params = array(vec![Rc::new(PdfObject::new_number_int(1))]);
+        assert!(decode_params(Some(&params), 1).is_err());
+        assert!(decode_params(Some(&Rc::new(PdfObject::new_boolean(true))), 1).is_err());
     }
-}
 
-struct AsciiData(Vec<u8>);
-
-impl AsciiData {
-    fn ascii85_iter(self) -> Ascii85Iterator {
-        let len = self.0.len();
-        Ascii85Iterator {
-            data: self.0,
-            data_cursor: 0,
-            last_index: len,
-            buffer: [Option::None; 5],
-            buffer_cursor: 0,
-        }
+    #[test]
+    fn test_decode_params_in_stream() {
+        use crate::test_utils::*;
+        let content = b"0 0 m 100 100 l S\n".repeat(5);
+        let hex: String = content.iter().map(|c| format!("{:02x}", c)).collect::<String>() + ">";
+        let mut builder = MiniPdfBuilder::new();
+        let stream = builder.add_stream(&TestFilter::Flate.encode(hex.as_bytes()), TestFilter::None,
+                                        "/Filter [/FlateDecode /ASCIIHexDecode] /DecodeParms [<< /Predictor 1 >>]");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.get_stream_data(ObjectId::new(stream, 0)).unwrap(), content);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_filter_stages() {
+        use crate::test_utils::*;
+        let content = b"BT /F1 12 Tf (Hello) Tj ET ".repeat(20);
+        let flate = TestFilter::Flate.encode(&content);
+        let hex: String = flate.iter().map(|c| format!("{:02x}", c)).collect::<String>() + ">";
+        let mut builder = MiniPdfBuilder::new();
+        let good = builder.add_stream(hex.as_bytes(), TestFilter::None, "/Filter [/ASCIIHexDecode /FlateDecode]");
+        let bad = builder.add_stream(b"00ff>", TestFilter::None, "/Filter [/ASCIIHexDecode /FlateDecode]");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let stream = pdf.get_object(ObjectId::new(good, 0)).unwrap().try_into_binary_stream().unwrap();
+        assert!(stream.filter_stages().is_empty());
+        assert_eq!(stream.data().unwrap(), &content[..]);
+        let stage = |index, filter: &str, input_bytes, output_bytes| {
+            FilterStage { index, filter: filter.to_string(), input_bytes, output_bytes }
+        };
+        assert_eq!(stream.filter_stages(), &[stage(0, "ASCIIHex Filter", hex.len(), flate.len()),
+                                             stage(1, "Flate Filter", flate.len(), content.len())]);
+
+        let error = pdf.get_stream_data(ObjectId::new(bad, 0)).unwrap_err();
+        match error.kind() {
+            ErrorKind::FilterError(message, _) => {
+                assert_eq!(message, &format!("Flate Filter (filter 1) failed on 2 input bytes in stream {} 0", bad))
+            },
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
 
     #[test]
+    #[cfg(feature = "fs")]
     fn flate_example() {
         let _pdf_file = PdfFileHandler::create_pdf_from_file("data/document.pdf").unwrap();
         //TODO: Example
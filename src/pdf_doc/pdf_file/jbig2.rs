@@ -0,0 +1,603 @@
+//! JBIG2 decoding (ITU T.88) for the JBIG2Decode filter, covering pages built from generic regions coded with
+//! the arithmetic coder.  Symbol dictionaries and text regions, halftones, refinement and MMR-coded regions
+//! aren't supported.
+
+use std::convert::TryFrom;
+
+use crate::errors::*;
+
+/// Probability estimates for the arithmetic decoder (T.88 Table E.1): Qe, the next index after a more probable
+/// symbol, the next after a less probable one, and whether the less probable one swaps the sense of the MPS.
+const QE_TABLE: [(u32, u8, u8, bool); 47] = [
+    (0x5601, 1, 1, true), (0x3401, 2, 6, false), (0x1801, 3, 9, false), (0x0AC1, 4, 12, false),
+    (0x0521, 5, 29, false), (0x0221, 38, 33, false), (0x5601, 7, 6, true), (0x5401, 8, 14, false),
+    (0x4801, 9, 14, false), (0x3801, 10, 14, false), (0x3001, 11, 17, false), (0x2401, 12, 18, false),
+    (0x1C01, 13, 20, false), (0x1601, 29, 21, false), (0x5601, 15, 14, true), (0x5401, 16, 14, false),
+    (0x5101, 17, 15, false), (0x4801, 18, 16, false), (0x3801, 19, 17, false), (0x3401, 20, 18, false),
+    (0x3001, 21, 19, false), (0x2801, 22, 19, false), (0x2401, 23, 20, false), (0x2201, 24, 21, false),
+    (0x1C01, 25, 22, false), (0x1801, 26, 23, false), (0x1601, 27, 24, false), (0x1401, 28, 25, false),
+    (0x1201, 29, 26, false), (0x1101, 30, 27, false), (0x0AC1, 31, 28, false), (0x09C1, 32, 29, false),
+    (0x08A1, 33, 30, false), (0x0521, 34, 31, false), (0x0441, 35, 32, false), (0x02A1, 36, 33, false),
+    (0x0221, 37, 34, false), (0x0141, 38, 35, false), (0x0111, 39, 36, false), (0x0085, 40, 37, false),
+    (0x0049, 41, 38, false), (0x0025, 42, 39, false), (0x0015, 43, 40, false), (0x0009, 44, 41, false),
+    (0x0005, 45, 42, false), (0x0001, 45, 43, false), (0x5601, 46, 46, false),
+];
+
+/// The fixed pixels of each generic region template (T.88 6.2.5.3), as offsets from the pixel being decoded,
+/// before the adaptive ones.
+const TEMPLATES: [&[(i32, i32)]; 4] = [
+    &[(-1, -2), (0, -2), (1, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1), (-4, 0), (-3, 0), (-2, 0), (-1, 0)],
+    &[(-1, -2), (0, -2), (1, -2), (2, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (2, -1), (-3, 0), (-2, 0), (-1, 0)],
+    &[(-1, -2), (0, -2), (1, -2), (-2, -1), (-1, -1), (0, -1), (1, -1), (-2, 0), (-1, 0)],
+    &[(-3, -1), (-2, -1), (-1, -1), (0, -1), (1, -1), (-4, 0), (-3, 0), (-2, 0), (-1, 0)],
+];
+/// The context whose decision says a row repeats the one above it, for typical prediction (T.88 6.2.5.7).
+const TYPICAL_PREDICTION_CONTEXTS: [usize; 4] = [0x9B25, 0x0795, 0x00E5, 0x0195];
+
+const PAGE_INFORMATION: u8 = 48;
+/// Pages whose height is only known once the last stripe ends give this as their height.
+const UNKNOWN_HEIGHT: u32 = 0xFFFF_FFFF;
+/// The most pixels a bitmap may have when there's no ParserOptions::max_stream_bytes
+const MAX_PIXELS: usize = 1 << 30;
+
+/// The arithmetic (MQ) decoder of T.88 Annex E.
+struct ArithmeticDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    c_high: u32,
+    c_low: u32,
+    a: u32,
+    ct: u32,
+}
+
+impl<'a> ArithmeticDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut decoder = ArithmeticDecoder { data, position: 0, c_high: 0, c_low: 0, a: 0, ct: 0 };
+        decoder.c_high = decoder.byte(0);
+        decoder.byte_in();
+        decoder.c_high = ((decoder.c_high << 7) & 0xFFFF) | ((decoder.c_low >> 9) & 0x7F);
+        decoder.c_low = (decoder.c_low << 7) & 0xFFFF;
+        decoder.ct -= 7;
+        decoder.a = 0x8000;
+        decoder
+    }
+
+    /// Past the end of the data, the decoder reads 0xFF bytes, as at an end marker.
+    fn byte(&self, position: usize) -> u32 {
+        self.data.get(position).copied().unwrap_or(0xFF) as u32
+    }
+
+    fn byte_in(&mut self) {
+        if self.byte(self.position) == 0xFF {
+            if self.byte(self.position + 1) > 0x8F {
+                self.c_low += 0xFF00;
+                self.ct = 8;
+            } else {
+                self.position += 1;
+                self.c_low += self.byte(self.position) << 9;
+                self.ct = 7;
+            };
+        } else {
+            self.position += 1;
+            self.c_low += self.byte(self.position) << 8;
+            self.ct = 8;
+        };
+        if self.c_low > 0xFFFF {
+            self.c_high += self.c_low >> 16;
+            self.c_low &= 0xFFFF;
+        };
+    }
+
+    /// Decode one bit, with contexts[context] holding its probability state index and MPS in the low bit.
+    fn decode(&mut self, contexts: &mut [u8], context: usize) -> u8 {
+        let (mut index, mut mps) = (contexts[context] >> 1, contexts[context] & 1);
+        let (qe, next_mps, next_lps, switch) = QE_TABLE[index as usize];
+        let mut a = self.a - qe;
+        let bit;
+        if self.c_high < qe {
+            if a < qe {
+                bit = mps;
+                index = next_mps;
+            } else {
+                bit = 1 - mps;
+                if switch { mps = bit };
+                index = next_lps;
+            };
+            a = qe;
+        } else {
+            self.c_high -= qe;
+            if a & 0x8000 != 0 {
+                self.a = a;
+                return mps;
+            };
+            if a < qe {
+                bit = 1 - mps;
+                if switch { mps = bit };
+                index = next_lps;
+            } else {
+                bit = mps;
+                index = next_mps;
+            };
+        };
+        while a & 0x8000 == 0 {
+            if self.ct == 0 {
+                self.byte_in();
+            };
+            a <<= 1;
+            self.c_high = ((self.c_high << 1) & 0xFFFF) | ((self.c_low >> 15) & 1);
+            self.c_low = (self.c_low << 1) & 0xFFFF;
+            self.ct -= 1;
+        }
+        self.a = a;
+        contexts[context] = (index << 1) | mps;
+        bit
+    }
+}
+
+/// A bilevel image, one byte a pixel, with 1 for black as in JBIG2.
+#[derive(Debug, Clone, PartialEq)]
+struct Bitmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    fn new(width: u32, height: u32, value: u8, max_pixels: usize) -> Result<Self> {
+        let size = Bitmap::size(width, height, max_pixels)?;
+        Ok(Bitmap { width, height, pixels: vec![value; size] })
+    }
+
+    /// The number of pixels in a width x height bitmap, which is also its size in bytes, if it's at most
+    /// max_pixels.
+    fn size(width: u32, height: u32, max_pixels: usize) -> Result<usize> {
+        (width as usize).checked_mul(height as usize)
+                        .filter(|size| *size <= max_pixels)
+                        .ok_or_else(|| jbig2_error(format!("{}x{} bitmap is too large", width, height)))
+    }
+
+    /// The pixel at (x, y), or 0 outside the bitmap.
+    fn get(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            0
+        } else {
+            self.pixels[y as usize * self.width as usize + x as usize]
+        }
+    }
+
+    /// Combine region into this bitmap with its top left corner at (x, y), by the combination operator of
+    /// T.88 7.4.1.5: OR, AND, XOR, XNOR or REPLACE.
+    fn compose(&mut self, region: &Bitmap, x: u32, y: u32, operator: u8) -> Result<()> {
+        for row in 0..region.height.min(self.height.saturating_sub(y)) {
+            for column in 0..region.width.min(self.width.saturating_sub(x)) {
+                let source = region.pixels[(row * region.width + column) as usize];
+                let target = &mut self.pixels[((y + row) * self.width + x + column) as usize];
+                *target = match operator {
+                    0 => *target | source,
+                    1 => *target & source,
+                    2 => *target ^ source,
+                    3 => 1 - (*target ^ source),
+                    4 => source,
+                    _ => Err(jbig2_error(format!("Invalid combination operator {}", operator)))?
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Rows of one bit a pixel, each starting on a byte boundary, with 0 for black as in PDF image samples.
+    fn to_samples(&self) -> Vec<u8> {
+        let row_bytes = (self.width as usize).div_ceil(8);
+        let mut samples = vec![0xFF; row_bytes * self.height as usize];
+        for (index, pixel) in self.pixels.iter().enumerate() {
+            if *pixel == 1 {
+                let (row, column) = (index / self.width as usize, index % self.width as usize);
+                samples[row * row_bytes + column / 8] &= !(0x80 >> (column % 8));
+            };
+        }
+        samples
+    }
+}
+
+/// A segment header and its data (T.88 7.2).
+struct Segment<'a> {
+    number: u32,
+    segment_type: u8,
+    data: &'a [u8],
+}
+
+/// Reads big-endian integers from segment data, failing at the end.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position + count)
+                             .ok_or_else(|| jbig2_error(format!("Data ends at {}", self.data.len())))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(<[u8; 4]>::try_from(self.bytes(4)?).unwrap()))
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+}
+
+/// The segments of embedded JBIG2 data, which is a sequence of segments with no file header (PDF 7.4.7).
+fn segments(data: &[u8]) -> Result<Vec<Segment<'_>>> {
+    let mut reader = Reader { data, position: 0 };
+    let mut segments = Vec::new();
+    while !reader.at_end() {
+        let number = reader.u32()?;
+        let flags = reader.u8()?;
+        let referred = reader.u8()?;
+        let referred_count = match referred >> 5 {
+            7 => {
+                reader.position -= 1;
+                let count = reader.u32()? & 0x1FFF_FFFF;
+                reader.bytes((count as usize + 8) / 8)?;
+                count
+            },
+            count => count as u32
+        };
+        let number_size = if number <= 256 { 1 } else if number <= 65536 { 2 } else { 4 };
+        reader.bytes(referred_count as usize * number_size)?;
+        reader.bytes(if flags & 0x40 != 0 { 4 } else { 1 })?;
+        let length = reader.u32()?;
+        if length == 0xFFFF_FFFF {
+            Err(jbig2_error(format!("Segment {} has an unknown length", number)))?
+        };
+        segments.push(Segment { number, segment_type: flags & 0x3F, data: reader.bytes(length as usize)? });
+    }
+    Ok(segments)
+}
+
+/// Decode the JBIG2Decode data of an image, with the segments of its /JBIG2Globals stream if it has one, into
+/// rows of one bit a pixel as the filter gives them.  Bitmaps are held a byte a pixel, so none may have more
+/// pixels than max_bytes (ParserOptions::max_stream_bytes), or MAX_PIXELS.
+pub(super) fn decode_jbig2(data: &[u8], globals: Option<&[u8]>, max_bytes: Option<usize>) -> Result<Vec<u8>> {
+    let max_pixels = max_bytes.map_or(MAX_PIXELS, |max| max.min(MAX_PIXELS));
+    let mut all_segments = match globals {
+        Some(globals) => segments(globals)?,
+        None => Vec::new()
+    };
+    all_segments.extend(segments(data)?);
+    let mut page: Option<(Bitmap, bool)> = None;
+    for segment in all_segments {
+        match segment.segment_type {
+            PAGE_INFORMATION => {
+                let mut reader = Reader { data: segment.data, position: 0 };
+                let (width, height) = (reader.u32()?, reader.u32()?);
+                reader.bytes(8)?;
+                let flags = reader.u8()?;
+                let unknown_height = height == UNKNOWN_HEIGHT;
+                let height = if unknown_height { 0 } else { height };
+                page = Some((Bitmap::new(width, height, (flags >> 2) & 1, max_pixels)?, unknown_height));
+            },
+            // Immediate generic regions, lossy and lossless
+            38 | 39 => {
+                let (page, unknown_height) = page.as_mut().ok_or_else(|| jbig2_error(format!(
+                    "Region segment {} comes before the page information", segment.number)))?;
+                let mut reader = Reader { data: segment.data, position: 0 };
+                let (width, height, x, y) = (reader.u32()?, reader.u32()?, reader.u32()?, reader.u32()?);
+                let operator = reader.u8()? & 7;
+                let region = generic_region(&mut reader, width, height, max_pixels)?;
+                if *unknown_height && y.saturating_add(height) > page.height {
+                    let grown = y.saturating_add(height);
+                    let size = Bitmap::size(page.width, grown, max_pixels)?;
+                    page.pixels.resize(size, page.pixels.first().copied().unwrap_or(0));
+                    page.height = grown;
+                };
+                page.compose(&region, x, y, operator)?;
+            },
+            // Symbol dictionaries, which only text regions use; the end of a stripe, page or file; profiles,
+            // tables and extensions
+            0 | 49 | 50 | 51 | 52 | 53 | 62 => {},
+            segment_type => Err(jbig2_error(format!("Segment {} has unsupported type {}", segment.number,
+                                                    segment_type)))?
+        }
+    }
+    match page {
+        Some((page, _)) => Ok(page.to_samples()),
+        None => Err(jbig2_error("No page information segment".to_string()))?
+    }
+}
+
+/// Decode the bitmap of a generic region segment, from its generic region flags on (T.88 7.4.6).
+fn generic_region(reader: &mut Reader<'_>, width: u32, height: u32, max_pixels: usize) -> Result<Bitmap> {
+    let flags = reader.u8()?;
+    if flags & 1 != 0 {
+        Err(jbig2_error("MMR-coded generic regions aren't supported".to_string()))?
+    };
+    let template = ((flags >> 1) & 3) as usize;
+    let typical_prediction = flags & 8 != 0;
+    let mut pixels = TEMPLATES[template].to_vec();
+    for _ in 0..if template == 0 { 4 } else { 1 } {
+        let (x, y) = (reader.u8()? as i8, reader.u8()? as i8);
+        pixels.push((x as i32, y as i32));
+    }
+    // Context bits run from the top left of the template, which is the order the typical prediction contexts
+    // are given in
+    pixels.sort_by_key(|(x, y)| (*y, *x));
+
+    let mut decoder = ArithmeticDecoder::new(&reader.data[reader.position..]);
+    let mut contexts = vec![0u8; 1 << 16];
+    let mut bitmap = Bitmap::new(width, height, 0, max_pixels)?;
+    let mut repeat_row = false;
+    for y in 0..height as i32 {
+        if typical_prediction {
+            repeat_row ^= decoder.decode(&mut contexts, TYPICAL_PREDICTION_CONTEXTS[template]) == 1;
+            if repeat_row {
+                let row = width as usize;
+                if y > 0 {
+                    let start = y as usize * row;
+                    bitmap.pixels.copy_within(start - row..start, start);
+                };
+                continue;
+            };
+        };
+        for x in 0..width as i32 {
+            let context = pixels.iter().fold(0, |context, (dx, dy)| {
+                (context << 1) | bitmap.get(x + dx, y + dy) as usize
+            });
+            bitmap.pixels[y as usize * width as usize + x as usize] = decoder.decode(&mut contexts, context);
+        }
+    }
+    Ok(bitmap)
+}
+
+fn jbig2_error(message: String) -> Error {
+    ErrorKind::FilterError(message, "decode_jbig2").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use crate::doc_tree::pdf_file::*;
+    use crate::doc_tree::pdf_objects::PdfObjectInterface;
+
+    /// The arithmetic coder of T.88 Annex E, to make test data.
+    struct ArithmeticEncoder {
+        output: Vec<u8>,
+        a: u32,
+        c: u32,
+        ct: u32,
+        b: u32,
+    }
+
+    impl ArithmeticEncoder {
+        fn new() -> Self {
+            ArithmeticEncoder { output: Vec::new(), a: 0x8000, c: 0, ct: 12, b: 0 }
+        }
+
+        fn encode(&mut self, contexts: &mut [u8], context: usize, bit: u8) {
+            let (mut index, mut mps) = (contexts[context] >> 1, contexts[context] & 1);
+            let (qe, next_mps, next_lps, switch) = QE_TABLE[index as usize];
+            if bit == mps {
+                self.a -= qe;
+                if self.a & 0x8000 == 0 {
+                    if self.a < qe { self.a = qe } else { self.c += qe };
+                    index = next_mps;
+                    self.renormalize();
+                } else {
+                    self.c += qe;
+                };
+            } else {
+                self.a -= qe;
+                if self.a < qe { self.c += qe } else { self.a = qe };
+                if switch { mps = 1 - mps };
+                index = next_lps;
+                self.renormalize();
+            };
+            contexts[context] = (index << 1) | mps;
+        }
+
+        fn renormalize(&mut self) {
+            while self.a & 0x8000 == 0 {
+                self.a <<= 1;
+                self.c <<= 1;
+                self.ct -= 1;
+                if self.ct == 0 {
+                    self.byte_out();
+                };
+            }
+        }
+
+        fn byte_out(&mut self) {
+            if self.b == 0xFF {
+                self.push();
+                self.b = self.c >> 20;
+                self.c &= 0xFFFFF;
+                self.ct = 7;
+            } else if self.c < 0x8000000 {
+                self.push();
+                self.b = self.c >> 19;
+                self.c &= 0x7FFFF;
+                self.ct = 8;
+            } else {
+                self.b += 1;
+                if self.b == 0xFF {
+                    self.c &= 0x7FFFFFF;
+                    self.push();
+                    self.b = self.c >> 20;
+                    self.c &= 0xFFFFF;
+                    self.ct = 7;
+                } else {
+                    self.push();
+                    self.b = self.c >> 19;
+                    self.c &= 0x7FFFF;
+                    self.ct = 8;
+                };
+            };
+        }
+
+        /// Write the byte held back, which the first call has none of.
+        fn push(&mut self) {
+            self.output.push(self.b as u8);
+        }
+
+        fn flush(mut self) -> Vec<u8> {
+            let temp = self.c + self.a;
+            self.c |= 0xFFFF;
+            if self.c >= temp { self.c -= 0x8000 };
+            self.c <<= self.ct;
+            self.byte_out();
+            self.c <<= self.ct;
+            self.byte_out();
+            self.push();
+            if self.b != 0xFF {
+                self.output.extend_from_slice(&[0xFF, 0xAC]);
+            };
+            // The byte before the first output byte is never written
+            self.output.remove(0);
+            self.output
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_decoder() {
+        // The test sequence of T.88 H.2, coded in a single context
+        let input = [0x00, 0x02, 0x00, 0x51, 0x00, 0x00, 0x00, 0xC0, 0x03, 0x52, 0x87, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA,
+                     0x82, 0xC0, 0x20, 0x00, 0xFC, 0xD7, 0x9E, 0xF6, 0xBF, 0x7F, 0xED, 0x90, 0x4F, 0x46, 0xA3, 0xBF];
+        let coded = [0x84, 0xC7, 0x3B, 0xFC, 0xE1, 0xA1, 0x43, 0x04, 0x02, 0x20, 0x00, 0x00, 0x41, 0x0D, 0xBB, 0x86,
+                     0xF4, 0x31, 0x7F, 0xFF, 0x88, 0xFF, 0x37, 0x47, 0x1A, 0xDB, 0x6A, 0xDF, 0xFF, 0xAC];
+        let mut decoder = ArithmeticDecoder::new(&coded);
+        let mut contexts = [0u8];
+        let decoded = (0..input.len()).map(|_| (0..8).fold(0, |byte, _| (byte << 1) | decoder.decode(&mut contexts, 0)))
+                                      .collect::<Vec<u8>>();
+        assert_eq!(decoded, input);
+
+        let mut encoder = ArithmeticEncoder::new();
+        let mut contexts = [0u8];
+        for byte in &input {
+            for bit in (0..8).rev() {
+                encoder.encode(&mut contexts, 0, (byte >> bit) & 1);
+            }
+        }
+        assert_eq!(encoder.flush(), coded);
+    }
+
+    /// A segment header with one byte page association, and the data.
+    fn segment(number: u32, segment_type: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = number.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[segment_type, 0, 1]);
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// A generic region segment for the bitmap, coded with the template and typical prediction.
+    fn generic_region_segment(number: u32, bitmap: &Bitmap, x: u32, y: u32, template: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in &[bitmap.width, bitmap.height, x, y] {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data.push(0);
+        data.push(((template as u8) << 1) | 8);
+        let adaptive: &[(i8, i8)] = if template == 0 { &[(3, -1), (-3, -1), (2, -2), (-2, -2)] } else { &[(3, -1)] };
+        let mut pixels = TEMPLATES[template].to_vec();
+        for (x, y) in adaptive {
+            data.extend_from_slice(&[*x as u8, *y as u8]);
+            pixels.push((*x as i32, *y as i32));
+        }
+        pixels.sort_by_key(|(x, y)| (*y, *x));
+
+        let mut encoder = ArithmeticEncoder::new();
+        let mut contexts = vec![0u8; 1 << 16];
+        let width = bitmap.width as usize;
+        let mut repeating = false;
+        for y in 0..bitmap.height as i32 {
+            let row = &bitmap.pixels[y as usize * width..(y as usize + 1) * width];
+            let repeats = if y == 0 {
+                row.iter().all(|pixel| *pixel == 0)
+            } else {
+                row == &bitmap.pixels[(y as usize - 1) * width..y as usize * width]
+            };
+            encoder.encode(&mut contexts, TYPICAL_PREDICTION_CONTEXTS[template], (repeats != repeating) as u8);
+            repeating = repeats;
+            if repeats {
+                continue;
+            };
+            for x in 0..bitmap.width as i32 {
+                let context = pixels.iter().fold(0, |context, (dx, dy)| {
+                    (context << 1) | bitmap.get(x + dx, y + dy) as usize
+                });
+                encoder.encode(&mut contexts, context, row[x as usize]);
+            }
+        }
+        data.extend(encoder.flush());
+        segment(number, 38, &data)
+    }
+
+    #[test]
+    fn test_generic_regions() {
+        // A 20x12 page with a frame, part of a diagonal and repeated rows for typical prediction
+        let (width, height) = (20, 12);
+        let mut bitmap = Bitmap::new(width, height, 0, MAX_PIXELS).unwrap();
+        for y in 0..height {
+            for x in 0..width {
+                let frame = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                let on = frame || x == y && y < 7 || (4..7).contains(&y) && x % 3 == 0;
+                bitmap.pixels[(y * width + x) as usize] = on as u8;
+            }
+        }
+        let mut page_information = Vec::new();
+        for value in &[width, height, 0, 0] {
+            page_information.extend_from_slice(&value.to_be_bytes());
+        }
+        page_information.extend_from_slice(&[0, 0, 0]);
+        for template in 0..4 {
+            let mut data = segment(1, PAGE_INFORMATION, &page_information);
+            data.extend(generic_region_segment(2, &bitmap, 0, 0, template));
+            data.extend(segment(3, 49, &[]));
+            let samples = decode_jbig2(&data, None, None).unwrap();
+            assert_eq!(samples, bitmap.to_samples(), "template {}", template);
+        }
+
+        // A region placed inside the page, with the page information from the globals
+        let mut small = Bitmap::new(3, 2, 1, MAX_PIXELS).unwrap();
+        small.pixels[4] = 0;
+        let globals = segment(1, PAGE_INFORMATION, &[0, 0, 0, 10, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let samples = decode_jbig2(&generic_region_segment(2, &small, 2, 1, 0), Some(&globals), None).unwrap();
+        // Black is 0 in the output, and rows are padded to a byte boundary
+        assert_eq!(samples, vec![0xFF, 0xFF, 0xC7, 0xFF, 0xD7, 0xFF, 0xFF, 0xFF]);
+
+        assert!(decode_jbig2(&segment(1, 6, &[]), None, None).is_err());
+
+        // A page of unknown height can't grow past the limit, nor can a page be larger than max_bytes
+        let mut data = segment(1, PAGE_INFORMATION, &[0, 0, 0x40, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                                                  0, 0]);
+        data.extend(generic_region_segment(2, &small, 0, 0xFFFF_0000, 0));
+        assert!(decode_jbig2(&data, None, None).is_err());
+        assert!(decode_jbig2(&generic_region_segment(2, &small, 2, 1, 0), Some(&globals), Some(39)).is_err());
+        assert!(decode_jbig2(&generic_region_segment(2, &small, 2, 1, 0), Some(&globals), Some(40)).is_ok());
+    }
+
+    #[test]
+    fn test_jbig2_images() {
+        let mut small = Bitmap::new(3, 2, 1, MAX_PIXELS).unwrap();
+        small.pixels[4] = 0;
+        let mut builder = MiniPdfBuilder::new();
+        let globals = builder.add_stream(&segment(1, PAGE_INFORMATION, &[0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0,
+                                                                         0, 0, 0, 0, 0]),
+                                         TestFilter::Flate, "");
+        let image = builder.add_stream(&generic_region_segment(2, &small, 0, 0, 1), TestFilter::None, &format!(
+            "/Type /XObject /Subtype /Image /Width 3 /Height 2 /BitsPerComponent 1 /ColorSpace /DeviceGray \
+             /Filter /JBIG2Decode /DecodeParms << /JBIG2Globals {} 0 R >>", globals));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let stream = pdf.get_object(ObjectId::new(image, 0)).unwrap().try_into_binary_stream().unwrap();
+        // Black is 0, with the row padded out with white
+        assert_eq!(stream.image_data().unwrap(), (vec![0x1F, 0x5F], None));
+    }
+}
@@ -1,69 +1,528 @@
 pub mod decode;
-mod util;
-mod file_reader;
+pub mod util;
+mod jbig2;
+mod pool;
+#[cfg(test)]
+mod proptests;
+mod writer;
 
 
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::fmt;
+#[cfg(feature = "fs")]
 use std::fs;
+use std::ops::Range;
 use std::rc::{Rc, Weak};
 use std::str;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
 use crate::errors::*;
 
 pub use super::pdf_objects::*;
+pub use decode::PdfContentStream;
+pub use pool::*;
 use util::*;
+pub use writer::*;
+
+/// How far from the end of the file the last %%EOF marker is looked for.
+const EOF_SEARCH_LENGTH: usize = 1024;
+/// The largest generation number an object can have (spec 7.5.4).
+pub const MAX_GENERATION: u32 = 65535;
 
 pub trait PdfFileInterface<T: PdfObjectInterface> {
     fn retrieve_object_by_ref(&self, id: u32, gen: u32) -> Result<Rc<T>>;
     fn retrieve_trailer(&self) -> Result<SharedObject>;
 }
 
+/// Settings for reading files.  The limits bound how much a file can make the parser allocate, for reading
+/// untrusted files; each is off when None, as it is by default.
+#[derive(Debug, Clone, Default)]
+pub struct ParserOptions {
+    /// Largest stream body to read, both as declared by /Length and after decoding
+    pub max_stream_bytes: Option<usize>,
+    /// Most elements in an array, or key-value pairs in a dictionary
+    pub max_collection_entries: Option<usize>,
+    /// Most objects in the cross-reference index
+    pub max_objects: Option<usize>,
+    /// Most tokens in one object, counting those of the arrays and dictionaries inside it, so that a huge
+    /// object fails with ErrorKind::ObjectTooComplex instead of stalling the parser
+    pub max_object_tokens: Option<usize>,
+    /// Longest literal string, in bytes after escapes are read, so that one whose closing parenthesis is missing
+    /// can't run on through the rest of the file
+    pub max_string_bytes: Option<usize>,
+    /// Record comments found inside objects, for ObjectCache::comments.  They are skipped either way.
+    pub collect_comments: bool,
+    /// Recover from common generator mistakes, like a missing endobj, instead of failing
+    pub lenient: bool,
+    /// Called as long operations on the file or document move along
+    pub progress: Option<ProgressHook>,
+    /// Checked while parsing and extracting, which fail with ErrorKind::Cancelled once it's cancelled
+    pub cancel: Option<CancellationToken>,
+}
+
+/// A flag that can be set from any thread to stop work on a file promptly.  Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A stage of a long operation, as reported to ParserOptions::progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressPhase {
+    /// Reading cross-reference sections, from the last one back through /Prev.  The total is only known once
+    /// the first section is reached.
+    XrefScan,
+    /// Parsing objects in bulk, as ObjectCache::prefetch does
+    ObjectParse,
+    /// Working through the pages of the document, as PdfDoc::text does
+    Pages,
+}
+
+/// How far along a phase is: done steps out of total, if the total is known yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    pub done: usize,
+    pub total: Option<usize>,
+}
+
+impl Progress {
+    pub fn percent(&self) -> Option<f32> {
+        self.total.map(|total| if total == 0 { 100.0 } else { 100.0 * self.done as f32 / total as f32 })
+    }
+}
+
+/// A callback for progress reports, shared by clones of the options it's set in.
+#[derive(Clone)]
+pub struct ProgressHook(Rc<dyn Fn(Progress)>);
+
+impl ProgressHook {
+    pub fn new<F: Fn(Progress) + 'static>(callback: F) -> Self {
+        ProgressHook(Rc::new(callback))
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProgressHook")
+    }
+}
+
+impl ParserOptions {
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => Err(ErrorKind::Cancelled.into()),
+            _ => Ok(())
+        }
+    }
+
+    pub(crate) fn report(&self, phase: ProgressPhase, done: usize, total: Option<usize>) {
+        if let Some(hook) = &self.progress {
+            (hook.0)(Progress { phase, done, total });
+        };
+    }
+
+    /// Check a generation number read from the file against MAX_GENERATION.  Lenient parsing clamps larger
+    /// ones with a warning, the same way in xref entries and references, so that the two still match.
+    fn generation<F: FnOnce() -> String>(&self, generation: u32, context: F, cache: Option<&ObjectCache>)
+                                         -> Result<u32> {
+        if generation <= MAX_GENERATION {
+            return Ok(generation);
+        };
+        let message = format!("Generation number {} {} is over {}", generation, context(), MAX_GENERATION);
+        if !self.lenient {
+            return Err(ErrorKind::ParsingError(message).into());
+        };
+        repaired(cache, "generation number", message);
+        Ok(MAX_GENERATION)
+    }
+
+    fn check(limit: &'static str, value: usize, max: Option<usize>) -> Result<()> {
+        match max {
+            Some(max) if value > max => Err(ErrorKind::LimitExceeded(limit, value, max).into()),
+            _ => Ok(())
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ObjectCache {
     cache: RefCell<HashMap<ObjectId, Rc<PdfObject>>>,
     index_map: RefCell<HashMap<ObjectId, usize>>,
     data: Vec<u8>,
-    self_ref: RefCell<Weak<Self>>
+    /// Handed to parsed references so they can resolve through the cache without keeping it alive
+    self_ref: Weak<Self>,
+    options: ParserOptions,
+    /// Objects replaced or added since the file was read, which exist only in the cache
+    edited: RefCell<HashSet<ObjectId>>,
+    /// Bumped by every set_object, so that results worked out from the objects can tell they're stale
+    edit_count: Cell<usize>,
+    /// Comments by offset, if ParserOptions::collect_comments is set.  Keyed by offset because objects can be
+    /// parsed more than once.
+    comments: RefCell<BTreeMap<usize, String>>,
+    stats: RefCell<BTreeMap<&'static str, ObjectStats>>,
+    /// Offsets of every object header in the file, found by ObjectCache::definitions on first use
+    definitions: OnceCell<HashMap<ObjectId, Vec<usize>>>,
+    /// Small values shared by the objects parsed from the file
+    pool: ObjectPool,
+    /// Problems lenient parsing has worked around, by kind
+    repairs: RefCell<BTreeMap<&'static str, usize>>,
+}
+
+/// Approximate memory held by an open file, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// The file itself, which is kept for as long as it's open
+    pub file_bytes: usize,
+    /// Decoded data of cached streams
+    pub decoded_stream_bytes: usize,
+    /// Cached objects other than their stream data.  An estimate, since hash table overhead isn't counted.
+    pub object_bytes: usize,
+    pub cached_objects: usize,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> usize {
+        self.file_bytes + self.decoded_stream_bytes + self.object_bytes
+    }
+}
+
+/// Where the current version of an object comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectLocation {
+    /// Read from the file, at this byte offset
+    Offset(usize),
+    /// Added or replaced since the file was read
+    Edited,
+}
+
+/// How the cache has been used for one kind of object.  Stream objects count as misses when they're parsed; the
+/// time spent applying their filters, which happens later, is counted separately.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectStats {
+    /// Retrievals answered from the cache
+    pub hits: usize,
+    /// Objects parsed from the file, whether on retrieval or by prefetch
+    pub misses: usize,
+    pub parse_time: Duration,
+    /// Time spent decoding stream data
+    pub decode_time: Duration,
+}
+
+/// Measures time for ObjectCache::stats.  Instant panics on wasm32-unknown-unknown, so no time passes there.
+pub(crate) struct Timer {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Timer {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return Duration::ZERO;
+    }
+}
+
+/// A comment inside an object, at the offset of its % sign.  The header and %%EOF markers aren't included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfComment {
+    pub offset: usize,
+    pub text: String,
 }
 
 
 impl ObjectCache {
-    fn new(data: Vec<u8>, index: HashMap<ObjectId, usize>, weak_ref: Weak<Self>) -> Self {
-        ObjectCache{
+    fn new(data: Vec<u8>, index: HashMap<ObjectId, usize>, options: ParserOptions) -> Rc<Self> {
+        Rc::new_cyclic(|self_ref| ObjectCache {
             cache: RefCell::new(HashMap::new()),
             index_map: RefCell::new(index),
             data,
-            self_ref: RefCell::new(weak_ref)
+            self_ref: Weak::clone(self_ref),
+            options,
+            edited: RefCell::new(HashSet::new()),
+            edit_count: Cell::new(0),
+            comments: RefCell::new(BTreeMap::new()),
+            stats: RefCell::new(BTreeMap::new()),
+            definitions: OnceCell::new(),
+            pool: ObjectPool::default(),
+            repairs: RefCell::new(BTreeMap::new()),
+        })
+    }
+
+    /// The options the file was read with.
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    /// Cache hits and misses, and time spent parsing and decoding, by kind of object ("dictionary", "stream" and
+    /// so on), for finding where time goes in large documents.
+    pub fn stats(&self) -> BTreeMap<&'static str, ObjectStats> {
+        self.stats.borrow().clone()
+    }
+
+    /// How much memory the file, its decoded streams and its parsed objects take up.  Streams whose data hasn't
+    /// been asked for yet hold none.
+    pub fn memory_report(&self) -> MemoryReport {
+        let cache = self.cache.borrow();
+        let mut report = MemoryReport {
+            file_bytes: self.data.len(),
+            cached_objects: cache.len(),
+            ..Default::default()
+        };
+        for object in cache.values() {
+            add_object_size(object, &mut report);
+        }
+        report
+    }
+
+    /// How many small values parsed objects share, and how many allocations sharing them has saved.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// Record time spent decoding a stream's data.
+    pub(crate) fn record_decode(&self, time: Duration) {
+        self.stats.borrow_mut().entry("stream").or_default().decode_time += time;
+    }
+
+    /// Ids of all objects in the cross-reference index or added since, sorted by object number and generation.
+    pub fn object_ids(&self) -> Vec<ObjectId> {
+        let mut ids: Vec<ObjectId> = self.index_map.borrow().keys().cloned().collect();
+        ids.extend(self.edited.borrow().iter().filter(|id| !self.index_map.borrow().contains_key(id)));
+        ids.sort();
+        ids
+    }
+
+    /// Every object with where it comes from, in the same order as object_ids.
+    pub fn locations(&self) -> impl Iterator<Item = (ObjectId, ObjectLocation)> {
+        let index = self.index_map.borrow();
+        let locations: Vec<(ObjectId, ObjectLocation)> = self.object_ids().into_iter().map(|id| {
+            match index.get(&id) {
+                Some(offset) if !self.is_edited(id) => (id, ObjectLocation::Offset(*offset)),
+                _ => (id, ObjectLocation::Edited),
+            }
+        }).collect();
+        locations.into_iter()
+    }
+
+    /// Offsets of every "N G obj" header for the given object in the file, in file order.  These include
+    /// definitions the cross-reference index doesn't use, like ones replaced by incremental updates or left twice
+    /// in one body by broken generators.  Reads use the index's offset, which is the newest xref section's, unless
+    /// lenient parsing falls back on the newest readable definition because nothing can be parsed there.
+    /// The whole file is scanned for headers once, so text in stream data that looks like one is counted too.
+    pub fn definitions(&self, id: ObjectId) -> Vec<usize> {
+        self.definitions.get_or_init(|| scan_object_headers(&self.data)).get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Whether the cross-reference index contains an entry for the given object, or it has been added since.
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.index_map.borrow().contains_key(&id) || self.edited.borrow().contains(&id)
+    }
+
+    /// Replace an object, or add one under a new id.  The change is seen by later reads and written out by
+    /// PdfFileHandler::write; the file data itself is untouched.
+    pub fn set_object(&self, id: ObjectId, obj: PdfObject) {
+        self.cache.borrow_mut().insert(id, Rc::new(obj));
+        self.edited.borrow_mut().insert(id);
+        self.edit_count.set(self.edit_count.get() + 1);
+    }
+
+    /// Add an object under the next unused object number, returning its id.
+    pub fn add_object(&self, obj: PdfObject) -> ObjectId {
+        let id = ObjectId::new(self.object_ids().last().map_or(1, |last| last.number() + 1), 0);
+        self.set_object(id, obj);
+        id
+    }
+
+    /// A reference to the object with the given id, resolved through this cache.
+    pub fn reference(&self, id: ObjectId) -> PdfObject {
+        PdfObject::new_reference(id.number(), id.generation(), Weak::clone(&self.self_ref))
+    }
+
+    /// Problems in the file that lenient parsing has worked around so far, like a missing endobj, by kind.  Each
+    /// is counted every time it's met, so an object parsed twice counts twice.
+    pub fn repairs(&self) -> BTreeMap<&'static str, usize> {
+        self.repairs.borrow().clone()
+    }
+
+    /// How many times an object has been replaced or added since the file was read.  Anything worked out from
+    /// the objects is out of date once this changes.
+    pub fn edit_count(&self) -> usize {
+        self.edit_count.get()
+    }
+
+    /// Whether an object has been replaced or added since the file was read.
+    pub fn is_edited(&self, id: ObjectId) -> bool {
+        self.edited.borrow().contains(&id)
+    }
+
+    /// The bytes of the file in range, borrowed from the file buffer.
+    pub fn get_span(&self, range: Range<usize>) -> Result<&[u8]> {
+        self.data.get(range.clone()).ok_or_else(|| ErrorKind::ParsingError(
+            format!("Span {:?} out of range (file length: {})", range, self.data.len())).into())
+    }
+
+    /// The stream object with the given id as a content stream, with the resources its names refer to.  The
+    /// cached object stays a binary stream, holding the decoded data, since pages sharing a stream can each give
+    /// it their own resources.  Images and streams whose filters couldn't be applied aren't content streams.
+    pub fn content_stream(&self, id: ObjectId, resources: Option<SharedObject>) -> Result<Rc<PdfContentStream>> {
+        let object = self.retrieve_object_by_ref(id.number(), id.generation())?;
+        match object.as_ref() {
+            PdfObject::Actual(ContentStream(stream)) => {
+                Ok(Rc::new(PdfContentStream::new(stream.attributes().clone(), stream.data().to_vec(), resources)))
+            },
+            PdfObject::Actual(BinaryStream(stream)) if stream.is_decoded() => {
+                Ok(Rc::new(PdfContentStream::from_binary_stream(stream, resources)?))
+            },
+            _ => Err(ErrorKind::UnavailableType("content stream".to_string(), format!("{}", id)))?
+        }
+    }
+
+    /// Comments collected so far, in file order.  Only objects that have been parsed are searched, so call
+    /// prefetch with object_ids first to find every comment.  Empty unless ParserOptions::collect_comments is set.
+    pub fn comments(&self) -> Vec<PdfComment> {
+        self.comments.borrow().iter().map(|(offset, text)| PdfComment { offset: *offset, text: text.clone() }).collect()
+    }
+
+    /// The bytes an indirect object occupies in the file, from its "N G obj" header through its endobj keyword,
+    /// including any stream body.  Objects added or replaced since the file was read have no span.
+    pub fn object_span(&self, id: ObjectId) -> Result<Range<usize>> {
+        if self.is_edited(id) {
+            Err(ErrorKind::ReferenceError(format!("Object {} has been edited and isn't in the file", id)))?
+        };
+        let start = *self.index_map.borrow().get(&id).ok_or_else(|| ErrorKind::ReferenceError(
+            format!("Object {} does not exist", id)))?;
+        let (object, mut end) = parse_object_at(&self.data, start, &self.self_ref, &self.options)?;
+        // Stream objects end just after the stream body, before endstream and endobj
+        if object.is_stream() {
+            end = find_bytes(&self.data, b"endobj", end).ok_or_else(|| ErrorKind::ParsingError(
+                format!("No endobj after stream object {}", id)))? + 6;
+        };
+        Ok(start..end)
+    }
+
+    /// A hexdump of the file from radius bytes before offset to radius bytes after it, with the line holding offset
+    /// flagged, to show what the parser saw there.  Lines start on multiples of 16 and stop at the end of the file.
+    pub fn debug_context(&self, offset: usize, radius: usize) -> String {
+        let start = offset.saturating_sub(radius).min(self.data.len()) / 16 * 16;
+        let end = offset.saturating_add(radius).min(self.data.len());
+        hexdump(&self.data[start..end], start, Some(offset))
+    }
+
+    /// Parse the object at offset, returning it and the offset just after it, without caching it.  At an
+    /// "N G obj" header that's the indirect object's value; anywhere else it's the direct object that starts there.
+    /// References in it resolve through this cache.
+    pub fn parse_single_object_at(&self, offset: usize) -> Result<(PdfObject, usize)> {
+        parse_object_at(&self.data, offset, &self.self_ref, &self.options)
+    }
+
+    /// Parse and cache the given objects in order of their offsets, so that a bulk read goes through the
+    /// file front to back instead of jumping around it.  Objects that are already cached or not in the index
+    /// are skipped.  Returns the number of objects parsed.
+    pub fn prefetch(&self, ids: &[ObjectId]) -> Result<usize> {
+        let mut targets: Vec<(usize, ObjectId)> = {
+            let index = self.index_map.borrow();
+            let cache = self.cache.borrow();
+            ids.iter()
+               .filter(|id| !cache.contains_key(id))
+               .filter_map(|id| index.get(id).map(|offset| (*offset, *id)))
+               .collect()
+        };
+        targets.sort();
+        targets.dedup();
+        for (done, (offset, id)) in targets.iter().enumerate() {
+            self.options.check_cancelled()?;
+            self.options.report(ProgressPhase::ObjectParse, done, Some(targets.len()));
+            // An earlier object in the sweep may have parsed this one already, through a reference
+            if !self.cache.borrow().contains_key(id) {
+                self.parse_and_cache(*id, *offset)?;
+            };
         }
+        self.options.report(ProgressPhase::ObjectParse, targets.len(), Some(targets.len()));
+        Ok(targets.len())
+    }
+
+    /// With lenient parsing, the last definition of the object in the file other than the one at offset that
+    /// parses, for when the cross-reference index points somewhere unreadable.  Later definitions are preferred,
+    /// as an incremental update or a generator that wrote the object twice meant them to replace earlier ones.
+    fn parse_newest_definition(&self, id: ObjectId, offset: usize) -> Option<PdfObject> {
+        if !self.options.lenient {
+            return None;
+        };
+        self.definitions(id).into_iter().rev().filter(|start| *start != offset).find_map(|start| {
+            let (object, _) = parse_object_at(&self.data, start, &self.self_ref, &self.options).ok()?;
+            repaired(Some(self), "object offset", format!("Object {} unreadable at {}, using its definition at {}",
+                                                          id, offset, start));
+            Some(object)
+        })
     }
-    fn update_reference(&self, new_ref: Weak<Self>) {
-        self.self_ref.replace(new_ref);
+
+    fn parse_and_cache(&self, id: ObjectId, offset: usize) -> Result<SharedObject> {
+        let start = Timer::start();
+        let new_obj = match parse_object_at(&self.data, offset, &self.self_ref, &self.options) {
+            Ok((object, _)) => Rc::new(object),
+            Err(error) => {
+                debug!("Object {} at byte {} couldn't be parsed: {}\n{}", id, offset, error,
+                       self.debug_context(offset, 64));
+                Rc::new(self.parse_newest_definition(id, offset).ok_or(error)?)
+            }
+        };
+        let mut stats = self.stats.borrow_mut();
+        let entry = stats.entry(object_kind(&new_obj)).or_default();
+        entry.misses += 1;
+        entry.parse_time += start.elapsed();
+        self.cache.borrow_mut().insert(id, Rc::clone(&new_obj));
+        Ok(new_obj)
     }
 }
 
 impl PdfFileInterface<PdfObject> for ObjectCache {
     fn retrieve_object_by_ref(&self, id: u32, gen: u32) -> Result<SharedObject> {
         
-        let key = ObjectId(id, gen);
+        let key = ObjectId::new(id, gen);
         let cache_results;
         {
             let map = self.cache.borrow_mut();
             cache_results = map.get(&key).map(|r| Rc::clone(r));
         } // Drop borrow of cache here, before potentially recursive call to parse_object_at
 
-        if let None = cache_results {
-            let new_obj = Rc::new(parse_object_at(&self.data,
-                *self.index_map.borrow().get(&key).ok_or(
-                    ErrorKind::ReferenceError(format!("Object #{} does not exist", id)))?,
-                    &Weak::clone(&self.self_ref.borrow())
-                )?.0);
-            let mut map = self.cache.borrow_mut();  // Mutable borrow of map
-            map.insert(key, new_obj);
-        };  // Mutable borrow of map dropped here
-        Ok(Rc::clone(self.cache.borrow().get(&key).unwrap()))  // Immutable borrow of map
+        match cache_results {
+            Some(obj) => {
+                self.stats.borrow_mut().entry(object_kind(&obj)).or_default().hits += 1;
+                Ok(obj)
+            },
+            None => {
+                let offset = *self.index_map.borrow().get(&key).ok_or(
+                    ErrorKind::ReferenceError(format!("Object #{} does not exist", id)))?;
+                self.parse_and_cache(key, offset)
+            }
+        }
 
     }
     fn retrieve_trailer(&self) -> Result<SharedObject> {
@@ -71,11 +530,97 @@ impl PdfFileInterface<PdfObject> for ObjectCache {
     }
 }
 
+/// Add an object and everything in it to a memory report.
+fn add_object_size(object: &PdfObject, report: &mut MemoryReport) {
+    use std::mem::size_of;
+    report.object_bytes += size_of::<PdfObject>();
+    let map_size = |map: &PdfMap, report: &mut MemoryReport| {
+        for (key, value) in map {
+            report.object_bytes += key.capacity() + size_of::<String>() + size_of::<SharedObject>();
+            add_object_size(value, report);
+        }
+    };
+    if let PdfObject::Actual(data) = object {
+        match data {
+            Name(s) | Comment(s) => report.object_bytes += s.capacity(),
+            CharString(s) => report.object_bytes += s.len(),
+            HexString(bytes) => report.object_bytes += bytes.capacity(),
+            Array(items) => for item in items.iter() {
+                report.object_bytes += size_of::<SharedObject>();
+                add_object_size(item, report);
+            },
+            Dictionary(map) => map_size(map, report),
+            BinaryStream(stream) => {
+                map_size(stream.attributes(), report);
+                if stream.is_loaded() {
+                    report.decoded_stream_bytes += stream.data().map_or(0, |data| data.len());
+                };
+            },
+            ContentStream(stream) => {
+                map_size(stream.attributes(), report);
+                report.decoded_stream_bytes += stream.data().len();
+            },
+            Boolean(_) | NumberInt(_) | NumberFloat(_) | Null => {},
+        }
+    };
+}
+
+/// The name ObjectCache::stats files an object under.
+fn object_kind(object: &PdfObject) -> &'static str {
+    match object {
+        PdfObject::Actual(data) => match data {
+            Boolean(_) => "boolean",
+            NumberInt(_) | NumberFloat(_) => "number",
+            Name(_) => "name",
+            CharString(_) | HexString(_) => "string",
+            Array(_) => "array",
+            Dictionary(_) => "dictionary",
+            ContentStream(_) | BinaryStream(_) => "stream",
+            Comment(_) => "comment",
+            Null => "null",
+        },
+        _ => "reference",
+    }
+}
+
+/// The file as first written, or one incremental update appended to it (spec 7.5.6).
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// The bytes this revision added, from the end of the previous revision through its %%EOF marker
+    pub byte_range: Range<usize>,
+    pub trailer: Rc<PdfMap>,
+    /// Objects this revision wrote or freed, sorted
+    pub changed_objects: Vec<ObjectId>,
+}
+
+/// An object defined more than once in one revision, from PdfFileHandler::duplicate_definitions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateDefinition {
+    pub id: ObjectId,
+    /// Index into PdfFileHandler::revisions
+    pub revision: usize,
+    /// Offsets of the definitions, in file order
+    pub offsets: Vec<usize>,
+    /// The offset the cross-reference index gives for the object, if it's one of these
+    pub used: Option<usize>,
+}
+
 #[derive(Debug)]
 pub struct PdfFileHandler {
     pub version: PDFVersion,
     trailer: Option<PDFTrailer>,
     pub object_map: Rc<ObjectCache>,
+    /// Oldest first
+    revisions: Vec<Revision>,
+    load_times: LoadTimes,
+}
+
+/// Time spent reading the cross-reference sections, and the trailers after them, when a file was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadTimes {
+    /// Finding and reading the cross-reference tables, without their trailers
+    pub xref: Duration,
+    pub trailer: Duration,
 }
 
 impl PdfFileInterface<PdfObject> for PdfFileHandler {
@@ -93,33 +638,253 @@ impl PdfFileInterface<PdfObject> for PdfFileHandler {
 }
 
 impl PdfFileHandler {
-    pub fn create_pdf_from_file(path: &str) -> Result<Self> {
+    #[cfg(feature = "fs")]
+    pub fn create_pdf_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        PdfFileHandler::create_pdf_from_bytes(fs::read(path)?)
+    }
+
+    pub fn create_pdf_from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        PdfFileHandler::create_pdf_from_bytes_with_options(bytes, ParserOptions::default())
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn create_pdf_from_file_with_options<P: AsRef<std::path::Path>>(path: P, options: ParserOptions)
+                                                                         -> Result<Self> {
+        PdfFileHandler::create_pdf_from_bytes_with_options(fs::read(path)?, options)
+    }
+
+    pub fn create_pdf_from_bytes_with_options(bytes: Vec<u8>, options: ParserOptions) -> Result<Self> {
         //TODO: Fix the index
-        let bytes = fs::read(path)?;
         let pdf_version = PdfFileHandler::get_version(&bytes)?;
-        let null_ref = Weak::new();
-        let cache_ref = Rc::new(ObjectCache::new(bytes, HashMap::new(), null_ref.clone()));
-        let weak_ref = Rc::downgrade(&cache_ref);
-        cache_ref.update_reference(Weak::clone(&weak_ref));
+        let cache_ref = ObjectCache::new(bytes, HashMap::new(), options);
         let mut pdf = PdfFileHandler {
             version: pdf_version,
             trailer: None,
             object_map: cache_ref,
+            revisions: Vec::new(),
+            load_times: LoadTimes::default(),
         };
-        let trailer_index = pdf.find_trailer_index(&pdf.object_map.data)?;
-        //println!("trailer starts at: {:?}", trailer_index);
-        pdf.trailer = Some(pdf.process_trailer(trailer_index)?);
+        let start = Timer::start();
+        let xref_index = find_last_xref(&pdf.object_map.data, &pdf.object_map.options, Some(&pdf.object_map))?;
         //pdf.set_trailer_and_xref()?;
-        let index = pdf.process_xref_table()?;
+        let index = pdf.process_xref_sections(xref_index)?;
+        pdf.load_times.xref = start.elapsed().saturating_sub(pdf.load_times.trailer);
         *pdf.object_map.index_map.borrow_mut() = index;
         Ok(pdf)
     }
 
+    /// An empty file that exists only in memory, for building a document from scratch.  Objects are added
+    /// through object_map, and the trailer is given with set_trailer before the file is used.
+    pub(crate) fn new_in_memory(version: PDFVersion) -> Self {
+        PdfFileHandler {
+            version,
+            trailer: None,
+            object_map: ObjectCache::new(Vec::new(), HashMap::new(), ParserOptions::default()),
+            revisions: Vec::new(),
+            load_times: LoadTimes::default(),
+        }
+    }
+
+    pub(crate) fn set_trailer(&mut self, trailer: PdfMap) {
+        self.trailer = Some(PDFTrailer { trailer_dict: Rc::new(PdfObject::new_dictionary(Rc::new(trailer))),
+                                         xref_index: 0 });
+    }
+
+    /// Add or replace one entry of the trailer, such as /Info, which is written out by PdfFileHandler::write.
+    pub(crate) fn set_trailer_entry(&mut self, key: &str, value: SharedObject) -> Result<()> {
+        let mut trailer = self.retrieve_trailer()?.try_into_map()?.as_ref().clone();
+        trailer.insert(key.to_string(), value);
+        let xref_index = self.trailer.as_ref().map_or(0, |trailer| trailer.xref_index);
+        self.trailer = Some(PDFTrailer { trailer_dict: Rc::new(PdfObject::new_dictionary(Rc::new(trailer))),
+                                         xref_index });
+        Ok(())
+    }
+
+    /// How long reading the cross-reference sections and trailers took when the file was opened.
+    pub fn load_times(&self) -> LoadTimes {
+        self.load_times
+    }
+
+    /// The file's revisions, oldest first.  A file without incremental updates has one.
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    /// Objects defined more than once within the bytes of one revision, by object id.  Each is logged as a
+    /// warning, naming the definition the cross-reference index uses, when this is called.
+    pub fn duplicate_definitions(&self) -> Vec<DuplicateDefinition> {
+        let mut duplicates = Vec::new();
+        let index = self.object_map.index_map.borrow();
+        for id in self.object_map.object_ids() {
+            let offsets = self.object_map.definitions(id);
+            for (revision, range) in self.revisions.iter().map(|revision| &revision.byte_range).enumerate() {
+                let in_revision: Vec<usize> = offsets.iter().copied().filter(|offset| range.contains(offset)).collect();
+                if in_revision.len() < 2 {
+                    continue;
+                };
+                let used = index.get(&id).copied().filter(|offset| in_revision.contains(offset));
+                warn!("Object {} defined {} times in revision {}, at offsets {:?}; using {:?}", id,
+                      in_revision.len(), revision, in_revision, used);
+                duplicates.push(DuplicateDefinition { id, revision, offsets: in_revision, used });
+            }
+        }
+        duplicates
+    }
+
+    /// Open the file as it was when the given revision was saved, by reading only the bytes up to its end.
+    pub fn at_revision(&self, revision: usize) -> Result<Self> {
+        let end = self.revisions.get(revision).ok_or_else(|| ErrorKind::ParsingError(
+            format!("Revision {} requested, but the file has {}", revision, self.revisions.len())))?.byte_range.end;
+        PdfFileHandler::create_pdf_from_bytes_with_options(self.object_map.data[..end].to_vec(),
+                                                           self.object_map.options.clone())
+    }
+
+    /// Retrieve the indirect object with the given id.
+    pub fn get_object(&self, id: ObjectId) -> Result<SharedObject> {
+        self.retrieve_object_by_ref(id.number(), id.generation())
+    }
+
+    /// Retrieve the indirect object with the given id as a dictionary.  The stream dictionary is returned
+    /// for stream objects.
+    pub fn get_dict(&self, id: ObjectId) -> Result<Rc<PdfMap>> {
+        let obj = self.get_object(id)?;
+        if let Ok(stream) = obj.try_into_binary_stream() {
+            return Ok(Rc::new(stream.attributes().clone()));
+        };
+        if let Ok(stream) = obj.try_into_content_stream() {
+            return Ok(Rc::new(stream.attributes().clone()));
+        };
+        obj.try_into_map()
+           .chain_err(|| ErrorKind::UnavailableType("dictionary".to_string(), format!("{}", id)))
+    }
+
+    /// Retrieve the indirect object with the given id as an array.
+    pub fn get_array(&self, id: ObjectId) -> Result<Rc<PdfArray>> {
+        self.get_object(id)?
+            .try_into_array()
+            .chain_err(|| ErrorKind::UnavailableType("array".to_string(), format!("{}", id)))
+    }
+
+    /// Retrieve the indirect object with the given id as an integer.
+    pub fn get_int(&self, id: ObjectId) -> Result<i32> {
+        self.get_object(id)?
+            .try_into_int()
+            .chain_err(|| ErrorKind::UnavailableType("integer".to_string(), format!("{}", id)))
+    }
+
+    /// Retrieve the indirect object with the given id as a number, converting integers to floats.
+    pub fn get_number(&self, id: ObjectId) -> Result<f32> {
+        let obj = self.get_object(id)?;
+        match obj.try_into_int() {
+            Ok(n) => Ok(n as f32),
+            Err(_) => obj.try_into_float()
+                         .chain_err(|| ErrorKind::UnavailableType("number".to_string(), format!("{}", id)))
+        }
+    }
+
+    /// Retrieve the indirect object with the given id as a name, string or comment.
+    pub fn get_string(&self, id: ObjectId) -> Result<String> {
+        Ok(self.get_object(id)?
+               .try_into_string()
+               .chain_err(|| ErrorKind::UnavailableType("string".to_string(), format!("{}", id)))?
+               .to_string())
+    }
+
+    /// The kind of every stream object in the cross-reference index, sorted by id.  Streams whose dictionaries
+    /// don't say what they are are classified by how other objects refer to them, and the result is recorded on
+    /// the stream.  Objects that fail to parse are skipped.
+    pub fn stream_types(&self) -> BTreeMap<ObjectId, StreamType> {
+        let mut context = HashMap::new();
+        let mut streams = Vec::new();
+        for id in self.object_map.object_ids() {
+            if let Ok(object) = self.get_object(id) {
+                stream_contexts(&object, &mut context, 0);
+                if object.is_stream() {
+                    streams.push((id, object));
+                };
+            };
+        }
+        streams.into_iter().map(|(id, object)| {
+            let stream_type = match object.as_ref() {
+                PdfObject::Actual(BinaryStream(stream)) => {
+                    if stream.stream_type() == StreamType::Unknown {
+                        if let Some(stream_type) = context.get(&id) {
+                            stream.set_stream_type(*stream_type);
+                        };
+                    };
+                    stream.stream_type()
+                },
+                _ => StreamType::Content,
+            };
+            (id, stream_type)
+        }).collect()
+    }
+
+    /// The byte range of the indirect object with the given id; see ObjectCache::object_span.
+    pub fn object_span(&self, id: ObjectId) -> Result<Range<usize>> {
+        self.object_map.object_span(id)
+    }
+
+    /// The bytes around offset as a hexdump; see ObjectCache::debug_context.
+    pub fn debug_context(&self, offset: usize, radius: usize) -> String {
+        self.object_map.debug_context(offset, radius)
+    }
+
+    /// The object at offset, parsed on its own; see ObjectCache::parse_single_object_at.
+    pub fn parse_single_object_at(&self, offset: usize) -> Result<(PdfObject, usize)> {
+        self.object_map.parse_single_object_at(offset)
+    }
+
+    /// Retrieve the decoded contents of the stream object with the given id.
+    pub fn get_stream_data(&self, id: ObjectId) -> Result<Vec<u8>> {
+        let obj = self.get_object(id)?;
+        if let Ok(stream) = obj.try_into_content_stream() {
+            return Ok(stream.data().to_vec());
+        };
+        Ok(obj.try_into_binary_stream()
+              .chain_err(|| ErrorKind::UnavailableType("stream".to_string(), format!("{}", id)))?
+              .data()?
+              .to_vec())
+    }
+
+    /// Visit every indirect object reachable from the trailer exactly once, in breadth-first order.
+    /// References to objects that are not in the cross-reference index are treated as null per spec 7.3.10
+    /// and skipped.
+    pub fn walk<F>(&self, visitor: F) -> Result<()>
+    where
+        F: FnMut(ObjectId, &SharedObject),
+    {
+        self.walk_with_replacements(&HashMap::new(), visitor)
+    }
+
+    /// As walk, following the objects in replacements instead of those with the same ids.
+    pub(crate) fn walk_with_replacements<F>(&self, replacements: &HashMap<ObjectId, SharedObject>, mut visitor: F)
+                                            -> Result<()>
+    where
+        F: FnMut(ObjectId, &SharedObject),
+    {
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<ObjectId> = self.retrieve_trailer()?.references().into_iter().collect();
+        while let Some(id) = queue.pop_front() {
+            self.object_map.options.check_cancelled()?;
+            if !seen.insert(id) || !self.object_map.contains(id) {
+                continue;
+            };
+            let obj = match replacements.get(&id) {
+                Some(obj) => Rc::clone(obj),
+                None => self.get_object(id)?
+            };
+            visitor(id, &obj);
+            queue.extend(obj.references().into_iter().filter(|next_id| !seen.contains(next_id)));
+        }
+        Ok(())
+    }
+
     fn get_version(bytes: &Vec<u8>) -> Result<PDFVersion> {
         let intro = String::from_utf8(
-            bytes[..12]
+            bytes[..bytes.len().min(12)]
                 .iter()
-                .map(|c| *c)
+                .copied()
                 .take_while(|c| !is_eol(*c))
                 .collect(),
         );
@@ -162,116 +927,286 @@ impl PdfFileHandler {
         Ok(())
     }
 
-    fn find_trailer_index(&self, bytes: &Vec<u8>) -> Result<usize> {
-        let mut state: usize = 0;
-        let mut current_index = bytes.len() as usize;
-        while state < 7 {
-            current_index -= 1;
-            let c = bytes[current_index] as char;
-            //println!("char {} with {}", c, state);
-            state = match state {
-                1 if c == 'e' => 2,
-                2 if c == 'l' => 3,
-                3 if c == 'i' => 4,
-                4 if c == 'a' => 5,
-                5 if c == 'r' => 6,
-                6 if c == 't' => 7,
-                _ if c == 'r' => 1,
-                _ => 0,
+    /// Read the chain of xref sections from the last one, at xref_index, back through /Prev, recording a
+    /// revision for each and merging them into one index in which later sections override earlier ones.  The
+    /// last section's trailer is the document's.
+    fn process_xref_sections(&mut self, xref_index: usize) -> Result<HashMap<ObjectId, usize>> {
+        let mut sections = Vec::new();
+        let mut next = Some(xref_index);
+        let mut seen = HashSet::new();
+        while let Some(xref_index) = next {
+            if !seen.insert(xref_index) {
+                Err(ErrorKind::ParsingError(format!("Cyclic /Prev chain at {}", xref_index)))?
             };
-
-            if current_index + state <= 6 {
-                return Err(ErrorKind::ParsingError(
-                    "Could not find trailer".to_string(),
-                ))?;
+            self.object_map.options.check_cancelled()?;
+            self.object_map.options.report(ProgressPhase::XrefScan, sections.len(), None);
+            let section = self.read_xref_section(xref_index)?;
+            self.load_times.trailer += section.trailer_time;
+            if self.trailer.is_none() {
+                self.trailer = Some(PDFTrailer { trailer_dict: Rc::clone(&section.trailer), xref_index });
+            };
+            next = match section.trailer.try_into_map()?.get("Prev") {
+                Some(prev) => Some(usize::try_from(prev.try_into_offset()?).map_err(|_| ErrorKind::ParsingError(
+                    format!("Invalid /Prev offset in trailer at {}", xref_index)))?),
+                None => None
             };
+            sections.push(section);
         }
-        Result::Ok(current_index)
+        self.object_map.options.report(ProgressPhase::XrefScan, sections.len(), Some(sections.len()));
+        sections.reverse();
+
+        let mut by_number: HashMap<u32, (ObjectId, usize)> = HashMap::new();
+        let mut start = 0;
+        for section in sections {
+            for (id, offset) in &section.entries {
+                match offset {
+                    Some(offset) => by_number.insert(id.number(), (*id, *offset)),
+                    None => by_number.remove(&id.number()),
+                };
+                ParserOptions::check("object count", by_number.len(), self.object_map.options.max_objects)?;
+            }
+            let mut changed_objects: Vec<ObjectId> = section.entries.iter().map(|(id, _)| *id).collect();
+            changed_objects.sort();
+            self.revisions.push(Revision {
+                byte_range: start..section.end,
+                trailer: section.trailer.try_into_map()?,
+                changed_objects,
+            });
+            start = section.end;
+        }
+        Ok(by_number.into_values().collect())
     }
 
-    fn process_trailer(&mut self, start_index: usize) -> Result<PDFTrailer> {
-        assert_eq!(
-            &(String::from_utf8(Vec::from(&self.object_map.data[start_index..start_index + 7])).unwrap()),
-            "trailer"
-        );
-        let (trailer_dict, next_index) = parse_object_at(&self.object_map.data,
-                                                         start_index + 7,
-                                                         &Weak::clone(&self.object_map.self_ref.borrow()))?;
-        let trailer_string = String::from_utf8(self.object_map.data[(next_index + 1)..].to_vec())
-            .expect("Could not convert trailer to string!");
-        let mut trailer_lines = trailer_string.lines().filter(|l| !l.trim().is_empty());
-        let first_line = trailer_lines.next().expect("No line after trailer dict!");
-        //println!("{}", trailer_string);
-        if first_line != "startxref" {
-            Err(ErrorKind::ParsingError(format!(
-                "startxref keyword not found at {}",
-                next_index
-            )))?
-        };
-        let second_line = trailer_lines.next().expect("No xref location in trailer");
-        let xref_index = second_line
-            .trim()
-            .parse()
-            .expect("Invalid xref index in trailer");
-        let third_line = trailer_lines.next().expect("Missing file terminator!");
-        assert_eq!(third_line, "%%EOF");
-        assert_eq!(trailer_lines.next(), None);
-        return Ok(PDFTrailer {
-            start_index,
-            trailer_dict: Rc::new(trailer_dict),
-            xref_index,
-        });
-    }
-
-    fn process_xref_table(&mut self) -> Result<HashMap<ObjectId, usize>> {
-        let trailer = self
-            .trailer
-            .as_ref()
-            .expect("Parse trailer before parsing xref table!");
-        let start_index = trailer.xref_index;
-        let end_index = trailer.start_index - 1;
-        let table = String::from_utf8(self.object_map.data[start_index..end_index].to_vec())
-            .expect("Invalid xref table");
+    /// Parse the xref table at the given offset and the trailer after it.  Entries are None for freed objects;
+    /// the head of the free list, object 0, is left out.
+    fn read_xref_section(&self, start_index: usize) -> Result<XrefSection> {
+        let data = &self.object_map.data;
+        let trailer_index = find_bytes(data, b"trailer", start_index).ok_or_else(|| ErrorKind::ParsingError(
+            format!("No trailer after xref table at {}", start_index)))?;
+        let table = String::from_utf8(data[start_index..trailer_index].to_vec())
+            .map_err(|_| ErrorKind::ParsingError(format!("Invalid xref table at {}", start_index)))?;
         //println!("{}", table);
-        let mut map = HashMap::new();
-        let mut line_iter = table.lines();
+        let mut entries = Vec::new();
+        let mut line_iter = table.lines().filter(|line| !line.trim().is_empty());
         let mut obj_number = 0;
-        assert_eq!(line_iter.next().unwrap(), "xref");
-        loop {
-            let line = line_iter.next();
-            if let None = line {
-                return Ok(map);
-            };
+        let mut in_use = 0;
+        if line_iter.next().map(|line| line.trim()) != Some("xref") {
+            Err(ErrorKind::ParsingError(format!("xref keyword not found at {}", start_index)))?
+        };
+        let invalid = |line: &str| ErrorKind::ParsingError(format!("Invalid line in xref table: {:?}", line));
+        for line in line_iter {
             //println!("{:?}", line);
-            let parts: Vec<&str> = line.unwrap().split_whitespace().collect();
+            let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() == 3 {
+                let generation = parts[1].parse().map_err(|_| invalid(line))?;
+                let generation = self.object_map.options.generation(generation, || format!("in xref entry {}",
+                                                                                          obj_number),
+                                                                    Some(&self.object_map))?;
                 if parts[2] == "f" {
-                    obj_number += 1
+                    if obj_number != 0 {
+                        entries.push((ObjectId::new(obj_number, generation), None));
+                    };
                 } else {
-                    map.insert(
-                        ObjectId(
-                            obj_number,
-                            parts[1].parse().expect("Could not parse gen number"),
-                        ),
-                        parts[0].parse().expect("Could not parse offset"),
-                    );
-                    obj_number += 1;
-                }
+                    let offset = parts[0].parse().map_err(|_| invalid(line))?;
+                    entries.push((ObjectId::new(obj_number, generation), Some(offset)));
+                    // Checked as the table is read, so that a huge one fails before it's all held
+                    in_use += 1;
+                    ParserOptions::check("object count", in_use, self.object_map.options.max_objects)?;
+                };
+                obj_number += 1;
             } else if parts.len() == 2 {
-                obj_number = parts[0].parse().expect("Could not parse object number");
+                obj_number = parts[0].parse().map_err(|_| invalid(line))?;
             } else {
                 //println!("{:?}", parts);
-                return Err(ErrorKind::ParsingError(format!(
-                    "Invalid line in xref table: {:?}",
-                    parts
-                )))?;
+                return Err(invalid(line))?;
             }
         }
+        let trailer_start = Timer::start();
+        let (trailer, next_index) = parse_object_at(data, trailer_index + 7, &self.object_map.self_ref,
+                                                    &self.object_map.options)?;
+        let trailer_time = trailer_start.elapsed();
+        let end = match find_bytes(data, b"%%EOF", next_index) {
+            Some(eof) => {
+                let mut end = eof + 5;
+                if data.get(end) == Some(&b'\r') {
+                    end += 1;
+                };
+                if data.get(end) == Some(&b'\n') {
+                    end += 1;
+                };
+                end
+            },
+            None => data.len()
+        };
+        Ok(XrefSection { entries, trailer: Rc::new(trailer), end, trailer_time })
+    }
+}
+
+/// Record the kinds of streams an object refers to: page contents, font files, metadata and ICC profiles.
+fn stream_contexts(object: &PdfObject, context: &mut HashMap<ObjectId, StreamType>, depth: usize) {
+    if depth > 64 {
+        return;
+    };
+    let mut mark = |target: &PdfObject, stream_type| {
+        if let Some(id) = target.reference_id() {
+            context.entry(id).or_insert(stream_type);
+        };
+    };
+    let dict = match object {
+        PdfObject::Actual(Dictionary(dict)) => dict.as_ref(),
+        PdfObject::Actual(BinaryStream(stream)) => stream.attributes(),
+        PdfObject::Actual(Array(array)) => {
+            let is_icc = array.first()
+                              .is_some_and(|first| first.try_into_string().is_ok_and(|name| *name == "ICCBased"));
+            if let (true, Some(profile)) = (is_icc, array.get(1)) {
+                mark(profile, StreamType::IccProfile);
+            };
+            array.iter().for_each(|item| stream_contexts(item, context, depth + 1));
+            return;
+        },
+        _ => return
+    };
+    let is_page = dict.get("Type").is_some_and(|t| t.try_into_string().is_ok_and(|t| *t == "Page"));
+    for (key, value) in dict.iter() {
+        match key.as_str() {
+            "Contents" if is_page => match value.as_ref() {
+                PdfObject::Actual(Array(parts)) => parts.iter().for_each(|part| mark(part, StreamType::Content)),
+                _ => mark(value, StreamType::Content),
+            },
+            "FontFile" | "FontFile2" | "FontFile3" => mark(value, StreamType::FontFile),
+            "Metadata" => mark(value, StreamType::Metadata),
+            _ => {}
+        };
+    }
+    dict.values().for_each(|value| stream_contexts(value, context, depth + 1));
+}
+
+/// Where the data of a stream starts, given the index just past its stream keyword.  The keyword must be
+/// followed by CRLF or LF; a lone CR is ambiguous, since the data could start with LF (spec 7.3.8.1).  In
+/// lenient mode, spaces or tabs before the EOL and a lone CR are accepted too.
+fn stream_data_start(data: &[u8], index: usize, options: &ParserOptions, cache: Option<&ObjectCache>)
+                     -> Result<usize> {
+    let invalid = || ErrorKind::ParsingError(format!("Stream keyword not followed by an EOL at {}", index));
+    let mut start = index;
+    if options.lenient {
+        while matches!(data.get(start), Some(b' ') | Some(b'\t')) {
+            start += 1;
+        }
+    };
+    let end = match (data.get(start), data.get(start + 1)) {
+        (Some(b'\r'), Some(b'\n')) => start + 2,
+        (Some(b'\n'), _) => start + 1,
+        (Some(b'\r'), _) if options.lenient => start + 1,
+        _ => Err(invalid())?
+    };
+    // Spaces before the EOL, or a lone CR
+    if start > index || data[end - 1] == b'\r' {
+        repaired(cache, "stream keyword EOL", format!("Irregular EOL after stream keyword at {}", index));
+    };
+    Ok(end)
+}
+
+/// Log a problem that lenient parsing worked around, counting it in the cache's repairs if there is a cache.
+fn repaired(cache: Option<&ObjectCache>, kind: &'static str, message: String) {
+    warn!("{}", message);
+    if let Some(cache) = cache {
+        *cache.repairs.borrow_mut().entry(kind).or_default() += 1;
+    };
+}
+
+/// Where the "N G obj" header whose obj keyword starts at keyword_start begins.
+fn object_header_start(data: &[u8], keyword_start: usize) -> usize {
+    let mut index = keyword_start;
+    for _ in 0..2 {
+        while index > 0 && is_whitespace(data[index - 1]) {
+            index -= 1;
+        }
+        while index > 0 && data[index - 1].is_ascii_digit() {
+            index -= 1;
+        }
+    }
+    index
+}
+
+/// The offset of the last cross-reference section, from the last startxref keyword before the last %%EOF marker.
+/// Like viewers, this looks for the marker only in the last EOF_SEARCH_LENGTH bytes, and ignores anything after
+/// it, such as padding, nulls or junk added by mail clients.  Without the marker, the file is rejected unless
+/// options are lenient.  The trailer is then read from after that section's table, rather than by searching
+/// for the trailer keyword, which can turn up in stream data.
+fn find_last_xref(data: &[u8], options: &ParserOptions, cache: Option<&ObjectCache>) -> Result<usize> {
+    let tail_start = data.len().saturating_sub(EOF_SEARCH_LENGTH);
+    let end = match data[tail_start..].windows(5).rposition(|window| window == b"%%EOF") {
+        Some(position) => tail_start + position,
+        None if options.lenient => {
+            repaired(cache, "missing %%EOF", format!("No %%EOF marker in the last {} bytes", EOF_SEARCH_LENGTH));
+            data.len()
+        }
+        None => Err(ErrorKind::ParsingError(format!("No %%EOF marker in the last {} bytes", EOF_SEARCH_LENGTH)))?
+    };
+    let keyword = data[..end].windows(9).rposition(|window| window == b"startxref")
+                      .ok_or_else(|| ErrorKind::ParsingError("startxref keyword not found".to_string()))?;
+    let digits: Vec<u8> = data[keyword + 9..].iter()
+                                             .copied()
+                                             .skip_while(|c| is_whitespace(*c))
+                                             .take_while(|c| c.is_ascii_digit())
+                                             .collect();
+    str::from_utf8(&digits).ok()
+        .and_then(|digits| digits.parse().ok())
+        .filter(|offset| *offset < data.len())
+        .ok_or_else(|| ErrorKind::ParsingError(format!("Invalid xref offset after startxref at {}", keyword)).into())
+}
+
+/// Offsets of the "N G obj" headers in data, by object id, in file order.
+fn scan_object_headers(data: &[u8]) -> HashMap<ObjectId, Vec<usize>> {
+    let header = regex::bytes::Regex::new(r"(?-u)(\d+)[\x00\t\n\x0C\r ]+(\d+)[\x00\t\n\x0C\r ]+obj").unwrap();
+    let mut headers: HashMap<ObjectId, Vec<usize>> = HashMap::new();
+    for captures in header.captures_iter(data) {
+        let whole = captures.get(0).unwrap();
+        let separated = |c: Option<&u8>| c.is_none_or(|c| is_whitespace(*c) || is_delimiter(*c));
+        if !separated(whole.start().checked_sub(1).and_then(|i| data.get(i))) || !separated(data.get(whole.end())) {
+            continue;
+        };
+        let number = |i: usize| str::from_utf8(captures.get(i).unwrap().as_bytes()).ok()?.parse::<u32>().ok();
+        if let (Some(number), Some(generation)) = (number(1), number(2)) {
+            headers.entry(ObjectId::new(number, generation)).or_default().push(whole.start());
+        };
     }
+    headers
 }
 
+/// Lines of 16 bytes in hex and as ASCII, each starting with its offset counted from base.  The line holding
+/// mark, if it's given, is flagged with a >.
+pub fn hexdump(data: &[u8], base: usize, mark: Option<usize>) -> String {
+    let mut lines = Vec::new();
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let start = base + i * 16;
+        let flag = if mark.is_some_and(|mark| mark >= start && mark < start + 16) { ">" } else { " " };
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = chunk.iter().map(|&byte| if (0x20..0x7F).contains(&byte) { byte as char } else { '.' })
+                                 .collect();
+        lines.push(format!("{}{:08x}  {:<47}  |{}|", flag, start, hex.join(" "), ascii));
+    }
+    lines.join("\n")
+}
+
+/// The position of the first occurrence of needle at or after start.
+fn find_bytes(data: &[u8], needle: &[u8], start: usize) -> Option<usize> {
+    data.get(start..)?.windows(needle.len()).position(|window| window == needle).map(|i| i + start)
+}
 
-fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCache>) -> Result<(PdfObject, usize)> {
+fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCache>, options: &ParserOptions)
+                   -> Result<(PdfObject, usize)> {
+    parse_object_within_budget(data, start_index, weak_ref, options, &mut 0).map_err(|error| match error.kind() {
+        // Reported against the whole object, not the nested array or dictionary that went over
+        ErrorKind::ObjectTooComplex(_, max) => ErrorKind::ObjectTooComplex(start_index, *max).into(),
+        _ => error,
+    })
+}
+
+/// Parse the object at start_index, adding the tokens read to tokens, which is shared with the objects it's
+/// nested in so that ParserOptions::max_object_tokens covers the outermost one as a whole.
+fn parse_object_within_budget(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCache>,
+                              options: &ParserOptions, tokens: &mut usize) -> Result<(PdfObject, usize)> {
     let mut state = ParserState::Neutral;
     let mut index = start_index;
     let mut this_object_type = PDFComplexObject::Unknown;
@@ -285,13 +1220,67 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
     }
     let mut char_buffer = Vec::new();
     let mut object_buffer = Vec::new();
+    let mut comment_start = 0;
+    let mut string_start = 0;
+    // Where the literal string being read could have ended if an unescaped ( in it was a mistake: the index of the
+    // last ) that brought it back to nesting depth 0, and the length of the string before that )
+    let mut balanced_close: Option<(usize, usize)> = None;
+    // Positions in object_buffer of integers written as plain digits, which can start a reference
+    let mut unsigned_ints: Vec<usize> = Vec::new();
+    // Objects parsed without a cache, as in tests, just aren't pooled
+    let cache = weak_ref.upgrade();
+    let pool = cache.as_deref().map(|cache| &cache.pool);
+    options.check_cancelled()?;
     loop {
+        // Checked now and then, so that a huge object can't hold up cancellation
+        if index.is_multiple_of(4096) {
+            options.check_cancelled()?;
+        };
+        if let ParserState::CharString(_) = state {
+            let overlong = ParserOptions::check("string length", char_buffer.len(), options.max_string_bytes);
+            if index >= length || overlong.is_err() {
+                // Buggy generators leave parentheses unescaped and unbalanced, so the string never ends; in the
+                // likeliest case that's an extra (, and the string should have ended at the last balanced )
+                match balanced_close {
+                    Some((close, kept)) if options.lenient => {
+                        repaired(cache.as_deref(), "unbalanced string", format!(
+                            "String at {} has unbalanced parentheses; ending it at {}", string_start, close));
+                        char_buffer.truncate(kept);
+                        object_buffer.push(flush_buffer_to_object(&ParserState::CharString(0), &mut char_buffer)?);
+                        state = ParserState::Neutral;
+                        index = close + 1;
+                        continue;
+                    }
+                    _ => overlong?,
+                };
+            };
+        };
         if index >= length {
+            if options.lenient && this_object_type == PDFComplexObject::IndirectObj && state == ParserState::Neutral {
+                repaired(cache.as_deref(), "missing endobj", format!("No endobj for object at {}", start_index));
+                return make_object_from_object_buffer(object_buffer, length);
+            };
             return Err(ErrorKind::ParsingError(
                 "end of file while parsing object".to_string(),
             ))?;
         };
+        let entries = match this_object_type {
+            PDFComplexObject::Array => object_buffer.len(),
+            PDFComplexObject::Dict => object_buffer.len() / 2,
+            _ => 0
+        };
+        ParserOptions::check("collection size", entries, options.max_collection_entries)?;
         let c = data[index];
+        // Each token, delimiter included, starts from the neutral state.  A nested object starts on the bracket
+        // its parent has counted already.
+        if state == ParserState::Neutral && !is_whitespace(c) && (index != start_index || *tokens == 0) {
+            *tokens += 1;
+            if let Some(max) = options.max_object_tokens {
+                if *tokens > max {
+                    Err(ErrorKind::ObjectTooComplex(start_index, max))?
+                };
+            };
+        };
         state = match state {
             ParserState::Neutral => match c {
                 b'[' if this_object_type == PDFComplexObject::Unknown => {
@@ -299,14 +1288,14 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                     state
                 }
                 b'[' => {
-                    let (new_array, end_index) = parse_object_at(data, index, weak_ref)?;
+                    let (new_array, end_index) = parse_object_within_budget(data, index, weak_ref, options, tokens)?;
                     index = end_index;
                     object_buffer.push(new_array);
                     state
                 }
                 b']' => {
                     if this_object_type == PDFComplexObject::Array {
-                        return make_array_from_object_buffer(object_buffer, index);
+                        return make_array_from_object_buffer(object_buffer, index, pool);
                     } else {
                         return Err(ErrorKind::ParsingError(format!(
                             "Invalid terminator for {:?} at {}: ]",
@@ -321,7 +1310,7 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                     //println!("Dict started at: {}", index);
                     } else {
                         //println!("Nested dict in {:?} at {}", this_object_type, index);
-                        let (new_dict, end_index) = parse_object_at(data, index, weak_ref)?;
+                        let (new_dict, end_index) = parse_object_within_budget(data, index, weak_ref, options, tokens)?;
                         index = end_index;
                         //println!("Nested dict closed at {}", index);
                         object_buffer.push(new_dict);
@@ -332,62 +1321,31 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                 b'>' if (peek_ahead_by_n(data, index, 1) == Some(b'>')) => {
                     if this_object_type == PDFComplexObject::Dict {
                         //println!("Dictionary ended at {}", index + 1);
-                        return make_dict_from_object_buffer(object_buffer, index + 1);
+                        return make_dict_from_object_buffer(object_buffer, index + 1, pool);
                     } else {
-                        println!("-------Dictionary ended but I'm a {:?}", this_object_type);
-                        println!("Buffer: {:#?}", object_buffer);
                         return Err(ErrorKind::ParsingError(format!(
                             "Invalid terminator for {:?} at {}: >>",
                             this_object_type, index
                         )))?;
                     }
                 }
-                b'(' => ParserState::CharString(0),
+                b'(' => {
+                    string_start = index;
+                    balanced_close = None;
+                    ParserState::CharString(0)
+                }
                 b'/' => ParserState::Name,
-                b'R' => {
-                    let object_buffer_length = object_buffer.len();
-                    if object_buffer_length <= 1
-                        || !object_buffer[object_buffer_length - 2].is_int()
-                        || !object_buffer[object_buffer_length - 1].is_int()
-                        || object_buffer[object_buffer_length - 2]
-                            .try_into_int()
-                            .unwrap()
-                            < 0
-                        || object_buffer[object_buffer_length - 1]
-                            .try_into_int()
-                            .unwrap()
-                            < 0
-                    {
-                        println!("object buffer: {:#?}", object_buffer);
-                        return Err(ErrorKind::ParsingError(format!(
-                            "Could not parse reference to object at {}",
-                            index
-                        )))?;
-                    };
-                    let new_object = PdfObject::new_reference(
-                        <u32>::try_from(
-                            object_buffer[object_buffer_length - 2]
-                            .try_into_int()
-                            .unwrap()
-                        ).map_err(|_e| ErrorKind::ParsingError("Invalid id".to_string()))?,
-                        <u32>::try_from(
-                            object_buffer[object_buffer_length - 1]
-                            .try_into_int()
-                            .unwrap()
-                        ).map_err(|_e| ErrorKind::ParsingError("Invalid gen".to_string()))?,
-                        Weak::clone(weak_ref),
-                    );
-
-                    object_buffer.truncate(object_buffer_length - 2);
-                    object_buffer.push(new_object);
-                    state
+                b'%' => {
+                    comment_start = index;
+                    ParserState::Comment
                 }
-                b's' | b'e' | b'o' | b'n' | b't' | b'f' => {
+                // Keywords, including the R of a reference, are bare words ended by whitespace or a delimiter
+                _ if c.is_ascii_alphabetic() => {
                     char_buffer.push(c);
                     ParserState::Keyword
                 }
                 b'0'..=b'9' | b'+' | b'-' => {
-                    index -= 1;
+                    char_buffer.push(c);
                     ParserState::Number
                 }
                 _ if is_whitespace(c) => state,
@@ -403,11 +1361,17 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                     object_buffer.push(flush_buffer_to_object(&state, &mut char_buffer)?);
                     ParserState::Neutral
                 }
-                b'0'..=b'9' | b'A'..=b'F' => {
-                    char_buffer.push(c);
+                // Digits are stored in upper case, so strings compare and write out the same whatever their case
+                _ if is_hex(c) => {
+                    char_buffer.push(c.to_ascii_uppercase());
                     state
                 }
                 _ if is_whitespace(c) => state,
+                _ if options.lenient => {
+                    repaired(cache.as_deref(), "hex string character",
+                             format!("Skipping invalid character in hex string at {}: {:?}", index, c as char));
+                    state
+                }
                 _ => {
                     return Err(ErrorKind::ParsingError(format!(
                         "invalid character in hexstring at {}: {}",
@@ -421,52 +1385,31 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                     object_buffer.push(flush_buffer_to_object(&state, &mut char_buffer)?);
                     ParserState::Neutral
                 }
-                b')' if depth > 0 => ParserState::CharString(depth - 1),
-                b'(' => ParserState::CharString(depth + 1),
-                b'\\' if index + 1 < length => {
-                    match data[index + 1] {
-                        15 => {
-                            index += 1; // Skip carriage return
-                            if index + 1 < length && data[index + 1] == 12 {
-                                index += 1
-                            }; // Skip linefeed too
-                            state
-                        }
-                        12 => {
-                            index += 1;
-                            state
-                        } // Escape naked LF
-                        b'\\' => {
-                            index += 1;
-                            char_buffer.push(b'\\');
-                            state
-                        }
-                        b'(' => {
-                            index += 1;
-                            char_buffer.push(b'(');
-                            state
-                        }
-                        b')' => {
-                            index += 1;
-                            char_buffer.push(b')');
-                            state
-                        }
-                        d @ b'0'..=b'7' => {
-                            index += 1;
-                            let mut code = d - b'0';
-                            if index + 1 < length && is_octal(data[index + 1]) {
-                                code = code * 8 + (data[index + 1] - b'0');
-                                index += 1;
-                                if index + 1 < length && is_octal(data[index + 1]) {
-                                    code = code * 8 + (data[index + 1] - b'0');
-                                    index += 1;
-                                }
-                            };
-                            char_buffer.push(code);
-                            state
-                        }
-                        _ => state, // Other escaped characters do not require special treatment
-                    }
+                // Balanced parentheses are part of the string
+                b')' => {
+                    if depth == 1 {
+                        balanced_close = Some((index, char_buffer.len()));
+                    };
+                    char_buffer.push(c);
+                    ParserState::CharString(depth - 1)
+                }
+                b'(' => {
+                    char_buffer.push(c);
+                    ParserState::CharString(depth + 1)
+                }
+                b'\\' => {
+                    let (byte, next) = read_string_escape(data, index);
+                    char_buffer.extend(byte);
+                    index = next - 1; // The loop steps past the last byte read
+                    state
+                }
+                // An end-of-line marker that isn't escaped stands for a line feed, however it's written
+                b'\r' => {
+                    if peek_ahead_by_n(data, index, 1) == Some(b'\n') {
+                        index += 1;
+                    };
+                    char_buffer.push(b'\n');
+                    state
                 }
                 _ => {
                     char_buffer.push(c);
@@ -474,7 +1417,7 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                 }
             },
             ParserState::Name => {
-                if c != b'%' && (is_whitespace(c) || is_delimiter(c)) {
+                if is_whitespace(c) || is_delimiter(c) {
                     object_buffer.push(flush_buffer_to_object(&state, &mut char_buffer)?);
                     index -= 1; // Need to parse delimiter character on next iteration
                     ParserState::Neutral
@@ -502,6 +1445,12 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                     state
                 }
                 _ if is_whitespace(c) || is_delimiter(c) => {
+                    if char_buffer.iter().all(|c| c.is_ascii_digit()) {
+                        // Drop positions left over from the contents of arrays and dictionaries since closed
+                        let open = unsigned_ints.iter().rposition(|&i| i < object_buffer.len()).map_or(0, |i| i + 1);
+                        unsigned_ints.truncate(open);
+                        unsigned_ints.push(object_buffer.len());
+                    };
                     object_buffer.push(flush_buffer_to_object(&state, &mut char_buffer)?);
                     index -= 1; // Need to parse delimiter character on next iteration
                     ParserState::Neutral
@@ -514,8 +1463,13 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                 }
             },
             ParserState::Comment => {
+                // Comments are whitespace to the parser (spec 7.2.4), so they never reach the object buffer
                 if is_eol(c) {
-                    object_buffer.push(flush_buffer_to_object(&state, &mut char_buffer)?);
+                    if let (true, Some(cache)) = (options.collect_comments, weak_ref.upgrade()) {
+                        let text = String::from_utf8_lossy(&char_buffer).to_string();
+                        cache.comments.borrow_mut().insert(comment_start, text);
+                    };
+                    char_buffer.clear();
                     ParserState::Neutral
                 } else {
                     char_buffer.push(c);
@@ -523,30 +1477,48 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                 }
             }
             ParserState::Keyword => {
-                if !is_body_keyword_letter(c) {
+                // Stray content operators like T* are read whole, so that lenient parsing can skip them below
+                if options.lenient && !(is_delimiter(c) || is_whitespace(c)) {
+                    char_buffer.push(c);
+                    state
+                } else if !c.is_ascii_alphabetic() {
                     if !(is_delimiter(c) || is_whitespace(c)) {
                         return Err(ErrorKind::ParsingError(format!(
                             "invalid character in keyword at {}: {}",
                             index, c as char
                         )))?;
                     };
-                    let s = str::from_utf8(&char_buffer).unwrap();
-                    let this_keyword = match s {
-                        "obj" => PDFKeyword::Obj,
-                        "endobj" => PDFKeyword::EndObj,
-                        "stream" => PDFKeyword::Stream,
-                        "endstream" => PDFKeyword::EndStream,
-                        "null" => PDFKeyword::Null,
-                        "false" => PDFKeyword::False,
-                        "true" => PDFKeyword::True,
-                        _ => Err(ErrorKind::ParsingError(format!(
-                            "Invalid PDF keyword: {}",
-                            s
-                        )))?,
+                    let keyword_start = index - char_buffer.len();
+                    let this_keyword = match &char_buffer[..] {
+                        b"R" => Some(PDFKeyword::R),
+                        b"obj" => Some(PDFKeyword::Obj),
+                        b"endobj" => Some(PDFKeyword::EndObj),
+                        b"stream" => Some(PDFKeyword::Stream),
+                        b"endstream" => Some(PDFKeyword::EndStream),
+                        b"null" => Some(PDFKeyword::Null),
+                        b"false" => Some(PDFKeyword::False),
+                        b"true" => Some(PDFKeyword::True),
+                        b"xref" => Some(PDFKeyword::XRef),
+                        b"trailer" => Some(PDFKeyword::Trailer),
+                        b"startxref" => Some(PDFKeyword::StartXRef),
+                        _ => None,
                     };
                     char_buffer.clear();
                     match this_keyword {
-                        PDFKeyword::EndObj => {
+                        // Left by generator bugs, like content operators or an endstream with no stream.  Skipping
+                        // them keeps the damage to the object they're in.
+                        None | Some(PDFKeyword::EndStream) if options.lenient => {
+                            repaired(cache.as_deref(), "stray keyword", format!(
+                                "Skipping unexpected keyword at {}: {}", keyword_start,
+                                String::from_utf8_lossy(&data[keyword_start..index])));
+                            index -= 1;
+                            ParserState::Neutral
+                        }
+                        None => Err(ErrorKind::ParsingError(format!(
+                            "Invalid PDF keyword: {}",
+                            String::from_utf8_lossy(&data[keyword_start..index])
+                        )))?,
+                        Some(PDFKeyword::EndObj) => {
                             if this_object_type == PDFComplexObject::IndirectObj {
                                 return make_object_from_object_buffer(object_buffer, index);
                             } else {
@@ -556,34 +1528,59 @@ fn parse_object_at(data: &Vec<u8>, start_index: usize, weak_ref: &Weak<ObjectCac
                                 )))?;
                             };
                         }
-                        PDFKeyword::Stream => {
-                            return make_stream_object(data, object_buffer, index)
+                        Some(PDFKeyword::Stream) => {
+                            return make_stream_object(data, object_buffer, index, weak_ref, options)
+                        }
+                        // With endobj missing, the next object's header or the cross-reference section ends the
+                        // object instead
+                        Some(PDFKeyword::Obj) if options.lenient
+                                                 && this_object_type == PDFComplexObject::IndirectObj
+                                                 && object_buffer.len() == 5
+                                                 && object_buffer[3].is_int() && object_buffer[4].is_int() => {
+                            object_buffer.truncate(3);
+                            let end_index = object_header_start(data, keyword_start);
+                            repaired(cache.as_deref(), "missing endobj",
+                                     format!("No endobj for object at {}", start_index));
+                            return make_object_from_object_buffer(object_buffer, end_index);
                         }
-                        PDFKeyword::Obj if this_object_type != PDFComplexObject::Unknown => {
+                        Some(PDFKeyword::XRef) | Some(PDFKeyword::Trailer) | Some(PDFKeyword::StartXRef)
+                            if options.lenient && this_object_type == PDFComplexObject::IndirectObj => {
+                            repaired(cache.as_deref(), "missing endobj",
+                                     format!("No endobj for object at {}", start_index));
+                            return make_object_from_object_buffer(object_buffer, keyword_start);
+                        }
+                        Some(PDFKeyword::Obj) if this_object_type != PDFComplexObject::Unknown => {
                             return Err(ErrorKind::ParsingError(format!(
                                 "Encountered nested obj declaration at {}",
                                 index
                             )))?
                         }
-                        PDFKeyword::Obj => {
+                        Some(PDFKeyword::Obj) => {
                             this_object_type = PDFComplexObject::IndirectObj;
                             index -= 1;
                             ParserState::Neutral
                         }
-                        PDFKeyword::True => {
-                            object_buffer.push(PdfObject::new_boolean(true));
+                        Some(PDFKeyword::R) => {
+                            let reference = make_reference(&mut object_buffer, &mut unsigned_ints, keyword_start,
+                                                           weak_ref, options)?;
+                            object_buffer.push(reference);
                             index -= 1;
                             ParserState::Neutral
-                        },
-                        PDFKeyword::Null => {
-                            object_buffer.push(PdfObject::Actual(Null));
+                        }
+                        Some(keyword @ PDFKeyword::True) | Some(keyword @ PDFKeyword::False)
+                        | Some(keyword @ PDFKeyword::Null) => {
+                            object_buffer.push(match keyword {
+                                PDFKeyword::True => PdfObject::new_boolean(true),
+                                PDFKeyword::False => PdfObject::new_boolean(false),
+                                _ => PdfObject::Actual(Null),
+                            });
                             index -= 1;
                             ParserState::Neutral
                         }
-                        _ => {
+                        Some(keyword) => {
                             return Err(ErrorKind::ParsingError(format!(
                                 "Unrecognized keyword at {}: {:?}",
-                                index, this_keyword
+                                index, keyword
                             )))?
                         }
                     }
@@ -602,6 +1599,8 @@ fn make_stream_object(
     data: &Vec<u8>,
     mut object_buffer: Vec<PdfObject>,
     index: usize,
+    weak_ref: &Weak<ObjectCache>,
+    options: &ParserOptions,
 ) -> Result<(PdfObject, usize)> {
     if object_buffer.len() != 3 {
         Err(ErrorKind::ParsingError(format!(
@@ -609,23 +1608,7 @@ fn make_stream_object(
             index
         )))?;
     };
-    let binary_start_index = match data[index] {
-        b'\n' => index + 1,
-        b'\r' => {
-            if let Some(b'\n') = peek_ahead_by_n(data, index, 1) {
-                index + 2
-            } else {
-                Err(ErrorKind::ParsingError(format!(
-                    "Stream tag not followed by appropriate spacing at {}",
-                    index
-                )))?
-            }
-        }
-        _ => Err(ErrorKind::ParsingError(format!(
-            "Stream tag not followed by appropriate spacing at {}",
-            index
-        )))?,
-    };
+    let binary_start_index = stream_data_start(data, index, options, weak_ref.upgrade().as_deref())?;
     let stream_dict = object_buffer
         .pop()
         .unwrap()
@@ -641,7 +1624,7 @@ fn make_stream_object(
     let id_number = object_buffer[0]
         .try_into_int()
         .chain_err(|| ErrorKind::ParsingError("Invalid object number".to_string()))?;
-    let gen_number = object_buffer[0]
+    let gen_number = object_buffer[1]
         .try_into_int()
         .chain_err(|| ErrorKind::ParsingError("Invalid gen number".to_string()))?;
     let binary_length = stream_dict
@@ -651,9 +1634,11 @@ fn make_stream_object(
             id_number,
             gen_number
         )))?
-        .try_into_int()
-        .chain_err(|| ErrorKind::ParsingError("Invalid gen number".to_string()))?
-        as usize;
+        .try_into_offset()
+        .ok()
+        .and_then(|length| usize::try_from(length).ok())
+        .ok_or_else(|| ErrorKind::ParsingError("Invalid stream length".to_string()))?;
+    ParserOptions::check("stream length", binary_length, options.max_stream_bytes)?;
     // TODO: Confirm endstream included
     if binary_start_index + binary_length >= data.len() {
         Err(ErrorKind::ParsingError(format!(
@@ -661,17 +1646,18 @@ fn make_stream_object(
             id_number, gen_number, binary_length
         )))?
     };
-    Ok((
-        decode::decode_stream(
-            Rc::try_unwrap(stream_dict).expect("Could not unwrap Rc in make_stream_object call to decode_stream"),
-            Vec::from(&data[binary_start_index..(binary_start_index + binary_length)]),
-        )?,
-        binary_start_index + binary_length + 9,
-    ))
+    let span = binary_start_index..(binary_start_index + binary_length);
+    let stream = decode::decode_stream(
+        Rc::try_unwrap(stream_dict).expect("Could not unwrap Rc in make_stream_object call to decode_stream"),
+        &data[span.clone()],
+        decode::RawSource::new(weak_ref, ObjectId::new(id_number as u32, gen_number as u32), span),
+        options.max_stream_bytes,
+    )?;
+    Ok((stream, binary_start_index + binary_length + 9))
 }
 
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PDFVersion {
     V1_0,
     V1_1,
@@ -684,10 +1670,43 @@ pub enum PDFVersion {
     V2_0,
 }
 
+impl fmt::Display for PDFVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use PDFVersion::*;
+        let version = match self {
+            V1_0 => "1.0",
+            V1_1 => "1.1",
+            V1_2 => "1.2",
+            V1_3 => "1.3",
+            V1_4 => "1.4",
+            V1_5 => "1.5",
+            V1_6 => "1.6",
+            V1_7 => "1.7",
+            V2_0 => "2.0",
+        };
+        write!(f, "{}", version)
+    }
+}
+
+
+/// Identifies an indirect object by object number and generation number.  Ordering is by object number
+/// first, then by generation.
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub struct ObjectId(u32, u32);
 
-//TODO: Remove pub fields
-#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
-pub struct ObjectId(pub u32, pub u32);
+impl ObjectId {
+    pub fn new(number: u32, generation: u32) -> Self {
+        ObjectId(number, generation)
+    }
+
+    pub fn number(&self) -> u32 {
+        self.0
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.1
+    }
+}
 
 impl fmt::Display for ObjectId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -695,17 +1714,46 @@ impl fmt::Display for ObjectId {
     }
 }
 
+impl str::FromStr for ObjectId {
+    type Err = Error;
+
+    /// Parses an object reference ("12 0 R") or indirect object header ("12 0 obj").  A bare "12 0" is
+    /// also accepted.
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let (number, generation) = match parts[..] {
+            [number, generation] | [number, generation, "R"] | [number, generation, "obj"] => (number, generation),
+            _ => Err(ErrorKind::ParsingError(format!("Invalid object identifier: {:?}", s)))?,
+        };
+        Ok(ObjectId(
+            number.parse().chain_err(|| ErrorKind::ParsingError(format!("Invalid object number in {:?}", s)))?,
+            generation.parse().chain_err(|| ErrorKind::ParsingError(format!("Invalid gen number in {:?}", s)))?,
+        ))
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct PDFStreamObject {
     object_type: StreamType,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-enum StreamType {
+/// What a stream holds, judged by its dictionary or, failing that, by where it's referenced from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum StreamType {
+    /// Page description operators, such as a page's /Contents
     Content,
+    /// An object stream (/Type /ObjStm)
     Object,
     XRef,
     Image,
+    /// A form XObject
+    Form,
+    /// An XMP metadata stream
+    Metadata,
+    /// An embedded font program
+    FontFile,
+    /// The ICC profile of an ICCBased color space
+    IccProfile,
     Unknown,
 }
 
@@ -717,9 +1765,16 @@ enum PDFComplexObject {
     IndirectObj,
 }
 
+struct XrefSection {
+    entries: Vec<(ObjectId, Option<usize>)>,
+    trailer: SharedObject,
+    /// Just past the %%EOF marker ending this section's revision
+    end: usize,
+    trailer_time: Duration,
+}
+
 #[derive(Debug)]
 struct PDFTrailer {
-    start_index: usize,
     trailer_dict: SharedObject,
     xref_index: usize,
 }
@@ -728,7 +1783,7 @@ struct PDFTrailer {
 enum ParserState {
     Neutral,
     HexString,
-    CharString(u8),
+    CharString(usize),
     Name,
     Number,
     Comment,
@@ -758,7 +1813,7 @@ fn flush_buffer_to_object(state: &ParserState, buffer: &mut Vec<u8>) -> Result<P
             "Called flush buffer in Neutral context".to_string(),
         ))?,
         ParserState::HexString => {
-            //TODO: ADD PADDING
+            // An odd final digit is padded with 0 when the string is read, by PdfObject::try_into_bytes
             for c in buffer.iter() {
                 if !is_hex(*c) {
                     Err(ErrorKind::ParsingError(format!("Invalid character in hex string: {}", c)))?
@@ -767,12 +1822,12 @@ fn flush_buffer_to_object(state: &ParserState, buffer: &mut Vec<u8>) -> Result<P
             PdfObject::new_hex_string(buffer.clone() as Vec<u8>)
         }
         ParserState::CharString(0) => {
-            PdfObject::new_char_string(String::from_utf8_lossy(buffer).to_owned())
+            PdfObject::new_char_string(buffer.clone())
         }
         ParserState::CharString(_c) => {
             Err(ErrorKind::ParsingError(format!("String contains unclosed parentheses: {:?}", buffer)))?
         }
-        ParserState::Name => PdfObject::new_name(str::from_utf8(buffer)
+        ParserState::Name => PdfObject::new_name(String::from_utf8(decode_name(buffer))
                 .chain_err(|| ErrorKind::ParsingError(format!("Name contains invalid UTF-8: {:?}", buffer)))?),
         ParserState::Number => {
             if buffer.contains(&b'.') {
@@ -785,7 +1840,7 @@ fn flush_buffer_to_object(state: &ParserState, buffer: &mut Vec<u8>) -> Result<P
                 PdfObject::new_number_int(
                     str::from_utf8(buffer)
                         .chain_err(|| ErrorKind::ParsingError(format!("Number contains invalid UTF-8: {:?}", buffer)))?
-                        .parse::<i32>()?
+                        .parse::<i64>()?
                 )
             }
         }
@@ -798,18 +1853,52 @@ fn flush_buffer_to_object(state: &ParserState, buffer: &mut Vec<u8>) -> Result<P
     return Ok(new_obj);
 }
 
+/// Replace the two integers before an R keyword with the reference they make up.  They must be the last two
+/// objects parsed, both written as unsigned integers, so that the R in [/R 1 2 R] or [1 [2] 0 R] is read
+/// correctly or rejected.
+fn make_reference(object_buffer: &mut Vec<PdfObject>, unsigned_ints: &mut Vec<usize>, index: usize,
+                  weak_ref: &Weak<ObjectCache>, options: &ParserOptions) -> Result<PdfObject> {
+    let length = object_buffer.len();
+    let invalid = || ErrorKind::ParsingError(format!("Could not parse reference to object at {}", index));
+    if length < 2 || !unsigned_ints.ends_with(&[length - 2, length - 1]) {
+        Err(invalid())?
+    };
+    // Read as they're stored, as i64, so that numbers past i32 still make references
+    let unsigned = |object: &PdfObject| match object {
+        PdfObject::Actual(NumberInt(n)) => u32::try_from(*n).map_err(|_| invalid()),
+        _ => Err(invalid())
+    };
+    let number = unsigned(&object_buffer[length - 2])?;
+    let generation = unsigned(&object_buffer[length - 1])?;
+    let generation = options.generation(generation, || format!("in reference at {}", index),
+                                        weak_ref.upgrade().as_deref())?;
+    object_buffer.truncate(length - 2);
+    unsigned_ints.truncate(unsigned_ints.len() - 2);
+    Ok(PdfObject::new_reference(number, generation, Weak::clone(weak_ref)))
+}
+
+/// Wrap an element of an array or dictionary, sharing it through the pool if there is one.
+fn share(object: PdfObject, pool: Option<&ObjectPool>) -> SharedObject {
+    match pool {
+        Some(pool) => pool.share(object),
+        None => Rc::new(object)
+    }
+}
+
 fn make_array_from_object_buffer(
     object_buffer: Vec<PdfObject>,
     end_index: usize,
+    pool: Option<&ObjectPool>,
 ) -> Result<(PdfObject, usize)> {
-    Ok((PdfObject::new_array(Rc::new(object_buffer.into_iter().map(|obj| Rc::new(obj)).collect())), end_index))
+    Ok((PdfObject::new_array(Rc::new(object_buffer.into_iter().map(|obj| share(obj, pool)).collect())), end_index))
 }
 
 fn make_dict_from_object_buffer(
     object_buffer: Vec<PdfObject>,
     end_index: usize,
+    pool: Option<&ObjectPool>,
 ) -> Result<(PdfObject, usize)> {
-    let mut dict = HashMap::new();
+    let mut dict = PdfMap::new();
     let mut object_it = object_buffer.into_iter();
     loop {
         let key = match object_it.next() {
@@ -824,7 +1913,7 @@ fn make_dict_from_object_buffer(
             None => Err(ErrorKind::ParsingError(format!("No object for key: {:?}", key)))?,
             Some(obj) => obj
         };
-        dict.insert(key.try_into_string().unwrap().to_string(), Rc::new(value));
+        dict.insert(key.try_into_string().unwrap().to_string(), share(value, pool));
     }
 }
 
@@ -856,6 +1945,7 @@ mod tests {
     ];
 
     #[test]
+    #[cfg(feature = "fs")]
     fn test_sample_pdfs_sensitive() {
         let mut results = Vec::new();
         for path in &TEST_PDFS {
@@ -877,6 +1967,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "fs")]
     fn test_sample_pdfs_stoic() {
         for path in &TEST_PDFS {
             println!("{}", path);
@@ -888,12 +1979,12 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "fs")]
     fn add_all_objects(pdf: &mut PdfFileHandler) -> Result<()> {
-        let objects_to_add: Vec<(ObjectId, usize)> =
-            pdf.object_map.as_ref().index_map.borrow().iter().map(|(a, b)| (*a, *b)).collect();
-        for (object_number, _index) in objects_to_add {
+        let objects_to_add: Vec<(ObjectId, ObjectLocation)> = pdf.object_map.locations().collect();
+        for (object_number, _location) in objects_to_add {
             println!("Retrieving Obj #{}:", object_number);
-            match pdf.retrieve_object_by_ref(object_number.0, object_number.1) {
+            match pdf.retrieve_object_by_ref(object_number.number(), object_number.generation()) {
                 Ok(obj) => { println!("Obj #{} successfully retrieved: {}", object_number, obj); },
                 Err(e) => {
                     println!("**Obj #{} ERROR**: {}", object_number, e);
@@ -903,4 +1994,754 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_typed_retrieval() {
+        let pdf = PdfFileHandler::create_pdf_from_file("data/simple_pdf.pdf").unwrap();
+        let catalog = pdf.get_dict(ObjectId::new(1, 0)).unwrap();
+        assert_eq!(*catalog.get("Type").unwrap().try_into_string().unwrap(), "Catalog");
+        let pages = pdf.get_dict(ObjectId::new(2, 0)).unwrap();
+        assert_eq!(pages.get("Count").unwrap().try_into_int().unwrap(), 1);
+        assert!(pdf.get_int(ObjectId::new(1, 0)).is_err());
+        assert!(pdf.get_array(ObjectId::new(3, 0)).is_err());
+        assert!(pdf.get_stream_data(ObjectId::new(3, 0)).is_err());
+        assert!(pdf.get_dict(ObjectId::new(99, 0)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_walk() {
+        let pdf = PdfFileHandler::create_pdf_from_file("data/simple_pdf.pdf").unwrap();
+        let mut visited = Vec::new();
+        pdf.walk(|id, _obj| visited.push(id)).unwrap();
+        // Page 3 refers back to its parent, which must not be visited twice
+        assert_eq!(visited, vec![ObjectId::new(1, 0), ObjectId::new(2, 0), ObjectId::new(3, 0)]);
+    }
+
+    #[test]
+    fn test_object_id_from_str() {
+        assert_eq!("12 0 R".parse::<ObjectId>().unwrap(), ObjectId::new(12, 0));
+        assert_eq!("12 3 obj".parse::<ObjectId>().unwrap(), ObjectId::new(12, 3));
+        assert_eq!("  7   1 ".parse::<ObjectId>().unwrap(), ObjectId::new(7, 1));
+        assert!("12 R".parse::<ObjectId>().is_err());
+        assert!("12 0 X".parse::<ObjectId>().is_err());
+        assert!("-1 0 R".parse::<ObjectId>().is_err());
+    }
+
+    #[test]
+    fn test_object_id_ordering() {
+        let mut ids = vec![ObjectId::new(3, 0), ObjectId::new(1, 2), ObjectId::new(1, 0)];
+        ids.sort();
+        assert_eq!(ids, vec![ObjectId::new(1, 0), ObjectId::new(1, 2), ObjectId::new(3, 0)]);
+        assert_eq!(ids[1].number(), 1);
+        assert_eq!(ids[1].generation(), 2);
+    }
+
+    #[test]
+    fn test_prefetch() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let first = builder.add_object("(first)");
+        let second = builder.add_object("[1 0 R]");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let cache = &pdf.object_map;
+        pdf.get_object(ObjectId::new(first, 0)).unwrap();
+        let ids = [ObjectId::new(second, 0), ObjectId::new(99, 0), ObjectId::new(first, 0), ObjectId::new(1, 0),
+                   ObjectId::new(second, 0)];
+        // The first object is already cached and object 99 doesn't exist
+        assert_eq!(cache.prefetch(&ids).unwrap(), 2);
+        assert_eq!(cache.cache.borrow().len(), 3);
+        assert_eq!(cache.prefetch(&ids).unwrap(), 0);
+        assert_eq!(pdf.get_array(ObjectId::new(second, 0)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parser_limits() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let array = builder.add_object("[1 2 3 4]");
+        let dict = builder.add_object("<< /A 1 /B 2 /C [1 2] >>");
+        let stream = builder.add_stream(&[b'x'; 100], TestFilter::Flate, "");
+        let bytes = builder.build();
+        let limited = |options| PdfFileHandler::create_pdf_from_bytes_with_options(bytes.clone(), options);
+
+        let pdf = limited(ParserOptions { max_collection_entries: Some(3), ..Default::default() }).unwrap();
+        match pdf.get_object(ObjectId::new(array, 0)).unwrap_err().kind() {
+            ErrorKind::LimitExceeded(limit, value, max) => {
+                assert_eq!((*limit, *value, *max), ("collection size", 4, 3))
+            },
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+        assert!(pdf.get_dict(ObjectId::new(dict, 0)).is_ok());
+
+        // The raw stream is under the limit, but it decodes to 100 bytes, which is found when it's decoded
+        let pdf = limited(ParserOptions { max_stream_bytes: Some(50), ..Default::default() }).unwrap();
+        assert!(pdf.get_dict(ObjectId::new(stream, 0)).is_ok());
+        match pdf.get_stream_data(ObjectId::new(stream, 0)).unwrap_err().kind() {
+            ErrorKind::LimitExceeded(limit, ..) => assert_eq!(*limit, "decoded stream length"),
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+        let pdf = limited(ParserOptions { max_stream_bytes: Some(100), ..Default::default() }).unwrap();
+        assert_eq!(pdf.get_stream_data(ObjectId::new(stream, 0)).unwrap().len(), 100);
+
+        assert!(limited(ParserOptions { max_objects: Some(4), ..Default::default() }).is_err());
+        assert!(limited(ParserOptions { max_objects: Some(5), ..Default::default() }).is_ok());
+
+        // Decoding stops just past the limit rather than inflating the whole stream, and image data is limited too
+        let mut builder = MiniPdfBuilder::new();
+        let bomb = builder.add_stream(&vec![0; 1_000_000], TestFilter::Flate, "");
+        let image_id = builder.add_stream(&[0; 100], TestFilter::Flate,
+                                          "/Type /XObject /Subtype /Image /Width 10 /Height 10 /BitsPerComponent 8");
+        let bytes = builder.build();
+        let options = ParserOptions { max_stream_bytes: Some(20_000), ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes.clone(), options).unwrap();
+        match pdf.get_stream_data(ObjectId::new(bomb, 0)).unwrap_err().kind() {
+            ErrorKind::LimitExceeded(_, value, max) => assert_eq!((*value, *max), (20_001, 20_000)),
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+        let image = pdf.get_object(ObjectId::new(image_id, 0)).unwrap().try_into_binary_stream().unwrap();
+        assert_eq!(image.image_data().unwrap().0.len(), 100);
+        let options = ParserOptions { max_stream_bytes: Some(50), ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes, options).unwrap();
+        let image = pdf.get_object(ObjectId::new(image_id, 0)).unwrap().try_into_binary_stream().unwrap();
+        assert!(image.image_data().is_err());
+
+        // The index is counted as the xref table is read
+        let mut table = b"%PDF-1.4\nxref\n0 100001\n0000000000 65535 f \n".to_vec();
+        for _ in 0..100_000 {
+            table.extend_from_slice(b"0000000009 00000 n \n");
+        }
+        table.extend_from_slice(b"trailer\n<< /Size 100001 >>\nstartxref\n9\n%%EOF\n");
+        let options = ParserOptions { max_objects: Some(10), ..Default::default() };
+        match PdfFileHandler::create_pdf_from_bytes_with_options(table, options).unwrap_err().kind() {
+            ErrorKind::LimitExceeded(limit, value, _) => assert_eq!((*limit, *value), ("object count", 11)),
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_object_token_budget() {
+        let options = ParserOptions { max_object_tokens: Some(1000), ..Default::default() };
+        let parse = |body: &str| {
+            let data = format!("1 0 obj\n{}\nendobj\n", body).into_bytes();
+            parse_object_at(&data, 0, &Weak::new(), &options).map(|(object, _)| object)
+        };
+        // The header, endobj and brackets count too, as do the tokens of nested arrays
+        let digits = |n| vec!["1"; n].join(" ");
+        assert_eq!(parse(&format!("[{}]", digits(994))).unwrap().try_into_array().unwrap().len(), 994);
+        for body in [format!("[{}]", digits(995)), format!("<< /A [[{}]] >>", digits(500) + "] [" + &digits(500))]
+            .iter() {
+            match parse(body).unwrap_err().kind() {
+                ErrorKind::ObjectTooComplex(offset, max) => assert_eq!((*offset, *max), (0, 1000)),
+                kind => panic!("Unexpected error {:?}", kind),
+            }
+        }
+        let error = parse(&format!("[{}]", digits(2000))).unwrap_err();
+        assert!(error.to_string().contains("too complex"), "{}", error);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    #[cfg(unix)]
+    fn test_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let name = std::ffi::OsStr::from_bytes(b"pdfparser-caf\xE9-test.pdf");
+        let path = std::env::temp_dir().join(name);
+        let pdf = PdfFileHandler::create_pdf_from_bytes(crate::test_utils::MiniPdfBuilder::new().build()).unwrap();
+        pdf.save(&path, &WriteOptions::default()).unwrap();
+        let reread = PdfFileHandler::create_pdf_from_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reread.unwrap().object_map.object_ids(), pdf.object_map.object_ids());
+    }
+
+    #[test]
+    fn test_edits() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let first = ObjectId::new(builder.add_object("(original)"), 0);
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let cache = &pdf.object_map;
+        cache.set_object(first, PdfObject::new_char_string("edited"));
+        let added = cache.add_object(PdfObject::new_array(Rc::new(vec![Rc::new(cache.reference(first))])));
+        assert_eq!(added, ObjectId::new(first.number() + 1, 0));
+        assert!(cache.contains(added) && cache.is_edited(first) && !cache.is_edited(ObjectId::new(1, 0)));
+        assert_eq!(pdf.get_array(added).unwrap()[0].try_into_string().unwrap().as_str(), "edited");
+
+        let (bytes, _) = pdf.write(&WriteOptions::default()).unwrap();
+        let reread = PdfFileHandler::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(reread.get_string(first).unwrap(), "edited");
+        assert_eq!(reread.get_array(added).unwrap()[0].reference_id(), Some(first));
+    }
+
+    #[test]
+    fn test_object_span() {
+        let mut builder = crate::test_utils::MiniPdfBuilder::new();
+        let stream = builder.add_stream(b"endobj inside data", crate::test_utils::TestFilter::None, "");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let catalog = pdf.object_span(ObjectId::new(1, 0)).unwrap();
+        assert_eq!(&pdf.object_map.data[catalog], &b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R  >>\nendobj"[..]);
+        let span = pdf.object_span(ObjectId::new(stream, 0)).unwrap();
+        let text = String::from_utf8_lossy(&pdf.object_map.data[span.clone()]).to_string();
+        assert!(text.starts_with("3 0 obj\n<< /Length"));
+        assert!(text.ends_with("\nendstream\nendobj"));
+        assert_eq!(pdf.object_map.data[span.end], b'\n');
+
+        assert!(pdf.object_span(ObjectId::new(9, 0)).is_err());
+        let added = pdf.object_map.add_object(PdfObject::new_boolean(true));
+        assert!(pdf.object_span(added).is_err());
+        pdf.object_map.set_object(ObjectId::new(1, 0), PdfObject::new_boolean(false));
+        assert!(pdf.object_span(ObjectId::new(1, 0)).is_err());
+    }
+
+    #[test]
+    fn test_raw_stream_data() {
+        use crate::test_utils::*;
+        let content = b"0 0 m 100 100 l S\n".repeat(10);
+        let mut builder = MiniPdfBuilder::new();
+        let stream = builder.add_stream(&content, TestFilter::Flate, "");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let object = pdf.get_object(ObjectId::new(stream, 0)).unwrap();
+        let raw = object.raw_stream_data().unwrap();
+        assert_eq!(raw, TestFilter::Flate.encode(&content));
+        assert_eq!(pdf.get_stream_data(ObjectId::new(stream, 0)).unwrap(), content);
+        let span = object.try_into_binary_stream().unwrap().raw_span().unwrap();
+        assert_eq!(pdf.object_map.data[span], raw[..]);
+
+        let made = decode::PdfBinaryStream::new(PdfMap::new(), b"q Q".to_vec());
+        let made = PdfObject::Actual(BinaryStream(Rc::new(made)));
+        assert_eq!(made.raw_stream_data().unwrap(), b"q Q");
+        assert!(PdfObject::new_boolean(true).raw_stream_data().is_err());
+    }
+
+    #[test]
+    fn test_lazy_decoding() {
+        use crate::test_utils::*;
+        let content = b"0 0 m 100 100 l S\n".repeat(10);
+        let mut builder = MiniPdfBuilder::new();
+        let stream = builder.add_stream(&content, TestFilter::Flate, "/Type /Example");
+        let broken = builder.add_stream(b"not flate data", TestFilter::None, "/Filter /FlateDecode");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+
+        let object = pdf.get_object(ObjectId::new(stream, 0)).unwrap();
+        let binary = object.try_into_binary_stream().unwrap();
+        assert!(!binary.is_loaded());
+        assert_eq!(*pdf.get_dict(ObjectId::new(stream, 0)).unwrap()["Type"].try_into_string().unwrap(), "Example");
+        assert!(!binary.is_loaded());
+        assert_eq!(binary.data().unwrap(), &content[..]);
+        assert!(binary.is_loaded());
+        assert_eq!(*object.try_into_binary().unwrap(), content);
+
+        // Bad data is only noticed when it's decoded
+        assert!(pdf.get_dict(ObjectId::new(broken, 0)).is_ok());
+        assert!(pdf.get_stream_data(ObjectId::new(broken, 0)).is_err());
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let stream = ObjectId::new(builder.add_stream(&b"x".repeat(100), TestFilter::Flate, ""), 0);
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let cache = &pdf.object_map;
+        assert!(cache.stats().is_empty());
+
+        cache.retrieve_object_by_ref(1, 0).unwrap();
+        cache.retrieve_object_by_ref(1, 0).unwrap();
+        let object = pdf.get_object(stream).unwrap();
+        object.try_into_binary_stream().unwrap().data().unwrap();
+        let stats = cache.stats();
+        assert_eq!((stats["dictionary"].hits, stats["dictionary"].misses), (1, 1));
+        assert!(stats["dictionary"].decode_time.is_zero());
+        assert_eq!((stats["stream"].hits, stats["stream"].misses), (0, 1));
+        assert!(stats["stream"].decode_time > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_locations() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        for number in 0..12 {
+            builder.add_object(number.to_string());
+        }
+        let bytes = builder.build();
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        pdf.object_map.set_object(ObjectId::new(5, 0), PdfObject::new_boolean(true));
+        let added = pdf.object_map.add_object(PdfObject::new_boolean(false));
+
+        let locations: Vec<(ObjectId, ObjectLocation)> = pdf.object_map.locations().collect();
+        let ids: Vec<ObjectId> = locations.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, (1..=15).map(|number| ObjectId::new(number, 0)).collect::<Vec<_>>());
+        assert_eq!(ids, pdf.object_map.object_ids());
+        assert_eq!(locations[4].1, ObjectLocation::Edited);
+        assert_eq!(locations.last().unwrap(), &(added, ObjectLocation::Edited));
+        match locations[5].1 {
+            ObjectLocation::Offset(offset) => assert!(bytes[offset..].starts_with(b"6 0 obj")),
+            location => panic!("Unexpected location {:?}", location),
+        }
+    }
+
+    #[test]
+    fn test_memory_report() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let stream = ObjectId::new(builder.add_stream(&[b'x'; 1000], TestFilter::Flate, ""), 0);
+        let text = ObjectId::new(builder.add_object(format!("({})", "y".repeat(500))), 0);
+        let bytes = builder.build();
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        let cache = &pdf.object_map;
+        let empty = cache.memory_report();
+        assert_eq!(empty, MemoryReport { file_bytes: bytes.len(), ..Default::default() });
+
+        pdf.get_object(stream).unwrap();
+        pdf.get_object(text).unwrap();
+        let parsed = cache.memory_report();
+        assert_eq!((parsed.cached_objects, parsed.decoded_stream_bytes), (2, 0));
+        assert!(parsed.object_bytes >= 500);
+
+        pdf.get_stream_data(stream).unwrap();
+        let decoded = cache.memory_report();
+        assert_eq!(decoded.decoded_stream_bytes, 1000);
+        assert_eq!(decoded.total(), parsed.total() + 1000);
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut builder = crate::test_utils::MiniPdfBuilder::new();
+        let dict = builder.add_object("<< /Key % the value follows\n/Value /Array [1 %one\r 2] \
+                                         /Name%no space\n/Tail >>");
+        let bytes = builder.build();
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        let map = pdf.get_dict(ObjectId::new(dict, 0)).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["Key"].try_into_string().unwrap().as_str(), "Value");
+        assert_eq!(map["Array"].try_into_array().unwrap().len(), 2);
+        assert_eq!(map["Name"].try_into_string().unwrap().as_str(), "Tail");
+        assert!(pdf.object_map.comments().is_empty());
+
+        let options = ParserOptions { collect_comments: true, ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes.clone(), options).unwrap();
+        pdf.object_map.prefetch(&pdf.object_map.object_ids()).unwrap();
+        pdf.get_dict(ObjectId::new(dict, 0)).unwrap();
+        let comments = pdf.object_map.comments();
+        assert_eq!(comments.iter().map(|comment| comment.text.as_str()).collect::<Vec<&str>>(),
+                   vec![" the value follows", "one", "no space"]);
+        assert_eq!(&bytes[comments[1].offset..comments[1].offset + 4], b"%one");
+    }
+
+    #[test]
+    fn test_missing_endobj() {
+        let mut builder = crate::test_utils::MiniPdfBuilder::new();
+        let dict = builder.add_object("<< /A [1 2] >>");
+        let number = builder.add_object("42");
+        // Blanked out rather than removed, so the xref offsets stay right
+        let bytes = String::from_utf8(builder.build()).unwrap().replace("endobj", "      ").into_bytes();
+        let strict = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        assert!(strict.get_dict(ObjectId::new(dict, 0)).is_err());
+
+        let options = ParserOptions { lenient: true, ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes, options).unwrap();
+        assert_eq!(pdf.get_dict(ObjectId::new(dict, 0)).unwrap()["A"].try_into_array().unwrap().len(), 2);
+        // The last object is ended by the xref keyword
+        assert_eq!(pdf.get_int(ObjectId::new(number, 0)).unwrap(), 42);
+        let span = pdf.object_span(ObjectId::new(dict, 0)).unwrap();
+        assert!(pdf.object_map.data[span.end..].starts_with(format!("{} 0 obj", number).as_bytes()));
+        let span = pdf.object_span(ObjectId::new(number, 0)).unwrap();
+        assert!(pdf.object_map.data[span.end..].starts_with(b"xref"));
+    }
+
+    #[test]
+    fn test_keyword_values() {
+        let mut builder = crate::test_utils::MiniPdfBuilder::new();
+        let dict = builder.add_object("<< /Yes true /No false /Nothing null /List [true false null false] >>");
+        let flag = builder.add_object("false");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let map = pdf.get_dict(ObjectId::new(dict, 0)).unwrap();
+        assert!(map["Yes"].try_into_bool().unwrap());
+        assert!(!map["No"].try_into_bool().unwrap());
+        assert!(matches!(map["Nothing"].as_ref(), PdfObject::Actual(Null)));
+        let list = map["List"].try_into_array().unwrap();
+        let bools: Vec<Option<bool>> = list.iter().map(|item| item.try_into_bool().ok()).collect();
+        assert_eq!(bools, vec![Some(true), Some(false), None, Some(false)]);
+        assert!(!pdf.get_object(ObjectId::new(flag, 0)).unwrap().try_into_bool().unwrap());
+    }
+
+    #[test]
+    fn test_stream_keyword_spacing() {
+        let parse = |before: &str, after: &str, lenient: bool| {
+            let data = format!("1 0 obj\n<< /Length 3 >>{}stream{}abc\nendstream\nendobj\n", before, after);
+            let options = ParserOptions { lenient, ..Default::default() };
+            parse_object_at(&data.into_bytes(), 0, &Weak::new(), &options)
+                .map(|(object, _)| object.try_into_binary_stream().unwrap().data().unwrap().to_vec())
+        };
+        for before in &["", " ", "\n", "\r\n  "] {
+            for after in &["\n", "\r\n"] {
+                assert_eq!(parse(before, after, false).unwrap(), b"abc", "{:?} {:?}", before, after);
+            }
+        }
+        for after in &["\r", " \n", "\t\r\n"] {
+            assert!(parse("\n", after, false).is_err(), "{:?}", after);
+            assert_eq!(parse("\n", after, true).unwrap(), b"abc", "{:?}", after);
+        }
+        let truncated = b"1 0 obj\n<< /Length 3 >>\nstream".to_vec();
+        assert!(parse_object_at(&truncated, 0, &Weak::new(), &ParserOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_stream_types() {
+        use crate::test_utils::*;
+        let mut builder = MiniPdfBuilder::new();
+        let image = builder.add_stream(b"\x00", TestFilter::None, "/Subtype /Image /Width 1 /Height 1");
+        let form = builder.add_stream(b"", TestFilter::None, "/Type /XObject /Subtype /Form");
+        let xmp = builder.add_stream(b"<x/>", TestFilter::None, "/Type /Metadata /Subtype /XML");
+        let font = builder.add_stream(b"", TestFilter::None, "/Length1 0");
+        let cff = builder.add_stream(b"", TestFilter::Flate, "");
+        let icc = builder.add_stream(b"", TestFilter::None, "/N 3");
+        let other = builder.add_stream(b"", TestFilter::None, "");
+        builder.add_object(format!("<< /Type /FontDescriptor /FontFile3 {} 0 R >>", cff));
+        let page = builder.add_page_with_entries(b"", TestFilter::None, &format!(
+            "/Resources << /ColorSpace << /CS0 [/ICCBased {} 0 R] >> >>", icc));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        let stream = |id: u32| pdf.get_object(ObjectId::new(id, 0)).unwrap().try_into_binary_stream().unwrap();
+        assert_eq!(stream(cff).stream_type(), StreamType::Unknown);
+
+        let types = pdf.stream_types();
+        let expected = [(image, StreamType::Image), (form, StreamType::Form), (xmp, StreamType::Metadata),
+                        (font, StreamType::FontFile), (cff, StreamType::FontFile), (icc, StreamType::IccProfile),
+                        (other, StreamType::Unknown), (page - 1, StreamType::Content)];
+        assert_eq!(types.len(), expected.len());
+        for (id, stream_type) in &expected {
+            assert_eq!(types[&ObjectId::new(*id, 0)], *stream_type, "object {}", id);
+        }
+        assert_eq!(stream(cff).stream_type(), StreamType::FontFile);
+    }
+
+    #[test]
+    fn test_trailer_from_last_xref() {
+        use crate::test_utils::*;
+        // A stream holding the word "trailer", and junk with another one after the %%EOF marker
+        let mut builder = MiniPdfBuilder::new();
+        let data = b"trailer\n<< /Root 9 0 R >>\nstartxref\n0\n%%EOF";
+        let stream = builder.add_stream(data, TestFilter::None, "");
+        let mut bytes = builder.build();
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        assert_eq!(pdf.get_stream_data(ObjectId::new(stream, 0)).unwrap(), data);
+        bytes.extend_from_slice(b"\n% appended by a mail client: trailer << /Root 9 0 R >>\n");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes).unwrap();
+        let root = pdf.retrieve_trailer().unwrap().try_to_get("Root").unwrap().unwrap();
+        assert_eq!(root.reference_id(), Some(ObjectId::new(1, 0)));
+
+        for bad_offset in ["", "abc", "999999"] {
+            let data = format!("%PDF-1.4\nstartxref\n{}\n%%EOF\n", bad_offset).into_bytes();
+            assert!(PdfFileHandler::create_pdf_from_bytes(data).is_err(), "{:?}", bad_offset);
+        }
+        assert!(PdfFileHandler::create_pdf_from_bytes(b"%PDF-1.4\n%%EOF\n".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_trailing_bytes_after_eof() {
+        use crate::test_utils::*;
+        let bytes = MiniPdfBuilder::new().build();
+        let with_tail = |tail: &[u8]| [bytes.as_slice(), tail].concat();
+        for tail in [&b"\r\n\r\n"[..], b"\0\0\0\0", b"\n%%EOF\n%%EOF\n", b"\nstartxref\n5\n", &[b' '; 900]] {
+            assert!(PdfFileHandler::create_pdf_from_bytes(with_tail(tail)).is_ok(), "{:?}", tail);
+        }
+
+        // Too much padding hides the marker, unless parsing is lenient
+        let padded = with_tail(&[0; 2000]);
+        assert!(PdfFileHandler::create_pdf_from_bytes(padded.clone()).is_err());
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        assert!(PdfFileHandler::create_pdf_from_bytes_with_options(padded, lenient.clone()).is_ok());
+        let truncated = bytes[..bytes.len() - 6].to_vec();
+        assert!(PdfFileHandler::create_pdf_from_bytes(truncated.clone()).is_err());
+        assert!(PdfFileHandler::create_pdf_from_bytes_with_options(truncated, lenient).is_ok());
+    }
+
+    #[test]
+    fn test_pooled_values() {
+        use crate::test_utils::*;
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>", "<< /Type /Pages /Kids [] /Count 0 >>",
+                       "[/Type 0 0 true null 70000 70000]"];
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_from_objects(&objects, "<< /Root 1 0 R /Size 4 >>"))
+            .unwrap();
+        let catalog = pdf.get_dict(ObjectId::new(1, 0)).unwrap();
+        let pages = pdf.get_dict(ObjectId::new(2, 0)).unwrap();
+        let array = pdf.get_array(ObjectId::new(3, 0)).unwrap();
+        assert!(Rc::ptr_eq(&pages["Count"], &array[1]) && Rc::ptr_eq(&array[1], &array[2]));
+        assert!(!Rc::ptr_eq(&array[5], &array[6]));
+        assert_eq!(catalog["Type"].try_into_string().unwrap().as_str(), "Catalog");
+        assert_eq!(array[0].try_into_string().unwrap().as_str(), "Type");
+        // The trailer's 4 is pooled too, along with Catalog, Pages, 0, Type, true and null; both 0s in the array
+        // are hits
+        assert_eq!(pdf.object_map.pool_stats(), PoolStats { values: 7, hits: 2 });
+    }
+
+    #[test]
+    fn test_large_offsets() {
+        let parse = |body: &[u8]| {
+            let data = [b"1 0 obj\n", body, b"\nendobj\n"].concat();
+            parse_object_at(&data, 0, &Weak::new(), &ParserOptions::default()).map(|(object, _)| object)
+        };
+        // Offsets past 2GB and 4GB, as in the /Prev of a large file's trailer
+        let trailer = parse(b"<< /Prev 3000000000 /XRefStm 9999999999 /Size 4 >>").unwrap().try_into_map().unwrap();
+        assert_eq!(trailer["Prev"].try_into_offset().unwrap(), 3_000_000_000);
+        assert_eq!(trailer["XRefStm"].try_into_offset().unwrap(), 9_999_999_999);
+        assert!(trailer["Prev"].try_into_int().is_err());
+        assert_eq!(trailer["Size"].try_into_int().unwrap(), 4);
+
+        let mut written = Vec::new();
+        write_object(&mut written, &trailer["XRefStm"], &None, &WriteOptions::default()).unwrap();
+        assert_eq!(written, b"9999999999");
+        // A negative length is rejected rather than wrapping around to a huge one
+        assert!(parse(b"<< /Length -5 >>\nstream\nabcde\nendstream").is_err());
+        assert!(parse(b"<< /Length 5 >>\nstream\nabcde\nendstream").is_ok());
+        assert!(PdfObject::new_number_int(-1).try_into_offset().is_err());
+
+        // Object numbers past i32 but within u32 make references; larger or negative ones are errors, not panics
+        let array = parse(b"[3000000000 0 R]").unwrap().try_into_array().unwrap();
+        assert_eq!(array[0].reference_id(), Some(ObjectId::new(3_000_000_000, 0)));
+        assert!(parse(b"[9999999999 0 R]").is_err());
+        assert!(parse(b"[-9223372036854775807 0 R]").is_err());
+    }
+
+    #[test]
+    fn test_reference_lookback() {
+        let parse = |body: &[u8]| {
+            let data = [b"1 0 obj\n", body, b"\nendobj\n"].concat();
+            parse_object_at(&data, 0, &Weak::new(), &ParserOptions::default()).map(|(object, _)| object)
+        };
+        let ids = |object: PdfObject| -> Vec<Option<ObjectId>> {
+            object.try_into_array().unwrap().iter().map(|o| o.reference_id()).collect()
+        };
+        assert_eq!(ids(parse(b"[/R 1 2 R]").unwrap()), vec![None, Some(ObjectId::new(1, 2))]);
+        assert_eq!(ids(parse(b"[1 2 R 3 4 R]").unwrap()), vec![Some(ObjectId::new(1, 2)), Some(ObjectId::new(3, 4))]);
+        assert_eq!(ids(parse(b"[7 1 2 R/R]").unwrap()), vec![None, Some(ObjectId::new(1, 2)), None]);
+        let dict = parse(b"<< /R 5 0 R /S [/R] >>").unwrap().try_into_map().unwrap();
+        assert_eq!(dict["R"].reference_id(), Some(ObjectId::new(5, 0)));
+
+        // R only completes a reference straight after two unsigned integers, and only as a whole word
+        for body in [&b"[/Name R]"[..], b"[/Name 0 R]", b"[1.0 0 R]", b"[+1 0 R]", b"[1 -0 R]", b"[1 [0] R]",
+                     b"[1 0 Rx]", b"1 0 R R", b"[[/a /b /c 0] /x /y /z /w 1 R]"].iter() {
+            assert!(parse(body).is_err(), "{}", String::from_utf8_lossy(body));
+        }
+    }
+
+    #[test]
+    fn test_generation_over_limit() {
+        use crate::test_utils::*;
+        // The reference is padded so that giving it a large generation doesn't move anything after it
+        let objects = ["<< /Type /Catalog /Pages 2 0 R /Note 3 0     R >>", "<< /Type /Pages /Kids [] /Count 0 >>",
+                       "(note)"];
+        let bytes = String::from_utf8(pdf_from_objects(&objects, "<< /Root 1 0 R /Size 4 >>")).unwrap();
+        let entry = bytes.rfind(" 00000 n ").unwrap();
+        let bytes = format!("{} 70000 n {}", &bytes[..entry], &bytes[entry + 9..]).replace("3 0     R", "3 70000 R");
+
+        let error = PdfFileHandler::create_pdf_from_bytes(bytes.clone().into_bytes()).unwrap_err();
+        assert!(error.to_string().contains("Generation number 70000 in xref entry 3 is over 65535"), "{}", error);
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes.into_bytes(), lenient).unwrap();
+        let note = pdf.get_dict(ObjectId::new(1, 0)).unwrap()["Note"].clone();
+        assert_eq!(note.reference_id(), Some(ObjectId::new(3, MAX_GENERATION)));
+        assert_eq!(note.try_into_string().unwrap().as_str(), "note");
+
+        // Nor will the writer produce one
+        pdf.object_map.set_object(ObjectId::new(4, 70000), PdfObject::new_boolean(true));
+        assert!(pdf.write(&WriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_definitions() {
+        use crate::test_utils::*;
+        // Object 3 is defined twice in the original body; the xref table points at the second definition
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>",
+                       "<< /Type /Pages /Kids [] /Count 0 >>\nendobj\n3 0 obj\n(stale)",
+                       "(current)",
+                       "<< /Note (4 0 obj, quoted) >>"];
+        let mut bytes = pdf_from_objects(&objects, "<< /Root 1 0 R /Size 5 >>");
+        append_incremental_update(&mut bytes, &[(3, "(updated)")], "/Root 1 0 R");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(pdf.get_object(ObjectId::new(3, 0)).unwrap().try_into_bytes().unwrap(), b"updated");
+
+        let id = ObjectId::new(3, 0);
+        let definitions = pdf.object_map.definitions(id);
+        assert_eq!(definitions.len(), 3);
+        assert!(pdf.object_map.definitions(ObjectId::new(4, 0)).len() == 1);
+        assert!(pdf.object_map.definitions(ObjectId::new(9, 0)).is_empty());
+        assert_eq!(pdf.duplicate_definitions(), vec![DuplicateDefinition {
+            id,
+            revision: 0,
+            offsets: definitions[..2].to_vec(),
+            used: None,
+        }]);
+        let original = pdf.at_revision(0).unwrap();
+        assert_eq!(original.get_object(id).unwrap().try_into_bytes().unwrap(), b"current");
+        assert_eq!(original.duplicate_definitions()[0].used, Some(definitions[1]));
+
+        // When the index points somewhere unreadable, lenient parsing uses the newest definition instead
+        let objects = ["<< /Type /Catalog /Pages 2 0 R /Note 3 0 R >>",
+                       "<< /Type /Pages /Kids [] /Count 0 >>\nendobj\n3 0 obj\n(stale)",
+                       "(current)"];
+        let bytes = pdf_from_objects(&objects, "<< /Root 1 0 R /Size 4 >>");
+        let text = String::from_utf8_lossy(&bytes).to_string();
+        let entry = format!("{:010} 00000 n \n", text.rfind("3 0 obj").unwrap());
+        let broken = text.replace(&entry, &format!("{:010} 00000 n \n", text.find("xref").unwrap())).into_bytes();
+        assert!(PdfFileHandler::create_pdf_from_bytes(broken.clone()).unwrap().get_object(id).is_err());
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(broken, lenient).unwrap();
+        assert_eq!(pdf.get_object(id).unwrap().try_into_bytes().unwrap(), b"current");
+        assert_eq!(pdf.object_map.repairs().get("object offset"), Some(&1));
+    }
+
+    #[test]
+    fn test_hex_strings() {
+        let parse = |body: &str, lenient: bool| {
+            let data = format!("1 0 obj\n{}\nendobj\n", body).into_bytes();
+            let options = ParserOptions { lenient, ..Default::default() };
+            parse_object_at(&data, 0, &Weak::new(), &options).map(|(object, _)| object.try_into_bytes().unwrap())
+        };
+        assert_eq!(parse("<48656c6C 6F\r\n2>", false).unwrap(), b"Hello ");
+        assert_eq!(parse("<>", false).unwrap(), b"");
+        assert!(parse("<4865zz6C>", false).is_err());
+        assert_eq!(parse("<4865zz6C>", true).unwrap(), b"Hel");
+        let mut written = Vec::new();
+        let data = b"1 0 obj <ab> endobj\n".to_vec();
+        let object = parse_object_at(&data, 0, &Weak::new(), &ParserOptions::default()).unwrap().0;
+        write_object(&mut written, &object, &None, &WriteOptions::default()).unwrap();
+        assert_eq!(written, b"<AB>");
+    }
+
+    #[test]
+    fn test_literal_strings() {
+        let cases: &[(&[u8], &[u8])] = &[
+            (b"(a\\nb\\rc\\td\\be\\ff)", b"a\nb\rc\td\x08e\x0Cf"),
+            (b"(\\(\\)\\\\)", b"()\\"),
+            (b"(\\101\\102C\\0053)", b"ABC\x053"),
+            (b"(\\q)", b"q"),
+            (b"(con\\\r\ntinued)", b"continued"),
+            (b"(con\\\rtinued)", b"continued"),
+            (b"(con\\\ntinued)", b"continued"),
+            (b"(two\r\nlines)", b"two\nlines"),
+            (b"(two\rlines)", b"two\nlines"),
+            (b"(two\nlines)", b"two\nlines"),
+            (b"(two\n\rlines)", b"two\n\nlines"),
+            // High-order overflow in octal escapes is ignored, and bytes that aren't UTF-8 are kept as they are
+            (b"(\\400\\777\\3777)", b"\x00\xFF\xFF7"),
+            (b"(caf\xE9 \x80\xFE)", b"caf\xE9 \x80\xFE"),
+        ];
+        for (body, expected) in cases {
+            let mut data = b"1 0 obj\n".to_vec();
+            data.extend_from_slice(body);
+            data.extend_from_slice(b"\nendobj\n");
+            let object = parse_object_at(&data, 0, &Weak::new(), &ParserOptions::default()).unwrap().0;
+            assert_eq!(object.try_into_bytes().unwrap(), *expected, "{:?}", String::from_utf8_lossy(body));
+            // And they're written so that they read back the same
+            let mut written = b"1 0 obj\n".to_vec();
+            write_object(&mut written, &object, &None, &WriteOptions::default()).unwrap();
+            written.extend_from_slice(b"\nendobj\n");
+            let reread = parse_object_at(&written, 0, &Weak::new(), &ParserOptions::default()).unwrap().0;
+            assert_eq!(reread.try_into_bytes().unwrap(), *expected);
+        }
+        let latin1 = PdfObject::new_char_string(b"caf\xE9".to_vec());
+        assert_eq!(latin1.try_into_text().unwrap(), "caf\u{E9}");
+        assert_eq!(latin1.try_into_string().unwrap().as_str(), "caf\u{FFFD}");
+    }
+
+    #[test]
+    fn test_unbalanced_strings() {
+        let parse = |body: &str, options: &ParserOptions| {
+            let data = format!("1 0 obj\n{}\nendobj\n", body).into_bytes();
+            parse_object_at(&data, 0, &Weak::new(), options).map(|(object, _)| object)
+        };
+        let strict = ParserOptions::default();
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        let text = |object: PdfObject| object.try_into_string().unwrap().to_string();
+        assert_eq!(text(parse("(a (b) c)", &strict).unwrap()), "a (b) c");
+        let deep = format!("({}{})", "(".repeat(300), ")".repeat(300));
+        assert_eq!(text(parse(&deep, &strict).unwrap()).len(), 600);
+
+        // The extra ( keeps the title open to the end of the file, until it's cut back to the last balanced )
+        let body = "<< /Title (Chapter (1 draft) /Author (Ann) >>";
+        assert!(parse(body, &strict).is_err());
+        let dict = parse(body, &lenient).unwrap().try_into_map().unwrap();
+        assert_eq!(dict["Title"].try_into_string().unwrap().as_str(), "Chapter (1 draft) /Author (Ann");
+        assert!(parse("(never (ends", &lenient).is_err());
+
+        // The length limit stops the string before the end of the file, and recovery goes back from there
+        let body = "[(x (y) 1 2 3 4 5 6 7 8]";
+        let limited = ParserOptions { max_string_bytes: Some(10), ..Default::default() };
+        match parse(body, &limited).unwrap_err().kind() {
+            ErrorKind::LimitExceeded(limit, value, max) => {
+                assert_eq!((*limit, *value, *max), ("string length", 11, 10))
+            },
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+        let array = parse(body, &ParserOptions { lenient: true, ..limited.clone() }).unwrap().try_into_array().unwrap();
+        assert_eq!(text(array[0].as_ref().clone()), "x (y");
+        assert_eq!(array.len(), 9);
+        assert!(parse("(0123456789)", &limited).is_ok());
+    }
+
+    #[test]
+    fn test_stray_keywords() {
+        let parse = |body: &str, lenient: bool| {
+            let data = format!("1 0 obj\n{}\nendobj\n", body).into_bytes();
+            let options = ParserOptions { lenient, ..Default::default() };
+            parse_object_at(&data, 0, &Weak::new(), &options).map(|(object, _)| object)
+        };
+        for body in ["<< /A 1 >>\nendstream", "[1 BT 2 T* 3]", "<< /A [/B ET] /C 4 0 R >> Tj"].iter() {
+            assert!(parse(body, false).is_err(), "{}", body);
+        }
+        let dict = parse("<< /A 1 >>\nendstream", true).unwrap().try_into_map().unwrap();
+        assert_eq!(dict["A"].try_into_int().unwrap(), 1);
+        let array = parse("[1 BT 2 T* 3]", true).unwrap().try_into_array().unwrap();
+        assert_eq!(array.iter().map(|o| o.try_into_int().unwrap()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let dict = parse("<< /A [/B ET] /C 4 0 R >> Tj", true).unwrap().try_into_map().unwrap();
+        assert_eq!(dict["C"].reference_id(), Some(ObjectId::new(4, 0)));
+
+        // The rest of the file reads as usual
+        use crate::test_utils::*;
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>\nendstream", "<< /Type /Pages /Kids [] /Count 0 >>"];
+        let bytes = pdf_from_objects(&objects, "<< /Root 1 0 R /Size 3 >>");
+        let lenient = ParserOptions { lenient: true, ..Default::default() };
+        let pdf = PdfFileHandler::create_pdf_from_bytes_with_options(bytes, lenient).unwrap();
+        assert_eq!(pdf.get_dict(ObjectId::new(2, 0)).unwrap()["Count"].try_into_int().unwrap(), 0);
+        assert!(pdf.get_dict(ObjectId::new(1, 0)).unwrap().contains_key("Pages"));
+    }
+
+    #[test]
+    fn test_get_span() {
+        let pdf = PdfFileHandler::create_pdf_from_bytes(crate::test_utils::MiniPdfBuilder::new().build()).unwrap();
+        assert_eq!(pdf.object_map.get_span(0..8).unwrap(), b"%PDF-1.4");
+        let length = pdf.object_map.data.len();
+        assert!(pdf.object_map.get_span(length - 1..length + 1).is_err());
+    }
+
+    #[test]
+    fn test_debug_helpers() {
+        let pdf = PdfFileHandler::create_pdf_from_bytes(crate::test_utils::MiniPdfBuilder::new().build()).unwrap();
+        assert_eq!(pdf.debug_context(20, 8), concat!(
+            " 00000000  25 50 44 46 2d 31 2e 34 0a 31 20 30 20 6f 62 6a  |%PDF-1.4.1 0 obj|\n",
+            ">00000010  0a 3c 3c 20 2f 54 79 70 65 20 2f 43              |.<< /Type /C|"));
+        let length = pdf.object_map.data.len();
+        let all = pdf.debug_context(length - 1, length);
+        assert_eq!(all.lines().count(), (length + 15) / 16);
+        assert!(all.lines().last().unwrap().starts_with('>'));
+
+        // From the header and from the dictionary itself
+        for offset in &[9, 17] {
+            let (object, end) = pdf.parse_single_object_at(*offset).unwrap();
+            let dict = object.try_into_map().unwrap();
+            assert_eq!(dict["Type"].try_into_string().unwrap().as_str(), "Catalog");
+            assert_eq!(dict["Pages"].reference_id(), Some(ObjectId::new(2, 0)));
+            assert!(end > 17 && end <= 60);
+        }
+        assert!(pdf.object_map.cache.borrow().is_empty());
+        assert!(pdf.parse_single_object_at(length + 1).is_err());
+    }
+
+    #[test]
+    fn test_cache_self_reference() {
+        let cache = ObjectCache::new(Vec::new(), HashMap::new(), ParserOptions::default());
+        assert!(Rc::ptr_eq(&cache.self_ref.upgrade().unwrap(), &cache));
+    }
 }
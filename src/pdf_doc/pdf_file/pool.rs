@@ -0,0 +1,84 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::*;
+
+/// Integers further from zero than this aren't pooled, since they rarely repeat and would only grow the pool.
+const MAX_POOLED_INT: i64 = 65535;
+
+/// Shares one allocation among equal small values in a file's arrays and dictionaries: names, booleans, null
+/// and small integers.  A large document repeats the same few hundred of these millions of times, so without
+/// the pool, scanning it allocates each one separately.  Pooled values are immutable like every parsed object,
+/// so sharing them is invisible to readers, apart from Rc::ptr_eq.
+#[derive(Debug, Default)]
+pub struct ObjectPool {
+    values: RefCell<HashMap<PoolKey, SharedObject>>,
+    hits: Cell<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PoolKey {
+    Null,
+    Boolean(bool),
+    Int(i64),
+    Name(Rc<String>),
+}
+
+/// How much an ObjectPool has saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Distinct values in the pool
+    pub values: usize,
+    /// Values found in the pool instead of being allocated again
+    pub hits: usize,
+}
+
+impl ObjectPool {
+    /// The shared copy of object, if it's a kind that's pooled, or object in a new Rc if not.
+    pub fn share(&self, object: PdfObject) -> SharedObject {
+        let key = match &object {
+            PdfObject::Actual(PdfData::Null) => PoolKey::Null,
+            PdfObject::Actual(PdfData::Boolean(value)) => PoolKey::Boolean(*value),
+            PdfObject::Actual(PdfData::NumberInt(value)) if (-MAX_POOLED_INT..=MAX_POOLED_INT).contains(value) => {
+                PoolKey::Int(*value)
+            },
+            PdfObject::Actual(PdfData::Name(name)) => PoolKey::Name(Rc::clone(name)),
+            _ => return Rc::new(object)
+        };
+        let mut values = self.values.borrow_mut();
+        if let Some(shared) = values.get(&key) {
+            self.hits.set(self.hits.get() + 1);
+            return Rc::clone(shared);
+        };
+        let shared = Rc::new(object);
+        values.insert(key, Rc::clone(&shared));
+        shared
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats { values: self.values.borrow().len(), hits: self.hits.get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share() {
+        let pool = ObjectPool::default();
+        let first = pool.share(PdfObject::new_name("Type"));
+        assert!(Rc::ptr_eq(&first, &pool.share(PdfObject::new_name("Type"))));
+        assert!(!Rc::ptr_eq(&first, &pool.share(PdfObject::new_name("Page"))));
+        assert!(Rc::ptr_eq(&pool.share(PdfObject::new_number_int(0)), &pool.share(PdfObject::new_number_int(0))));
+        // Large integers and strings get their own allocations
+        let large = PdfObject::new_number_int(1_000_000);
+        assert!(!Rc::ptr_eq(&pool.share(large.clone()), &pool.share(large)));
+        let smallest = PdfObject::new_number_int(i64::MIN);
+        assert!(!Rc::ptr_eq(&pool.share(smallest.clone()), &pool.share(smallest)));
+        let text = PdfObject::new_char_string("text".to_string());
+        assert!(!Rc::ptr_eq(&pool.share(text.clone()), &pool.share(text)));
+        assert_eq!(pool.stats(), PoolStats { values: 3, hits: 2 });
+    }
+}
@@ -0,0 +1,204 @@
+//! Property tests for the object tokenizer: random object trees are written out as PDF syntax, with randomly
+//! chosen spacing, comments and string escapes, parsed back with parse_object_at, and then round-tripped through
+//! the writer and parsed again.
+
+use std::collections::BTreeMap;
+
+use proptest::prelude::*;
+
+use super::*;
+
+/// The object a piece of generated syntax should parse to.
+#[derive(Debug, Clone)]
+enum Model {
+    Int(i32),
+    /// A real number, with the text it's written as
+    Real(f32, String),
+    Name(String),
+    Literal(Vec<u8>),
+    Hex(Vec<u8>),
+    Bool(bool),
+    Null,
+    Array(Vec<Model>),
+    Dict(BTreeMap<String, Model>),
+}
+
+/// Rendering decisions, read from a fixed byte sequence so that proptest can generate and shrink them.
+struct Choices {
+    bytes: Vec<u8>,
+    position: usize,
+}
+
+impl Choices {
+    /// A choice in 0..n.
+    fn next(&mut self, n: usize) -> usize {
+        if self.bytes.is_empty() {
+            return 0;
+        };
+        let byte = self.bytes[self.position % self.bytes.len()];
+        self.position += 1;
+        byte as usize % n
+    }
+}
+
+impl Model {
+    /// Whether the syntax for this object starts and ends with a delimiter, so it needs no surrounding space.
+    fn self_delimiting(&self) -> bool {
+        matches!(self, Model::Literal(_) | Model::Hex(_) | Model::Array(_) | Model::Dict(_))
+    }
+
+    fn render(&self, output: &mut Vec<u8>, choices: &mut Choices) {
+        match self {
+            Model::Int(n) => output.extend_from_slice(n.to_string().as_bytes()),
+            Model::Real(_, text) => output.extend_from_slice(text.as_bytes()),
+            Model::Name(name) => {
+                output.push(b'/');
+                output.extend_from_slice(name.as_bytes());
+            },
+            Model::Literal(bytes) => {
+                output.push(b'(');
+                for &c in bytes {
+                    render_literal_byte(output, c, choices);
+                }
+                output.push(b')');
+            },
+            Model::Hex(bytes) => {
+                output.push(b'<');
+                for c in bytes {
+                    let digits = if choices.next(2) == 0 { format!("{:02X}", c) } else { format!("{:02x}", c) };
+                    output.extend_from_slice(digits.as_bytes());
+                    if choices.next(4) == 0 {
+                        output.extend_from_slice(b" \n");
+                    };
+                }
+                output.push(b'>');
+            },
+            Model::Bool(b) => output.extend_from_slice(b.to_string().as_bytes()),
+            Model::Null => output.extend_from_slice(b"null"),
+            Model::Array(items) => {
+                output.push(b'[');
+                let mut previous: Option<&Model> = None;
+                for item in items {
+                    if let Some(previous) = previous {
+                        separate(output, previous.self_delimiting() || item.self_delimiting(), choices);
+                    };
+                    item.render(output, choices);
+                    previous = Some(item);
+                }
+                output.push(b']');
+            },
+            Model::Dict(entries) => {
+                output.extend_from_slice(b"<<");
+                for (key, value) in entries {
+                    separate(output, true, choices);
+                    output.push(b'/');
+                    output.extend_from_slice(key.as_bytes());
+                    // A name followed by a name, number or keyword needs space between them
+                    let needs_space = matches!(value, Model::Int(_) | Model::Real(..) | Model::Bool(_) | Model::Null);
+                    separate(output, !needs_space, choices);
+                    value.render(output, choices);
+                }
+                separate(output, true, choices);
+                output.extend_from_slice(b">>");
+            },
+        }
+    }
+
+    fn matches(&self, object: &PdfObject) -> bool {
+        match self {
+            Model::Int(n) => object.is_int() && object.try_into_int().unwrap() == *n,
+            // The writer writes whole reals like integers, so either kind of number is accepted
+            Model::Real(value, _) => object.is_number() && (object.try_into_number().unwrap() - value).abs() < 1e-3,
+            Model::Name(name) => object.is_name() && object.try_into_string().unwrap().as_str() == name,
+            Model::Literal(bytes) | Model::Hex(bytes) => object.try_into_bytes().is_ok_and(|b| &b == bytes),
+            Model::Bool(b) => object.try_into_bool().is_ok_and(|parsed| parsed == *b),
+            Model::Null => matches!(object, PdfObject::Actual(Null)),
+            Model::Array(items) => object.try_into_array().is_ok_and(|array| {
+                array.len() == items.len() && items.iter().zip(array.iter()).all(|(item, parsed)| item.matches(parsed))
+            }),
+            Model::Dict(entries) => object.try_into_map().is_ok_and(|map| {
+                map.len() == entries.len()
+                    && entries.iter().all(|(key, value)| map.get(key).is_some_and(|parsed| value.matches(parsed)))
+            }),
+        }
+    }
+}
+
+/// One byte of a literal string: as is, as an octal escape, or with a backslash the parser should ignore.
+/// Parentheses and backslashes are always escaped, so the string's parentheses never need to balance.
+fn render_literal_byte(output: &mut Vec<u8>, c: u8, choices: &mut Choices) {
+    let plain_escape_ok = c.is_ascii_alphabetic() && !b"nrtbf".contains(&c);
+    match choices.next(3) {
+        _ if c == b'(' || c == b')' || c == b'\\' => {
+            if choices.next(2) == 0 {
+                output.push(b'\\');
+                output.push(c);
+            } else {
+                output.extend_from_slice(format!("\\{:03o}", c).as_bytes());
+            };
+        },
+        0 => output.push(c),
+        1 => output.extend_from_slice(format!("\\{:03o}", c).as_bytes()),
+        _ if plain_escape_ok => {
+            output.push(b'\\');
+            output.push(c);
+        },
+        _ => output.push(c),
+    }
+}
+
+/// Whitespace or a comment between tokens, which may be left out when delimiters already separate them.
+fn separate(output: &mut Vec<u8>, optional: bool, choices: &mut Choices) {
+    let options: [&[u8]; 7] = [b" ", b"\n", b"\r\n", b"\t ", b"\x0C", b" % a comment with ) and >>\n", b""];
+    let count = if optional { options.len() } else { options.len() - 1 };
+    output.extend_from_slice(options[choices.next(count)]);
+}
+
+fn name() -> impl Strategy<Value = String> {
+    "[A-Za-z][A-Za-z0-9_.-]{0,7}"
+}
+
+fn model() -> impl Strategy<Value = Model> {
+    let leaf = prop_oneof![
+        any::<i32>().prop_map(Model::Int),
+        (-99999i32..99999, 0u32..1000).prop_map(|(whole, fraction)| {
+            let text = format!("{}.{:03}", whole, fraction);
+            Model::Real(text.parse().unwrap(), text)
+        }),
+        name().prop_map(Model::Name),
+        // Printable ASCII and tabs; strings are stored as text, so other bytes don't survive parsing
+        prop::collection::vec(prop_oneof![0x20u8..0x7F, Just(b'\t')], 0..24).prop_map(Model::Literal),
+        prop::collection::vec(any::<u8>(), 0..12).prop_map(Model::Hex),
+        any::<bool>().prop_map(Model::Bool),
+        Just(Model::Null),
+    ];
+    leaf.prop_recursive(4, 64, 6, |inner| prop_oneof![
+        prop::collection::vec(inner.clone(), 0..6).prop_map(Model::Array),
+        prop::collection::btree_map(name(), inner, 0..6).prop_map(Model::Dict),
+    ])
+}
+
+fn parse_indirect(body: &[u8]) -> Result<PdfObject> {
+    let mut data = b"1 0 obj\n".to_vec();
+    data.extend_from_slice(body);
+    data.extend_from_slice(b"\nendobj\n");
+    parse_object_at(&data, 0, &Weak::new(), &ParserOptions::default()).map(|(object, _)| object)
+}
+
+proptest! {
+    #[test]
+    fn parsed_objects_match_their_syntax(model in model(), choices in prop::collection::vec(any::<u8>(), 1..64)) {
+        let mut syntax = Vec::new();
+        model.render(&mut syntax, &mut Choices { bytes: choices, position: 0 });
+        let parsed = parse_indirect(&syntax);
+        prop_assert!(parsed.as_ref().is_ok_and(|parsed| model.matches(parsed)),
+                     "{} parsed as {:?}", String::from_utf8_lossy(&syntax), parsed);
+
+        let mut written = Vec::new();
+        write_object(&mut written, &parsed.unwrap(), &None, &WriteOptions::default()).unwrap();
+        let reparsed = parse_indirect(&written);
+        prop_assert!(reparsed.as_ref().is_ok_and(|reparsed| model.matches(reparsed)),
+                     "{} written as {} and reparsed as {:?}", String::from_utf8_lossy(&syntax),
+                     String::from_utf8_lossy(&written), reparsed);
+    }
+}
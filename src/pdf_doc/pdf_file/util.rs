@@ -1,3 +1,5 @@
+use std::str;
+
 pub fn peek_ahead_by_n(bytes: &Vec<u8>, index: usize, n: usize) -> Option<u8> {
     if index + n >= bytes.len() {
         return None;
@@ -21,7 +23,7 @@ pub fn is_delimiter(c: u8) -> bool {
 }
 
 pub fn is_hex(c: u8) -> bool {
-    (b'0' <= c && c <= b'9') || (b'A' <= c && c <= b'F')
+    c.is_ascii_hexdigit()
 }
 
 pub fn is_eol(c: u8) -> bool {
@@ -54,6 +56,56 @@ pub fn is_xref_table_keyword_letter(c: u8) -> bool {
     }
 }
 
+/// Read the escape sequence in a literal string whose backslash is at data[index] (spec 7.3.4.2, table 3), giving
+/// the byte it stands for and the index just after it.  A backslash before an end-of-line marker continues the
+/// string on the next line and stands for nothing, as does one at the end of the data.  One to three octal digits
+/// give a byte, with any high-order overflow ignored, and a backslash before any other character is ignored.
+pub fn read_string_escape(data: &[u8], index: usize) -> (Option<u8>, usize) {
+    let escaped = match data.get(index + 1) {
+        Some(&escaped) => escaped,
+        None => return (None, index + 1),
+    };
+    let after = index + 2;
+    let byte = match escaped {
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'b' => 8,
+        b'f' => 12,
+        b'\r' if data.get(after) == Some(&b'\n') => return (None, after + 1),
+        b'\r' | b'\n' => return (None, after),
+        b'0'..=b'7' => {
+            let digits = data[index + 1..].iter().take(3).take_while(|&&c| is_octal(c)).count();
+            let code = data[index + 1..index + 1 + digits].iter().fold(0u32, |code, &d| code * 8 + (d - b'0') as u32);
+            return (Some(code as u8), index + 1 + digits);
+        }
+        // Including \( \) and \\
+        _ => escaped,
+    };
+    (Some(byte), after)
+}
+
+/// The bytes of a name with its #xx escapes replaced by the bytes they stand for (spec 7.3.5).  A # not
+/// followed by two hex digits is kept as it is.
+pub fn decode_name(data: &[u8]) -> Vec<u8> {
+    let mut name = Vec::with_capacity(data.len());
+    let mut index = 0;
+    while index < data.len() {
+        match data.get(index..index + 3) {
+            Some([b'#', high, low]) if is_hex(*high) && is_hex(*low) => {
+                let digits = str::from_utf8(&data[index + 1..index + 3]).unwrap();
+                name.push(u8::from_str_radix(digits, 16).unwrap());
+                index += 3;
+            },
+            _ => {
+                name.push(data[index]);
+                index += 1;
+            }
+        }
+    }
+    name
+}
+
 /// Is c a valid character for ASCII85Decode Filter described in spec 7.4.3
 pub fn is_valid_ascii_85_byte(c: u8) -> bool {
     match c {
@@ -67,6 +119,45 @@ pub fn is_valid_ascii_85_byte(c: u8) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_string_escapes() {
+        let cases: &[(&[u8], Option<u8>, usize)] = &[
+            (b"\\n", Some(b'\n'), 2),
+            (b"\\r", Some(b'\r'), 2),
+            (b"\\t", Some(b'\t'), 2),
+            (b"\\b", Some(8), 2),
+            (b"\\f", Some(12), 2),
+            (b"\\(", Some(b'('), 2),
+            (b"\\)", Some(b')'), 2),
+            (b"\\\\", Some(b'\\'), 2),
+            (b"\\q", Some(b'q'), 2),
+            (b"\\0", Some(0), 2),
+            (b"\\53", Some(b'+'), 3),
+            (b"\\053", Some(b'+'), 4),
+            (b"\\0053", Some(5), 4),
+            (b"\\377", Some(255), 4),
+            (b"\\400", Some(0), 4),
+            (b"\\8", Some(b'8'), 2),
+            (b"\\\r\nx", None, 3),
+            (b"\\\rx", None, 2),
+            (b"\\\nx", None, 2),
+            (b"\\\n\rx", None, 2),
+            (b"\\", None, 1),
+        ];
+        for (data, byte, end) in cases {
+            assert_eq!(read_string_escape(data, 0), (*byte, *end), "{:?}", String::from_utf8_lossy(data));
+        }
+    }
+
+    #[test]
+    fn test_name_escapes() {
+        assert_eq!(decode_name(b"Times#20New#2dRoman"), b"Times New-Roman");
+        assert_eq!(decode_name(b"L#61unch"), b"Launch");
+        assert_eq!(decode_name(b"A#23#"), b"A##");
+        assert_eq!(decode_name(b"A#2"), b"A#2");
+        assert_eq!(decode_name(b"A#zz"), b"A#zz");
+    }
+
     #[test]
     fn test_body_keyword_letters() {
         let keywords = "stream_endstream_obj_endobj_null_true_false";
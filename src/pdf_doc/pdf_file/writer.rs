@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+use super::decode::flate_encode;
+use crate::errors::*;
+
+const BINARY_MARKER: &[u8] = b"%\xE2\xE3\xCF\xD3\n";  // Spec 7.5.2 recommends a comment line of high bytes
+
+const OBJECTS_PER_OBJECT_STREAM: usize = 100;
+const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+/// This library's name and version, for WriteOptions::producer.
+pub const PRODUCER: &str = concat!("pdfparser ", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Drop objects that are not reachable from the trailer and renumber the remaining objects consecutively.
+    pub garbage_collect: bool,
+    /// Flate compression level (0-9) applied to decoded streams.  If None, decoded streams are written without
+    /// filters.
+    pub compression_level: Option<u32>,
+    /// Store non-stream objects in compressed object streams indexed by a cross-reference stream.  Ignored for
+    /// documents older than version 1.5.
+    pub object_streams: bool,
+    /// Copy the data of streams read from the file as it is there, with its original filters, instead of writing
+    /// it decoded or recompressing it.  Streams made in memory are written as usual.
+    pub pass_through_streams: bool,
+    /// Record the program that wrote the file, such as PRODUCER, as /Producer in the document information
+    /// dictionary, adding the dictionary if there is none.
+    pub producer: Option<String>,
+    /// Remove resources that pages declare but never use from their /Resources, and drop the fonts, images and
+    /// other objects left unreferenced, as garbage_collect does.  See PdfDoc::write.
+    pub remove_unused_resources: bool,
+    /// Write dictionary keys in sorted order rather than the order they were read in, so that output can be
+    /// compared without key order counting
+    pub sort_keys: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteReport {
+    pub bytes_written: usize,
+    pub objects_written: usize,
+    pub objects_dropped: usize,
+    /// Size of the serialized form of the dropped objects.
+    pub reclaimed_bytes: usize,
+    /// Page resource entries removed by WriteOptions::remove_unused_resources.
+    pub resources_removed: usize,
+    /// Problems found in the document written, such as page boxes that don't nest; see PdfDoc::write.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum XRefEntry {
+    InFile { offset: usize, gen: u32 },
+    Compressed { stream_number: u32, index: usize },
+}
+
+impl PdfFileHandler {
+    /// Serialize the document to a new file with a single cross-reference section.  The second part of the
+    /// trailer's /ID is regenerated to mark the new version of the file, and the first is kept if there is one.
+    pub fn write(&self, options: &WriteOptions) -> Result<(Vec<u8>, WriteReport)> {
+        self.write_with_replacements(options, &HashMap::new())
+    }
+
+    /// As write, with the objects in replacements written in place of those with the same ids.
+    pub(crate) fn write_with_replacements(&self, options: &WriteOptions,
+                                          replacements: &HashMap<ObjectId, SharedObject>)
+                                          -> Result<(Vec<u8>, WriteReport)> {
+        let get_object = |id: ObjectId| match replacements.get(&id) {
+            Some(obj) => Ok(Rc::clone(obj)),
+            None => self.get_object(id)
+        };
+        let all_ids = self.object_map.object_ids();
+        // An unused id in the file's numbering, for an information dictionary added for the producer
+        let added_info_id = ObjectId::new(all_ids.last().map_or(1, |id| id.number() + 1), 0);
+        let mut report = WriteReport::default();
+        let renumbering = if options.garbage_collect {
+            let mut reachable = Vec::new();
+            self.walk_with_replacements(replacements, |id, _obj| reachable.push(id))?;
+            reachable.sort();
+            Some(reachable.into_iter()
+                          .enumerate()
+                          .map(|(i, id)| (id, ObjectId::new(i as u32 + 1, 0)))
+                          .collect::<HashMap<ObjectId, ObjectId>>())
+        } else { None };
+        let mut trailer = self.retrieve_trailer()?.try_into_map()?.as_ref().clone();
+        let info_id = trailer.get("Info").and_then(|info| info.reference_id());
+        let edited_info = match &options.producer {
+            Some(producer) => {
+                let mut info = match trailer.get("Info") {
+                    Some(info) => info.try_into_map()?.as_ref().clone(),
+                    None => PdfMap::new()
+                };
+                info.insert("Producer".to_string(), Rc::new(PdfObject::new_text_string(producer)));
+                Some(Rc::new(PdfObject::new_dictionary(Rc::new(info))))
+            },
+            None => None
+        };
+        let use_object_streams = options.object_streams && self.version >= PDFVersion::V1_5;
+        let encrypt_id = self.retrieve_trailer()?.try_to_get("Encrypt")?.and_then(|obj| obj.reference_id());
+
+        let mut output = Vec::new();
+        writeln!(output, "%PDF-{}", self.version)?;
+        output.extend_from_slice(BINARY_MARKER);
+        let mut entries = HashMap::new();
+        let mut compressible = Vec::new();
+        for id in all_ids {
+            let new_id = match &renumbering {
+                None => id,
+                Some(map) => match map.get(&id) {
+                    Some(new_id) => *new_id,
+                    None => {
+                        let mut dropped = Vec::new();
+                        write_indirect_object(&mut dropped, id, get_object(id)?.as_ref(), &renumbering, options)?;
+                        report.objects_dropped += 1;
+                        report.reclaimed_bytes += dropped.len();
+                        continue;
+                    }
+                }
+            };
+            let obj = match &edited_info {
+                Some(info) if Some(id) == info_id => Rc::clone(info),
+                _ => get_object(id)?
+            };
+            report.objects_written += 1;
+            // Spec 7.5.7: streams, objects with non-zero generations and the encryption dictionary can't be compressed
+            if use_object_streams && !obj.is_stream() && new_id.generation() == 0 && Some(id) != encrypt_id {
+                compressible.push((new_id, obj));
+                continue;
+            };
+            if new_id.generation() > MAX_GENERATION {
+                Err(ErrorKind::ParsingError(format!("Generation number of object {} is over {}", new_id.number(),
+                                                    MAX_GENERATION)))?
+            };
+            entries.insert(new_id.number(), XRefEntry::InFile { offset: output.len(), gen: new_id.generation() });
+            write_indirect_object(&mut output, new_id, obj.as_ref(), &renumbering, options)?;
+        }
+
+        let mut next_number = entries.keys().cloned().chain(compressible.iter().map(|(id, _)| id.number())).max().map_or(1, |n| n + 1);
+        // A missing or direct information dictionary is written as a new object
+        let mut renumbering = renumbering;
+        if let (Some(info), None) = (&edited_info, info_id) {
+            let new_id = match &mut renumbering {
+                Some(map) => {
+                    map.insert(added_info_id, ObjectId::new(next_number, 0));
+                    ObjectId::new(next_number, 0)
+                },
+                None => added_info_id
+            };
+            next_number = new_id.number() + 1;
+            entries.insert(new_id.number(), XRefEntry::InFile { offset: output.len(), gen: 0 });
+            write_indirect_object(&mut output, new_id, info, &renumbering, options)?;
+            report.objects_written += 1;
+            trailer.insert("Info".to_string(), Rc::new(PdfObject::new_reference(added_info_id.number(), 0u32,
+                                                                                Weak::new())));
+        };
+        let level = options.compression_level.unwrap_or(DEFAULT_COMPRESSION_LEVEL);
+        for chunk in compressible.chunks(OBJECTS_PER_OBJECT_STREAM) {
+            let stream_number = next_number;
+            next_number += 1;
+            let mut header = Vec::new();
+            let mut body = Vec::new();
+            for (index, (id, obj)) in chunk.iter().enumerate() {
+                write!(header, "{} {} ", id.number(), body.len())?;
+                write_object(&mut body, obj, &renumbering, options)?;
+                body.push(b'\n');
+                entries.insert(id.number(), XRefEntry::Compressed { stream_number, index });
+            }
+            let mut dict = PdfMap::new();
+            dict.insert("Type".to_string(), Rc::new(PdfObject::new_name("ObjStm")));
+            dict.insert("N".to_string(), Rc::new(PdfObject::new_number_int(chunk.len() as i32)));
+            dict.insert("First".to_string(), Rc::new(PdfObject::new_number_int(header.len() as i32)));
+            header.extend(body);
+            entries.insert(stream_number, XRefEntry::InFile { offset: output.len(), gen: 0 });
+            writeln!(output, "{} 0 obj", stream_number)?;
+            write_compressed_stream(&mut output, dict, &header, level, &renumbering, options)?;
+            output.extend_from_slice(b"\nendobj\n");
+        }
+
+        trailer.remove("Prev");
+        trailer.remove("XRefStm");
+        let info = match &edited_info {
+            Some(info) => Some(info.try_into_map()?),
+            // A damaged information dictionary only leaves it out of the /ID
+            None => trailer.get("Info").and_then(|info| info.try_into_map().ok())
+        };
+        let id = file_id(trailer.get("ID"), output.len(), info.as_deref())?;
+        trailer.insert("ID".to_string(), Rc::new(id));
+        let xref_index = output.len();
+        if use_object_streams {
+            let xref_number = next_number;
+            entries.insert(xref_number, XRefEntry::InFile { offset: xref_index, gen: 0 });
+            let size = xref_number + 1;
+            let offset_width = byte_width(xref_index.max(next_number as usize));
+            let index_width = byte_width(OBJECTS_PER_OBJECT_STREAM.max(65535));
+            let mut rows = Vec::new();
+            for number in 0..size {
+                let (entry_type, field_2, field_3) = match entries.get(&number) {
+                    None => (0, 0, if number == 0 { 65535 } else { 0 }),
+                    Some(XRefEntry::InFile { offset, gen }) => (1, *offset, *gen as usize),
+                    Some(XRefEntry::Compressed { stream_number, index }) => (2, *stream_number as usize, *index),
+                };
+                rows.push(entry_type);
+                rows.extend_from_slice(&(field_2 as u64).to_be_bytes()[(8 - offset_width)..]);
+                rows.extend_from_slice(&(field_3 as u64).to_be_bytes()[(8 - index_width)..]);
+            }
+            trailer.insert("Type".to_string(), Rc::new(PdfObject::new_name("XRef")));
+            trailer.insert("Size".to_string(), Rc::new(PdfObject::new_number_int(size as i32)));
+            trailer.insert("W".to_string(), Rc::new(PdfObject::new_array(Rc::new(
+                [1, offset_width, index_width].iter()
+                                              .map(|w| Rc::new(PdfObject::new_number_int(*w as i32)))
+                                              .collect()))));
+            writeln!(output, "{} 0 obj", xref_number)?;
+            write_compressed_stream(&mut output, trailer, &rows, level, &renumbering, options)?;
+            output.extend_from_slice(b"\nendobj\n");
+        } else {
+            let size = entries.keys().max().map_or(1, |n| n + 1);
+            writeln!(output, "xref\n0 {}", size)?;
+            for number in 0..size {
+                match entries.get(&number) {
+                    Some(XRefEntry::InFile { offset, gen }) => writeln!(output, "{:010} {:05} n ", offset, gen)?,
+                    _ => writeln!(output, "{:010} {:05} f ", 0, 65535)?
+                };
+            }
+            trailer.insert("Size".to_string(), Rc::new(PdfObject::new_number_int(size as i32)));
+            output.extend_from_slice(b"trailer\n");
+            write_object(&mut output, &PdfObject::new_dictionary(Rc::new(trailer)), &renumbering, options)?;
+            output.push(b'\n');
+        };
+        write!(output, "startxref\n{}\n%%EOF\n", xref_index)?;
+        report.bytes_written = output.len();
+        Ok((output, report))
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P, options: &WriteOptions) -> Result<WriteReport> {
+        let (bytes, report) = self.write(options)?;
+        fs::write(path, bytes)?;
+        Ok(report)
+    }
+}
+
+/// A trailer /ID array (spec 14.4): the first part from the existing /ID, if there is one, and the second an MD5
+/// hash of the current time, the size of the file so far and the information dictionary.
+fn file_id(existing: Option<&SharedObject>, size: usize, info: Option<&PdfMap>) -> Result<PdfObject> {
+    let mut seed = Vec::new();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        write!(seed, "{}", now.as_nanos())?;
+    };
+    write!(seed, "{}", size)?;
+    if let Some(info) = info {
+        write_dictionary(&mut seed, info, &None, &WriteOptions::default())?;
+    };
+    let digest = Rc::new(PdfObject::new_hex_string(format!("{:x}", md5::compute(&seed)).into_bytes()));
+    let first = match existing.map(|id| id.try_into_array()) {
+        Some(Ok(parts)) if !parts.is_empty() && parts[0].try_into_bytes().is_ok() => Rc::clone(&parts[0]),
+        _ => Rc::clone(&digest)
+    };
+    Ok(PdfObject::new_array(Rc::new(vec![first, digest])))
+}
+
+/// Number of bytes needed to store n in big-endian form.
+fn byte_width(n: usize) -> usize {
+    let mut width = 1;
+    while width < 8 && (n >> (8 * width)) > 0 {
+        width += 1;
+    }
+    width
+}
+
+fn write_indirect_object(output: &mut Vec<u8>, id: ObjectId, obj: &PdfObject,
+                         renumbering: &Option<HashMap<ObjectId, ObjectId>>, options: &WriteOptions) -> Result<()> {
+    writeln!(output, "{} {} obj", id.number(), id.generation())?;
+    write_object(output, obj, renumbering, options)?;
+    output.extend_from_slice(b"\nendobj\n");
+    Ok(())
+}
+
+/// Write obj in PDF syntax.  If a renumbering is provided, references are rewritten to the new ids and
+/// references to objects outside the renumbering are written as null.
+pub fn write_object(output: &mut Vec<u8>, obj: &PdfObject,
+                    renumbering: &Option<HashMap<ObjectId, ObjectId>>, options: &WriteOptions) -> Result<()> {
+    if let Some(id) = obj.reference_id() {
+        match renumbering {
+            None => write!(output, "{} {} R", id.number(), id.generation())?,
+            Some(map) => match map.get(&id) {
+                Some(new_id) => write!(output, "{} {} R", new_id.number(), new_id.generation())?,
+                None => output.extend_from_slice(b"null")
+            }
+        };
+        return Ok(());
+    };
+    let data = match obj {
+        PdfObject::Actual(data) => data,
+        _ => unreachable!("references handled above")
+    };
+    match data {
+        Boolean(b) => write!(output, "{}", b)?,
+        NumberInt(n) => write!(output, "{}", n)?,
+        NumberFloat(n) => write!(output, "{}", n)?,
+        Name(s) => write_name(output, s),
+        CharString(s) => write_char_string(output, s.as_bytes()),
+        HexString(digits) => {
+            output.push(b'<');
+            output.extend_from_slice(digits);
+            output.push(b'>');
+        },
+        Array(arr) => {
+            output.push(b'[');
+            for (i, item) in arr.iter().enumerate() {
+                if i > 0 { output.push(b' ') };
+                write_object(output, item, renumbering, options)?;
+            }
+            output.push(b']');
+        },
+        Dictionary(map) => write_dictionary(output, map, renumbering, options)?,
+        BinaryStream(stream) if options.pass_through_streams && stream.raw_span().is_some() => {
+            write_stream(output, stream.attributes(), &stream.raw_data()?, false, renumbering, options)?
+        },
+        BinaryStream(stream) => {
+            write_stream(output, stream.attributes(), stream.data()?, stream.is_decoded(), renumbering, options)?
+        },
+        ContentStream(stream) if options.pass_through_streams => {
+            write_stream(output, stream.attributes(), &stream.raw_data()?, false, renumbering, options)?
+        },
+        ContentStream(stream) => {
+            write_stream(output, stream.attributes(), stream.data(), true, renumbering, options)?
+        },
+        Comment(s) => writeln!(output, "%{}", s)?,
+        Null => output.extend_from_slice(b"null"),
+    };
+    Ok(())
+}
+
+fn write_dictionary(output: &mut Vec<u8>, map: &PdfMap,
+                    renumbering: &Option<HashMap<ObjectId, ObjectId>>, options: &WriteOptions) -> Result<()> {
+    output.extend_from_slice(b"<<");
+    let mut entries: Vec<_> = map.iter().collect();
+    if options.sort_keys {
+        entries.sort_by_key(|(key, _)| *key);
+    };
+    for (i, (key, value)) in entries.into_iter().enumerate() {
+        if i > 0 { output.push(b' ') };
+        write_name(output, key);
+        output.push(b' ');
+        write_object(output, value, renumbering, options)?;
+    }
+    output.extend_from_slice(b">>");
+    Ok(())
+}
+
+/// Write a stream with its dictionary.  Decoded data is written unfiltered, so the filter entries are dropped,
+/// unless a compression level is set, in which case it is recompressed with Flate.  Streams that were not
+/// decoded are copied through with their filters.
+fn write_stream(output: &mut Vec<u8>, attributes: &PdfMap, data: &[u8], decoded: bool,
+                renumbering: &Option<HashMap<ObjectId, ObjectId>>, options: &WriteOptions) -> Result<()> {
+    let mut attributes = attributes.clone();
+    if decoded {
+        attributes.remove("Filter");
+        attributes.remove("DecodeParms");
+        if let Some(level) = options.compression_level {
+            return write_compressed_stream(output, attributes, data, level, renumbering, options);
+        };
+    };
+    attributes.insert("Length".to_string(), Rc::new(PdfObject::new_number_int(data.len() as i64)));
+    write_dictionary(output, &attributes, renumbering, options)?;
+    output.extend_from_slice(b"\nstream\n");
+    output.extend_from_slice(data);
+    output.extend_from_slice(b"\nendstream");
+    Ok(())
+}
+
+fn write_compressed_stream(output: &mut Vec<u8>, mut attributes: PdfMap, data: &[u8], level: u32,
+                           renumbering: &Option<HashMap<ObjectId, ObjectId>>, options: &WriteOptions) -> Result<()> {
+    let encoded = flate_encode(data, level)?;
+    attributes.insert("Filter".to_string(), Rc::new(PdfObject::new_name("FlateDecode")));
+    attributes.insert("Length".to_string(), Rc::new(PdfObject::new_number_int(encoded.len() as i64)));
+    write_dictionary(output, &attributes, renumbering, options)?;
+    output.extend_from_slice(b"\nstream\n");
+    output.extend_from_slice(&encoded);
+    output.extend_from_slice(b"\nendstream");
+    Ok(())
+}
+
+/// Names escape delimiters, whitespace, '#' and non-printing bytes as #xx (spec 7.3.5).
+fn write_name(output: &mut Vec<u8>, name: &str) {
+    output.push(b'/');
+    for &c in name.as_bytes() {
+        if c == b'#' || !(b'!'..=b'~').contains(&c) || is_delimiter(c) {
+            output.extend_from_slice(format!("#{:02X}", c).as_bytes());
+        } else {
+            output.push(c);
+        }
+    }
+}
+
+fn write_char_string(output: &mut Vec<u8>, s: &[u8]) {
+    output.push(b'(');
+    for &c in s {
+        // A carriage return written as is would be read back as a line feed (spec 7.3.4.2)
+        if c == b'\r' {
+            output.extend_from_slice(b"\\r");
+            continue;
+        };
+        if c == b'(' || c == b')' || c == b'\\' {
+            output.push(b'\\');
+        };
+        output.push(c);
+    }
+    output.push(b')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_round_trip() {
+        let pdf = PdfFileHandler::create_pdf_from_file("data/simple_pdf.pdf").unwrap();
+        let (bytes, report) = pdf.write(&WriteOptions::default()).unwrap();
+        assert_eq!(report.objects_written, 3);
+        assert_eq!(report.objects_dropped, 0);
+        assert_eq!(report.bytes_written, bytes.len());
+        let reparsed = PdfFileHandler::create_pdf_from_bytes(bytes).unwrap();
+        assert_eq!(reparsed.version, PDFVersion::V1_7);
+        let page = reparsed.get_dict(ObjectId::new(3, 0)).unwrap();
+        assert_eq!(page.get("MediaBox").unwrap().try_into_array().unwrap().len(), 4);
+        assert_eq!(reparsed.get_dict(ObjectId::new(2, 0)).unwrap().get("Count").unwrap().try_into_int().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let bytes = pdf_from_objects(
+            &["<< /Type /Catalog /Pages 3 0 R >>",
+              "(orphaned by an editor)",
+              "<< /Type /Pages /Kids [4 0 R] /Count 1 >>",
+              "<< /Type /Page /Parent 3 0 R /MediaBox [0 0 612 792] >>"],
+            "<< /Root 1 0 R /Size 5 >>");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(bytes.clone()).unwrap();
+        let options = WriteOptions { garbage_collect: true, ..Default::default() };
+        let (output, report) = pdf.write(&options).unwrap();
+        assert_eq!(report.objects_written, 3);
+        assert_eq!(report.objects_dropped, 1);
+        assert_eq!(report.reclaimed_bytes, "2 0 obj\n(orphaned by an editor)\nendobj\n".len());
+        // Dictionaries keep their keys in file order
+        assert!(find_bytes(&output, b"<</Type /Page /Parent 2 0 R /MediaBox [0 0 612 792]>>", 0).is_some());
+
+        let reparsed = PdfFileHandler::create_pdf_from_bytes(output).unwrap();
+        assert_eq!(reparsed.object_map.object_ids(),
+                   vec![ObjectId::new(1, 0), ObjectId::new(2, 0), ObjectId::new(3, 0)]);
+        let pages = reparsed.get_dict(ObjectId::new(2, 0)).unwrap();
+        assert_eq!(*pages.get("Type").unwrap().try_into_string().unwrap(), "Pages");
+        let kid = pages.get("Kids").unwrap().try_to_index(0).unwrap();
+        assert_eq!(kid.reference_id(), Some(ObjectId::new(3, 0)));
+        assert_eq!(reparsed.retrieve_trailer().unwrap().try_to_get("Size").unwrap().unwrap().try_into_int().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_file_id_and_producer() {
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>",
+                       "<< /Type /Pages /Kids [] /Count 0 >>",
+                       "(orphaned)"];
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_from_objects(&objects, "<< /Root 1 0 R >>")).unwrap();
+        let ids = |output: Vec<u8>| -> (Vec<Vec<u8>>, PdfFileHandler) {
+            let reparsed = PdfFileHandler::create_pdf_from_bytes(output).unwrap();
+            let id = reparsed.retrieve_trailer().unwrap().try_to_get("ID").unwrap().unwrap();
+            (id.try_into_array().unwrap().iter().map(|part| part.try_into_bytes().unwrap()).collect(), reparsed)
+        };
+        // Without an existing /ID, both parts are the fresh hash
+        let (id, reparsed) = ids(pdf.write(&WriteOptions::default()).unwrap().0);
+        assert_eq!(id.len(), 2);
+        assert_eq!(id[0].len(), 16);
+        assert_eq!(id[0], id[1]);
+        assert!(reparsed.retrieve_trailer().unwrap().try_to_get("Info").unwrap().is_none());
+
+        // An information dictionary is added for the producer, including when objects are renumbered
+        for garbage_collect in [false, true] {
+            let options = WriteOptions { producer: Some(PRODUCER.to_string()), garbage_collect, ..Default::default() };
+            let (output, report) = pdf.write(&options).unwrap();
+            assert_eq!(report.objects_written, if garbage_collect { 3 } else { 4 });
+            let (_, reparsed) = ids(output);
+            let info = reparsed.retrieve_trailer().unwrap().try_to_get("Info").unwrap().unwrap();
+            assert_eq!(info.reference_id(), Some(ObjectId::new(if garbage_collect { 3 } else { 4 }, 0)));
+            let producer = info.try_to_get("Producer").unwrap().unwrap().try_into_bytes().unwrap();
+            assert_eq!(String::from_utf8(producer).unwrap(), PRODUCER);
+        }
+
+        // An existing first part and information dictionary are kept
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>",
+                       "<< /Type /Pages /Kids [] /Count 0 >>",
+                       "<< /Title (Report) /Producer (Other) >>"];
+        let trailer = "<< /Root 1 0 R /Info 3 0 R /ID [<00112233445566778899AABBCCDDEEFF> <00>] >>";
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_from_objects(&objects, trailer)).unwrap();
+        let options = WriteOptions { producer: Some("Tests \u{2013} Z\u{FC}rich".to_string()), ..Default::default() };
+        let (id, reparsed) = ids(pdf.write(&options).unwrap().0);
+        assert_eq!(id[0], b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xAA\xBB\xCC\xDD\xEE\xFF");
+        assert_eq!(id[1].len(), 16);
+        assert_ne!(id[0], id[1]);
+        let info = reparsed.get_dict(ObjectId::new(3, 0)).unwrap();
+        assert_eq!(info.get("Title").unwrap().try_into_bytes().unwrap(), b"Report");
+        // Written as UTF-16, since it isn't ASCII
+        let producer = info.get("Producer").unwrap().try_into_bytes().unwrap();
+        assert_eq!(&producer[..6], b"\xFE\xFF\x00T\x00e");
+        assert_eq!(info.get("Producer").unwrap().try_into_text().unwrap(), "Tests \u{2013} Z\u{FC}rich");
+    }
+
+    fn pdf_with_content(content_filter: &str, content: &[u8]) -> Vec<u8> {
+        pdf_from_objects(
+            &[b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+              b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+              b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R >>".to_vec(),
+              stream_object(content, TestFilter::None, content_filter)],
+            "<< /Root 1 0 R /Size 5 >>")
+    }
+
+    #[test]
+    fn test_recompression() {
+        let content = b"0 0 m 100 100 l S\n".repeat(200);
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_with_content("", &content)).unwrap();
+        let (plain, plain_report) = pdf.write(&WriteOptions::default()).unwrap();
+        let options = WriteOptions { compression_level: Some(9), ..Default::default() };
+        let (compressed, compressed_report) = pdf.write(&options).unwrap();
+        assert!(compressed_report.bytes_written * 5 < plain_report.bytes_written);
+
+        let plain = PdfFileHandler::create_pdf_from_bytes(plain).unwrap();
+        let compressed = PdfFileHandler::create_pdf_from_bytes(compressed).unwrap();
+        assert_eq!(plain.get_stream_data(ObjectId::new(4, 0)).unwrap(), content);
+        assert_eq!(compressed.get_stream_data(ObjectId::new(4, 0)).unwrap(), content);
+        let filter = compressed.get_dict(ObjectId::new(4, 0)).unwrap().get("Filter").unwrap().try_into_string().unwrap();
+        assert_eq!(*filter, "FlateDecode");
+    }
+
+    #[test]
+    fn test_ascii_filters_converted_to_flate() {
+        let content = b"0 0 m 100 100 l S\n".repeat(20);
+        let hex: String = content.iter().map(|c| format!("{:02x}", c)).collect::<String>() + ">";
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_with_content("/Filter /ASCIIHexDecode", hex.as_bytes())).unwrap();
+        let options = WriteOptions { compression_level: Some(6), ..Default::default() };
+        let (output, report) = pdf.write(&options).unwrap();
+        assert!(report.bytes_written < pdf_with_content("/Filter /ASCIIHexDecode", hex.as_bytes()).len());
+        let reparsed = PdfFileHandler::create_pdf_from_bytes(output).unwrap();
+        assert_eq!(reparsed.get_stream_data(ObjectId::new(4, 0)).unwrap(), content);
+        let filter = reparsed.get_dict(ObjectId::new(4, 0)).unwrap().get("Filter").unwrap().try_into_string().unwrap();
+        assert_eq!(*filter, "FlateDecode");
+    }
+
+    #[test]
+    fn test_pass_through_streams() {
+        let content = b"0 0 m 100 100 l S\n".repeat(20);
+        let hex: String = content.iter().map(|c| format!("{:02x}", c)).collect::<String>() + ">";
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_with_content("/Filter /ASCIIHexDecode", hex.as_bytes())).unwrap();
+        let options = WriteOptions { pass_through_streams: true, compression_level: Some(6), ..Default::default() };
+        let (output, _) = pdf.write(&options).unwrap();
+        assert!(find_bytes(&output, hex.as_bytes(), 0).is_some());
+        let reparsed = PdfFileHandler::create_pdf_from_bytes(output).unwrap();
+        assert_eq!(reparsed.get_stream_data(ObjectId::new(4, 0)).unwrap(), content);
+        let filter = reparsed.get_dict(ObjectId::new(4, 0)).unwrap().get("Filter").unwrap().try_into_string().unwrap();
+        assert_eq!(*filter, "ASCIIHexDecode");
+    }
+
+    #[test]
+    fn test_object_streams() {
+        let mut source = pdf_with_content("", b"0 0 m 100 100 l S");
+        source[5..8].copy_from_slice(b"1.5");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(source).unwrap();
+        let options = WriteOptions { object_streams: true, ..Default::default() };
+        let (output, _) = pdf.write(&options).unwrap();
+        let text = String::from_utf8_lossy(&output);
+        // The content stream stays top-level; the other three objects go into object stream 5
+        assert!(text.contains("4 0 obj"));
+        assert!(!text.contains("1 0 obj"));
+        // Entries are written in the order they were added, after those from the original trailer
+        assert!(text.contains("5 0 obj\n<</Type /ObjStm /N 3 /First 14 /Filter /FlateDecode /Length "));
+        assert!(text.contains("6 0 obj\n<</Root 1 0 R /Size 7 /ID [<"));
+        assert!(text.contains("/Type /XRef /W [1 2 2] /Filter /FlateDecode /Length "));
+        assert!(!text.contains("trailer"));
+    }
+
+    #[test]
+    fn test_object_streams_size() {
+        let page_count = 200;
+        let kids: Vec<String> = (0..page_count).map(|i| format!("{} 0 R", i + 3)).collect();
+        let mut objects = vec!["<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                               format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), page_count)];
+        for _ in 0..page_count {
+            objects.push("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Resources << >> >>".to_string());
+        }
+        let objects: Vec<&str> = objects.iter().map(|s| s.as_str()).collect();
+        let mut source = pdf_from_objects(&objects, "<< /Root 1 0 R >>");
+        source[5..8].copy_from_slice(b"1.5");
+        let pdf = PdfFileHandler::create_pdf_from_bytes(source).unwrap();
+        let (_, plain_report) = pdf.write(&WriteOptions::default()).unwrap();
+        let options = WriteOptions { object_streams: true, ..Default::default() };
+        let (_, report) = pdf.write(&options).unwrap();
+        assert_eq!(report.objects_written, plain_report.objects_written);
+        assert!(report.bytes_written * 5 < plain_report.bytes_written);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn test_object_streams_need_1_5() {
+        let pdf = PdfFileHandler::create_pdf_from_file("data/simple_pdf.pdf").unwrap();
+        let mut old_pdf = PdfFileHandler::create_pdf_from_bytes(pdf_with_content("", b"S")).unwrap();
+        old_pdf.version = PDFVersion::V1_4;
+        let options = WriteOptions { object_streams: true, ..Default::default() };
+        assert!(String::from_utf8_lossy(&old_pdf.write(&options).unwrap().0).contains("trailer"));
+        assert!(!String::from_utf8_lossy(&pdf.write(&options).unwrap().0).contains("trailer"));
+    }
+
+    #[test]
+    fn test_byte_width() {
+        assert_eq!(byte_width(0), 1);
+        assert_eq!(byte_width(255), 1);
+        assert_eq!(byte_width(256), 2);
+        assert_eq!(byte_width(65535), 2);
+        assert_eq!(byte_width(65536), 3);
+    }
+
+    #[test]
+    fn test_write_name_escapes() {
+        let mut output = Vec::new();
+        write_name(&mut output, "A B#(");
+        assert_eq!(output, b"/A#20B#23#28".to_vec());
+
+        // Escaped names are decoded when parsed, so writing them back gives the same name
+        let objects = ["<< /Type /Catalog /Pages 2 0 R >>",
+                       "<< /Type /Pages /Kids [] /Count 0 /F#31 3 0 R >>",
+                       "<< /Type /Font /BaseFont /Times#20New#23Roman >>"];
+        let pdf = PdfFileHandler::create_pdf_from_bytes(pdf_from_objects(&objects, "<< /Root 1 0 R >>")).unwrap();
+        let (written, _) = pdf.write(&WriteOptions::default()).unwrap();
+        assert!(written.windows(22).any(|window| window == b"/Times#20New#23Roman>>"));
+        let pdf = PdfFileHandler::create_pdf_from_bytes(written).unwrap();
+        let font = pdf.get_dict(ObjectId::new(3, 0)).unwrap();
+        assert_eq!(font["BaseFont"].try_into_string().unwrap().as_str(), "Times New#Roman");
+        assert!(pdf.get_dict(ObjectId::new(2, 0)).unwrap().contains_key("F1"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use std::ops::Index;
+use std::rc::Rc;
+
+use super::PdfObject;
+
+/// Past this many entries, lookups go through a hash index instead of scanning the entries.
+const INDEX_THRESHOLD: usize = 16;
+
+/// The entries of a dictionary, in the order they were read or inserted.  Key order means nothing in a PDF
+/// (spec 7.3.7), but keeping the file's lets written files and diffs follow it.  Most dictionaries hold fewer
+/// than a dozen entries, so lookups scan the entries; the few large ones, like name trees written as a single
+/// dictionary, get a hash index.
+#[derive(Debug, Clone, Default)]
+pub struct PdfMap {
+    entries: Vec<(String, Rc<PdfObject>)>,
+    index: Option<HashMap<String, usize>>,
+}
+
+impl PdfMap {
+    pub fn new() -> Self {
+        PdfMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        match &self.index {
+            Some(index) => index.get(key).copied(),
+            None => self.entries.iter().position(|(entry_key, _)| entry_key == key)
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Rc<PdfObject>> {
+        self.position(key).map(|position| &self.entries[position].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Rc<PdfObject>> {
+        self.position(key).map(move |position| &mut self.entries[position].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Set the value for key, returning the old value.  A replaced entry keeps its place; a new one goes last.
+    pub fn insert(&mut self, key: String, value: Rc<PdfObject>) -> Option<Rc<PdfObject>> {
+        if let Some(position) = self.position(&key) {
+            return Some(std::mem::replace(&mut self.entries[position].1, value));
+        };
+        if let Some(index) = &mut self.index {
+            index.insert(key.clone(), self.entries.len());
+        };
+        self.entries.push((key, value));
+        if self.index.is_none() && self.entries.len() > INDEX_THRESHOLD {
+            self.rebuild_index();
+        };
+        None
+    }
+
+    /// Remove the entry for key, keeping the others in order.
+    pub fn remove(&mut self, key: &str) -> Option<Rc<PdfObject>> {
+        let position = self.position(key)?;
+        let (_, value) = self.entries.remove(position);
+        if self.index.is_some() {
+            self.rebuild_index();
+        };
+        Some(value)
+    }
+
+    /// Keep only the entries for which keep returns true.
+    pub fn retain<F: FnMut(&String, &mut Rc<PdfObject>) -> bool>(&mut self, mut keep: F) {
+        self.entries.retain_mut(|(key, value)| keep(key, value));
+        if self.index.is_some() {
+            self.rebuild_index();
+        };
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index = if self.entries.len() > INDEX_THRESHOLD {
+            Some(self.entries.iter().enumerate().map(|(position, (key, _))| (key.clone(), position)).collect())
+        } else {
+            None
+        };
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Rc<PdfObject>)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Rc<PdfObject>> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl Index<&str> for PdfMap {
+    type Output = Rc<PdfObject>;
+
+    fn index(&self, key: &str) -> &Rc<PdfObject> {
+        self.get(key).unwrap_or_else(|| panic!("No /{} entry in dictionary", key))
+    }
+}
+
+impl Extend<(String, Rc<PdfObject>)> for PdfMap {
+    fn extend<I: IntoIterator<Item = (String, Rc<PdfObject>)>>(&mut self, entries: I) {
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl FromIterator<(String, Rc<PdfObject>)> for PdfMap {
+    fn from_iter<I: IntoIterator<Item = (String, Rc<PdfObject>)>>(entries: I) -> Self {
+        let mut map = PdfMap::new();
+        map.extend(entries);
+        map
+    }
+}
+
+impl IntoIterator for PdfMap {
+    type Item = (String, Rc<PdfObject>);
+    type IntoIter = std::vec::IntoIter<(String, Rc<PdfObject>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a PdfMap {
+    type Item = (&'a String, &'a Rc<PdfObject>);
+    type IntoIter = Box<dyn Iterator<Item = (&'a String, &'a Rc<PdfObject>)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::PdfObjectInterface;
+
+    fn int(n: i64) -> Rc<PdfObject> {
+        Rc::new(PdfObject::new_number_int(n))
+    }
+
+    #[test]
+    fn test_order() {
+        let mut map: PdfMap = ["Type", "Kids", "Count"].iter().enumerate()
+                                                      .map(|(n, key)| (key.to_string(), int(n as i64)))
+                                                      .collect();
+        map.insert("Kids".to_string(), int(9));
+        map.insert("Parent".to_string(), int(3));
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["Type", "Kids", "Count", "Parent"]);
+        assert_eq!(map["Kids"].try_into_int().unwrap(), 9);
+        assert!(map.remove("Type").is_some() && map.remove("Type").is_none());
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["Kids", "Count", "Parent"]);
+    }
+
+    #[test]
+    fn test_large_map() {
+        let mut map: PdfMap = (0..100).map(|n| (format!("K{}", n), int(n))).collect();
+        assert!(map.index.is_some());
+        assert_eq!(map["K57"].try_into_int().unwrap(), 57);
+        map.retain(|key, _| key.ends_with('7'));
+        assert_eq!(map.len(), 10);
+        assert!(map.index.is_none());
+        assert_eq!(map.get("K97").unwrap().try_into_int().unwrap(), 97);
+        assert!(!map.contains_key("K57 ") && !map.contains_key("K50"));
+    }
+}
@@ -1,16 +1,19 @@
-use std::collections::HashMap;
-use std::convert::Into;
+use std::convert::{Into, TryFrom};
 use std::fmt::Debug;
 use std::rc::{Rc, Weak};
 
+mod pdf_map;
+mod pdf_string;
+
 use super::*;
 use crate::errors::*;
 use crate::doc_tree::pdf_file::decode::*;
 
+pub use pdf_map::PdfMap;
+pub use pdf_string::PdfString;
 pub use PdfData::*;
 
 pub type SharedObject = Rc<PdfObject>;
-pub type PdfMap = HashMap<String, Rc<PdfObject>>;
 
 pub type PdfArray = Vec<Rc<PdfObject>>;
 
@@ -67,6 +70,12 @@ pub trait PdfObjectInterface: Debug {
             format!("{:?}", &self),
         ))?
     }
+    fn try_into_binary_stream(&self) -> Result<Rc<PdfBinaryStream>> {
+        Err(ErrorKind::UnavailableType(
+            "binary stream".to_string(),
+            format!("{:?}", &self),
+        ))?
+    }
     fn is_map(&self) -> bool {
         false
     }
@@ -102,10 +111,10 @@ pub trait PdfObjectInterface: Debug {
 #[derive(Debug, Clone)]
 pub enum PdfData {
     Boolean(bool),
-    NumberInt(i32),
+    NumberInt(i64),
     NumberFloat(f32),
     Name(Rc<String>),
-    CharString(Rc<String>),
+    CharString(Rc<PdfString>),
     HexString(Rc<Vec<u8>>),
     Array(Rc<PdfArray>),
     Dictionary(Rc<PdfMap>),
@@ -126,7 +135,7 @@ impl PdfObject {
         PdfObject::Actual(Boolean(data))
     }
 
-    pub fn new_number_int<T: Into<i32>>(data: T) -> PdfObject {
+    pub fn new_number_int<T: Into<i64>>(data: T) -> PdfObject {
         PdfObject::Actual(NumberInt(data.into()))
     }
 
@@ -138,7 +147,7 @@ impl PdfObject {
         PdfObject::Actual(Name(Rc::new(data.into())))
     }
 
-    pub fn new_char_string<T: Into<String>>(data: T) -> PdfObject {
+    pub fn new_char_string<T: Into<PdfString>>(data: T) -> PdfObject {
         PdfObject::Actual(CharString(Rc::new(data.into())))
     }
 
@@ -146,6 +155,22 @@ impl PdfObject {
         PdfObject::Actual(HexString(Rc::new(data)))
     }
 
+    /// A text string (spec 7.9.2.2), such as a title: written as is if it's ASCII, and otherwise as UTF-16BE
+    /// with a byte order mark.
+    pub fn new_text_string(text: &str) -> PdfObject {
+        if text.is_ascii() {
+            return PdfObject::new_char_string(text);
+        };
+        let mut data = vec![0xFE, 0xFF];
+        data.extend(text.encode_utf16().flat_map(|unit| unit.to_be_bytes()));
+        PdfObject::new_byte_string(&data)
+    }
+
+    /// A string of arbitrary bytes, kept as a hex string so that they survive exactly.
+    pub fn new_byte_string(data: &[u8]) -> PdfObject {
+        PdfObject::new_hex_string(data.iter().map(|byte| format!("{:02X}", byte)).collect::<String>().into_bytes())
+    }
+
     pub fn new_array(data: Rc<PdfArray>) -> PdfObject {
         PdfObject::Actual(Array(data))
     }
@@ -174,6 +199,91 @@ impl PdfObject {
     }
 }
 
+impl PdfObject {
+    /// The id of the referenced object, if this is a reference.
+    pub fn reference_id(&self) -> Option<ObjectId> {
+        match self {
+            PdfObject::Reference(r) => Some(ObjectId::new(r.id, r.gen)),
+            PdfObject::Actual(_) => None
+        }
+    }
+
+    /// Ids of all indirect objects referenced directly by this object, including references nested in
+    /// arrays, dictionaries and stream dictionaries.  References are not followed.
+    pub fn references(&self) -> Vec<ObjectId> {
+        let mut ids = Vec::new();
+        self.collect_references(&mut ids);
+        ids
+    }
+
+    /// A byte offset or length: a non-negative integer, which unlike the values try_into_int returns may need
+    /// more than 31 bits in a file over 2GB.
+    pub fn try_into_offset(&self) -> Result<u64> {
+        match self {
+            PdfObject::Reference(ref link) => link.get()?.try_into_offset(),
+            PdfObject::Actual(NumberInt(int)) => Ok(u64::try_from(*int).map_err(|_| ErrorKind::ParsingError(
+                format!("Invalid offset {}", int)))?),
+            _ => Err(ErrorKind::UnavailableType("integer".to_string(), "try_into_offset".to_string()))?
+        }
+    }
+
+    /// The value of an integer or real number.
+    pub fn try_into_number(&self) -> Result<f32> {
+        if self.is_int() {
+            Ok(self.try_into_int()? as f32)
+        } else {
+            self.try_into_float()
+        }
+    }
+
+    /// The bytes of a literal or hex string.
+    pub fn try_into_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            PdfObject::Reference(ref link) => link.get()?.try_into_bytes(),
+            PdfObject::Actual(CharString(s)) => Ok(s.as_bytes().to_vec()),
+            PdfObject::Actual(HexString(digits)) => Ok(digits.chunks(2).map(|pair| {
+                // An odd final digit is treated as if followed by 0 (spec 7.3.4.3)
+                let high = (pair[0] as char).to_digit(16).unwrap_or(0);
+                let low = pair.get(1).and_then(|c| (*c as char).to_digit(16)).unwrap_or(0);
+                (high * 16 + low) as u8
+            }).collect()),
+            _ => Err(ErrorKind::UnavailableType("bytes".to_string(), format!("{:?}", self)))?
+        }
+    }
+
+    /// The data of a stream as it appears in the file, before its filters are applied.
+    pub fn raw_stream_data(&self) -> Result<Vec<u8>> {
+        match self {
+            PdfObject::Reference(ref link) => link.get()?.raw_stream_data(),
+            PdfObject::Actual(BinaryStream(stream)) => stream.raw_data(),
+            PdfObject::Actual(ContentStream(stream)) => stream.raw_data(),
+            _ => Err(ErrorKind::UnavailableType("stream".to_string(), format!("{:?}", self)))?
+        }
+    }
+
+    /// A text string (spec 7.9.2): UTF-16BE after a byte order mark, and otherwise PDFDocEncoding, which is
+    /// read as Latin-1 unless the bytes are valid UTF-8.
+    pub fn try_into_text(&self) -> Result<String> {
+        match self {
+            PdfObject::Actual(CharString(s)) => Ok(s.to_text()),
+            _ => Ok(PdfString::new(self.try_into_bytes()?).to_text())
+        }
+    }
+
+    fn collect_references(&self, ids: &mut Vec<ObjectId>) {
+        match self {
+            PdfObject::Reference(r) => ids.push(ObjectId::new(r.id, r.gen)),
+            PdfObject::Actual(obj) => match obj {
+                Array(arr) => arr.iter().for_each(|item| item.collect_references(ids)),
+                Dictionary(map) => map.values().for_each(|item| item.collect_references(ids)),
+                BinaryStream(stream) => stream.attributes().values().for_each(|item| item.collect_references(ids)),
+                ContentStream(stream) => stream.attributes().values().for_each(|item| item.collect_references(ids)),
+                _ => {}
+            }
+        }
+    }
+}
+
 impl PdfObjectInterface for PdfObject {
     fn get_data_type(&self) -> Result<DataType> {
         match self {
@@ -256,7 +366,8 @@ impl PdfObjectInterface for PdfObject {
             PdfObject::Reference(ref link) => link.get()?.try_into_binary(),
             PdfObject::Actual(ref obj) =>  match obj {
                 HexString(vec) => Ok(Rc::clone(vec)),
-                //TODO: Add binary streams
+                BinaryStream(stream) => Ok(Rc::new(stream.data()?.to_vec())),
+                ContentStream(stream) => Ok(Rc::new(stream.data().to_vec())),
                 _ => Err(ErrorKind::UnavailableType("binary".to_string(), "try_into_binary".to_string()))?
             },
         }
@@ -265,7 +376,9 @@ impl PdfObjectInterface for PdfObject {
         match self {
             PdfObject::Reference(ref link) => link.get()?.try_into_string(),
             PdfObject::Actual(obj) => match obj {
-                CharString(s) | Name(s) | Comment(s) => Ok(Rc::clone(s)),
+                Name(s) | Comment(s) => Ok(Rc::clone(s)),
+                // Literal strings are bytes, which are read as UTF-8 here; try_into_text reads text strings
+                CharString(s) => Ok(Rc::new(s.to_string_lossy())),
                 _ => Err(ErrorKind::UnavailableType(
                     "string".to_string(),
                     format!("{:?}", &self)))?
@@ -276,7 +389,8 @@ impl PdfObjectInterface for PdfObject {
         match self {
             PdfObject::Reference(ref link) => link.get()?.try_into_int(),
             PdfObject::Actual(ref obj) =>  match obj {
-                NumberInt(int) => Ok(*int),
+                NumberInt(int) => Ok(i32::try_from(*int).map_err(|_| ErrorKind::ParsingError(
+                    format!("Integer {} out of range", int)))?),
                 _ => Err(ErrorKind::UnavailableType("integer".to_string(), "try_into_int".to_string()))?
             },
         }
@@ -299,6 +413,24 @@ impl PdfObjectInterface for PdfObject {
             },
         }
     }
+    fn try_into_content_stream(&self) -> Result<Rc<PdfContentStream>> {
+        match self {
+            PdfObject::Reference(ref link) => link.get()?.try_into_content_stream(),
+            PdfObject::Actual(ref obj) => match obj {
+                ContentStream(stream) => Ok(Rc::clone(stream)),
+                _ => Err(ErrorKind::UnavailableType("content stream".to_string(), "try_into_content_stream".to_string()))?
+            }
+        }
+    }
+    fn try_into_binary_stream(&self) -> Result<Rc<PdfBinaryStream>> {
+        match self {
+            PdfObject::Reference(ref link) => link.get()?.try_into_binary_stream(),
+            PdfObject::Actual(ref obj) => match obj {
+                BinaryStream(stream) => Ok(Rc::clone(stream)),
+                _ => Err(ErrorKind::UnavailableType("binary stream".to_string(), "try_into_binary_stream".to_string()))?
+            }
+        }
+    }
     fn is_map(&self) -> bool {
         match self {
             PdfObject::Reference(ref link) => match link.get() {
@@ -487,6 +619,7 @@ pub enum DataType {
     Null
 }
 
+#[derive(Debug)]
 pub enum PdfDataType {
     Boolean,
     Number,
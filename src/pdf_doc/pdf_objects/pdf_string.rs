@@ -0,0 +1,95 @@
+use std::fmt;
+
+/// The bytes of a literal string, as they are once its escapes are read.  A string is bytes, not text (spec
+/// 7.3.4): it may be a text string in PDFDocEncoding or UTF-16BE, character codes for a font, or binary data like
+/// a file identifier, so the bytes are kept exactly and only read as text when asked, by to_text.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PdfString(Vec<u8>);
+
+impl PdfString {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        PdfString(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The string as text (spec 7.9.2): UTF-16BE after a byte order mark, and otherwise PDFDocEncoding, which is
+    /// read as Latin-1 unless the bytes are valid UTF-8.
+    pub fn to_text(&self) -> String {
+        if let Some(utf16) = self.0.strip_prefix(b"\xFE\xFF") {
+            let units: Vec<u16> = utf16.chunks(2)
+                                       .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                                       .collect();
+            return String::from_utf16_lossy(&units);
+        };
+        match std::str::from_utf8(&self.0) {
+            Ok(text) => text.to_string(),
+            Err(_) => self.0.iter().map(|&c| c as char).collect()
+        }
+    }
+
+    /// The bytes as UTF-8, with any that aren't replaced by U+FFFD, for callers that want a string whatever the
+    /// bytes are.  Text strings should be read with to_text instead.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.0).into_owned()
+    }
+}
+
+impl From<Vec<u8>> for PdfString {
+    fn from(bytes: Vec<u8>) -> Self {
+        PdfString(bytes)
+    }
+}
+
+impl From<&[u8]> for PdfString {
+    fn from(bytes: &[u8]) -> Self {
+        PdfString(bytes.to_vec())
+    }
+}
+
+impl From<String> for PdfString {
+    fn from(text: String) -> Self {
+        PdfString(text.into_bytes())
+    }
+}
+
+impl From<&str> for PdfString {
+    fn from(text: &str) -> Self {
+        PdfString(text.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Display for PdfString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        assert_eq!(PdfString::from("plain").to_text(), "plain");
+        assert_eq!(PdfString::from("caf\u{E9}").to_text(), "caf\u{E9}");
+        assert_eq!(PdfString::new(b"caf\xE9".to_vec()).to_text(), "caf\u{E9}");
+        assert_eq!(PdfString::new(b"\xFE\xFF\x00A\x04\x10".to_vec()).to_text(), "A\u{410}");
+        assert_eq!(PdfString::new(b"caf\xE9".to_vec()).to_string_lossy(), "caf\u{FFFD}");
+        assert_eq!(PdfString::new(vec![0x80, 0xFF]).as_bytes(), &[0x80, 0xFF]);
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::HashSet;
+
+use super::*;
+
+/// Longest script or target quoted in a finding's detail, in characters.
+const MAX_DETAIL_LENGTH: usize = 80;
+
+/// Constructs that can make a viewer run code, open files or contact servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecurityRisk {
+    /// A /JS script, or a /JavaScript name tree
+    JavaScript,
+    /// A /Launch action, which runs or opens a file
+    Launch,
+    /// An /OpenAction run when the document is opened
+    OpenAction,
+    /// /AA additional actions, triggered by events like page visits or form input
+    AdditionalActions,
+    /// An /EmbeddedFile stream, or an /EmbeddedFiles name tree
+    EmbeddedFile,
+    /// A /URI action
+    Uri,
+}
+
+/// A potentially dangerous construct found in an indirect object, with any script or target it names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityFinding {
+    pub id: ObjectId,
+    pub risk: SecurityRisk,
+    pub detail: Option<String>,
+}
+
+/// The result of PdfDoc::security_report.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SecurityReport {
+    /// Findings in object order
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Whether any finding is of the given kind.
+    pub fn contains(&self, risk: SecurityRisk) -> bool {
+        self.findings.iter().any(|finding| finding.risk == risk)
+    }
+}
+
+impl PdfDoc {
+    /// Scan every object in the cross-reference index, including ones unreachable from the trailer, for
+    /// scripts, launch actions, automatic actions, embedded files and URIs.  Meant for triage of suspect
+    /// files, so objects that fail to parse are skipped rather than ending the scan.
+    pub fn security_report(&self) -> Result<SecurityReport> {
+        let mut report = SecurityReport::default();
+        for id in self.file.object_map.object_ids() {
+            if let Ok(object) = self.get_object(id) {
+                scan_object(id, &object, &mut report.findings, 0);
+            };
+        }
+        Ok(report)
+    }
+}
+
+/// What PdfDoc::sanitize removes.  By default, everything it can.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizeOptions {
+    /// JavaScript actions, the /JavaScript name tree and XFA forms, whose templates can carry scripts
+    pub javascript: bool,
+    /// /EmbeddedFile streams, /EF entries of file specifications, the /EmbeddedFiles name tree and file
+    /// attachment annotations
+    pub embedded_files: bool,
+    pub launch_actions: bool,
+    /// Actions that contact servers or open other documents: /URI, /GoToR, /GoToE, /SubmitForm and /ImportData
+    pub external_references: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        SanitizeOptions { javascript: true, embedded_files: true, launch_actions: true, external_references: true }
+    }
+}
+
+impl SanitizeOptions {
+    fn removes_action(&self, action_type: &str) -> bool {
+        match action_type {
+            "JavaScript" => self.javascript,
+            "Launch" => self.launch_actions,
+            "URI" | "GoToR" | "GoToE" | "SubmitForm" | "ImportData" => self.external_references,
+            _ => false
+        }
+    }
+}
+
+/// The result of PdfDoc::sanitize.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SanitizeReport {
+    pub actions_removed: usize,
+    /// Embedded file streams and file attachment annotations removed
+    pub files_removed: usize,
+    /// Objects replaced with a cleaned copy, or with null, in object order
+    pub changed: Vec<ObjectId>,
+    /// Objects that couldn't be parsed, and so are left as they are, in object order
+    pub unreadable: Vec<ObjectId>,
+}
+
+impl PdfDoc {
+    /// Remove active content and attachments from every object in the cross-reference index, as chosen by
+    /// options.  Actions are dropped from wherever they're used, with the rest of their /Next chain, and
+    /// dictionaries and arrays left empty by that are dropped too, so the catalog loses its /OpenAction and
+    /// /Names entries if nothing else is in them.  Removed indirect objects are replaced with null; write the
+    /// document with WriteOptions::garbage_collect to leave them out of the output.  Objects that fail to parse
+    /// can't be checked, so they're listed in SanitizeReport::unreadable rather than ending the sanitizing.
+    pub fn sanitize(&mut self, options: &SanitizeOptions) -> Result<SanitizeReport> {
+        let mut report = SanitizeReport::default();
+        // Objects to remove are found first, so that references to them can be dropped wherever they appear
+        let mut removed = HashSet::new();
+        let mut readable = Vec::new();
+        for id in self.file.object_map.object_ids() {
+            let object = match self.get_object(id) {
+                Ok(object) => object,
+                Err(e) => {
+                    warn!("Object {} can't be sanitized: {}", id, e);
+                    report.unreadable.push(id);
+                    continue;
+                }
+            };
+            if let Some(kind) = removal(object.as_ref(), options) {
+                kind.count(&mut report);
+                removed.insert(id);
+            };
+            readable.push((id, object));
+        }
+        for (id, object) in readable {
+            if removed.contains(&id) {
+                self.file.object_map.set_object(id, PdfObject::Actual(Null));
+                report.changed.push(id);
+            } else if let Some(cleaned) = clean(object.as_ref(), options, &removed, &mut report, 0)? {
+                self.file.object_map.set_object(id, cleaned);
+                report.changed.push(id);
+            };
+        }
+        self.reload_page_tree()?;
+        Ok(report)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Removal {
+    Action,
+    File,
+}
+
+impl Removal {
+    fn count(self, report: &mut SanitizeReport) {
+        match self {
+            Removal::Action => report.actions_removed += 1,
+            Removal::File => report.files_removed += 1,
+        }
+    }
+}
+
+/// Whether options call for removing an object outright, rather than cleaning what's in it.
+fn removal(object: &PdfObject, options: &SanitizeOptions) -> Option<Removal> {
+    let dict = match object {
+        PdfObject::Actual(Dictionary(dict)) => dict.as_ref(),
+        PdfObject::Actual(BinaryStream(stream)) => stream.attributes(),
+        _ => return None
+    };
+    if name_of(dict, "S").is_some_and(|action_type| options.removes_action(&action_type)) {
+        return Some(Removal::Action);
+    };
+    if options.embedded_files
+        && (name_is(dict, "Type", "EmbeddedFile") || name_is(dict, "Subtype", "FileAttachment")) {
+        return Some(Removal::File);
+    };
+    None
+}
+
+/// A copy of a direct object with removed objects, and references to them, left out, or None if nothing in it
+/// is removed.
+fn clean(object: &PdfObject, options: &SanitizeOptions, removed: &HashSet<ObjectId>, report: &mut SanitizeReport,
+         depth: usize) -> Result<Option<PdfObject>> {
+    if depth > 64 {
+        Err(ErrorKind::DocTreeError("Objects nested too deeply to sanitize".to_string()))?
+    };
+    // Whether an item should be left out, counting it if it's removed here rather than as an indirect object
+    let drop_item = |item: &PdfObject, report: &mut SanitizeReport| match item.reference_id() {
+        Some(id) => removed.contains(&id),
+        None => removal(item, options).map(|kind| kind.count(report)).is_some()
+    };
+    match object {
+        PdfObject::Actual(Array(array)) => {
+            let mut changed = false;
+            let mut items = PdfArray::new();
+            for item in array.iter() {
+                if drop_item(item, report) {
+                    changed = true;
+                    continue;
+                };
+                match clean(item, options, removed, report, depth + 1)? {
+                    Some(cleaned) => {
+                        changed = true;
+                        items.push(Rc::new(cleaned));
+                    },
+                    None => items.push(Rc::clone(item))
+                };
+            }
+            Ok(if changed { Some(PdfObject::new_array(Rc::new(items))) } else { None })
+        },
+        PdfObject::Actual(Dictionary(dict)) => {
+            let mut changed = false;
+            let mut entries = PdfMap::new();
+            for (key, value) in dict.iter() {
+                let unwanted = match key.as_str() {
+                    "JavaScript" | "XFA" => options.javascript,
+                    "EmbeddedFiles" | "EF" => options.embedded_files,
+                    _ => false
+                };
+                if unwanted || drop_item(value, report) {
+                    changed = true;
+                    continue;
+                };
+                match clean(value, options, removed, report, depth + 1)? {
+                    Some(cleaned) => {
+                        changed = true;
+                        if !is_empty_container(&cleaned) {
+                            entries.insert(key.clone(), Rc::new(cleaned));
+                        };
+                    },
+                    None => {
+                        entries.insert(key.clone(), Rc::clone(value));
+                    }
+                };
+            }
+            Ok(if changed { Some(PdfObject::new_dictionary(Rc::new(entries))) } else { None })
+        },
+        _ => Ok(None)
+    }
+}
+
+fn is_empty_container(object: &PdfObject) -> bool {
+    match object {
+        PdfObject::Actual(Array(array)) => array.is_empty(),
+        PdfObject::Actual(Dictionary(dict)) => dict.is_empty(),
+        _ => false
+    }
+}
+
+/// Check dictionaries and stream dictionaries within an object, without following references.
+fn scan_object(id: ObjectId, object: &PdfObject, findings: &mut Vec<SecurityFinding>, depth: usize) {
+    // Direct objects can't be cyclic, but a hostile file can still nest them deeply
+    if depth > 64 {
+        return;
+    };
+    let dict = match object {
+        PdfObject::Actual(Dictionary(dict)) => dict.as_ref(),
+        PdfObject::Actual(BinaryStream(stream)) => stream.attributes(),
+        PdfObject::Actual(Array(array)) => {
+            for item in array.iter() {
+                scan_object(id, item, findings, depth + 1);
+            }
+            return;
+        },
+        _ => return
+    };
+    let mut add = |risk, detail: Option<String>| findings.push(SecurityFinding { id, risk, detail });
+    if let Some(script) = dict.get("JS") {
+        add(SecurityRisk::JavaScript, describe(script));
+    };
+    if dict.contains_key("JavaScript") {
+        add(SecurityRisk::JavaScript, None);
+    };
+    if dict.contains_key("OpenAction") {
+        add(SecurityRisk::OpenAction, None);
+    };
+    if dict.contains_key("AA") {
+        add(SecurityRisk::AdditionalActions, None);
+    };
+    if dict.contains_key("EmbeddedFiles") || name_is(dict, "Type", "EmbeddedFile") {
+        add(SecurityRisk::EmbeddedFile, None);
+    };
+    if name_is(dict, "S", "Launch") {
+        add(SecurityRisk::Launch, dict.get("F").and_then(|file| describe(file)));
+    };
+    if name_is(dict, "S", "URI") {
+        add(SecurityRisk::Uri, dict.get("URI").and_then(|uri| describe(uri)));
+    };
+    for value in dict.values() {
+        scan_object(id, value, findings, depth + 1);
+    }
+}
+
+/// The name at key, following a reference to it.
+fn name_of(dict: &PdfMap, key: &str) -> Option<Rc<String>> {
+    dict.get(key)?.try_into_string().ok()
+}
+
+fn name_is(dict: &PdfMap, key: &str, name: &str) -> bool {
+    name_of(dict, key).is_some_and(|value| value.as_str() == name)
+}
+
+/// A short description of a script or target: the string itself, shortened, or the referenced object.
+fn describe(object: &PdfObject) -> Option<String> {
+    let text = match object {
+        PdfObject::Actual(CharString(_)) | PdfObject::Actual(HexString(_)) => {
+            String::from_utf8_lossy(&object.try_into_bytes().ok()?).to_string()
+        },
+        PdfObject::Actual(_) => return None,
+        _ => return object.references().first().map(|id| format!("{} {} R", id.number(), id.generation()))
+    };
+    match text.char_indices().nth(MAX_DETAIL_LENGTH) {
+        Some((end, _)) => Some(format!("{}...", &text[..end])),
+        None => Some(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_security_report() {
+        let mut builder = MiniPdfBuilder::new()
+            .with_catalog_entries("/OpenAction << /S /JavaScript /JS (app.alert\\('hi'\\);) >>");
+        let script = builder.add_stream(b"this.exportDataObject();", TestFilter::None, "");
+        let link = "<< /Subtype /Link /A << /S /URI /URI (http://example.com) >> >>";
+        builder.add_page_with_entries(b"", TestFilter::None,
+                                      &format!("/AA << /O << /S /JavaScript /JS {} 0 R >> >> /Annots [{}]", script, link));
+        // Not reachable from the trailer, but still reported
+        let launch = builder.add_object("<< /S /Launch /F (cmd.exe) >>");
+        let file = builder.add_stream(b"MZ", TestFilter::None, "/Type /EmbeddedFile");
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let report = doc.security_report().unwrap();
+        let found: Vec<(u32, SecurityRisk, Option<&str>)> = report.findings.iter()
+            .map(|finding| (finding.id.number(), finding.risk, finding.detail.as_deref()))
+            .collect();
+        let page = launch - 1;
+        assert_eq!(found.len(), 7);
+        assert!(found.contains(&(1, SecurityRisk::OpenAction, None)));
+        assert!(found.contains(&(1, SecurityRisk::JavaScript, Some("app.alert('hi');"))));
+        assert!(found.contains(&(page, SecurityRisk::AdditionalActions, None)));
+        assert!(found.contains(&(page, SecurityRisk::JavaScript, Some(&format!("{} 0 R", script)[..]))));
+        assert!(found.contains(&(page, SecurityRisk::Uri, Some("http://example.com"))));
+        assert!(found.contains(&(launch, SecurityRisk::Launch, Some("cmd.exe"))));
+        assert!(found.contains(&(file, SecurityRisk::EmbeddedFile, None)));
+
+        let clean = PdfDoc::create_pdf_from_bytes(MiniPdfBuilder::new().build()).unwrap();
+        assert!(clean.security_report().unwrap().is_clean());
+
+        // Escaped action types are read as the names they spell
+        let mut builder = MiniPdfBuilder::new();
+        let launch = builder.add_object("<< /S /L#61unch /F (cmd.exe) >>");
+        let uri = builder.add_object("<< /S /#55#52#49 /URI (http://example.com) >>");
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        let found: Vec<(u32, SecurityRisk)> = doc.security_report().unwrap().findings.iter()
+            .map(|finding| (finding.id.number(), finding.risk))
+            .collect();
+        assert_eq!(found, vec![(launch, SecurityRisk::Launch), (uri, SecurityRisk::Uri)]);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let build = || {
+            let mut builder = MiniPdfBuilder::new();
+            let script = builder.add_stream(b"app.alert(1);", TestFilter::None, "");
+            let file = builder.add_stream(b"MZ", TestFilter::None, "/Type /EmbeddedFile");
+            let spec = builder.add_object(format!("<< /Type /Filespec /F (a.exe) /EF << /F {} 0 R >> >>", file));
+            let attachment = builder.add_object(format!("<< /Subtype /FileAttachment /FS {} 0 R >>", spec));
+            let launch = builder.add_object("<< /S /Launch /F (cmd.exe) >>");
+            let font = builder.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+            let mut builder = builder.with_catalog_entries(&format!(
+                "/OpenAction << /S /JavaScript /JS {} 0 R >> /Names << /JavaScript << /Names [(a) << /S /JavaScript \
+                 /JS (x) >>] >> /EmbeddedFiles << /Names [(a.exe) {} 0 R] >> >>", script, spec));
+            let annots = format!("[<< /Subtype /Link /A << /S /URI /URI (http://example.com) >> >> \
+                                 << /Subtype /Link /A {} 0 R >> {} 0 R << /Subtype /Link /Dest [0 /Fit] >>]",
+                                 launch, attachment);
+            builder.add_page_with_entries(b"BT /F1 12 Tf (Kept) Tj ET", TestFilter::None, &format!(
+                "/AA << /O << /S /JavaScript /JS (x) >> >> /Annots {} /Resources << /Font << /F1 {} 0 R >> >>",
+                annots, font));
+            builder.build()
+        };
+
+        let mut doc = PdfDoc::create_pdf_from_bytes(build()).unwrap();
+        let report = doc.sanitize(&SanitizeOptions::default()).unwrap();
+        // The open action, the page's /AA action, the URI and the launch action; the name tree goes as a whole
+        assert_eq!(report.actions_removed, 4);
+        assert_eq!(report.files_removed, 2);
+        let options = WriteOptions { garbage_collect: true, ..WriteOptions::default() };
+        let (bytes, _) = doc.write(&options).unwrap();
+        let sanitized = PdfDoc::create_pdf_from_bytes(bytes).unwrap();
+        assert!(sanitized.security_report().unwrap().is_clean());
+        assert_eq!(sanitized.page_text(0).unwrap().trim(), "Kept");
+        let catalog = sanitized.file.retrieve_trailer().unwrap().try_into_map().unwrap()
+                               .get("Root").unwrap().try_into_map().unwrap();
+        assert!(!catalog.contains_key("OpenAction") && !catalog.contains_key("Names"));
+        let page = sanitized.get_dict(sanitized.page_id(0).unwrap()).unwrap();
+        assert!(!page.contains_key("AA"));
+        let annots = page.get("Annots").unwrap().try_into_array().unwrap();
+        assert_eq!(annots.len(), 3);
+        assert!(annots.iter().all(|annot| !annot.try_into_map().unwrap().contains_key("A")));
+
+        let mut doc = PdfDoc::create_pdf_from_bytes(build()).unwrap();
+        let options = SanitizeOptions { external_references: false, ..SanitizeOptions::default() };
+        doc.sanitize(&options).unwrap();
+        let report = doc.security_report().unwrap();
+        assert_eq!(report.findings.iter().map(|finding| finding.risk).collect::<Vec<_>>(), vec![SecurityRisk::Uri]);
+    }
+
+    #[test]
+    fn test_sanitize_disguised_actions() {
+        let mut builder = MiniPdfBuilder::new().with_catalog_entries("/OpenAction << /S /L#61unch /F (cmd.exe) >>");
+        let script = builder.add_object("<< /S /Java#53cript /JS (app.alert\\(1\\);) >>");
+        let action_type = builder.add_object("/Launch");
+        let launch = builder.add_object(format!("<< /S {} 0 R /F (calc.exe) >>", action_type));
+        builder.add_page_with_entries(b"", TestFilter::None,
+                                      &format!("/AA << /O {} 0 R /C {} 0 R >>", script, launch));
+        let bytes = builder.build();
+        let mut doc = PdfDoc::create_pdf_from_bytes(bytes.clone()).unwrap();
+        assert_eq!(doc.sanitize(&SanitizeOptions::default()).unwrap().actions_removed, 3);
+        let (written, _) = doc.write(&WriteOptions { garbage_collect: true, ..WriteOptions::default() }).unwrap();
+        let text = String::from_utf8_lossy(&written);
+        assert!(!text.contains("unch") && !text.contains("cript") && !text.contains(".exe"), "{}", text);
+
+        // An unparsable object is listed rather than stopping the rest being sanitized
+        let mut broken = bytes;
+        append_incremental_update(&mut broken, &[(20, "<< /S /Launch /F >>")], "");
+        let mut doc = PdfDoc::create_pdf_from_bytes(broken).unwrap();
+        let report = doc.sanitize(&SanitizeOptions::default()).unwrap();
+        assert_eq!((report.actions_removed, report.unreadable), (3, vec![ObjectId::new(20, 0)]));
+    }
+}
@@ -0,0 +1,125 @@
+//! Python bindings, built with the python feature.  The module is named pdfparser:
+//!
+//! ```python
+//! import pdfparser
+//! doc = pdfparser.PdfDoc.open("report.pdf")
+//! print(doc.metadata().get("Title"), doc.page_count)
+//! for page in doc.pages():
+//!     print(page.index, page.text())
+//! ```
+//!
+//! Documents hold reference-counted data, so they can only be used from the thread that opened them.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::doc_tree::{PdfDoc, TextOptions};
+use crate::errors::Error;
+
+fn to_py_err(error: Error) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+#[pyclass(name = "PdfDoc", module = "pdfparser", unsendable)]
+pub struct PyPdfDoc {
+    doc: PdfDoc,
+}
+
+#[pymethods]
+impl PyPdfDoc {
+    /// Read a document from a file.
+    #[staticmethod]
+    fn open(path: PathBuf) -> PyResult<Self> {
+        Ok(PyPdfDoc { doc: PdfDoc::create_pdf_from_file(path).map_err(to_py_err)? })
+    }
+
+    /// Read a document from the bytes of a file.
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        Ok(PyPdfDoc { doc: PdfDoc::create_pdf_from_bytes(data.to_vec()).map_err(to_py_err)? })
+    }
+
+    #[getter]
+    fn page_count(&self) -> usize {
+        self.doc.page_count()
+    }
+
+    /// The page at index, counting from 0.
+    fn page(slf: Bound<'_, Self>, index: usize) -> PyResult<PyPage> {
+        if index >= slf.borrow().doc.page_count() {
+            return Err(PyIndexError::new_err(format!("No page {}", index)));
+        };
+        Ok(PyPage { doc: slf.unbind(), index })
+    }
+
+    fn pages(slf: Bound<'_, Self>) -> Vec<PyPage> {
+        (0..slf.borrow().doc.page_count()).map(|index| PyPage { doc: slf.clone().unbind(), index }).collect()
+    }
+
+    /// The text of every page, with a form feed between pages.  Pass include_invisible=False to leave out
+    /// invisible text, like the OCR layer of a scanned document.
+    #[pyo3(signature = (include_invisible = true))]
+    fn text(&self, include_invisible: bool) -> PyResult<String> {
+        self.doc.text_with_options(&TextOptions { include_invisible }).map_err(to_py_err)
+    }
+
+    /// The text entries of the document information dictionary, such as Title and Author.
+    fn metadata(&self) -> PyResult<BTreeMap<String, String>> {
+        self.doc.info().map_err(to_py_err)
+    }
+}
+
+/// A page of a PdfDoc, which it keeps alive.
+#[pyclass(name = "Page", module = "pdfparser", unsendable)]
+pub struct PyPage {
+    doc: Py<PyPdfDoc>,
+    index: usize,
+}
+
+#[pymethods]
+impl PyPage {
+    #[getter]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The text on the page in reading order.
+    #[pyo3(signature = (include_invisible = true))]
+    fn text(&self, py: Python<'_>, include_invisible: bool) -> PyResult<String> {
+        let options = TextOptions { include_invisible };
+        self.doc.borrow(py).doc.page_text_with_options(self.index, &options).map_err(to_py_err)
+    }
+
+    /// The media box as (x0, y0, x1, y1).
+    fn media_box(&self, py: Python<'_>) -> PyResult<(f32, f32, f32, f32)> {
+        let rect = self.doc.borrow(py).doc.media_box(self.index).map_err(to_py_err)?;
+        Ok((rect.x0, rect.y0, rect.x1, rect.y1))
+    }
+
+    /// The page's decoded content streams, joined.
+    fn content<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let content = self.doc.borrow(py).doc.page_content(self.index).map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &content))
+    }
+}
+
+/// The text of every page of the document at path.
+#[pyfunction]
+#[pyo3(signature = (path, include_invisible = true))]
+fn extract_text(path: PathBuf, include_invisible: bool) -> PyResult<String> {
+    PdfDoc::create_pdf_from_file(path)
+        .and_then(|doc| doc.text_with_options(&TextOptions { include_invisible }))
+        .map_err(to_py_err)
+}
+
+#[pymodule]
+fn pdfparser(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPdfDoc>()?;
+    module.add_class::<PyPage>()?;
+    module.add_function(wrap_pyfunction!(extract_text, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! Helpers for generating small synthetic PDFs in unit tests, so tests of the parser, xref handling and
+//! filters don't depend on binary fixtures.
+
+use std::io::Write;
+
+/// Assemble a file with a correct xref table from the bodies of objects 1..=n.
+pub fn pdf_from_objects<T: AsRef<[u8]>>(objects: &[T], trailer: &str) -> Vec<u8> {
+    let mut output = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(output.len());
+        output.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        output.extend_from_slice(body.as_ref());
+        output.extend_from_slice(b"\nendobj\n");
+    }
+    let xref_index = output.len();
+    output.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        output.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    output.extend_from_slice(format!("trailer\n{}\nstartxref\n{}\n%%EOF\n", trailer, xref_index).as_bytes());
+    output
+}
+
+/// Append an incremental update to a file with a classic xref table: new bodies for the given object numbers,
+/// an xref section listing them, and a trailer pointing back to the previous section with /Prev.
+pub fn append_incremental_update(pdf: &mut Vec<u8>, objects: &[(u32, &str)], trailer_entries: &str) {
+    let text = String::from_utf8_lossy(pdf);
+    let startxref = text.rfind("startxref").expect("No startxref in file");
+    let prev: usize = text[startxref + 9..].split_whitespace().next().unwrap().parse().unwrap();
+    let size = objects.iter().map(|(number, _)| number + 1).max().unwrap_or(1);
+    let mut offsets = Vec::new();
+    for (number, body) in objects {
+        offsets.push((*number, pdf.len()));
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", number, body).as_bytes());
+    }
+    let xref_index = pdf.len();
+    pdf.extend_from_slice(b"xref\n0 1\n0000000000 65535 f \n");
+    for (number, offset) in offsets {
+        pdf.extend_from_slice(format!("{} 1\n{:010} 00000 n \n", number, offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!("trailer\n<< /Root 1 0 R /Size {} /Prev {} {} >>\nstartxref\n{}\n%%EOF\n",
+                                  size, prev, trailer_entries, xref_index).as_bytes());
+}
+
+/// Filters MiniPdfBuilder can apply to stream data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestFilter {
+    None,
+    Flate,
+    AsciiHex,
+    Ascii85,
+}
+
+impl TestFilter {
+    pub fn encode(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            TestFilter::None => data.to_vec(),
+            TestFilter::Flate => {
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            },
+            TestFilter::AsciiHex => {
+                let mut output: Vec<u8> = data.iter().flat_map(|byte| format!("{:02X}", byte).into_bytes()).collect();
+                output.push(b'>');
+                output
+            },
+            TestFilter::Ascii85 => ascii_85_encode(data),
+        }
+    }
+
+    fn name(self) -> Option<&'static str> {
+        match self {
+            TestFilter::None => None,
+            TestFilter::Flate => Some("FlateDecode"),
+            TestFilter::AsciiHex => Some("ASCIIHexDecode"),
+            TestFilter::Ascii85 => Some("ASCII85Decode"),
+        }
+    }
+}
+
+fn ascii_85_encode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for chunk in data.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        if value == 0 && chunk.len() == 4 {
+            output.push(b'z');
+            continue;
+        }
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = (value % 85) as u8 + b'!';
+            value /= 85;
+        }
+        output.extend_from_slice(&digits[..chunk.len() + 1]);
+    }
+    output.extend_from_slice(b"~>");
+    output
+}
+
+/// The body of a stream object holding data encoded with filter.  extra_entries are added to the stream
+/// dictionary verbatim.
+pub fn stream_object(data: &[u8], filter: TestFilter, extra_entries: &str) -> Vec<u8> {
+    let data = filter.encode(data);
+    let filter_entry = match filter.name() {
+        Some(name) => format!(" /Filter /{}", name),
+        None => String::new()
+    };
+    let mut output = format!("<< /Length {}{} {} >>\nstream\n", data.len(), filter_entry, extra_entries).into_bytes();
+    output.extend_from_slice(&data);
+    output.extend_from_slice(b"\nendstream");
+    output
+}
+
+/// Builds tiny but valid documents.  Objects 1 and 2 are always the catalog and the root page tree node; every
+/// page added is a direct kid of the root.
+pub struct MiniPdfBuilder {
+    version: String,
+    objects: Vec<Vec<u8>>,
+    pages: Vec<u32>,
+    catalog_entries: String,
+    trailer_entries: String,
+}
+
+impl MiniPdfBuilder {
+    pub fn new() -> Self {
+        MiniPdfBuilder {
+            version: "1.4".to_string(),
+            objects: vec![Vec::new(), Vec::new()],
+            pages: Vec::new(),
+            catalog_entries: String::new(),
+            trailer_entries: String::new(),
+        }
+    }
+
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Extra entries for the catalog dictionary, written verbatim.
+    pub fn with_catalog_entries(mut self, entries: &str) -> Self {
+        self.catalog_entries = entries.to_string();
+        self
+    }
+
+    /// Extra entries for the trailer dictionary, written verbatim.
+    pub fn with_trailer_entries(mut self, entries: &str) -> Self {
+        self.trailer_entries = entries.to_string();
+        self
+    }
+
+    /// Add an object with the given body and return its object number.
+    pub fn add_object<T: AsRef<[u8]>>(&mut self, body: T) -> u32 {
+        self.objects.push(body.as_ref().to_vec());
+        self.objects.len() as u32
+    }
+
+    pub fn add_stream(&mut self, data: &[u8], filter: TestFilter, extra_entries: &str) -> u32 {
+        self.add_object(stream_object(data, filter, extra_entries))
+    }
+
+    /// Add a letter-sized page with a single content stream and return the page's object number.
+    pub fn add_page(&mut self, content: &[u8], filter: TestFilter) -> u32 {
+        self.add_page_with_entries(content, filter, "")
+    }
+
+    /// As add_page, with extra entries (e.g. /Resources) for the page dictionary.
+    pub fn add_page_with_entries(&mut self, content: &[u8], filter: TestFilter, entries: &str) -> u32 {
+        let content_id = self.add_stream(content, filter, "");
+        let page_id = self.add_object(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents {} 0 R {} >>", content_id, entries));
+        self.pages.push(page_id);
+        page_id
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut objects = self.objects.clone();
+        objects[0] = format!("<< /Type /Catalog /Pages 2 0 R {} >>", self.catalog_entries).into_bytes();
+        let kids: Vec<String> = self.pages.iter().map(|id| format!("{} 0 R", id)).collect();
+        objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), kids.len()).into_bytes();
+        let trailer = format!("<< /Root 1 0 R /Size {} {} >>", objects.len() + 1, self.trailer_entries);
+        let mut output = pdf_from_objects(&objects, &trailer);
+        output[5..8].copy_from_slice(self.version.as_bytes());
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doc_tree::*;
+
+    #[test]
+    fn test_builder_pages() {
+        let mut builder = MiniPdfBuilder::new().with_version("1.7").with_catalog_entries("/PageMode /UseNone");
+        builder.add_page(b"0 0 m 10 10 l S", TestFilter::None);
+        builder.add_page(b"BT (two) Tj ET", TestFilter::Flate);
+        let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(pdf.version, PDFVersion::V1_7);
+        assert_eq!(*pdf.get_dict(ObjectId::new(1, 0)).unwrap().get("PageMode").unwrap().try_into_string().unwrap(), "UseNone");
+        let doc = PdfDoc::create_pdf_from_bytes(builder.build()).unwrap();
+        assert_eq!(doc.page_count(), 2);
+        assert_eq!(doc.page_content(0).unwrap(), b"0 0 m 10 10 l S");
+        assert_eq!(doc.page_content(1).unwrap(), b"BT (two) Tj ET");
+    }
+
+    #[test]
+    fn test_builder_filters() {
+        let data = b"Some stream data\x00\x00\x00\x00 with a zero group and a tail";
+        for &filter in &[TestFilter::None, TestFilter::Flate, TestFilter::AsciiHex, TestFilter::Ascii85] {
+            let mut builder = MiniPdfBuilder::new();
+            let id = builder.add_stream(data, filter, "/Type /Test");
+            let pdf = PdfFileHandler::create_pdf_from_bytes(builder.build()).unwrap();
+            assert_eq!(pdf.get_stream_data(ObjectId::new(id, 0)).unwrap(), data.to_vec(), "{:?}", filter);
+        }
+    }
+}